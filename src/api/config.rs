@@ -1,8 +1,8 @@
-use std::io;
+use std::io::{self, Read};
 
 use crate::{
     config::config::{FileFormat, RoozCfg},
-    model::types::AnyError,
+    model::error::RoozError,
 };
 
 use age::x25519::Identity;
@@ -18,12 +18,40 @@ impl<'a> ConfigApi<'a> {
         io::stdin().read_line(&mut String::new()).unwrap();
     }
 
+    fn parse_edited(body: &str, format: FileFormat) -> Result<RoozCfg, String> {
+        let config = RoozCfg::from_string(body, format).map_err(|e| e.to_string())?;
+        match (&config.vars, &config.secrets) {
+            (Some(vars), Some(secrets)) => {
+                if let Some(duplicate_key) =
+                    vars.keys().find(|k| secrets.contains_key(&k.to_string()))
+                {
+                    return Err(format!(
+                        "The key: '{}' can be only defined in either vars or secrets.",
+                        &duplicate_key.to_string()
+                    ));
+                }
+            }
+            _ => (),
+        };
+        Ok(config)
+    }
+
     pub async fn edit_string(
         &self,
         body: String,
         format: FileFormat,
         identity: &Identity,
-    ) -> Result<(RoozCfg, String), AnyError> {
+    ) -> Result<(RoozCfg, String), RoozError> {
+        // the edited config comes from stdin and gets validated once
+        if self.non_interactive {
+            let mut edited_body = String::new();
+            io::stdin().read_to_string(&mut edited_body)?;
+            let mut edited_config =
+                Self::parse_edited(&edited_body, format).map_err(RoozError::Config)?;
+            self.encrypt(&mut edited_config, identity).await?;
+            return Ok((edited_config, edited_body));
+        }
+
         let mut edited_body = body;
         let mut edited_config;
         loop {
@@ -34,28 +62,13 @@ impl<'a> ConfigApi<'a> {
                     continue;
                 }
             };
-            edited_config = match RoozCfg::from_string(&edited_body, format) {
+            edited_config = match Self::parse_edited(&edited_body, format) {
                 Ok(c) => c,
                 Err(err) => {
-                    self.edit_error(&err.to_string());
+                    self.edit_error(&err);
                     continue;
                 }
             };
-
-            match (&edited_config.vars, &edited_config.secrets) {
-                (Some(vars), Some(secrets)) => {
-                    if let Some(duplicate_key) =
-                        vars.keys().find(|k| secrets.contains_key(&k.to_string()))
-                    {
-                        self.edit_error(&format!(
-                            "The key: '{}' can be only defined in either vars or secrets.",
-                            &duplicate_key.to_string()
-                        ));
-                        continue;
-                    }
-                }
-                _ => (),
-            };
             break;
         }
         self.encrypt(&mut edited_config, identity).await?;
@@ -1,6 +1,7 @@
 use std::{
     collections::HashMap,
-    io::{stdout, Write},
+    io::{stdout, ErrorKind, Write},
+    net::{IpAddr, SocketAddr, TcpListener},
     time::Duration,
 };
 
@@ -8,11 +9,12 @@ use base64::{engine::general_purpose, Engine as _};
 use bollard::{
     container::{
         Config, CreateContainerOptions, InspectContainerOptions, KillContainerOptions,
-        ListContainersOptions, LogOutput::Console, LogsOptions, RemoveContainerOptions,
-        StartContainerOptions, StopContainerOptions,
+        ListContainersOptions, LogOutput, LogOutput::Console, LogsOptions, RemoveContainerOptions,
+        RenameContainerOptions, StartContainerOptions, StopContainerOptions,
+        UpdateContainerOptions,
     },
     errors::Error,
-    models::{ContainerState, HostConfig},
+    models::{ContainerState, HostConfig, RestartPolicy, RestartPolicyNameEnum},
     network::ConnectNetworkOptions,
     secret::ContainerStateStatusEnum,
     service::{ContainerInspectResponse, ContainerSummary, EndpointSettings, PortBinding},
@@ -22,11 +24,40 @@ use tokio::time::sleep;
 
 use crate::{
     api::ContainerApi,
-    model::types::{AnyError, ContainerResult, RunSpec},
+    config::config::{RoozIdMap, RoozRestart},
+    model::{
+        error::RoozError,
+        types::{ContainerResult, RunSpec},
+    },
     util::backend::ContainerBackend,
-    util::labels::{KeyValue, Labels},
+    util::labels::{self, KeyValue, Labels},
+    util::uid,
 };
 
+const LOCALHOST: &str = "127.0.0.1";
+
+fn restart_policy(restart: RoozRestart) -> RestartPolicy {
+    RestartPolicy {
+        name: Some(match restart {
+            RoozRestart::No => RestartPolicyNameEnum::NO,
+            RoozRestart::OnFailure => RestartPolicyNameEnum::ON_FAILURE,
+            RoozRestart::Always => RestartPolicyNameEnum::ALWAYS,
+            RoozRestart::UnlessStopped => RestartPolicyNameEnum::UNLESS_STOPPED,
+        }),
+        maximum_retry_count: None,
+    }
+}
+
+// addresses not present on this host (e.g. of a remote engine) can't be checked so count as free
+fn is_port_free(address: IpAddr, port: u16) -> bool {
+    !matches!(TcpListener::bind((address, port)), Err(e) if e.kind() == ErrorKind::AddrInUse)
+}
+
+// a single argument for the scripts run with inject, whatever quotes it contains
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 pub fn inject(script: &str, name: &str) -> Vec<String> {
     vec![
         "sh".to_string(),
@@ -42,7 +73,7 @@ pub fn inject(script: &str, name: &str) -> Vec<String> {
 }
 
 impl<'a> ContainerApi<'a> {
-    pub async fn get_all(&self, labels: &Labels) -> Result<Vec<ContainerSummary>, AnyError> {
+    pub async fn get_all(&self, labels: &Labels) -> Result<Vec<ContainerSummary>, RoozError> {
         let list_options = ListContainersOptions {
             filters: labels.into(),
             all: true,
@@ -52,7 +83,7 @@ impl<'a> ContainerApi<'a> {
         Ok(self.client.list_containers(Some(list_options)).await?)
     }
 
-    pub async fn get_running(&self, labels: &Labels) -> Result<Vec<ContainerSummary>, AnyError> {
+    pub async fn get_running(&self, labels: &Labels) -> Result<Vec<ContainerSummary>, RoozError> {
         let list_options = ListContainersOptions {
             filters: labels.into(),
             all: false,
@@ -62,15 +93,16 @@ impl<'a> ContainerApi<'a> {
         Ok(self.client.list_containers(Some(list_options)).await?)
     }
 
-    pub async fn get_single(&self, labels: &Labels) -> Result<Option<ContainerSummary>, AnyError> {
+    pub async fn get_single(&self, labels: &Labels) -> Result<Option<ContainerSummary>, RoozError> {
         match self.get_all(&labels).await?.as_slice() {
             [] => Ok(None),
             [container] => Ok(Some(container.clone())),
-            _ => panic!("Too many containers found"),
+            containers => Err(RoozError::AmbiguousContainer(containers.len())),
         }
     }
 
-    pub async fn remove(&self, container_id: &str, force: bool) -> Result<(), AnyError> {
+    #[tracing::instrument(name = "container.remove", level = "debug", skip_all, fields(id = %container_id))]
+    pub async fn remove(&self, container_id: &str, force: bool) -> Result<(), RoozError> {
         let force_display = if force { " (force)" } else { "" };
 
         if force {
@@ -94,10 +126,10 @@ impl<'a> ContainerApi<'a> {
                         // nothing to kill as the container is already stopped
                         ()
                     } else {
-                        panic!("{}", message)
+                        return Err(RoozError::Unexpected(message));
                     }
                 }
-                Err(e) => panic!("{}", e),
+                Err(e) => return Err(e.into()),
             }
         }
 
@@ -126,20 +158,11 @@ impl<'a> ContainerApi<'a> {
                 );
                 Ok(())
             }
-            Err(Error::DockerResponseServerError {
-                status_code,
-                message,
-            }) => Err(format!(
-                "{} (Error code: {})",
-                message.replace("\"", ""),
-                status_code
-            )
-            .into()),
-            Err(e) => panic!("{}", e),
+            Err(e) => Err(e.into()),
         }
     }
 
-    pub async fn kill(&self, container_id: &str) -> Result<(), AnyError> {
+    pub async fn kill(&self, container_id: &str) -> Result<(), RoozError> {
         match self
             .client
             .kill_container(&container_id, None::<KillContainerOptions<String>>)
@@ -149,11 +172,12 @@ impl<'a> ContainerApi<'a> {
                 sleep(Duration::from_millis(10)).await;
                 Ok(())
             }
-            Err(e) => Err(Box::new(e)),
+            Err(e) => Err(e.into()),
         }
     }
 
-    pub async fn stop(&self, container_id: &str) -> Result<(), AnyError> {
+    #[tracing::instrument(name = "container.stop", level = "debug", skip_all, fields(id = %container_id))]
+    pub async fn stop(&self, container_id: &str) -> Result<(), RoozError> {
         self.client
             .stop_container(&container_id, Some(StopContainerOptions { t: 0 }))
             .await?;
@@ -175,7 +199,77 @@ impl<'a> ContainerApi<'a> {
         Ok(())
     }
 
-    pub async fn create(&self, spec: RunSpec<'a>) -> Result<ContainerResult, AnyError> {
+    async fn port_owner(&self, port: u16) -> Result<Option<String>, RoozError> {
+        let list_options = ListContainersOptions {
+            filters: (&Labels::default()).into(),
+            ..Default::default()
+        };
+        Ok(self
+            .client
+            .list_containers(Some(list_options))
+            .await?
+            .into_iter()
+            .find(|c| {
+                c.ports
+                    .iter()
+                    .flatten()
+                    .any(|p| p.public_port == Some(port))
+            })
+            .and_then(|c| c.labels.and_then(|l| l.get(labels::WORKSPACE_KEY).cloned())))
+    }
+
+    // host ports are checked on the local host which is also where rooz remote forwards them to
+    async fn bind_ports(
+        &self,
+        ports: HashMap<String, Option<String>>,
+        auto_ports: bool,
+        bind_address: IpAddr,
+    ) -> Result<HashMap<String, (IpAddr, Option<u16>)>, RoozError> {
+        let mut bound = HashMap::new();
+        for (source, target) in ports {
+            // targets are either a host port or ip:port when the mapping sets its own address
+            let (address, host_port) = match target.as_deref().map(|t| t.parse::<SocketAddr>()) {
+                Some(Ok(addr)) => (addr.ip(), Some(addr.port())),
+                _ => (
+                    bind_address,
+                    target.as_deref().and_then(|p| p.parse::<u16>().ok()),
+                ),
+            };
+            let host_port = match host_port {
+                Some(port) if !is_port_free(address, port) => {
+                    if auto_ports {
+                        let free = TcpListener::bind((address, 0))
+                            .or_else(|_| TcpListener::bind((LOCALHOST, 0)))?
+                            .local_addr()?
+                            .port();
+                        println!(
+                            "Port {} is in use. Remapped {} to: {}",
+                            port,
+                            source,
+                            SocketAddr::new(address, free)
+                        );
+                        Some(free)
+                    } else {
+                        let owner = match self.port_owner(port).await? {
+                            Some(workspace) => format!("workspace {}", workspace),
+                            None => "another process".into(),
+                        };
+                        return Err(format!(
+                            "Port {} is already in use by {}. Change the port mapping or use --auto-ports",
+                            port, owner
+                        )
+                        .into());
+                    }
+                }
+                p => p,
+            };
+            bound.insert(source, (address, host_port));
+        }
+        Ok(bound)
+    }
+
+    #[tracing::instrument(name = "container.create", level = "debug", skip_all, fields(id = %spec.container_name))]
+    pub async fn create(&self, spec: RunSpec<'a>) -> Result<ContainerResult, RoozError> {
         log::debug!(
             "[{}]: Creating container - name: {}, uid: {}, user: {}, image: {}, auto-remove: {}",
             &spec.reason,
@@ -208,24 +302,71 @@ impl<'a> ContainerApi<'a> {
 
                 let options = CreateContainerOptions {
                     name: spec.container_name,
-                    platform: None,
+                    platform: spec.platform,
                 };
 
                 let oom_score_adj = match self.backend {
-                    ContainerBackend::Podman => Some(100),
+                    ContainerBackend::Podman { .. } => Some(100),
                     _ => None,
                 };
 
-                let localhost = "127.0.0.1";
-                let port_bindings = spec.ports.map(|ports| {
+                let userns_mode = match (spec.idmap, self.backend) {
+                    (Some(RoozIdMap::KeepId), ContainerBackend::Podman { .. })
+                    | (Some(RoozIdMap::Auto), ContainerBackend::Podman { rootless: true }) => {
+                        let (uid, gid) = uid::split(spec.uid);
+                        Some(format!("keep-id:uid={},gid={}", uid, gid))
+                    }
+                    (Some(RoozIdMap::KeepId), _) => {
+                        log::debug!("idmap: keep-id is only supported on Podman. Ignoring");
+                        None
+                    }
+                    _ => None,
+                };
+
+                let restart_policy = match spec.restart {
+                    Some(_) if spec.auto_remove => {
+                        log::debug!("restart: ignored for auto-removed containers");
+                        None
+                    }
+                    Some(restart) => Some(restart_policy(restart)),
+                    None => None,
+                };
+
+                let bind_address = spec
+                    .bind_address
+                    .and_then(|a| a.parse::<IpAddr>().ok())
+                    .unwrap_or(IpAddr::from([127, 0, 0, 1]));
+                let ports = match spec.ports {
+                    Some(ports) => Some(
+                        self.bind_ports(ports, spec.auto_ports, bind_address)
+                            .await?,
+                    ),
+                    None => None,
+                };
+
+                // host->container, as shown by rooz list. The address only shows if not the loopback
+                let mut published = ports
+                    .iter()
+                    .flatten()
+                    .filter_map(|(source, (address, port))| {
+                        port.map(|p| match address.is_loopback() {
+                            true => format!("{}->{}", p, source),
+                            false => format!("{}->{}", SocketAddr::new(*address, p), source),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                published.sort();
+                let published = published.join(",");
+
+                let port_bindings = ports.map(|ports| {
                     let mut bindings = HashMap::<String, Option<Vec<PortBinding>>>::new();
 
-                    for (source, target) in &ports {
+                    for (source, (address, port)) in &ports {
                         bindings.insert(
                             source.to_string(),
                             Some(vec![PortBinding {
-                                host_port: target.as_deref().map(|x| x.to_string()),
-                                host_ip: Some(localhost.to_string()),
+                                host_port: port.map(|p| p.to_string()),
+                                host_ip: Some(address.to_string()),
                             }]),
                         );
                     }
@@ -240,12 +381,28 @@ impl<'a> ContainerApi<'a> {
                     privileged: Some(spec.privileged),
                     port_bindings,
                     init: Some(true),
+                    userns_mode,
+                    restart_policy,
+                    security_opt: Some(spec.security.security_opts).filter(|o| !o.is_empty()),
+                    cap_add: Some(spec.security.cap_add).filter(|c| !c.is_empty()),
+                    cap_drop: Some(spec.security.cap_drop).filter(|c| !c.is_empty()),
+                    readonly_rootfs: Some(spec.security.read_only_rootfs),
+                    dns: Some(spec.dns.dns).filter(|d| !d.is_empty()),
+                    dns_search: Some(spec.dns.dns_search).filter(|d| !d.is_empty()),
+                    extra_hosts: Some(spec.dns.extra_hosts).filter(|h| !h.is_empty()),
+                    // rooz injects its helper scripts into /tmp
+                    tmpfs: spec
+                        .security
+                        .read_only_rootfs
+                        .then(|| HashMap::from([("/tmp".to_string(), String::new())])),
                     ..Default::default()
                 };
 
+                let (uid, gid) = uid::split(spec.uid);
                 let mut env_kv = vec![
                     KeyValue::new("ROOZ_META_IMAGE", &spec.image),
-                    KeyValue::new("ROOZ_META_UID", &spec.uid),
+                    KeyValue::new("ROOZ_META_UID", uid),
+                    KeyValue::new("ROOZ_META_GID", gid),
                     KeyValue::new("ROOZ_META_USER", &spec.user),
                     KeyValue::new("ROOZ_META_HOME", &spec.home_dir),
                     KeyValue::new("ROOZ_META_WORKSPACE", &spec.workspace_key),
@@ -258,6 +415,11 @@ impl<'a> ContainerApi<'a> {
 
                 let env = KeyValue::to_vec_str(&env_kv);
 
+                let mut container_labels: HashMap<&str, &str> = (&spec.labels).into();
+                if !published.is_empty() {
+                    container_labels.insert(labels::PORTS, &published);
+                }
+
                 let config = Config {
                     image: Some(spec.image),
                     entrypoint: spec.entrypoint,
@@ -270,7 +432,7 @@ impl<'a> ContainerApi<'a> {
                     tty: Some(true),
                     open_stdin: Some(true),
                     host_config: Some(host_config),
-                    labels: Some((&spec.labels).into()),
+                    labels: Some(container_labels),
                     env: Some(env),
                     ..Default::default()
                 };
@@ -280,11 +442,16 @@ impl<'a> ContainerApi<'a> {
                     .create_container(Some(options.clone()), config.clone())
                     .await?;
 
-                if let Some(network) = &spec.network {
+                let networks = spec
+                    .network
+                    .into_iter()
+                    .chain(spec.extra_networks.iter().flatten().map(String::as_str));
+
+                for network in networks {
                     let connect_network_options = ConnectNetworkOptions {
                         container: &response.id,
                         endpoint_config: EndpointSettings {
-                            aliases: spec.network_aliases,
+                            aliases: spec.network_aliases.clone(),
                             ..Default::default()
                         },
                     };
@@ -304,19 +471,36 @@ impl<'a> ContainerApi<'a> {
         Ok(container_id.clone())
     }
 
-    pub async fn start(&self, container_id: &str) -> Result<(), AnyError> {
-        match self
-            .client
+    #[tracing::instrument(name = "container.start", level = "debug", skip_all, fields(id = %container_id))]
+    pub async fn start(&self, container_id: &str) -> Result<(), RoozError> {
+        self.client
             .start_container(&container_id, None::<StartContainerOptions<String>>)
-            .await
-            .map_err(|e| Box::new(e))
-        {
-            Ok(_) => Ok(()),
-            Err(e) => Err(Box::new(e)),
-        }
+            .await?;
+        Ok(())
+    }
+
+    // changes the restart policy of an existing container in place
+    pub async fn set_restart(
+        &self,
+        container_id: &str,
+        restart: RoozRestart,
+    ) -> Result<(), RoozError> {
+        let options = UpdateContainerOptions::<String> {
+            restart_policy: Some(restart_policy(restart)),
+            ..Default::default()
+        };
+        self.client.update_container(container_id, options).await?;
+        Ok(())
     }
 
-    pub async fn logs_to_stdout(&self, container_name: &str) -> Result<(), AnyError> {
+    pub async fn rename(&self, container_id: &str, name: &str) -> Result<(), RoozError> {
+        self.client
+            .rename_container(container_id, RenameContainerOptions { name })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn logs_to_stdout(&self, container_name: &str) -> Result<(), RoozError> {
         let log_options = LogsOptions::<String> {
             stdout: true,
             follow: true,
@@ -328,10 +512,31 @@ impl<'a> ContainerApi<'a> {
         while let Some(l) = stream.next().await {
             match l {
                 Ok(Console { message: m }) => stdout().write_all(&m)?,
-                Ok(msg) => panic!("{}", msg),
-                Err(e) => panic!("{}", e),
+                Ok(msg) => stdout().write_all(&msg.into_bytes())?,
+                Err(e) => return Err(e.into()),
             };
         }
         Ok(())
     }
+
+    // the stdout of a one-shot container, until it exits
+    pub async fn logs_to_string(&self, container_id: &str) -> Result<String, RoozError> {
+        let log_options = LogsOptions::<String> {
+            stdout: true,
+            follow: true,
+            ..Default::default()
+        };
+
+        let mut stream = self.client.logs(container_id, Some(log_options));
+        let mut output = Vec::new();
+        while let Some(l) = stream.next().await {
+            match l? {
+                LogOutput::StdOut { message } | Console { message } => {
+                    output.extend_from_slice(&message)
+                }
+                _ => (),
+            }
+        }
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
 }
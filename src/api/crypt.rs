@@ -1,7 +1,14 @@
 use crate::api::container::inject;
 use crate::api::CryptApi;
-use crate::model::types::{AnyError, ContainerResult, RunSpec};
-use crate::{constants, util::id};
+use crate::cli::SecretsKey;
+use crate::model::{
+    error::RoozError,
+    types::{ContainerResult, RunSpec},
+};
+use crate::{
+    constants,
+    util::{id, ssh},
+};
 use age::x25519::Identity;
 use bollard::models::MountTypeEnum::VOLUME;
 use bollard::service::Mount;
@@ -19,17 +26,40 @@ impl<'a> CryptApi<'a> {
         }
     }
 
-    pub async fn read_age_identity(&self) -> Result<Identity, AnyError> {
-        let work_dir = "/tmp/.age";
-        let entrypoint = inject(&format!("cat {}/age.key", work_dir), "entrypoint.sh");
+    pub async fn read_age_identity(&self) -> Result<Identity, RoozError> {
+        match self.key {
+            SecretsKey::Age => {
+                let work_dir = "/tmp/.age";
+                let key = self
+                    .read_key(work_dir, self.mount(work_dir), "age.key")
+                    .await?;
+                Ok(Identity::from_str(&key)?)
+            }
+            SecretsKey::Ssh => {
+                let work_dir = "/tmp/.ssh";
+                let key = self
+                    .read_key(work_dir, ssh::mount(work_dir), ssh::PRIVATE_KEY)
+                    .await?;
+                ssh::age_identity(&key)
+            }
+        }
+    }
+
+    async fn read_key(
+        &self,
+        work_dir: &str,
+        mount: Mount,
+        file: &str,
+    ) -> Result<String, RoozError> {
+        let entrypoint = inject(&format!("cat {}/{}", work_dir, file), "entrypoint.sh");
         let run_spec = RunSpec {
             reason: "read-age-key",
-            image: constants::DEFAULT_IMAGE,
+            image: &self.api.image.helper,
             uid: constants::ROOT_UID,
             work_dir: None,
             container_name: &id::random_suffix("read-age"),
             workspace_key: &id::random_suffix("tmp"),
-            mounts: Some(vec![self.mount(work_dir)]),
+            mounts: Some(vec![mount]),
             entrypoint: Some(vec!["cat"]),
             privileged: false,
             force_recreate: false,
@@ -54,10 +84,9 @@ impl<'a> CryptApi<'a> {
                     )
                     .await?;
                 self.api.container.kill(&container_id).await?;
-
-                Ok(age::x25519::Identity::from_str(&data)?)
+                Ok(data)
             }
-            _ => panic!("Could not read age identity"),
+            _ => Err("Could not read the secrets key".into()),
         }
     }
 
@@ -65,17 +94,32 @@ impl<'a> CryptApi<'a> {
         &self,
         plaintext: String,
         recipient: &impl age::Recipient,
-    ) -> Result<String, AnyError> {
+    ) -> Result<String, RoozError> {
         Ok(
             age::encrypt_and_armor(recipient, plaintext.into_bytes().as_slice())?
                 .replace("\n", "|"),
         )
     }
 
-    //TODO: improve experience when there is no matching decryption key
-    pub fn decrypt(&self, identity: &Identity, secret: &str) -> Result<String, AnyError> {
+    pub fn decrypt(&self, identity: &Identity, secret: &str) -> Result<String, RoozError> {
         let formatted = secret.replace("|", "\n");
         let ciphertext = formatted.as_bytes();
-        Ok(std::str::from_utf8(age::decrypt(identity, ciphertext)?.as_slice())?.to_string())
+        let plaintext = match age::decrypt(identity, ciphertext) {
+            Ok(p) => p,
+            Err(age::DecryptError::NoMatchingKeys) => {
+                let hint = match self.key {
+                    SecretsKey::Age => "Restore it with: rooz system key import <backup> or use --secrets-key ssh if they were encrypted with the SSH key",
+                    SecretsKey::Ssh => "Use --secrets-key age if they were encrypted with the age identity",
+                };
+                return Err(format!(
+                    "No matching decryption key. The secrets were encrypted with another age identity than {}. {}",
+                    identity.to_public(),
+                    hint
+                )
+                .into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(std::str::from_utf8(plaintext.as_slice())?.to_string())
     }
 }
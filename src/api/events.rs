@@ -0,0 +1,25 @@
+use std::{collections::HashMap, pin::Pin};
+
+use bollard::{errors::Error, models::EventMessage, system::EventsOptions, Docker};
+use futures::Stream;
+
+use crate::api::Api;
+
+pub fn subscribe(
+    client: &Docker,
+    filters: HashMap<String, Vec<String>>,
+) -> Pin<Box<impl Stream<Item = Result<EventMessage, Error>>>> {
+    Box::pin(client.events(Some(EventsOptions::<String> {
+        filters,
+        ..Default::default()
+    })))
+}
+
+impl<'a> Api<'a> {
+    pub fn events(
+        &self,
+        filters: HashMap<String, Vec<String>>,
+    ) -> Pin<Box<impl Stream<Item = Result<EventMessage, Error>>>> {
+        subscribe(self.client, filters)
+    }
+}
@@ -1,6 +1,9 @@
 use crate::{
-    api::container, api::ExecApi, constants, model::types::AnyError,
-    util::backend::ContainerBackend,
+    api::container,
+    api::ExecApi,
+    constants,
+    model::error::RoozError,
+    util::{backend::ContainerBackend, term, uid},
 };
 use bollard::{
     container::LogOutput,
@@ -11,31 +14,63 @@ use bollard::{
 use futures::{channel::oneshot, Stream, StreamExt};
 
 use std::{
-    io::{stdout, Read, Write},
+    io::{stderr, stdout, Write},
     time::Duration,
 };
-use termion::{raw::IntoRawMode, terminal_size};
 use tokio::{io::AsyncWriteExt, spawn, time::sleep};
 
-async fn collect(stream: impl Stream<Item = Result<LogOutput, Error>>) -> Result<String, AnyError> {
+async fn collect(
+    stream: impl Stream<Item = Result<LogOutput, Error>>,
+) -> Result<String, RoozError> {
     let out = stream
-        .map(|x| match x {
-            Ok(r) => std::str::from_utf8(r.into_bytes().as_ref())
-                .unwrap()
-                .to_string(),
-            Err(err) => panic!("{}", err),
-        })
+        .map(|x| x.map(|r| String::from_utf8_lossy(r.into_bytes().as_ref()).to_string()))
         .collect::<Vec<_>>()
         .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?
         .join("");
 
     let trimmed = out.trim();
     Ok(trimmed.to_string())
 }
 
+// a mounted Docker socket is owned by a host group. The user joins the group with the same gid
+// (or a new docker-host one) so the docker CLI can use it
+const DOCKER_GROUP: &str = r#"
+if [ -S /var/run/docker.sock ]; then
+    gid=$(stat -c %g /var/run/docker.sock)
+    grep -q "^[^:]*:[^:]*:$gid:" /etc/group || echo "docker-host:x:$gid:" >> /etc/group
+    line=$(grep "^[^:]*:[^:]*:$gid:" /etc/group | head -n 1)
+    name=${line%%:*}
+    members=${line##*:}
+    case ",$members," in
+        *",$ROOZ_META_USER,"*) ;;
+        *) sed -i "s/^$name:\([^:]*\):$gid:.*/$name:\1:$gid:${members:+$members,}$ROOZ_META_USER/" /etc/group ;;
+    esac
+fi
+"#;
+
 impl<'a> ExecApi<'a> {
-    async fn start_tty(&self, exec_id: &str, interactive: bool) -> Result<(), AnyError> {
-        let tty_size = terminal_size()?;
+    // an exec without an exit code after its output ended lost its connection
+    async fn exit_status(&self, exec_id: &str) -> Result<(), RoozError> {
+        // the engine may report the exit code slightly after the output stream ends
+        for _ in 0..10 {
+            match self.client.inspect_exec(exec_id).await? {
+                ExecInspectResponse {
+                    exit_code: Some(0), ..
+                } => return Ok(()),
+                ExecInspectResponse {
+                    exit_code: Some(code),
+                    ..
+                } => return Err(RoozError::ExitCode(code)),
+                _ => sleep(Duration::from_millis(100)).await,
+            }
+        }
+        Err(RoozError::Disconnected)
+    }
+
+    async fn start_tty(&self, exec_id: &str, interactive: bool) -> Result<(), RoozError> {
+        let tty_size = term::size();
         if let StartExecResults::Attached {
             mut output,
             mut input,
@@ -53,14 +88,12 @@ impl<'a> ExecApi<'a> {
                 ) => {
                     let (s, mut r) = oneshot::channel::<bool>();
                     let handle = spawn(async move {
-                        let stdin = termion::async_stdin();
-                        let mut bytes = stdin.bytes();
                         loop {
-                            match bytes.next() {
-                                Some(Ok(b)) => {
+                            match term::try_read_stdin() {
+                                Some(b) => {
                                     input.write(&[b]).await.ok();
                                 }
-                                _ => {
+                                None => {
                                     if let Some(true) = r.try_recv().unwrap() {
                                         break;
                                     }
@@ -83,8 +116,7 @@ impl<'a> ExecApi<'a> {
                         .ok();
 
                     // set stdout in raw mode so we can do tty stuff
-                    let stdout = stdout();
-                    let mut stdout = stdout.lock().into_raw_mode()?;
+                    let mut stdout = term::raw_stdout()?;
                     // pipe docker exec output into stdout
                     while let Some(Ok(out)) = output.next().await {
                         let bytes = out.clone().into_bytes();
@@ -103,6 +135,7 @@ impl<'a> ExecApi<'a> {
                     // try ping to see if the connection was lost
                     // if this fails the calling code loops retrying to connect to the session
                     self.client.ping().await?;
+                    self.exit_status(exec_id).await?;
                 }
                 (
                     ExecInspectResponse {
@@ -150,15 +183,36 @@ impl<'a> ExecApi<'a> {
                         }
                     }
                     if exit_code != 0 {
-                        panic!("Exec terminated with exit code: {}.", exit_code);
+                        return Err(RoozError::ExitCode(exit_code));
                     }
                 }
-                _ => panic!("Unexpected exec state: {:?}", exec_state.clone()),
+                _ => {
+                    return Err(RoozError::Unexpected(format!(
+                        "exec state: {:?}",
+                        exec_state
+                    )))
+                }
             };
         }
         Ok(())
     }
 
+    // no TTY: stdin is never read, stderr stays separate and the exit code is always checked
+    async fn start_batch(&self, exec_id: &str) -> Result<(), RoozError> {
+        if let StartExecResults::Attached { mut output, .. } =
+            self.client.start_exec(exec_id, None).await?
+        {
+            while let Some(out) = output.next().await {
+                match out? {
+                    LogOutput::StdErr { message } => stderr().write_all(&message)?,
+                    out => stdout().write_all(&out.into_bytes())?,
+                }
+            }
+            stdout().flush()?;
+        }
+        self.exit_status(exec_id).await
+    }
+
     async fn create_exec(
         &self,
         reason: &str,
@@ -166,34 +220,31 @@ impl<'a> ExecApi<'a> {
         working_dir: Option<&str>,
         user: Option<&str>,
         cmd: Option<Vec<&str>>,
-    ) -> Result<String, AnyError> {
-        #[cfg(not(windows))]
-        {
-            log::debug!(
-                "[{}] exec: {:?} in working dir: {:?}",
-                reason,
-                cmd,
-                working_dir
-            );
+    ) -> Result<String, RoozError> {
+        log::debug!(
+            "[{}] exec: {:?} in working dir: {:?}",
+            reason,
+            cmd,
+            working_dir
+        );
 
-            Ok(self
-                .client
-                .create_exec(
-                    &container_id,
-                    CreateExecOptions {
-                        attach_stdout: Some(true),
-                        attach_stderr: Some(true),
-                        attach_stdin: Some(true),
-                        tty: Some(true),
-                        cmd,
-                        working_dir,
-                        user,
-                        ..Default::default()
-                    },
-                )
-                .await?
-                .id)
-        }
+        Ok(self
+            .client
+            .create_exec(
+                &container_id,
+                CreateExecOptions {
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    attach_stdin: Some(!self.non_interactive),
+                    tty: Some(!self.non_interactive),
+                    cmd,
+                    working_dir,
+                    user,
+                    ..Default::default()
+                },
+            )
+            .await?
+            .id)
     }
 
     pub async fn tty(
@@ -204,12 +255,16 @@ impl<'a> ExecApi<'a> {
         working_dir: Option<&str>,
         user: Option<&str>,
         cmd: Option<Vec<&str>>,
-    ) -> Result<(), AnyError> {
+    ) -> Result<(), RoozError> {
         let exec_id = self
             .create_exec(reason, container_id, working_dir, user, cmd)
             .await?;
 
-        self.start_tty(&exec_id, interactive).await
+        if self.non_interactive {
+            self.start_batch(&exec_id).await
+        } else {
+            self.start_tty(&exec_id, interactive).await
+        }
     }
 
     pub async fn output(
@@ -218,7 +273,7 @@ impl<'a> ExecApi<'a> {
         container_id: &str,
         user: Option<&str>,
         cmd: Option<Vec<&str>>,
-    ) -> Result<String, AnyError> {
+    ) -> Result<String, RoozError> {
         let exec_id = self
             .create_exec(reason, container_id, None, user, cmd)
             .await?;
@@ -227,33 +282,118 @@ impl<'a> ExecApi<'a> {
         {
             collect(output).await
         } else {
-            panic!("Could not start exec");
+            Err(RoozError::Unexpected("exec not attached".into()))
+        }
+    }
+
+    // the content goes through stdin so it shows up in neither the exec's nor the container's config.
+    // It runs as the container user so the file is theirs, readable by them only
+    pub async fn write_file(
+        &self,
+        reason: &str,
+        container_id: &str,
+        path: &str,
+        content: &str,
+    ) -> Result<(), RoozError> {
+        log::debug!("[{}] write: {} in {}", reason, path, container_id);
+        let script = format!("umask 077 && cat > '{}'", path);
+        let exec_id = self
+            .client
+            .create_exec(
+                container_id,
+                CreateExecOptions {
+                    attach_stdin: Some(true),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    cmd: Some(vec!["sh", "-c", &script]),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .id;
+        if let StartExecResults::Attached {
+            output, mut input, ..
+        } = self.client.start_exec(&exec_id, None).await?
+        {
+            input.write_all(content.as_bytes()).await?;
+            input.shutdown().await?;
+            collect(output).await?;
         }
+        self.exit_status(&exec_id).await
     }
 
-    pub async fn chown(&self, container_id: &str, uid: &str, dir: &str) -> Result<(), AnyError> {
-        if let ContainerBackend::Podman = self.backend {
-            log::debug!("Podman won't need chown. Skipping");
-            return Ok(());
+    // a missing executable fails the exec start on Docker and exits with 127 on Podman
+    pub async fn has_shell(
+        &self,
+        container_id: &str,
+        user: Option<&str>,
+        shell: &str,
+    ) -> Result<bool, RoozError> {
+        log::debug!("[probe] shell: {} in {}", shell, container_id);
+        let exec_id = self
+            .client
+            .create_exec(
+                container_id,
+                CreateExecOptions {
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    cmd: Some(vec![shell, "-c", "exit 0"]),
+                    user,
+                    ..Default::default()
+                },
+            )
+            .await?
+            .id;
+        match self.client.start_exec(&exec_id, None).await {
+            Ok(StartExecResults::Attached { output, .. }) => {
+                collect(output).await.ok();
+            }
+            Ok(StartExecResults::Detached) => (),
+            Err(e) => {
+                log::debug!("[probe] {}", e);
+                return Ok(false);
+            }
+        }
+        Ok(self.exit_status(&exec_id).await.is_ok())
+    }
+
+    async fn is_idmapped(&self, container_id: &str) -> Result<bool, RoozError> {
+        Ok(self
+            .client
+            .inspect_container(container_id, None)
+            .await?
+            .host_config
+            .and_then(|c| c.userns_mode)
+            .is_some_and(|m| m.starts_with("keep-id")))
+    }
+
+    pub async fn chown(&self, container_id: &str, uid: &str, dir: &str) -> Result<(), RoozError> {
+        if let ContainerBackend::Podman { .. } = self.backend {
+            if !self.is_idmapped(container_id).await? {
+                log::debug!("Podman won't need chown. Skipping");
+                return Ok(());
+            }
         };
 
         log::debug!("Changing ownership... ({} {})", &uid, &dir);
 
-        let uid_format = format!("{}:{}", &uid, &uid);
+        // the mounts nested in dir are skipped, e.g. ~/.ssh holds the key shared by all the workspaces
+        let script = format!(
+            r#"d="{dir}"
+               set --
+               for m in $(awk -v d="$d" '$5 != d && index($5, d "/") == 1 {{ print $5 }}' /proc/self/mountinfo); do
+                 set -- "$@" -path "$m" -prune -o
+               done
+               find "$d" "$@" -exec chown -h {owner} {{}} +"#,
+            dir = dir.replace("~", "${ROOZ_META_HOME}"),
+            owner = uid::owner(uid),
+        );
         let chown_response = self
             .output(
                 "chown",
                 container_id,
                 Some(constants::ROOT_USER),
-                Some(vec![
-                    "sh",
-                    "-c",
-                    &format!(
-                        "chown -R {} {}",
-                        &uid_format,
-                        &dir.replace("~", "${ROOZ_META_HOME}")
-                    ),
-                ]),
+                Some(vec!["sh", "-c", &script]),
             )
             .await?;
 
@@ -261,13 +401,16 @@ impl<'a> ExecApi<'a> {
         Ok(())
     }
 
-    pub async fn ensure_user(&self, container_id: &str) -> Result<(), AnyError> {
+    pub async fn ensure_user(&self, container_id: &str) -> Result<(), RoozError> {
         let ensure_user_cmd = container::inject(
-            format!(
+            &[
+                format!(
                     r#"whoami > /dev/null 2>&1 && [ "$(whoami)" = "$ROOZ_META_USER" ] || \
-                       echo "$ROOZ_META_USER:x:$ROOZ_META_UID:$ROOZ_META_UID:$ROOZ_META_USER:$ROOZ_META_HOME:/bin/sh" >> /etc/passwd"#,
-            )
-            .as_ref(),
+                       echo "$ROOZ_META_USER:x:$ROOZ_META_UID:${{ROOZ_META_GID:-$ROOZ_META_UID}}:$ROOZ_META_USER:$ROOZ_META_HOME:/bin/sh" >> /etc/passwd"#,
+                ),
+                DOCKER_GROUP.to_string(),
+            ]
+            .join("\n"),
             "make_user.sh",
         );
 
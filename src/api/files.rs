@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use base64::{engine::general_purpose, Engine as _};
+
+use crate::{
+    api::{container, Api},
+    constants,
+    model::{error::RoozError, types::RunSpec, volume::RoozVolume},
+    util::{backend::ContainerBackend, id, labels::Labels, uid},
+};
+
+impl<'a> Api<'a> {
+    pub async fn write_files(
+        &self,
+        reason: &str,
+        volume: &RoozVolume,
+        uid: &str,
+        files: &[(String, String)],
+    ) -> Result<(), RoozError> {
+        if files.is_empty() {
+            return Ok(());
+        }
+        let root = "/tmp/rooz-vol";
+        let mut script = String::new();
+        for (path, content) in files {
+            let target = format!("{}/{}", root, path);
+            script.push_str(&format!(
+                "mkdir -p \"$(dirname '{}')\" && echo '{}' | base64 -d > '{}'\n",
+                target,
+                general_purpose::STANDARD.encode(content),
+                target
+            ));
+            if let ContainerBackend::Podman { .. } = self.container.backend {
+                continue;
+            }
+            if let Some(first) = Path::new(path).components().next() {
+                script.push_str(&format!(
+                    "chown -R {} '{}/{}'\n",
+                    uid::owner(uid),
+                    root,
+                    first.as_os_str().to_string_lossy()
+                ));
+            }
+        }
+
+        let workspace_key = volume.key().unwrap_or(id::random_suffix("files"));
+        let entrypoint = container::inject(&script, "write_files.sh");
+        let mut mount = volume.to_mount(None);
+        mount.target = Some(root.into());
+
+        let run_spec = RunSpec {
+            reason,
+            image: &self.image.helper,
+            uid: constants::ROOT_UID,
+            container_name: &id::random_suffix("rooz-files"),
+            workspace_key: &workspace_key,
+            mounts: Some(vec![mount]),
+            entrypoint: Some(entrypoint.iter().map(String::as_str).collect()),
+            auto_remove: false,
+            labels: Labels::new(Some(&workspace_key), Some("files")),
+            ..Default::default()
+        };
+
+        let result = self.container.create(run_spec).await?;
+        self.container.start(result.id()).await?;
+        self.container.logs_to_stdout(result.id()).await?;
+        self.container.remove(result.id(), true).await?;
+        Ok(())
+    }
+}
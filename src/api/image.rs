@@ -1,4 +1,6 @@
-use crate::{api::ImageApi, model::types::AnyError};
+use crate::{
+    api::ImageApi, config::config::RoozPullPolicy, model::error::RoozError, util::parallel,
+};
 use bollard::errors::Error;
 use bollard::errors::Error::DockerResponseServerError;
 use bollard::image::CreateImageOptions;
@@ -7,9 +9,65 @@ use bollard::service::ImageInspect;
 use futures::StreamExt;
 use std::io::{stdout, Write};
 
+// Docker Hub images are listed without the registry and library/ prefixes by some engines
+fn repository(image: &str) -> &str {
+    let name = image.split('@').next().unwrap_or(image);
+    // a colon followed by a slash belongs to the registry port, not to the tag
+    let name = match name.rfind(':') {
+        Some(i) if !name[i..].contains('/') => &name[..i],
+        _ => name,
+    };
+    name.trim_start_matches("docker.io/")
+        .trim_start_matches("library/")
+}
+
+// os/arch[/variant], e.g. linux/arm64/v8. The variant is left to the engine
+fn matches_platform(image: &ImageInspect, platform: &str) -> bool {
+    let mut parts = platform.split('/');
+    parts.next() == image.os.as_deref() && parts.next() == image.architecture.as_deref()
+}
+
 impl<'a> ImageApi<'a> {
-    async fn pull(&self, image: &str) -> Result<Option<String>, AnyError> {
-        println!("Pulling image: {}", &image);
+    // the digest of the manifest the local image was pulled by
+    pub async fn digest(&self, image: &str) -> Result<Option<String>, RoozError> {
+        let digests = self
+            .client
+            .inspect_image(image)
+            .await?
+            .repo_digests
+            .unwrap_or_default();
+        let repo = repository(image);
+        Ok(digests
+            .iter()
+            .find(|d| {
+                d.split_once('@')
+                    .is_some_and(|(r, _)| repository(r) == repo)
+            })
+            .or(digests.first())
+            .and_then(|d| d.split_once('@'))
+            .map(|(_, digest)| digest.to_string()))
+    }
+
+    pub async fn registry_digest(&self, image: &str) -> Result<Option<String>, RoozError> {
+        Ok(self
+            .client
+            .inspect_registry_image(image, None)
+            .await?
+            .descriptor
+            .digest)
+    }
+
+    #[tracing::instrument(name = "image.pull", level = "debug", skip_all, fields(id = %image))]
+    async fn pull(
+        &self,
+        image: &str,
+        platform: Option<&str>,
+        progress: bool,
+    ) -> Result<Option<String>, RoozError> {
+        match platform {
+            Some(p) => println!("Pulling image: {} ({})", &image, p),
+            None => println!("Pulling image: {}", &image),
+        }
         let img_chunks = &image.split(':').collect::<Vec<&str>>();
         let mut image_info = self.client.create_image(
             Some(CreateImageOptions::<&str> {
@@ -18,6 +76,7 @@ impl<'a> ImageApi<'a> {
                     2 => img_chunks[1],
                     _ => "latest",
                 },
+                platform: platform.unwrap_or_default(),
                 ..Default::default()
             }),
             None,
@@ -26,6 +85,9 @@ impl<'a> ImageApi<'a> {
 
         while let Some(l) = image_info.next().await {
             match l {
+                Ok(CreateImageInfo {
+                    status: Some(_), ..
+                }) if !progress => (),
                 Ok(CreateImageInfo {
                     id,
                     status: Some(m),
@@ -45,33 +107,113 @@ impl<'a> ImageApi<'a> {
                     };
                     print!("\r");
                 }
-                Ok(msg) => panic!("{:?}", msg),
+                Ok(msg) => return Err(RoozError::Unexpected(format!("{:?}", msg))),
                 Err(Error::DockerStreamError { error }) => eprintln!("{}", error),
-                e => panic!("{:?}", e),
+                Err(e) => return Err(e.into()),
             };
         }
-        println!("");
+        if progress {
+            println!("");
+        } else {
+            println!("Pulled image: {}", &image);
+        }
         Ok(self.client.inspect_image(&image).await?.id)
     }
 
-    pub async fn ensure(&self, image: &str, always_pull: bool) -> Result<String, AnyError> {
-        log::debug!("Ensuring image: {}", &image);
+    pub async fn ensure(&self, image: &str, policy: RoozPullPolicy) -> Result<String, RoozError> {
+        Ok(self.ensure_with(image, None, policy, true).await?.0)
+    }
+
+    // returns the images that had to be pulled
+    pub async fn ensure_all(
+        &self,
+        images: &[(&str, Option<&str>)],
+        policy: RoozPullPolicy,
+    ) -> Result<Vec<String>, RoozError> {
+        let mut images = images.to_vec();
+        images.sort();
+        images.dedup();
+        // layer progress of concurrent pulls would garble the output
+        let progress = images.len() == 1;
+        let results = parallel::run_all(
+            "ensure images",
+            images.iter().map(|(i, platform)| {
+                (i.to_string(), async move {
+                    self.ensure_with(i, *platform, policy, progress)
+                        .await
+                        .map(|(_, pulled)| pulled.then(|| i.to_string()))
+                })
+            }),
+        )
+        .await?;
+        Ok(results.into_iter().flatten().collect())
+    }
 
-        let image_id = match self.client.inspect_image(&image).await {
-            Ok(ImageInspect { id, .. }) => {
-                if always_pull {
-                    self.pull(image).await?
-                } else {
-                    id
+    async fn ensure_with(
+        &self,
+        image: &str,
+        platform: Option<&str>,
+        policy: RoozPullPolicy,
+        progress: bool,
+    ) -> Result<(String, bool), RoozError> {
+        log::debug!("Ensuring image: {} (pull: {:?})", &image, policy);
+
+        let (image_id, pulled) = match (self.client.inspect_image(&image).await, policy) {
+            (Ok(ImageInspect { id, .. }), RoozPullPolicy::Always) if !self.offline => {
+                (self.pull(image, platform, progress).await?.or(id), false)
+            }
+            // the local image is of another platform. The pull replaces the tag
+            (Ok(i), p)
+                if p != RoozPullPolicy::Never
+                    && platform.is_some_and(|platform| !matches_platform(&i, platform)) =>
+            {
+                if self.offline {
+                    return Err(format!(
+                        "Image {} is not available locally for {} and can't be pulled in offline mode",
+                        image,
+                        platform.unwrap_or_default()
+                    )
+                    .into());
                 }
+                (self.pull(image, platform, progress).await?, true)
+            }
+            (Ok(ImageInspect { id, .. }), _) => (id, false),
+            (
+                Err(DockerResponseServerError {
+                    status_code: 404, ..
+                }),
+                _,
+            ) if self.offline => {
+                return Err(format!(
+                    "Image {} is not available locally and can't be pulled in offline mode",
+                    image
+                )
+                .into())
+            }
+            (
+                Err(DockerResponseServerError {
+                    status_code: 404, ..
+                }),
+                RoozPullPolicy::Never,
+            ) => {
+                return Err(format!(
+                    "Image {} is not available locally and the pull policy is: never",
+                    image
+                )
+                .into())
             }
-            Err(DockerResponseServerError {
-                status_code: 404, ..
-            }) => self.pull(image).await?,
-            Err(e) => panic!("{:?}", e),
+            (
+                Err(DockerResponseServerError {
+                    status_code: 404, ..
+                }),
+                _,
+            ) => (self.pull(image, platform, progress).await?, true),
+            (Err(e), _) => return Err(e.into()),
         };
 
         log::debug!("Image ID: {:?}", image_id);
-        Ok(image_id.unwrap())
+        image_id
+            .map(|id| (id, pulled))
+            .ok_or(format!("Could not resolve image ID: {}", image).into())
     }
 }
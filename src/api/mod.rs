@@ -1,11 +1,17 @@
 use bollard::Docker;
 
-use crate::util::backend::ContainerBackend;
+use crate::{
+    cli::SecretsKey,
+    config::config::RoozProxyCfg,
+    util::{backend::ContainerBackend, progress::Progress},
+};
 
 pub mod config;
 pub mod container;
 pub mod crypt;
+pub mod events;
 pub mod exec;
+pub mod files;
 pub mod image;
 pub mod sidecar;
 pub mod volume;
@@ -13,24 +19,33 @@ pub mod workspace;
 
 pub struct ImageApi<'a> {
     pub client: &'a Docker,
+    pub offline: bool,
+    // runs git clones, volume population and the other one-shot operations
+    pub helper: String,
 }
 
 pub struct ExecApi<'a> {
     pub client: &'a Docker,
     pub backend: &'a ContainerBackend,
+    pub non_interactive: bool,
 }
 
 pub struct VolumeApi<'a> {
     pub client: &'a Docker,
+    pub progress: &'a dyn Progress,
 }
 
 pub struct ContainerApi<'a> {
     pub client: &'a Docker,
     pub backend: &'a ContainerBackend,
+    // set when connected via a named engine
+    pub engine: Option<&'a str>,
 }
 
 pub struct CryptApi<'a> {
     pub api: &'a Api<'a>,
+    pub non_interactive: bool,
+    pub key: SecretsKey,
 }
 
 pub struct Api<'a> {
@@ -39,15 +54,21 @@ pub struct Api<'a> {
     pub volume: &'a VolumeApi<'a>,
     pub container: &'a ContainerApi<'a>,
     pub client: &'a Docker,
+    pub progress: &'a dyn Progress,
 }
 
 pub struct GitApi<'a> {
     pub api: &'a Api<'a>,
+    pub offline: bool,
+    pub proxy: RoozProxyCfg,
+    // the host's known_hosts and ssh config get mounted into the clone and work containers
+    pub host_ssh: bool,
 }
 
 pub struct ConfigApi<'a> {
     pub api: &'a Api<'a>,
     pub crypt: &'a CryptApi<'a>,
+    pub non_interactive: bool,
 }
 
 pub struct WorkspaceApi<'a> {
@@ -4,111 +4,247 @@ use bollard::network::CreateNetworkOptions;
 
 use crate::{
     api::WorkspaceApi,
-    config::config::{RoozCfg, RoozSidecar},
+    config::{
+        config::{RoozCfg, RoozDockerMode, RoozSidecar},
+        runtime::RuntimeConfig,
+    },
     constants,
-    model::{
-        types::{AnyError, RunSpec},
-        volume::RoozVolume,
+    model::{error::RoozError, types::RunSpec, volume::RoozVolume},
+    util::{
+        labels::{self, Labels},
+        parallel, progress,
     },
-    util::labels::{self, Labels},
 };
 
+pub struct SidecarInstance<'b> {
+    pub sidecar: &'b str,
+    // replicas are named <sidecar>-<n>
+    pub name: String,
+    pub container_name: String,
+    pub spec: &'b RoozSidecar,
+}
+
+impl SidecarInstance<'_> {
+    fn replicated(&self) -> bool {
+        self.name != self.sidecar
+    }
+
+    pub fn volumes(
+        &self,
+        workspace_key: &str,
+        volumes_key: &str,
+        work_dir: &str,
+    ) -> Vec<RoozVolume> {
+        let mut volumes = self
+            .spec
+            .mounts
+            .iter()
+            .flatten()
+            .map(|path| {
+                let volume = RoozVolume::sidecar_data(workspace_key, path);
+                if self.replicated() {
+                    volume.for_replica(&self.name)
+                } else {
+                    volume
+                }
+            })
+            .collect::<Vec<_>>();
+        if let Some(true) = self.spec.mount_work {
+            volumes.push(RoozVolume::work(volumes_key, work_dir));
+        }
+        volumes
+    }
+}
+
+pub fn sidecar_instances<'b>(
+    cfg: &'b RuntimeConfig,
+    workspace_key: &str,
+) -> Vec<SidecarInstance<'b>> {
+    cfg.sidecars
+        .iter()
+        .flat_map(|(sidecar, s)| {
+            s.instances(sidecar)
+                .into_iter()
+                .map(move |name| SidecarInstance {
+                    sidecar,
+                    container_name: format!("{}-{}", workspace_key, name),
+                    name,
+                    spec: s,
+                })
+        })
+        .collect()
+}
+
+// the workspace gets a network of its own once the work container isn't alone
+pub fn needs_network(cfg: &RuntimeConfig) -> bool {
+    !cfg.sidecars.is_empty()
+        || !cfg.containers.is_empty()
+        || cfg.docker == Some(RoozDockerMode::Dind)
+}
+
 impl<'a> WorkspaceApi<'a> {
+    // sidecars are reached by name on the workspace network, never through the proxy
+    pub fn proxy_env(&self, cfg: &RuntimeConfig) -> HashMap<String, String> {
+        let proxy = match &cfg.proxy {
+            Some(p) => p.or(&self.git.proxy),
+            None => self.git.proxy.clone(),
+        };
+        proxy
+            .with_no_proxy(
+                ["localhost", "127.0.0.1"]
+                    .into_iter()
+                    .chain(cfg.sidecars.keys().map(String::as_str)),
+            )
+            .env()
+    }
+
+    pub async fn ensure_network(&self, workspace_key: &str) -> Result<String, RoozError> {
+        let labels = &Labels::new(Some(workspace_key), None);
+        let network_options = CreateNetworkOptions::<&str> {
+            name: &workspace_key,
+            check_duplicate: true,
+            labels: labels.into(),
+            ..Default::default()
+        };
+
+        match self.api.client.create_network(network_options).await {
+            Ok(_) => (),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 409,
+                message,
+            }) => {
+                log::debug!("Could not create network: {}", message);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(workspace_key.to_string())
+    }
+
+    pub async fn resolve_network(
+        &self,
+        workspace_key: &str,
+        cfg: &RuntimeConfig,
+    ) -> Result<Option<String>, RoozError> {
+        match &cfg.network {
+            Some(name) => match self.api.client.inspect_network::<String>(name, None).await {
+                Ok(_) => Ok(Some(name.to_string())),
+                Err(bollard::errors::Error::DockerResponseServerError {
+                    status_code: 404, ..
+                }) => Err(format!("Network not found: {}", name).into()),
+                Err(e) => Err(e.into()),
+            },
+            None if needs_network(cfg) => Ok(Some(self.ensure_network(workspace_key).await?)),
+            None => Ok(None),
+        }
+    }
+
+    // the labels and volume mounts are left to the caller
+    pub fn sidecar_run_spec<'b>(
+        &self,
+        cfg: &'b RuntimeConfig,
+        workspace_key: &'b str,
+        instance: &'b SidecarInstance,
+        network: Option<&'b str>,
+        work_dir: &'b str,
+    ) -> RunSpec<'b> {
+        let s = instance.spec;
+        let expose = cfg.expose.clone().unwrap_or_default();
+        let mut extra_networks = s.networks.clone().unwrap_or_default();
+        extra_networks.extend(expose.extra_network(&s.expose.clone().unwrap_or_default(), network));
+        let mut ports = HashMap::<String, Option<String>>::new();
+        RoozCfg::parse_ports(&mut ports, s.ports.clone());
+        RunSpec {
+            container_name: &instance.container_name,
+            uid: &s.user.as_deref().unwrap_or(&constants::ROOT_UID),
+            image: &s.image,
+            workspace_key,
+            env: Some(
+                self.proxy_env(cfg)
+                    .into_iter()
+                    .chain(s.env.iter().flatten().map(|(k, v)| (k.clone(), v.clone())))
+                    .collect::<HashMap<_, _>>(),
+            ),
+            network,
+            // replicas also share the sidecar name so it resolves to all of them
+            network_aliases: Some(
+                [instance.name.to_string()]
+                    .into_iter()
+                    .chain(instance.replicated().then(|| instance.sidecar.to_string()))
+                    .chain(s.aliases.iter().flatten().cloned())
+                    .collect(),
+            ),
+            extra_networks: Some(extra_networks),
+            command: s
+                .command
+                .as_ref()
+                .map(|x| x.iter().map(|z| z.as_ref()).collect()),
+            ports: Some(ports),
+            auto_ports: cfg.auto_ports,
+            bind_address: cfg.bind_address.as_deref(),
+            work_dir: Some(s.work_dir.as_deref().unwrap_or(work_dir)),
+            restart: s.restart,
+            security: s.security(),
+            dns: s.dns(),
+            ..Default::default()
+        }
+    }
+
+    #[tracing::instrument(name = "sidecars.ensure", level = "debug", skip_all, fields(id = %workspace_key))]
     pub async fn ensure_sidecars(
         &self,
-        sidecars: &HashMap<String, RoozSidecar>,
+        cfg: &RuntimeConfig,
         workspace_key: &str,
+        volumes_key: &str,
         force: bool,
-        pull_image: bool,
         work_dir: &str,
-    ) -> Result<Option<String>, AnyError> {
+    ) -> Result<Option<String>, RoozError> {
         let labels = &Labels::new(Some(workspace_key), None);
+        let resolved_network = self.resolve_network(workspace_key, cfg).await?;
+        let network = resolved_network.as_deref();
 
-        let network = if !sidecars.is_empty() {
-            let network_options = CreateNetworkOptions::<&str> {
-                name: &workspace_key,
-                check_duplicate: true,
-                labels: labels.into(),
-                ..Default::default()
-            };
-
-            match self.api.client.create_network(network_options).await {
-                Ok(_) => (),
-                Err(bollard::errors::Error::DockerResponseServerError {
-                    status_code: 409,
-                    message,
-                }) => {
-                    log::debug!("Could not create network: {}", message);
-                }
-                e => panic!("{:?}", e),
-            };
-            Some(workspace_key.as_ref())
-        } else {
-            None
-        };
+        let expose = &cfg.expose.clone().unwrap_or_default();
+        let instances = sidecar_instances(cfg, workspace_key);
 
-        for (name, s) in sidecars {
-            log::debug!("Process sidecar: {}", name);
-            self.api.image.ensure(&s.image, pull_image).await?;
-            let container_name = format!("{}-{}", workspace_key, name);
-            let labels = labels
-                .clone()
-                .with_container(Some(&name))
-                .with_role(labels::ROLE_SIDECAR);
-            let mut ports = HashMap::<String, Option<String>>::new();
-            RoozCfg::parse_ports(&mut ports, s.ports.clone());
-
-            let mut mounts = Vec::<RoozVolume>::new();
-
-            let auto_mounts = s.mounts.as_ref().map(|paths| {
-                paths
-                    .iter()
-                    .map(|path| RoozVolume::sidecar_data(workspace_key, path))
-                    .collect::<Vec<_>>()
-            });
-
-            if let Some(v) = auto_mounts {
-                mounts.extend_from_slice(&v.as_slice());
-            }
+        let sidecars = instances.iter().map(|instance| {
+            let task = async move {
+                log::debug!("Process sidecar: {}", instance.name);
+                let step = progress::step(
+                    self.api.progress,
+                    format!("Creating sidecar {}", instance.name),
+                );
+                let s = instance.spec;
+                let exposed_hosts = s.expose.clone().unwrap_or_default();
+                let expose_labels = expose.labels(&instance.container_name, &exposed_hosts)?;
+                let labels = labels
+                    .clone()
+                    .with_container(Some(&instance.name))
+                    .with_role(labels::ROLE_SIDECAR)
+                    .with_image_digest(self.api.image.digest(&s.image).await?)
+                    .with_custom(s.labels.iter().flatten())?
+                    .with_custom(expose_labels.iter().map(|(k, v)| (k, v)))?;
+                let volumes = instance.volumes(workspace_key, volumes_key, work_dir);
 
-            let work_mount = if let Some(true) = s.mount_work {
-                Some(vec![RoozVolume::work(workspace_key, work_dir)])
-            } else {
-                None
+                let result = self
+                    .api
+                    .container
+                    .create(RunSpec {
+                        force_recreate: force,
+                        labels,
+                        mounts: Some(self.api.volume.ensure_mounts(&volumes, None).await?),
+                        ..self.sidecar_run_spec(cfg, workspace_key, instance, network, work_dir)
+                    })
+                    .await?;
+                step.done();
+                Ok::<_, RoozError>(result)
             };
+            (instance.name.clone(), task)
+        });
 
-            if let Some(v) = work_mount {
-                mounts.extend_from_slice(&v.as_slice());
-            }
-
-            self.api
-                .container
-                .create(RunSpec {
-                    container_name: &container_name,
-                    uid: &s.user.as_deref().unwrap_or(&constants::ROOT_UID),
-                    image: &s.image,
-                    force_recreate: force,
-                    workspace_key: &workspace_key,
-                    labels,
-                    env: s.env.clone().map(|x| {
-                        x.iter()
-                            .map(|(k, v)| (k.clone(), v.clone()))
-                            .collect::<HashMap<_, _>>()
-                    }),
-                    network,
-                    network_aliases: Some(vec![name.into()]),
-                    command: s
-                        .command
-                        .as_ref()
-                        .map(|x| x.iter().map(|z| z.as_ref()).collect()),
-                    mounts: Some(self.api.volume.ensure_mounts(&mounts, None).await?),
-                    ports: Some(ports),
-                    work_dir: Some(s.work_dir.as_deref().unwrap_or(work_dir)),
-                    ..Default::default()
-                })
-                .await?;
+        parallel::run_all("create sidecars", sidecars).await?;
+        if cfg.docker == Some(RoozDockerMode::Dind) {
+            self.ensure_dind(workspace_key, network, force).await?;
         }
 
-        Ok(network.map(|n| n.to_string()))
+        Ok(resolved_network)
     }
 }
@@ -1,38 +1,32 @@
 use crate::{
     api::VolumeApi,
     model::{
-        types::{AnyError, VolumeResult},
+        error::RoozError,
+        types::VolumeResult,
         volume::{RoozVolume, RoozVolumeRole},
     },
     util::labels::Labels,
 };
 use bollard::{errors::Error::DockerResponseServerError, volume::RemoveVolumeOptions};
 use bollard::{service::Mount, volume::CreateVolumeOptions};
+use std::time::Instant;
 
 impl<'a> VolumeApi<'a> {
     async fn create_volume(
         &self,
         options: CreateVolumeOptions<&str>,
-    ) -> Result<VolumeResult, AnyError> {
-        match &self.client.create_volume(options).await {
-            Ok(v) => {
-                log::debug!("Volume created: {:?}", v.name);
-                return Ok(VolumeResult::Created);
-            }
-            Err(e) => panic!("{}", e),
-        }
+    ) -> Result<VolumeResult, RoozError> {
+        let v = self.client.create_volume(options).await?;
+        log::debug!("Volume created: {:?}", v.name);
+        Ok(VolumeResult::Created)
     }
 
-    pub async fn remove_volume(&self, name: &str, force: bool) -> Result<(), AnyError> {
+    pub async fn remove_volume(&self, name: &str, force: bool) -> Result<(), RoozError> {
         let options = RemoveVolumeOptions { force };
-        match &self.client.remove_volume(name, Some(options)).await {
-            Ok(_) => {
-                let force_display = if force { " (force)" } else { "" };
-                log::debug!("Volume removed: {} {}", &name, &force_display);
-                return Ok(());
-            }
-            Err(e) => panic!("{}", e),
-        }
+        self.client.remove_volume(name, Some(options)).await?;
+        let force_display = if force { " (force)" } else { "" };
+        log::debug!("Volume removed: {} {}", &name, &force_display);
+        Ok(())
     }
 
     pub async fn ensure_volume(
@@ -41,13 +35,26 @@ impl<'a> VolumeApi<'a> {
         role: &RoozVolumeRole,
         workspace_key: Option<String>,
         force_recreate: bool,
-    ) -> Result<VolumeResult, AnyError> {
+    ) -> Result<VolumeResult, RoozError> {
+        self.ensure_volume_with(name, role, workspace_key, false, force_recreate)
+            .await
+    }
+
+    async fn ensure_volume_with(
+        &self,
+        name: &str,
+        role: &RoozVolumeRole,
+        workspace_key: Option<String>,
+        keep: bool,
+        force_recreate: bool,
+    ) -> Result<VolumeResult, RoozError> {
         let workspace_key_label = match role {
             RoozVolumeRole::Cache => None,
             _ => workspace_key,
         };
 
-        let labels = Labels::new(workspace_key_label.as_deref(), Some(role.as_str()));
+        let labels =
+            Labels::new(workspace_key_label.as_deref(), Some(role.as_str())).with_keep(keep);
 
         let create_vol_options = CreateVolumeOptions::<&str> {
             name,
@@ -69,7 +76,7 @@ impl<'a> VolumeApi<'a> {
                 status_code: 404,
                 message: _,
             }) => return self.create_volume(create_vol_options).await,
-            Err(e) => panic!("{}", e),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -77,13 +84,21 @@ impl<'a> VolumeApi<'a> {
         &self,
         volumes: &Vec<RoozVolume>,
         tilde_replacement: Option<&str>,
-    ) -> Result<Vec<Mount>, AnyError> {
+    ) -> Result<Vec<Mount>, RoozError> {
         let mut mounts = vec![];
         for v in volumes {
             log::debug!("Process volume: {:?}", &v);
             let mount = v.to_mount(tilde_replacement);
-            self.ensure_volume(&mount.source.clone().unwrap(), &v.role, v.key(), false)
-                .await?;
+            let name = mount.source.clone().unwrap();
+            let start = Instant::now();
+            // most volumes already exist, so only the created ones get reported
+            if let VolumeResult::Created = self
+                .ensure_volume_with(&name, &v.role, v.key(), v.keep, false)
+                .await?
+            {
+                self.progress
+                    .finished(&format!("Creating volume {}", name), start.elapsed(), true);
+            }
 
             mounts.push(mount);
         }
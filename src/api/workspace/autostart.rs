@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use colored::Colorize;
+
+use crate::{
+    api::WorkspaceApi,
+    config::config::RoozRestart,
+    model::error::RoozError,
+    util::{
+        labels::{self, Labels},
+        parallel,
+    },
+};
+
+impl<'a> WorkspaceApi<'a> {
+    // enabling sets unless-stopped on the work and sidecar containers,
+    // disabling restores the restart policies from the workspace config
+    pub async fn autostart(&self, workspace_key: &str, enable: bool) -> Result<(), RoozError> {
+        let cfg = self
+            .runtime_config(workspace_key)
+            .await?
+            .unwrap_or_default();
+        let configured = cfg
+            .sidecars
+            .iter()
+            .flat_map(|(name, s)| {
+                s.instances(name)
+                    .into_iter()
+                    .map(move |instance| (instance, s.restart))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let containers = self
+            .api
+            .container
+            .get_all(&Labels::new(Some(workspace_key), None))
+            .await?
+            .into_iter()
+            .filter_map(|c| {
+                let labels = c.labels.clone().unwrap_or_default();
+                // one-shot and init containers are never restarted
+                let restart = match labels.get(labels::ROLE).map(|r| r.as_str()) {
+                    Some(labels::ROLE_WORK) => cfg.restart,
+                    Some(labels::ROLE_SIDECAR) => labels
+                        .get(labels::CONTAINER)
+                        .and_then(|name| configured.get(name).cloned())
+                        .flatten(),
+                    _ => return None,
+                };
+                let restart = match enable {
+                    true => RoozRestart::UnlessStopped,
+                    false => restart.unwrap_or(RoozRestart::No),
+                };
+                Some((c, restart))
+            })
+            .collect::<Vec<_>>();
+
+        if containers.is_empty() {
+            return Err(RoozError::WorkspaceNotFound(workspace_key.to_string()));
+        }
+
+        let tasks = containers.into_iter().map(|(c, restart)| {
+            let name = c.names.unwrap_or_default().join(", ");
+            let container = name.clone();
+            let task = async move {
+                self.api
+                    .container
+                    .set_restart(&c.id.unwrap(), restart)
+                    .await?;
+                println!("Setting restart policy: {} ... {}", name, "OK".green());
+                Ok::<_, RoozError>(())
+            };
+            (container, task)
+        });
+        parallel::run_all("set restart policies", tasks).await?;
+        Ok(())
+    }
+}
@@ -1,56 +1,71 @@
-use std::path::Path;
+use bollard::models::Mount;
 
 use crate::{
     api::WorkspaceApi,
     constants,
     model::{
-        types::{AnyError, ContainerResult, RunSpec, WorkSpec, WorkspaceResult},
+        error::RoozError,
+        types::{ContainerResult, RunSpec, WorkSpec, WorkspaceResult},
         volume::RoozVolume,
     },
     util::ssh,
 };
 
-impl<'a> WorkspaceApi<'a> {
-    pub async fn create(&self, spec: &WorkSpec<'a>) -> Result<WorkspaceResult, AnyError> {
-        let home_dir = format!("/home/{}", &spec.user);
+// the volumes of a work container along with the home dir they get mounted relative to
+pub fn work_volumes(spec: &WorkSpec) -> (String, Vec<RoozVolume>) {
+    let home_dir = spec
+        .home_dir
+        .map(|h| h.to_string())
+        .unwrap_or(format!("/home/{}", &spec.user));
 
-        let mut volumes = vec![
-            RoozVolume::home(spec.container_name.into(), &home_dir),
-            RoozVolume::work(spec.container_name, constants::WORK_DIR),
-        ];
+    let volumes_key = spec.volumes_key.unwrap_or(spec.container_name);
 
-        if let Some(caches) = &spec.caches {
-            log::debug!("Processing caches");
-            let cache_vols = caches
-                .iter()
-                .map(|p| RoozVolume::cache(p))
-                .collect::<Vec<_>>();
+    let mut volumes = vec![
+        RoozVolume::home(volumes_key, &home_dir),
+        RoozVolume::work(volumes_key, constants::WORK_DIR),
+    ];
 
-            for c in caches {
-                log::debug!("Cache: {}", c);
-            }
+    if let Some(caches) = &spec.caches {
+        log::debug!("Processing caches");
+        let cache_vols = caches
+            .iter()
+            .map(|p| RoozVolume::from_cache_entry(volumes_key, p))
+            .collect::<Vec<_>>();
 
-            volumes.extend_from_slice(cache_vols.clone().as_slice());
-        } else {
-            log::debug!("No caches configured. Skipping");
+        for c in caches {
+            log::debug!("Cache: {}", c);
         }
 
-        let mut mounts = self
-            .api
-            .volume
-            .ensure_mounts(&volumes, Some(&home_dir))
-            .await?;
+        volumes.extend_from_slice(cache_vols.clone().as_slice());
+    } else {
+        log::debug!("No caches configured. Skipping");
+    }
 
-        mounts.push(ssh::mount(
-            Path::new(&home_dir).join(".ssh").to_string_lossy().as_ref(),
-        ));
+    if let Some(user_volumes) = &spec.volumes {
+        let mut names = user_volumes.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            log::debug!("Volume: {}", name);
+            volumes.push(RoozVolume::user(volumes_key, name, &user_volumes[name]));
+        }
+    }
+    (home_dir, volumes)
+}
 
-        mounts.push(
-            self.crypt
-                .mount(Path::new(&home_dir).join(".age").to_string_lossy().as_ref()),
-        );
+impl<'a> WorkspaceApi<'a> {
+    // what the container engine gets asked to create, also printed by the dry-run plan
+    pub fn work_run_spec<'b>(
+        &self,
+        spec: &'b WorkSpec,
+        home_dir: &'b str,
+        mut mounts: Vec<Mount>,
+    ) -> RunSpec<'b> {
+        // container paths, so never joined with the host path separator
+        mounts.push(ssh::mount(&format!("{}/.ssh", home_dir)));
+        mounts.push(self.crypt.mount(&format!("{}/.age", home_dir)));
+        mounts.extend(spec.mounts.iter().cloned());
 
-        let run_spec = RunSpec {
+        RunSpec {
             reason: "work",
             image: &spec.image,
             uid: &spec.uid,
@@ -62,17 +77,40 @@ impl<'a> WorkspaceApi<'a> {
             mounts: Some(mounts),
             entrypoint: Some(vec!["cat"]),
             privileged: spec.privileged,
+            security: spec.security.clone(),
+            dns: spec.dns.clone(),
             force_recreate: spec.force_recreate,
             auto_remove: spec.ephemeral,
             labels: spec.labels.clone(),
             network: spec.network,
+            network_aliases: spec.network_aliases.clone(),
+            extra_networks: spec.extra_networks.clone(),
             env: spec.env_vars.clone(),
             ports: spec.ports.clone(),
+            auto_ports: spec.auto_ports,
+            bind_address: spec.bind_address,
+            idmap: Some(spec.idmap),
+            restart: spec.restart,
+            platform: spec.platform,
             ..Default::default()
-        };
+        }
+    }
+
+    pub async fn create(&self, spec: &WorkSpec<'a>) -> Result<WorkspaceResult, RoozError> {
+        let (home_dir, volumes) = work_volumes(spec);
+        let mounts = self
+            .api
+            .volume
+            .ensure_mounts(&volumes, Some(&home_dir))
+            .await?;
+        let run_spec = self.work_run_spec(spec, &home_dir, mounts);
 
         match self.api.container.create(run_spec).await? {
-        ContainerResult::Created { .. } =>
+        ContainerResult::AlreadyExists { .. } if !spec.keep_existing => {
+            Err(format!("Container already exists. Did you mean: rooz enter {}? Otherwise, use --apply to reconfigure containers or --replace to recreate the whole workspace.", spec.workspace_key).into())
+        }
+
+        _ =>
 
             Ok(
                 WorkspaceResult {
@@ -80,10 +118,6 @@ impl<'a> WorkspaceApi<'a> {
                     working_dir: (&spec).container_working_dir.to_string(),
                     orig_uid: spec.uid.to_string(),
                     volumes: volumes.iter().map(|v|v.clone()).collect::<Vec<_>>() }),
-
-        ContainerResult::AlreadyExists { .. } => {
-            Err(format!("Container already exists. Did you mean: rooz enter {}? Otherwise, use --apply to reconfigure containers or --replace to recreate the whole workspace.", spec.workspace_key).into())
-        }
     }
     }
 }
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use bollard::models::{Mount, MountTypeEnum};
+
+use crate::{
+    api::WorkspaceApi,
+    config::config::RoozDockerMode,
+    constants,
+    model::{error::RoozError, types::RunSpec, volume::RoozVolume},
+    util::{
+        backend::ContainerBackend,
+        labels::{self, Labels},
+    },
+};
+
+pub const DIND_IMAGE: &str = "docker.io/library/docker:dind";
+// the sidecar name, also resolving to the daemon on the workspace network
+pub const DIND_NAME: &str = "docker";
+const CERTS_DIR: &str = "/certs";
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+impl<'a> WorkspaceApi<'a> {
+    // the path on the container engine host. The one of rootless Podman depends on the host user
+    fn host_socket(&self) -> Result<&str, RoozError> {
+        match self.api.container.backend {
+            ContainerBackend::Podman { rootless: false } => Ok("/run/podman/podman.sock"),
+            ContainerBackend::Podman { rootless: true } => Err(RoozError::Config(
+                "docker = \"socket\" is not supported on rootless Podman. Use docker = \"dind\""
+                    .into(),
+            )),
+            _ => Ok(DOCKER_SOCKET),
+        }
+    }
+
+    // the mounts and env the docker CLI of the work container needs. The socket is always mounted
+    // at the Docker default path so the CLI finds it either way
+    pub fn docker_client(
+        &self,
+        mode: RoozDockerMode,
+        workspace_key: &str,
+    ) -> Result<(Vec<Mount>, HashMap<String, String>), RoozError> {
+        Ok(match mode {
+            RoozDockerMode::Socket => (
+                vec![Mount {
+                    typ: Some(MountTypeEnum::BIND),
+                    source: Some(self.host_socket()?.into()),
+                    target: Some(DOCKER_SOCKET.into()),
+                    ..Default::default()
+                }],
+                [(
+                    "DOCKER_HOST".to_string(),
+                    format!("unix://{}", DOCKER_SOCKET),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+            RoozDockerMode::Dind => (
+                vec![Mount {
+                    read_only: Some(true),
+                    ..RoozVolume::sidecar_data(workspace_key, CERTS_DIR).to_mount(None)
+                }],
+                [
+                    ("DOCKER_HOST", format!("tcp://{}:2376", DIND_NAME)),
+                    ("DOCKER_TLS_VERIFY", "1".into()),
+                    ("DOCKER_CERT_PATH", format!("{}/client", CERTS_DIR)),
+                ]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            ),
+        })
+    }
+
+    // the daemon generates the CA, server and client certs into the certs volume on its first start.
+    // The work container mounts the volume read-only to get the client ones
+    pub async fn ensure_dind(
+        &self,
+        workspace_key: &str,
+        network: Option<&str>,
+        force: bool,
+    ) -> Result<(), RoozError> {
+        let mounts = self
+            .api
+            .volume
+            .ensure_mounts(
+                &vec![
+                    RoozVolume::sidecar_data(workspace_key, CERTS_DIR),
+                    RoozVolume::sidecar_data(workspace_key, "/var/lib/docker"),
+                ],
+                None,
+            )
+            .await?;
+        let container_name = format!("{}-{}", workspace_key, DIND_NAME);
+        self.api
+            .container
+            .create(RunSpec {
+                reason: "dind",
+                image: DIND_IMAGE,
+                uid: constants::ROOT_UID,
+                container_name: &container_name,
+                workspace_key,
+                mounts: Some(mounts),
+                privileged: true,
+                force_recreate: force,
+                labels: Labels::new(Some(workspace_key), Some(labels::ROLE_SIDECAR))
+                    .with_container(Some(DIND_NAME)),
+                env: Some(
+                    [("DOCKER_TLS_CERTDIR".to_string(), CERTS_DIR.to_string())]
+                        .into_iter()
+                        .collect(),
+                ),
+                network,
+                network_aliases: Some(vec![DIND_NAME.into()]),
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+}
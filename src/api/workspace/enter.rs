@@ -1,21 +1,120 @@
 use std::{
     process::{Command, Stdio},
     thread::sleep,
-    time::Duration,
 };
 
+use bollard::service::ContainerSummary;
+use colored::Colorize;
+
 use crate::{
     api::WorkspaceApi,
-    config::runtime::RuntimeConfig,
+    config::{
+        config::{RoozIdeCfg, RoozMux},
+        runtime::RuntimeConfig,
+        system::LastUsed,
+    },
     constants,
-    model::{types::AnyError, volume::RoozVolume},
-    util::labels::{self, Labels},
+    model::{error::RoozError, types::ReconnectPolicy, volume::RoozVolume},
+    util::{
+        labels::{self, Labels},
+        term,
+    },
 };
 
+const SSH_PORT: &str = "22";
+const DEFAULT_IDE_PRODUCT: &str = "IU";
+// scratch-ish images rarely have the configured shell but often have one of these
+pub const FALLBACK_SHELLS: [&str; 2] = ["bash", "sh"];
+
+// percent-encodes a deep link query value
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn open_command(url: &str) -> Command {
+    let mut command = Command::new("open");
+    command.arg(url);
+    command
+}
+
+#[cfg(windows)]
+fn open_command(url: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.args(["/C", "start", "", url]);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn open_command(url: &str) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(url);
+    command
+}
+
+fn mux_command(mux: RoozMux, session: &str, shell: &[String]) -> Vec<String> {
+    let (name, command) = match mux {
+        RoozMux::Tmux => ("tmux", r#"exec tmux new-session -A -s "$0" "$@""#),
+        RoozMux::Zellij => (
+            "zellij",
+            r#"SHELL="$(command -v "$1")" exec zellij attach --create "$0""#,
+        ),
+    };
+    let script = format!(
+        r#"command -v {} > /dev/null 2>&1 || {{ echo "{} not found in the container image" >&2; exit 127; }}; {}"#,
+        name, name, command
+    );
+    let mut args = vec!["sh".into(), "-c".into(), script, session.into()];
+    args.extend_from_slice(shell);
+    args
+}
+
 impl<'a> WorkspaceApi<'a> {
-    pub async fn attach_vscode(&self, workspace_key: &str) -> Result<(), AnyError> {
+    pub async fn runtime_config(
+        &self,
+        workspace_key: &str,
+    ) -> Result<Option<RuntimeConfig>, RoozError> {
+        let labels = Labels::new(Some(workspace_key), Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+        match self
+            .api
+            .container
+            .get_single(&labels)
+            .await?
+            .and_then(|c| c.labels)
+            .and_then(|l| l.get(labels::RUNTIME_CONFIG).cloned())
+        {
+            Some(config) => Ok(Some(RuntimeConfig::from_string(config)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn attach_vscode(&self, workspace_key: &str) -> Result<(), RoozError> {
         self.start(workspace_key).await?;
 
+        let labels = Labels::new(Some(workspace_key), Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+        let working_dir = match self.api.container.get_single(&labels).await? {
+            Some(ContainerSummary { id: Some(id), .. }) => self
+                .api
+                .client
+                .inspect_container(&id, None)
+                .await?
+                .config
+                .and_then(|c| c.working_dir)
+                .filter(|d| !d.is_empty()),
+            _ => None,
+        }
+        .unwrap_or(constants::WORK_DIR.to_string());
+
         let hex = format!(r#"{{"containerName":"{}"}}"#, workspace_key)
             .as_bytes()
             .iter()
@@ -24,42 +123,185 @@ impl<'a> WorkspaceApi<'a> {
             .join("");
         let mut command = Command::new("code");
         command.arg("--folder-uri");
-        command.arg(format!("vscode-remote://attached-container+{}/work", hex));
+        command.arg(format!(
+            "vscode-remote://attached-container+{}{}",
+            hex, working_dir
+        ));
         command.stdout(Stdio::null());
         command.stderr(Stdio::null());
         match command.spawn() {
             Ok(_) => Ok(()),
-            Err(e) => Err(Box::new(e)),
+            Err(e) => Err(e.into()),
         }
     }
 
+    pub async fn attach_gateway(&self, workspace_key: &str, print: bool) -> Result<(), RoozError> {
+        self.start(workspace_key).await?;
+
+        let labels = Labels::new(Some(workspace_key), Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+        let Some(ContainerSummary {
+            id: Some(id),
+            labels: Some(container_labels),
+            ..
+        }) = self.api.container.get_single(&labels).await?
+        else {
+            return Err(RoozError::WorkspaceNotFound(workspace_key.to_string()));
+        };
+
+        let cfg = match container_labels.get(labels::RUNTIME_CONFIG) {
+            Some(config) => RuntimeConfig::from_string(config.clone())?,
+            None => RuntimeConfig::default(),
+        };
+
+        // Gateway connects over SSH so the workspace has to publish its sshd port
+        let port = container_labels
+            .get(labels::PORTS)
+            .into_iter()
+            .flat_map(|p| p.split(','))
+            .filter_map(|p| p.split_once("->"))
+            .find(|(_, container)| container.trim_end_matches("/tcp") == SSH_PORT)
+            .map(|(host, _)| host.rsplit(':').next().unwrap_or(host).to_string())
+            .ok_or(RoozError::Message(format!(
+                "Workspace {} does not publish port {}. Run sshd in the image and add e.g. '2222:{}' to ports",
+                workspace_key, SSH_PORT, SSH_PORT
+            )))?;
+
+        let ide = cfg.ide.unwrap_or(RoozIdeCfg {
+            product: None,
+            project: None,
+        });
+
+        let project = match ide.project {
+            Some(project) => project,
+            None => self
+                .api
+                .client
+                .inspect_container(&id, None)
+                .await?
+                .config
+                .and_then(|c| c.working_dir)
+                .filter(|d| !d.is_empty())
+                .unwrap_or(constants::WORK_DIR.to_string()),
+        };
+
+        let link = format!(
+            "jetbrains-gateway://connect#type=ssh&deploy=true&productCode={}&host=localhost&port={}&user={}&projectPath={}",
+            encode(ide.product.as_deref().unwrap_or(DEFAULT_IDE_PRODUCT)),
+            port,
+            encode(&cfg.user),
+            encode(&project)
+        );
+
+        if print {
+            println!("{}", link);
+            return Ok(());
+        }
+
+        let mut command = open_command(&link);
+        command.stdout(Stdio::null());
+        command.stderr(Stdio::null());
+        if let Err(e) = command.spawn() {
+            log::debug!("Could not open the Gateway link: {}", e);
+            println!(
+                "Open the below link to connect JetBrains Gateway:\n{}",
+                link
+            );
+        }
+        Ok(())
+    }
+
+    // the configured shell, else the first fallback the container has
+    pub async fn probe_shell(
+        &self,
+        container_id: &str,
+        user: Option<&str>,
+        shell: &[String],
+    ) -> Result<Option<Vec<String>>, RoozError> {
+        let fallbacks = FALLBACK_SHELLS
+            .iter()
+            .filter(|s| shell.first().map(|f| f.as_str()) != Some(**s))
+            .map(|s| vec![s.to_string()]);
+        for candidate in std::iter::once(shell.to_vec()).chain(fallbacks) {
+            if let Some(exe) = candidate.first() {
+                if self.api.exec.has_shell(container_id, user, exe).await? {
+                    return Ok(Some(candidate));
+                }
+            }
+        }
+        Ok(None)
+    }
+
     pub async fn enter(
         &self,
         workspace_key: &str,
         working_dir: Option<&str>,
         shell: Option<Vec<&str>>,
+        command: Option<&str>,
         container_id: Option<&str>,
         volumes: Vec<RoozVolume>,
         chown_uid: &str,
         root: bool,
         ephemeral: bool,
-    ) -> Result<(), AnyError> {
-        let enter_labels = Labels::new(Some(workspace_key), None)
-            .with_container(container_id.or(Some(constants::DEFAULT_CONTAINER_NAME)));
+        mux: Option<(Option<RoozMux>, &str)>,
+        reconnect: ReconnectPolicy,
+    ) -> Result<(), RoozError> {
+        let container_name = container_id.unwrap_or(constants::DEFAULT_CONTAINER_NAME);
+        let enter_labels =
+            Labels::new(Some(workspace_key), None).with_container(Some(container_name));
 
         let container = self
             .api
             .container
             .get_single(&enter_labels)
             .await?
-            .ok_or(format!("Workspace not found: {}", &workspace_key))?;
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+        if !ephemeral {
+            if let Err(e) = LastUsed::record(workspace_key) {
+                log::debug!("Could not record the last use of {}: {}", workspace_key, e);
+            }
+        }
 
         let mut shell_value = vec![constants::DEFAULT_SHELL.to_string()];
+        let mut configured_mux = None;
+        let mut sidecar = None;
+        let is_sidecar = container
+            .labels
+            .as_ref()
+            .and_then(|l| l.get(labels::ROLE))
+            .map(|r| r.as_str())
+            == Some(labels::ROLE_SIDECAR);
 
         if let Some(labels) = &container.labels {
-            if labels.contains_key(labels::RUNTIME_CONFIG) {
-                shell_value =
-                    RuntimeConfig::from_string(labels[labels::RUNTIME_CONFIG].clone())?.shell;
+            if is_sidecar {
+                // sidecars run third-party images, so nothing from the work container applies
+                sidecar = self
+                    .runtime_config(workspace_key)
+                    .await?
+                    .and_then(|cfg| {
+                        cfg.sidecars
+                            .into_iter()
+                            .find(|(name, s)| s.instances(name).iter().any(|i| i == container_name))
+                    })
+                    .map(|(_, s)| s);
+                if let Some(shell) = sidecar.as_ref().and_then(|s| s.shell.clone()) {
+                    shell_value = shell;
+                }
+            } else if labels.contains_key(labels::RUNTIME_CONFIG) {
+                let cfg = RuntimeConfig::from_string(labels[labels::RUNTIME_CONFIG].clone())?;
+                shell_value = cfg.shell;
+                configured_mux = cfg.mux;
+            } else if labels.get(labels::ROLE).map(|r| r.as_str()) == Some(labels::ROLE_WORK) {
+                if let Some(cfg) = self.runtime_config(workspace_key).await? {
+                    if let Some(shell) = cfg
+                        .containers
+                        .get(container_name)
+                        .and_then(|c| c.shell.clone())
+                    {
+                        shell_value = shell;
+                    }
+                    configured_mux = cfg.mux;
+                }
             }
         }
 
@@ -67,24 +309,55 @@ impl<'a> WorkspaceApi<'a> {
             shell_value = shell.iter().map(|v| v.to_string()).collect::<Vec<_>>();
         }
 
+        // a one-liner runs in the resolved shell and exits with it, so there is no session to keep
+        let reconnect = match command {
+            Some(_) => ReconnectPolicy::none(),
+            None => reconnect,
+        };
+        let mux = mux.and_then(|(mux, session)| mux.or(configured_mux).map(|m| (m, session)));
+        let mut argv = Vec::<String>::new();
+
         let container_id = container.id.as_deref().unwrap();
+        let working_dir = working_dir.or(sidecar.as_ref().and_then(|s| s.work_dir.as_deref()));
+        let user = if root {
+            Some(constants::ROOT_USER)
+        } else {
+            sidecar.as_ref().and_then(|s| s.user.as_deref())
+        };
 
+        let mut attempt = 0;
+        let retry = |attempt: &mut u32, e: RoozError| {
+            if *attempt >= reconnect.retries {
+                return Err(e);
+            }
+            *attempt += 1;
+            let delay = reconnect.delay(*attempt);
+            eprintln!(
+                "{}. Reconnecting in {}s ({}/{})",
+                e,
+                delay.as_secs_f32(),
+                attempt,
+                reconnect.retries
+            );
+            sleep(delay);
+            Ok(())
+        };
 
         // the loop here is needed for auto-reconnecting the session
         loop {
-            println!("{}", termion::clear::All);
-            match self.start(workspace_key).await {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!("{}", e);
-                    sleep(Duration::from_millis(2_000));
-                    continue;
-                }
+            if command.is_none() {
+                term::clear();
+            }
+            if let Err(e) = self.start(workspace_key).await {
+                retry(&mut attempt, e)?;
+                continue;
             };
 
-            if !root {
+            // the sidecar user comes from its image so there is nothing to set up
+            if !root && !is_sidecar {
                 self.api.exec.ensure_user(container_id).await?;
-                for v in &volumes {
+                // shared volumes (caches) are used by workspaces of other uids too
+                for v in volumes.iter().filter(|v| v.is_exclusive()) {
                     self.api
                         .exec
                         .chown(&container_id, chown_uid, &v.path)
@@ -92,6 +365,37 @@ impl<'a> WorkspaceApi<'a> {
                 }
             }
 
+            // the shell is only known to exist once the container runs
+            if argv.is_empty() {
+                let configured = shell_value.first().cloned().unwrap_or_default();
+                let shell = match self.probe_shell(container_id, user, &shell_value).await? {
+                    Some(shell) => shell,
+                    None => {
+                        return Err(RoozError::Message(format!(
+                            "No shell found in {}. Tried {}, {}",
+                            container_name,
+                            configured,
+                            FALLBACK_SHELLS.join(", ")
+                        )))
+                    }
+                };
+                if shell != shell_value {
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "{} not found in {}. Falling back to {}",
+                            configured, container_name, shell[0]
+                        )
+                        .yellow()
+                    );
+                }
+                argv = match (command, mux) {
+                    (Some(command), _) => [shell, vec!["-c".into(), command.into()]].concat(),
+                    (None, Some((mux, session))) => mux_command(mux, session, &shell),
+                    (None, None) => shell,
+                };
+            }
+
             match self
                 .api
                 .exec
@@ -100,20 +404,20 @@ impl<'a> WorkspaceApi<'a> {
                     &container_id,
                     true,
                     working_dir,
-                    if root {
-                        Some(constants::ROOT_USER)
-                    } else {
-                        None
-                    },
-                    Some(shell_value.iter().map(|v| v.as_str()).collect::<Vec<_>>()),
+                    user,
+                    Some(argv.iter().map(|v| v.as_str()).collect::<Vec<_>>()),
                 )
                 .await
             {
                 Ok(_) => break,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    sleep(Duration::from_millis(2_000));
+                // the shell exited on its own, its status is rooz's exit status
+                Err(e @ RoozError::ExitCode(_)) => return Err(e),
+                // the session was up so the retry budget starts over
+                Err(RoozError::Disconnected) => {
+                    attempt = 0;
+                    retry(&mut attempt, RoozError::Disconnected)?;
                 }
+                Err(e) => retry(&mut attempt, e)?,
             };
         }
         if ephemeral {
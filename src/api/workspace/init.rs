@@ -0,0 +1,110 @@
+use tracing::Instrument;
+
+use crate::{
+    api::WorkspaceApi,
+    config::{config::RoozInitContainer, runtime::RuntimeConfig},
+    model::{error::RoozError, types::RunSpec, volume::RoozVolume},
+    util::{
+        labels::{self, Labels},
+        progress,
+    },
+};
+
+impl<'a> WorkspaceApi<'a> {
+    // the labels, volume mounts and network are left to the caller
+    pub fn init_run_spec<'b>(
+        &self,
+        cfg: &'b RuntimeConfig,
+        workspace_key: &'b str,
+        uid: &'b str,
+        container_name: &'b str,
+        init: &'b RoozInitContainer,
+        home_dir: &'b str,
+    ) -> RunSpec<'b> {
+        let mut env = self.proxy_env(cfg);
+        env.extend(cfg.env.clone());
+        env.extend(
+            init.env
+                .iter()
+                .flatten()
+                .map(|(k, v)| (k.clone(), v.clone())),
+        );
+        RunSpec {
+            reason: "init",
+            image: init.image.as_deref().unwrap_or(&cfg.image),
+            uid: init.user.as_deref().unwrap_or(uid),
+            user: &cfg.user,
+            work_dir: init.work_dir.as_deref(),
+            home_dir,
+            container_name,
+            workspace_key,
+            command: init
+                .command
+                .as_ref()
+                .map(|c| c.iter().map(String::as_str).collect()),
+            force_recreate: true,
+            env: Some(env),
+            extra_networks: Some(cfg.networks.clone()),
+            idmap: Some(cfg.idmap),
+            dns: cfg.dns(),
+            ..Default::default()
+        }
+    }
+
+    // runs the init containers one by one, each one to completion, before the work container gets started
+    pub async fn run_init_containers(
+        &self,
+        cfg: &RuntimeConfig,
+        workspace_key: &str,
+        uid: &str,
+        volumes: &[RoozVolume],
+        network: Option<&str>,
+    ) -> Result<(), RoozError> {
+        let home_dir = cfg.home_dir();
+        let mounts = volumes
+            .iter()
+            .map(|v| v.to_mount(Some(&home_dir)))
+            .collect::<Vec<_>>();
+
+        for (name, init) in &cfg.init_containers {
+            let step = progress::step(
+                self.api.progress,
+                format!("Running init container {}", name),
+            );
+            let container_name = format!("{}-init-{}", workspace_key, name);
+            let run_spec = RunSpec {
+                mounts: Some(mounts.clone()),
+                labels: Labels::new(Some(workspace_key), Some(labels::ROLE_INIT))
+                    .with_container(Some(name)),
+                network,
+                ..self.init_run_spec(cfg, workspace_key, uid, &container_name, init, &home_dir)
+            };
+
+            let exit_code = async {
+                let id = self.api.container.create(run_spec).await?.id().to_string();
+                self.api.container.start(&id).await?;
+                self.api.container.logs_to_stdout(&id).await?;
+                let exit_code = self
+                    .api
+                    .client
+                    .inspect_container(&id, None)
+                    .await?
+                    .state
+                    .and_then(|s| s.exit_code)
+                    .unwrap_or_default();
+                self.api.container.remove(&id, true).await?;
+                Ok::<_, RoozError>(exit_code)
+            }
+            .instrument(tracing::debug_span!("init.run", id = %name))
+            .await?;
+            if exit_code != 0 {
+                return Err(RoozError::Message(format!(
+                    "Init container {} failed with exit code: {}. The workspace has not been started",
+                    name, exit_code
+                )));
+            }
+            step.done();
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,13 @@
+pub mod autostart;
 pub mod create;
+pub mod docker;
 pub mod enter;
+pub mod init;
+pub mod project;
 pub mod remove;
+pub mod run;
+pub mod sessions;
+pub mod signing;
 pub mod start;
 pub mod stop;
+pub mod sync;
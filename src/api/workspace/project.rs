@@ -0,0 +1,92 @@
+use std::collections::BTreeSet;
+
+use colored::Colorize;
+
+use crate::{
+    api::WorkspaceApi,
+    constants,
+    model::error::RoozError,
+    util::{
+        labels::{self, Labels, WORKSPACE_KEY},
+        parallel,
+    },
+};
+
+impl<'a> WorkspaceApi<'a> {
+    // the project label is only set on the main work container of a workspace
+    async fn project_workspaces(&self, project: &str) -> Result<BTreeSet<String>, RoozError> {
+        let labels = Labels::new(None, Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME))
+            .with_project(Some(project));
+        let workspaces = self
+            .api
+            .container
+            .get_all(&labels)
+            .await?
+            .into_iter()
+            .filter_map(|c| c.labels.and_then(|l| l.get(WORKSPACE_KEY).cloned()))
+            .collect::<BTreeSet<_>>();
+        if workspaces.is_empty() {
+            return Err(RoozError::Message(format!(
+                "No workspaces found in project: {}",
+                project
+            )));
+        }
+        Ok(workspaces)
+    }
+
+    pub async fn start_project(&self, project: &str) -> Result<(), RoozError> {
+        let tasks = self
+            .project_workspaces(project)
+            .await?
+            .into_iter()
+            .map(|key| {
+                let workspace = key.clone();
+                let task = async move { self.start(&key).await };
+                (workspace, task)
+            });
+        parallel::run_all("start workspaces", tasks).await?;
+        Ok(())
+    }
+
+    pub async fn stop_project(&self, project: &str, force: bool) -> Result<(), RoozError> {
+        let tasks = self
+            .project_workspaces(project)
+            .await?
+            .into_iter()
+            .map(|key| {
+                let workspace = key.clone();
+                let task = async move {
+                    self.guard_sessions(&key, "stop", force).await?;
+                    self.stop(&key).await
+                };
+                (workspace, task)
+            });
+        parallel::run_all("stop workspaces", tasks).await?;
+        Ok(())
+    }
+
+    pub async fn remove_project(
+        &self,
+        project: &str,
+        force: bool,
+        purge_data: bool,
+    ) -> Result<(), RoozError> {
+        let tasks = self
+            .project_workspaces(project)
+            .await?
+            .into_iter()
+            .map(|key| {
+                let workspace = key.clone();
+                let task = async move {
+                    self.guard_sessions(&key, "remove", force).await?;
+                    self.remove(&key, force, purge_data).await?;
+                    println!("Removing workspace: {} ... {}", key, "OK".green());
+                    Ok::<_, RoozError>(())
+                };
+                (workspace, task)
+            });
+        parallel::run_all("remove workspaces", tasks).await?;
+        Ok(())
+    }
+}
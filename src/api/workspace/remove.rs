@@ -1,29 +1,52 @@
+use std::collections::BTreeSet;
+
 use bollard::{
+    errors::Error,
     network::ListNetworksOptions,
     service::{ContainerSummary, Volume},
     volume::ListVolumesOptions,
 };
 
+use colored::Colorize;
+
 use crate::{
     api::{self, WorkspaceApi},
-    model::{types::AnyError, volume::CACHE_ROLE},
+    config::system::WorkspaceEngines,
+    constants,
+    model::{error::RoozError, volume::CACHE_ROLE},
     util::{
-        labels::{Labels, ROLE},
-        ssh,
+        labels::{Labels, KEEP, ROLE, ROLE_WORK, VOLUMES_KEY, WORKSPACE_KEY},
+        parallel, ssh,
     },
 };
 
 impl<'a> WorkspaceApi<'a> {
-    async fn remove_containers(&self, labels: &Labels, force: bool) -> Result<(), AnyError> {
-        for cs in self.api.container.get_all(labels).await? {
-            if let ContainerSummary { id: Some(id), .. } = cs {
-                self.api.container.remove(&id, force).await?
-            }
-        }
+    async fn remove_containers(&self, labels: &Labels, force: bool) -> Result<(), RoozError> {
+        let tasks = self
+            .api
+            .container
+            .get_all(labels)
+            .await?
+            .into_iter()
+            .filter_map(|cs| match cs {
+                ContainerSummary { id: Some(id), .. } => Some(id),
+                _ => None,
+            })
+            .map(|id| {
+                let container = id.clone();
+                let task = async move { self.api.container.remove(&id, force).await };
+                (container, task)
+            });
+        parallel::run_all("remove containers", tasks).await?;
         Ok(())
     }
 
-    async fn remove_core(&self, labels: &Labels, force: bool) -> Result<(), AnyError> {
+    async fn remove_core(
+        &self,
+        labels: &Labels,
+        force: bool,
+        purge_data: bool,
+    ) -> Result<(), RoozError> {
         self.remove_containers(labels, force).await?;
         let ls_vol_options = ListVolumesOptions {
             filters: labels.into(),
@@ -46,10 +69,21 @@ impl<'a> WorkspaceApi<'a> {
                     }
                     Volume { labels, .. } => match labels.get(ROLE) {
                         Some(role) if role == CACHE_ROLE => continue,
+                        _ if labels.contains_key(KEEP) && !purge_data => {
+                            log::debug!("Keep volume: {}", &v.name);
+                            continue;
+                        }
                         _ => {}
                     },
                 };
-                self.api.volume.remove_volume(&v.name, force).await?
+                // remove_all sweeps after removing each workspace in parallel, so some may be gone already
+                match self.api.volume.remove_volume(&v.name, force).await {
+                    Err(RoozError::Engine(Error::DockerResponseServerError {
+                        status_code: 404,
+                        ..
+                    })) => log::debug!("No such volume. Skipping: {}", &v.name),
+                    r => r?,
+                }
             }
         }
 
@@ -65,7 +99,13 @@ impl<'a> WorkspaceApi<'a> {
             if let Some(name) = n.name {
                 let force_display = if force { " (force)" } else { "" };
                 log::debug!("Remove network: {}{}", &name, &force_display);
-                self.api.client.remove_network(&name).await?
+                match self.api.client.remove_network(&name).await {
+                    Ok(_) => (),
+                    Err(Error::DockerResponseServerError {
+                        status_code: 404, ..
+                    }) => log::debug!("No such network. Skipping: {}", &name),
+                    Err(e) => return Err(e.into()),
+                }
             }
         }
 
@@ -73,25 +113,61 @@ impl<'a> WorkspaceApi<'a> {
         Ok(())
     }
 
-    pub async fn remove(&self, workspace_key: &str, force: bool) -> Result<(), AnyError> {
-        let labels = Labels::new(Some(workspace_key), None);
-        self.remove_core((&labels).into(), force).await?;
-        Ok(())
-    }
-
-    pub async fn remove_containers_only(
+    pub async fn remove(
         &self,
         workspace_key: &str,
         force: bool,
-    ) -> Result<(), AnyError> {
+        purge_data: bool,
+    ) -> Result<(), RoozError> {
         let labels = Labels::new(Some(workspace_key), None);
-        self.remove_containers((&labels).into(), force).await?;
+        let adopted_key = self
+            .api
+            .container
+            .get_single(
+                &Labels::new(Some(workspace_key), Some(ROLE_WORK))
+                    .with_container(Some(constants::DEFAULT_CONTAINER_NAME)),
+            )
+            .await?
+            .and_then(|c| c.labels)
+            .and_then(|l| l.get(VOLUMES_KEY).cloned());
+
+        self.remove_core((&labels).into(), force, purge_data)
+            .await?;
+
+        // the adopted volumes still carry their original workspace key, so the same keep and cache rules apply to them
+        if let Some(key) = adopted_key.filter(|k| k != workspace_key) {
+            self.remove_core(&Labels::new(Some(&key), None), force, purge_data)
+                .await?;
+        }
+        if let Some(engine) = self.api.container.engine {
+            WorkspaceEngines::forget(workspace_key, engine)?;
+        }
         Ok(())
     }
 
-    pub async fn remove_all(&self, force: bool) -> Result<(), AnyError> {
+    pub async fn remove_all(&self, force: bool, purge_data: bool) -> Result<(), RoozError> {
         let labels = Labels::default();
-        self.remove_core(&labels, force).await?;
+        let workspaces = self
+            .api
+            .container
+            .get_all(&labels)
+            .await?
+            .into_iter()
+            .filter_map(|c| c.labels.and_then(|l| l.get(WORKSPACE_KEY).cloned()))
+            .collect::<BTreeSet<_>>();
+        let tasks = workspaces.into_iter().map(|key| {
+            let workspace = key.clone();
+            let task = async move {
+                self.guard_sessions(&key, "remove", force).await?;
+                self.remove(&key, force, purge_data).await?;
+                println!("Removing workspace: {} ... {}", key, "OK".green());
+                Ok::<_, RoozError>(())
+            };
+            (workspace, task)
+        });
+        parallel::run_all("remove workspaces", tasks).await?;
+        // sweeps whatever is left without a container e.g. volumes of failed creates
+        self.remove_core(&labels, force, purge_data).await?;
         Ok(())
     }
 }
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use bollard::{
+    models::{Mount, MountPointTypeEnum, MountTypeEnum},
+    service::ContainerInspectResponse,
+};
+
+use crate::{
+    api::WorkspaceApi,
+    constants,
+    model::{
+        error::RoozError,
+        types::{DnsSpec, RunSpec, SecuritySpec},
+    },
+    util::{
+        id,
+        labels::{self, Labels},
+    },
+};
+
+fn mounts_of(container: &ContainerInspectResponse) -> Vec<Mount> {
+    container
+        .mounts
+        .iter()
+        .flatten()
+        .filter_map(|m| {
+            let (typ, source) = match m.typ {
+                Some(MountPointTypeEnum::VOLUME) => (MountTypeEnum::VOLUME, m.name.clone()),
+                Some(MountPointTypeEnum::BIND) => (MountTypeEnum::BIND, m.source.clone()),
+                _ => return None,
+            };
+            Some(Mount {
+                typ: Some(typ),
+                source,
+                target: m.destination.clone(),
+                read_only: m.rw.map(|rw| !rw),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+fn networks_of(container: &ContainerInspectResponse) -> Vec<String> {
+    let mut networks = container
+        .network_settings
+        .as_ref()
+        .and_then(|s| s.networks.as_ref())
+        .map(|n| n.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    networks.retain(|n| !["bridge", "podman", "host", "none"].contains(&n.as_str()));
+    networks.sort();
+    networks
+}
+
+impl<'a> WorkspaceApi<'a> {
+    pub async fn run(
+        &self,
+        workspace_key: &str,
+        container_name: Option<&str>,
+        working_dir: Option<&str>,
+        command: Vec<&str>,
+    ) -> Result<(), RoozError> {
+        let container_name = container_name.unwrap_or(constants::DEFAULT_CONTAINER_NAME);
+        let labels = Labels::new(Some(workspace_key), None).with_container(Some(container_name));
+
+        let container_id = self
+            .api
+            .container
+            .get_single(&labels)
+            .await?
+            .and_then(|c| c.id)
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+
+        self.start(workspace_key).await?;
+
+        let source = self
+            .api
+            .client
+            .inspect_container(&container_id, None)
+            .await?;
+        let config = source.config.clone().unwrap_or_default();
+        let image = config.image.clone().unwrap_or_default();
+        let env = config
+            .env
+            .iter()
+            .flatten()
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        let meta = |key: &str| env.get(key).cloned().unwrap_or_default();
+        let uid = match env.get("ROOZ_META_GID") {
+            Some(gid) => format!("{}:{}", meta("ROOZ_META_UID"), gid),
+            None => meta("ROOZ_META_UID"),
+        };
+        let (user, home_dir) = (meta("ROOZ_META_USER"), meta("ROOZ_META_HOME"));
+        let env = env
+            .iter()
+            .filter(|(k, _)| !k.starts_with("ROOZ_META_"))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<HashMap<_, _>>();
+        let networks = networks_of(&source);
+        let (network, extra_networks) = match networks.split_first() {
+            Some((first, rest)) => (Some(first.as_str()), Some(rest.to_vec())),
+            None => (None, None),
+        };
+        let work_dir = working_dir
+            .map(|d| d.to_string())
+            .or(config.working_dir.clone())
+            .filter(|d| !d.is_empty());
+        let idmap = self.runtime_config(workspace_key).await?.map(|c| c.idmap);
+        let host_config = source.host_config.clone().unwrap_or_default();
+        let security = SecuritySpec {
+            security_opts: host_config.security_opt.unwrap_or_default(),
+            cap_add: host_config.cap_add.unwrap_or_default(),
+            cap_drop: host_config.cap_drop.unwrap_or_default(),
+            read_only_rootfs: host_config.readonly_rootfs.unwrap_or_default(),
+        };
+        let dns = DnsSpec {
+            dns: host_config.dns.unwrap_or_default(),
+            dns_search: host_config.dns_search.unwrap_or_default(),
+            extra_hosts: host_config.extra_hosts.unwrap_or_default(),
+        };
+
+        let run_container_name = id::random_suffix(&format!("{}-run", workspace_key));
+        let run_spec = RunSpec {
+            reason: "run",
+            image: &image,
+            uid: &uid,
+            user: &user,
+            work_dir: work_dir.as_deref(),
+            home_dir: &home_dir,
+            container_name: &run_container_name,
+            workspace_key,
+            mounts: Some(mounts_of(&source)),
+            entrypoint: Some(vec!["cat"]),
+            privileged: host_config.privileged.unwrap_or(false),
+            auto_remove: true,
+            labels: Labels::new(Some(workspace_key), Some(labels::ROLE_RUN)),
+            env: Some(env),
+            network,
+            extra_networks,
+            idmap,
+            security,
+            dns,
+            ..Default::default()
+        };
+
+        let id = self.api.container.create(run_spec).await?.id().to_string();
+        self.api.container.start(&id).await?;
+        self.api.exec.ensure_user(&id).await?;
+
+        let result = self
+            .api
+            .exec
+            .tty("run", &id, true, work_dir.as_deref(), None, Some(command))
+            .await;
+        self.api.container.kill(&id).await?;
+        result
+    }
+}
@@ -0,0 +1,108 @@
+use bollard::service::{ContainerInspectResponse, ExecInspectResponse, ProcessConfig};
+use colored::Colorize;
+
+use crate::{
+    api::WorkspaceApi,
+    model::error::RoozError,
+    util::labels::{Labels, CONTAINER},
+};
+
+pub struct Session {
+    pub container: String,
+    pub user: String,
+    pub command: String,
+}
+
+impl Session {
+    fn print(sessions: &[Session]) -> String {
+        sessions
+            .iter()
+            .map(|s| format!("  {} ({}): {}", s.container, s.user, s.command))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl<'a> WorkspaceApi<'a> {
+    // rooz enter and rooz run attach via tty execs, so running tty execs are the attached sessions
+    pub async fn sessions(&self, workspace_key: &str) -> Result<Vec<Session>, RoozError> {
+        let mut sessions = Vec::new();
+        for c in self
+            .api
+            .container
+            .get_running(&Labels::new(Some(workspace_key), None))
+            .await?
+        {
+            let Some(id) = c.id.as_deref() else {
+                continue;
+            };
+            let container = c
+                .labels
+                .as_ref()
+                .and_then(|l| l.get(CONTAINER))
+                .cloned()
+                .unwrap_or(id.to_string());
+            let ContainerInspectResponse { exec_ids, .. } =
+                self.api.client.inspect_container(id, None).await?;
+            for exec_id in exec_ids.unwrap_or_default() {
+                if let ExecInspectResponse {
+                    running: Some(true),
+                    process_config:
+                        Some(ProcessConfig {
+                            tty: Some(true),
+                            user,
+                            entrypoint,
+                            arguments,
+                            ..
+                        }),
+                    ..
+                } = self.api.client.inspect_exec(&exec_id).await?
+                {
+                    sessions.push(Session {
+                        container: container.to_string(),
+                        user: user.filter(|u| !u.is_empty()).unwrap_or("default".into()),
+                        command: entrypoint
+                            .into_iter()
+                            .chain(arguments.unwrap_or_default())
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    });
+                }
+            }
+        }
+        Ok(sessions)
+    }
+
+    // keeps a shared remote host from killing someone else's session by accident
+    pub async fn guard_sessions(
+        &self,
+        workspace_key: &str,
+        action: &str,
+        force: bool,
+    ) -> Result<(), RoozError> {
+        let sessions = self.sessions(workspace_key).await?;
+        if sessions.is_empty() {
+            return Ok(());
+        }
+        if force {
+            eprintln!(
+                "{}\n{}",
+                format!(
+                    "Closing {} session(s) attached to {}:",
+                    sessions.len(),
+                    workspace_key
+                )
+                .yellow(),
+                Session::print(&sessions)
+            );
+            return Ok(());
+        }
+        Err(RoozError::Message(format!(
+            "Workspace {} has {} attached session(s):\n{}\nUse --force to {} it anyway",
+            workspace_key,
+            sessions.len(),
+            Session::print(&sessions),
+            action
+        )))
+    }
+}
@@ -0,0 +1,61 @@
+use std::{collections::HashMap, env, process::Command};
+
+use bollard::models::{Mount, MountTypeEnum};
+
+use crate::{api::WorkspaceApi, config::config::RoozGitSigning, model::error::RoozError};
+
+const SSH_AGENT_SOCKET: &str = "/run/rooz/ssh-agent.sock";
+
+// the extra socket is the restricted one gpg-agent offers for forwarding
+pub fn gpg_agent_socket() -> Option<String> {
+    let output = Command::new("gpgconf")
+        .args(["--list-dirs", "agent-extra-socket"])
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|s| output.status.success() && !s.is_empty())
+}
+
+pub fn ssh_agent_socket() -> Option<String> {
+    env::var("SSH_AUTH_SOCK").ok().filter(|s| !s.is_empty())
+}
+
+impl<'a> WorkspaceApi<'a> {
+    // the agent sockets are bind-mounted so they have to be on the container engine host,
+    // which is the case for local engines only
+    pub fn signing_agent(
+        &self,
+        signing: RoozGitSigning,
+        home_dir: &str,
+    ) -> Result<(Vec<Mount>, HashMap<String, String>), RoozError> {
+        let bind = |source: String, target: &str| Mount {
+            typ: Some(MountTypeEnum::BIND),
+            source: Some(source),
+            target: Some(target.into()),
+            ..Default::default()
+        };
+        Ok(match signing {
+            RoozGitSigning::Ssh => {
+                let socket = ssh_agent_socket().ok_or(RoozError::Config(
+                    "git.sign = \"ssh\" needs a running ssh-agent (SSH_AUTH_SOCK is not set)"
+                        .into(),
+                ))?;
+                (
+                    vec![bind(socket, SSH_AGENT_SOCKET)],
+                    [("SSH_AUTH_SOCK".to_string(), SSH_AGENT_SOCKET.to_string())]
+                        .into_iter()
+                        .collect(),
+                )
+            }
+            RoozGitSigning::Gpg => {
+                let socket = gpg_agent_socket().ok_or(RoozError::Config(
+                    "git.sign = \"gpg\" needs gpgconf to find the gpg-agent socket".into(),
+                ))?;
+                (
+                    vec![bind(socket, &format!("{}/.gnupg/S.gpg-agent", home_dir))],
+                    HashMap::new(),
+                )
+            }
+        })
+    }
+}
@@ -1,15 +1,28 @@
-use crate::{api::WorkspaceApi, model::types::AnyError, util::labels::Labels};
+use crate::{
+    api::WorkspaceApi,
+    model::error::RoozError,
+    util::{labels::Labels, parallel},
+};
 use colored::Colorize;
 
 impl<'a> WorkspaceApi<'a> {
-    pub async fn start(&self, workspace_key: &str) -> Result<(), AnyError> {
+    pub async fn start(&self, workspace_key: &str) -> Result<(), RoozError> {
         let labels = Labels::new(Some(workspace_key), None);
 
-        for c in self.api.container.get_all(&labels).await? {
-            print!("Starting container: {} ... ", c.names.unwrap().join(", "));
-            self.api.container.start(&c.id.unwrap()).await?;
-            println!("{}", format!("OK").green())
-        }
+        let containers = self.api.container.get_all(&labels).await?;
+        let tasks = containers
+            .into_iter()
+            .filter_map(|c| c.id.map(|id| (id, c.names.unwrap_or_default().join(", "))))
+            .map(|(id, name)| {
+                let container = name.clone();
+                let task = async move {
+                    self.api.container.start(&id).await?;
+                    println!("Starting container: {} ... {}", name, format!("OK").green());
+                    Ok::<_, RoozError>(())
+                };
+                (container, task)
+            });
+        parallel::run_all("start containers", tasks).await?;
         Ok(())
     }
 }
@@ -1,25 +1,53 @@
+use std::collections::BTreeSet;
+
 use colored::Colorize;
 
-use crate::{api::WorkspaceApi, model::types::AnyError, util::labels::Labels};
+use crate::{
+    api::WorkspaceApi,
+    model::error::RoozError,
+    util::{
+        labels::{Labels, WORKSPACE_KEY},
+        parallel,
+    },
+};
 
 impl<'a> WorkspaceApi<'a> {
-    pub async fn stop(&self, workspace_key: &str) -> Result<(), AnyError> {
+    pub async fn stop(&self, workspace_key: &str) -> Result<(), RoozError> {
         let labels = Labels::new(Some(workspace_key), None);
-        for c in self.api.container.get_running(&labels).await? {
-            print!("Stopping container: {} ... ", c.names.unwrap().join(", "));
-            self.api.container.stop(&c.id.unwrap()).await?;
-            println!("{}", format!("OK").green())
-        }
+        let containers = self.api.container.get_running(&labels).await?;
+        let tasks = containers.into_iter().map(|c| {
+            let name = c.names.unwrap_or_default().join(", ");
+            let container = name.clone();
+            let task = async move {
+                self.api.container.stop(&c.id.unwrap()).await?;
+                println!("Stopping container: {} ... {}", name, format!("OK").green());
+                Ok::<_, RoozError>(())
+            };
+            (container, task)
+        });
+        parallel::run_all("stop containers", tasks).await?;
         Ok(())
     }
 
-    pub async fn stop_all(&self) -> Result<(), AnyError> {
+    pub async fn stop_all(&self, force: bool) -> Result<(), RoozError> {
         let labels = Labels::default();
-        for c in self.api.container.get_running(&labels).await? {
-            print!("Stopping container: {} ... ", c.names.unwrap().join(", "));
-            self.api.container.stop(&c.id.unwrap()).await?;
-            println!("{}", format!("OK").green())
-        }
+        let workspaces = self
+            .api
+            .container
+            .get_running(&labels)
+            .await?
+            .into_iter()
+            .filter_map(|c| c.labels.and_then(|l| l.get(WORKSPACE_KEY).cloned()))
+            .collect::<BTreeSet<_>>();
+        let tasks = workspaces.into_iter().map(|key| {
+            let workspace = key.clone();
+            let task = async move {
+                self.guard_sessions(&key, "stop", force).await?;
+                self.stop(&key).await
+            };
+            (workspace, task)
+        });
+        parallel::run_all("stop workspaces", tasks).await?;
         Ok(())
     }
 }
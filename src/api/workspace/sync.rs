@@ -0,0 +1,102 @@
+use bollard::models::MountTypeEnum;
+use colored::Colorize;
+
+use crate::{
+    api::{
+        container::{self, quote},
+        WorkspaceApi,
+    },
+    cmd::snapshot,
+    config::config::RoozSyncCfg,
+    constants,
+    model::{error::RoozError, types::RunSpec, volume::RoozVolume},
+    util::labels::{self, Labels},
+};
+
+const HOST_DIR: &str = "/host";
+const STATE_DIR: &str = "/unison";
+
+// unison patterns: plain names match at any depth, entries with a slash match paths from the root
+fn ignore_args(sync: &RoozSyncCfg) -> String {
+    [".unison".to_string()]
+        .iter()
+        .chain(sync.ignore.iter().flatten())
+        .map(|i| {
+            let i = i.trim_matches('/');
+            let kind = if i.contains('/') { "Path" } else { "Name" };
+            format!("-ignore {}", quote(&format!("{} {}", kind, i)))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// unison-fsmonitor watches both replicas with inotify. Docker Desktop forwards the host file events
+// into the VM so edits on either side propagate within a second. Newer files win conflicts,
+// the other version is kept as a copy
+fn script(sync: &RoozSyncCfg, uid: &str) -> String {
+    format!(
+        r#"
+set -e
+command -v unison-fsmonitor >/dev/null || apk add --no-cache unison su-exec >/dev/null
+chown {uid} {state}
+exec su-exec {uid} unison {host} {work} -batch -auto -times -perms 0 -prefer newer -copyonconflict -repeat watch {ignore}
+"#,
+        uid = uid,
+        state = STATE_DIR,
+        host = HOST_DIR,
+        work = constants::WORK_DIR,
+        ignore = ignore_args(sync)
+    )
+}
+
+impl<'a> WorkspaceApi<'a> {
+    // the work tree stays in the work volume and a helper container mirrors it to the host folder.
+    // It carries the workspace labels so it starts, stops and goes away with the workspace
+    pub async fn ensure_sync(
+        &self,
+        sync: &RoozSyncCfg,
+        workspace_key: &str,
+        volumes_key: &str,
+        uid: &str,
+        force: bool,
+    ) -> Result<(), RoozError> {
+        let host_path = sync.host_path().map_err(|e| e.to_string())?;
+        let mut mounts = self
+            .api
+            .volume
+            .ensure_mounts(
+                &vec![
+                    RoozVolume::work(volumes_key, constants::WORK_DIR),
+                    RoozVolume::sidecar_data(workspace_key, STATE_DIR),
+                ],
+                None,
+            )
+            .await?;
+        mounts.push(snapshot::mount(MountTypeEnum::BIND, &host_path, HOST_DIR));
+        let entrypoint = container::inject(&script(sync, uid), "sync.sh");
+        let container_name = format!("{}-{}", workspace_key, labels::ROLE_SYNC);
+        self.api
+            .container
+            .create(RunSpec {
+                reason: "sync",
+                image: sync.image(),
+                uid: constants::ROOT_UID,
+                container_name: &container_name,
+                workspace_key,
+                mounts: Some(mounts),
+                entrypoint: Some(entrypoint.iter().map(String::as_str).collect()),
+                force_recreate: force,
+                labels: Labels::new(Some(workspace_key), Some(labels::ROLE_SYNC))
+                    .with_container(Some(labels::ROLE_SYNC)),
+                ..Default::default()
+            })
+            .await?;
+        println!(
+            "Syncing {} with {} ... {}",
+            constants::WORK_DIR,
+            host_path,
+            "OK".green()
+        );
+        Ok(())
+    }
+}
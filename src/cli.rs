@@ -1,10 +1,62 @@
+use crate::config::config::{RoozMux, RoozPullPolicy};
 use clap::{Parser, Subcommand};
 use clap_complete::Shell;
+use std::collections::HashMap;
 
 #[derive(Parser, Debug)]
 #[command(about = "Prunes all rooz resources")]
 pub struct PruneParams {}
 
+#[derive(Parser, Debug)]
+#[command(
+    about = "Removes orphaned rooz resources: volumes and networks of removed workspaces and stale one-shot containers"
+)]
+pub struct GcParams {
+    #[arg(long, help = "Lists what would be removed without removing anything")]
+    pub dry_run: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Re-creates the containers of workspaces created by older rooz versions from their stored config"
+)]
+pub struct MigrateParams {
+    #[arg(long, help = "Lists the workspaces to migrate without changing them")]
+    pub dry_run: bool,
+    #[arg(short, long, help = "Migrates workspaces with sessions attached too")]
+    pub force: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Upgrades rooz to the latest GitHub release")]
+pub struct UpgradeParams {
+    #[arg(long, help = "Only checks whether a newer version is available")]
+    pub check: bool,
+    #[arg(
+        long,
+        conflicts_with = "rollback",
+        help = "Installs the given release tag instead of the latest one (downgrades too)"
+    )]
+    pub version: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "check",
+        help = "Restores the binary replaced by the last upgrade"
+    )]
+    pub rollback: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Shows the log of operations changing workspaces (new, update, rm, start, stop, ...)"
+)]
+pub struct AuditParams {
+    #[arg(long, help = "Shows only the operations on the given workspace")]
+    pub workspace: Option<String>,
+    #[arg(long, help = "Shows only the last N entries")]
+    pub limit: Option<usize>,
+}
+
 #[derive(Parser, Debug)]
 #[command(about = "Initializes rooz system")]
 pub struct InitParams {
@@ -15,6 +67,102 @@ pub struct InitParams {
         help = "Initializes rooz with the provided age identity rather than generating a new one"
     )]
     pub age_identity: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "age_identity",
+        help = "Initializes rooz with the age identity from a backup created by: rooz system key export"
+    )]
+    pub from_backup: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Prints the age identity so it can be backed up")]
+pub struct ExportKeyParams {
+    #[arg(long, help = "Encrypts the identity with a passphrase and armors it")]
+    pub passphrase: bool,
+    #[arg(short, long, help = "Skips the confirmation")]
+    pub yes: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Restores the age identity from a backup")]
+pub struct ImportKeyParams {
+    #[arg(help = "A file created by: rooz system key export")]
+    pub file: String,
+    #[arg(short, long, help = "Replaces the existing age identity")]
+    pub force: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeyCommands {
+    Export(ExportKeyParams),
+    Import(ImportKeyParams),
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Encryption key subcommands")]
+pub struct Key {
+    #[command(subcommand)]
+    pub command: KeyCommands,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Prints the public SSH key, e.g. to add it to GitHub")]
+pub struct ShowSshKeyParams {}
+
+#[derive(Parser, Debug)]
+#[command(about = "Replaces the SSH key with a newly generated one")]
+pub struct RegenerateSshKeyParams {
+    #[arg(short, long, help = "Skips the confirmation")]
+    pub yes: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Replaces the SSH key with an existing ed25519 private key")]
+pub struct ImportSshKeyParams {
+    #[arg(help = "An OpenSSH ed25519 private key without a passphrase")]
+    pub file: String,
+    #[arg(short, long, help = "Replaces the existing SSH key")]
+    pub force: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SshCommands {
+    ShowKey(ShowSshKeyParams),
+    Regenerate(RegenerateSshKeyParams),
+    Import(ImportSshKeyParams),
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "SSH key subcommands")]
+pub struct Ssh {
+    #[command(subcommand)]
+    pub command: SshCommands,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Restarts the workspace containers when the container engine starts")]
+pub struct EnableAutostartParams {
+    pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Restores the restart policies from the workspace config")]
+pub struct DisableAutostartParams {
+    pub name: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AutostartCommands {
+    Enable(EnableAutostartParams),
+    Disable(DisableAutostartParams),
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Workspace autostart subcommands")]
+pub struct Autostart {
+    #[command(subcommand)]
+    pub command: AutostartCommands,
 }
 
 #[derive(Parser, Debug)]
@@ -23,11 +171,70 @@ pub struct CompletionParams {
     pub shell: Shell,
 }
 
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum CompleteKind {
+    Workspaces,
+    Containers,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Lists workspace or container names for shell completion",
+    hide = true
+)]
+pub struct CompleteParams {
+    #[arg(value_enum)]
+    pub kind: CompleteKind,
+    #[arg(
+        required_if_eq("kind", "containers"),
+        help = "The workspace to list the containers of"
+    )]
+    pub name: Option<String>,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum SystemCommands {
     Prune(PruneParams),
+    Gc(GcParams),
     Init(InitParams),
+    Key(Key),
+    Ssh(Ssh),
+    Autostart(Autostart),
     Completion(CompletionParams),
+    Complete(CompleteParams),
+    Audit(AuditParams),
+    Upgrade(UpgradeParams),
+    Migrate(MigrateParams),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum K8sCommands {
+    New(K8sNewParams),
+    Enter(K8sEnterParams),
+    Rm(K8sRemoveParams),
+    List(K8sListParams),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImageCommands {
+    List(ListImageParams),
+    Prune(PruneImageParams),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommands {
+    List(ListCacheParams),
+    Inspect(InspectCacheParams),
+    Rm(RemoveCacheParams),
+    Size(CacheSizeParams),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotCommands {
+    Create(CreateSnapshotParams),
+    List(ListSnapshotParams),
+    Restore(RestoreSnapshotParams),
+    Rm(RemoveSnapshotParams),
 }
 
 #[derive(Subcommand, Debug)]
@@ -37,6 +244,72 @@ pub enum ConfigCommands {
     Show(ShowConfigParams),
 }
 
+#[derive(Parser, Debug)]
+#[command(
+    about = "Experimental: workspaces as Kubernetes pods with PVCs for volumes. Requires kubectl in $PATH"
+)]
+pub struct K8s {
+    #[command(subcommand)]
+    pub command: K8sCommands,
+    #[arg(
+        long,
+        global = true,
+        env = "ROOZ_K8S_CONTEXT",
+        help = "The kubectl context. Defaults to the current one"
+    )]
+    pub context: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "ROOZ_K8S_NAMESPACE",
+        help = "Defaults to the namespace of the context"
+    )]
+    pub namespace: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Creates a workspace pod")]
+pub struct K8sNewParams {
+    pub name: String,
+    #[arg(
+        long,
+        help = "Configures the workspace from a local config file. Sidecars share the pod so they are reached via localhost",
+        alias = "config"
+    )]
+    pub config_path: Option<String>,
+    #[arg(long, default_value = "5Gi", help = "The size of each PVC")]
+    pub storage: String,
+    #[arg(
+        long,
+        help = "UID (or UID:GID) the pod runs as. 'host' takes the ones of the current user. Defaults to 1000"
+    )]
+    pub uid: Option<String>,
+    #[arg(
+        long,
+        default_value = "rooz-ssh-key",
+        help = "The secret holding the SSH key (as id_ed25519) to clone the repo with and use in the workspace. Skipped if missing"
+    )]
+    pub ssh_secret: String,
+    #[arg(long, help = "Prints the manifest without applying it")]
+    pub dry_run: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Enters a workspace pod, forwarding the configured ports")]
+pub struct K8sEnterParams {
+    pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Removes a workspace pod along with its PVCs")]
+pub struct K8sRemoveParams {
+    pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Lists workspace pods")]
+pub struct K8sListParams {}
+
 #[derive(Parser, Debug)]
 #[command(about = "System subcommands")]
 pub struct System {
@@ -44,6 +317,159 @@ pub struct System {
     pub command: SystemCommands,
 }
 
+#[derive(Parser, Debug)]
+#[command(about = "Shared cache subcommands")]
+pub struct Cache {
+    #[command(subcommand)]
+    pub command: CacheCommands,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Lists shared caches", alias = "ls")]
+pub struct ListCacheParams {}
+
+#[derive(Parser, Debug)]
+#[command(about = "Shows details of a shared cache")]
+pub struct InspectCacheParams {
+    #[arg(help = "Cache path (as configured in caches) or volume name")]
+    pub cache: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Removes a shared cache not used by any workspace")]
+pub struct RemoveCacheParams {
+    #[arg(help = "Cache path (as configured in caches) or volume name")]
+    pub cache: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Shows disk usage of shared caches")]
+pub struct CacheSizeParams {
+    #[arg(help = "Cache path (as configured in caches) or volume name")]
+    pub cache: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Subcommands for the images rooz pulled")]
+pub struct Image {
+    #[command(subcommand)]
+    pub command: ImageCommands,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Lists the images rooz pulled and the workspaces they were pulled for",
+    alias = "ls"
+)]
+pub struct ListImageParams {}
+
+#[derive(Parser, Debug)]
+#[command(about = "Removes the images rooz pulled that no container uses anymore")]
+pub struct PruneImageParams {
+    #[arg(long, help = "Only shows what would be removed")]
+    pub dry_run: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Workspace snapshot subcommands")]
+pub struct Snapshot {
+    #[command(subcommand)]
+    pub command: SnapshotCommands,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Snapshots the work volume of a workspace. Stops the workspace")]
+pub struct CreateSnapshotParams {
+    pub name: String,
+    #[arg(
+        long,
+        help = "Snapshot name. Defaults to the current UTC timestamp",
+        conflicts_with = "file"
+    )]
+    pub snapshot: Option<String>,
+    #[arg(long, help = "Include the home volume")]
+    pub home: bool,
+    #[arg(
+        long,
+        help = "Write the snapshot to a tar.gz file instead of a volume. The path is on the container engine host"
+    )]
+    pub file: Option<String>,
+    #[arg(long, help = "Include the files matched by .gitignore and .roozignore")]
+    pub all: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Lists workspace snapshots", alias = "ls")]
+pub struct ListSnapshotParams {
+    pub name: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Restores a workspace from a snapshot in place. Stops the workspace")]
+pub struct RestoreSnapshotParams {
+    pub name: String,
+    #[arg(required_unless_present = "file")]
+    pub snapshot: Option<String>,
+    #[arg(
+        long,
+        help = "Restore from a tar.gz file created with snapshot create --file",
+        conflicts_with = "snapshot"
+    )]
+    pub file: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Removes a workspace snapshot")]
+pub struct RemoveSnapshotParams {
+    pub name: String,
+    pub snapshot: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Backs up the work volume of a workspace to a local or remote target. Stops the workspace"
+)]
+pub struct BackupParams {
+    pub name: String,
+    #[arg(
+        long,
+        help = "A directory on the container engine host, s3://bucket/prefix or ssh://user@host/path"
+    )]
+    pub target: String,
+    #[arg(long, help = "Include the home volume")]
+    pub home: bool,
+    #[arg(long, help = "Include the files matched by .gitignore and .roozignore")]
+    pub all: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Archives the volumes of a workspace into a compressed archive volume and removes the workspace"
+)]
+pub struct ArchiveParams {
+    pub name: String,
+    #[arg(
+        short,
+        long,
+        help = "Archives the workspace even with sessions attached"
+    )]
+    pub force: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Re-creates an archived workspace with its volumes restored")]
+pub struct UnarchiveParams {
+    pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Restores a workspace from a backup in place. Stops the workspace")]
+pub struct RestoreParams {
+    pub name: String,
+    #[arg(long, help = "The backup location printed by rooz backup")]
+    pub from: String,
+}
+
 #[derive(Parser, Debug)]
 #[command(about = "Config subcommands")]
 pub struct Config {
@@ -52,17 +478,76 @@ pub struct Config {
 }
 
 #[derive(Parser, Debug)]
-#[command(about = "Establishes a connection to a remote host")]
+#[command(about = "Shows the state of the tunnel. Fails if it is not running or not connected")]
+pub struct RemoteStatusParams {
+    #[arg(env = "DOCKER_HOST", hide = true)]
+    pub local_docker_host: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RemoteCommands {
+    Status(RemoteStatusParams),
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Establishes a connection to a remote host",
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
 pub struct RemoteParams {
+    #[command(subcommand)]
+    pub command: Option<RemoteCommands>,
     #[arg(
         long,
         short,
         env = "ROOZ_REMOTE_SSH_URL",
+        required = true,
         help = "Remote host's SSH url"
     )]
-    pub ssh_url: String,
-    #[arg(env = "DOCKER_HOST", hide = true)]
-    pub local_docker_host: String,
+    pub ssh_url: Option<String>,
+    #[arg(env = "DOCKER_HOST", required = true, hide = true)]
+    pub local_docker_host: Option<String>,
+    #[arg(
+        long,
+        help = "Runs as a service (e.g. under systemd or launchd): writes a pid file and logs to --log-file"
+    )]
+    pub daemon: bool,
+    #[arg(
+        long,
+        requires = "daemon",
+        help = "Defaults to the local socket path with the .pid extension"
+    )]
+    pub pid_file: Option<String>,
+    #[arg(
+        long,
+        requires = "daemon",
+        help = "Appends the output to the file instead of printing it"
+    )]
+    pub log_file: Option<String>,
+    #[arg(
+        long,
+        use_value_delimiter = true,
+        help = "Auto-tunnels only the ports of the given workspaces"
+    )]
+    pub only: Vec<String>,
+    #[arg(
+        long,
+        use_value_delimiter = true,
+        help = "Never auto-tunnels the given ports or port ranges (e.g. 5432,6379,8000-8100)"
+    )]
+    pub exclude_ports: Vec<String>,
+    #[arg(
+        long,
+        help = "Tunnels a local port to a remote port (local:remote). Can be repeated. Takes precedence over auto-tunnelling"
+    )]
+    pub map: Vec<String>,
+    #[arg(
+        long,
+        use_value_delimiter = true,
+        help = "Sets how a tunnel gets health-checked by its local port (port=tcp|tls|none). Defaults to tcp"
+    )]
+    pub check: Vec<String>,
 }
 
 #[derive(Clone, Parser, Debug)]
@@ -108,12 +593,40 @@ impl Default for WorkEnvParams {
 pub struct WorkParams {
     #[arg(short, long, alias = "git")]
     pub git_ssh_url: Option<String>,
+    #[arg(
+        long,
+        help = "Directory to clone the git repo into. Relative paths are resolved against /work"
+    )]
+    pub clone_dir: Option<String>,
+    #[arg(
+        long,
+        help = "Creates a shallow clone of the git repo with the given depth"
+    )]
+    pub clone_depth: Option<i64>,
+    #[arg(long, help = "Clones the git repo at the given branch or tag")]
+    pub branch: Option<String>,
+    #[arg(long, help = "Checks out the given commit after cloning the git repo")]
+    pub commit: Option<String>,
+    #[arg(long, help = "Clones only the history of a single branch")]
+    pub single_branch: bool,
+    #[arg(long, help = "Initializes and clones git submodules")]
+    pub recurse_submodules: bool,
     #[arg(short, long)]
     pub image: Option<String>,
-    #[arg(long)]
-    pub pull_image: bool,
+    #[arg(long, value_enum, help = "Image pull policy. Defaults to missing")]
+    pub pull: Option<RoozPullPolicy>,
+    #[arg(
+        long,
+        help = "Platform of the work container images, e.g. linux/amd64. Foreign platforms run emulated"
+    )]
+    pub platform: Option<String>,
     #[arg(short, long)]
     pub user: Option<String>,
+    #[arg(
+        long,
+        help = "UID (or UID:GID) of the workspace user. 'host' takes the ones of the current user. Defaults to the ones of the user in the image, otherwise 1000"
+    )]
+    pub uid: Option<String>,
     #[arg(
         short,
         long,
@@ -123,12 +636,26 @@ pub struct WorkParams {
     pub caches: Option<Vec<String>>,
     #[arg(short, long)]
     pub privileged: Option<bool>,
+    #[arg(
+        long,
+        help = "Remaps host ports already in use to free ones instead of failing"
+    )]
+    pub auto_ports: bool,
+    #[arg(long, help = "Groups the workspace into a project")]
+    pub project: Option<String>,
     #[arg(
         long,
         default_value = "true",
         help = "Starts the workspace immediately"
     )]
     pub start: Option<bool>,
+    #[arg(skip)]
+    pub reset_home: bool,
+    #[arg(skip)]
+    pub keep_existing: bool,
+    // the encrypted values generated for the workspace before, kept across updates
+    #[arg(skip)]
+    pub generated_secrets: HashMap<String, String>,
     #[command(flatten)]
     pub env: WorkEnvParams,
 }
@@ -137,12 +664,25 @@ impl Default for WorkParams {
     fn default() -> Self {
         Self {
             git_ssh_url: Default::default(),
+            clone_dir: Default::default(),
+            clone_depth: Default::default(),
+            branch: Default::default(),
+            commit: Default::default(),
+            single_branch: Default::default(),
+            recurse_submodules: Default::default(),
             image: Default::default(),
-            pull_image: Default::default(),
+            pull: Default::default(),
+            platform: Default::default(),
             user: Default::default(),
+            uid: Default::default(),
             caches: Default::default(),
             privileged: Default::default(),
+            auto_ports: Default::default(),
+            project: Default::default(),
             start: Default::default(),
+            reset_home: Default::default(),
+            keep_existing: Default::default(),
+            generated_secrets: Default::default(),
             env: Default::default(),
         }
     }
@@ -151,15 +691,37 @@ impl Default for WorkParams {
 #[derive(Parser, Debug)]
 #[command(about = "Creates a new workspace (container + volumes)")]
 pub struct NewParams {
-    pub name: String,
+    #[arg(
+        required_unless_present = "name_from_branch",
+        help = "The workspace name. With --name-from-branch it prefixes the branch instead of the repo name"
+    )]
+    pub name: Option<String>,
     #[command(flatten)]
     pub work: WorkParams,
     #[arg(
         long,
-        help = "Configures the new workspace from a config file given by the path.",
+        requires_all = ["git_ssh_url", "branch"],
+        help = "Names the workspace <repo>-<branch> so several branches of a repo can live side by side"
+    )]
+    pub name_from_branch: bool,
+    #[arg(
+        long,
+        help = "Configures the new workspace from a config file given by the path. Use - to read it from stdin",
         alias = "config"
     )]
     pub config_path: Option<String>,
+    #[arg(long, value_enum, help = "The format of the config read from stdin")]
+    pub format: Option<ConfigFormat>,
+    #[arg(
+        long,
+        help = "Reuses the home and work volumes left behind by a removed workspace with the given key"
+    )]
+    pub adopt_volumes: Option<String>,
+    #[arg(
+        long,
+        help = "Prints the containers, volumes and network the workspace would be created with, without creating anything"
+    )]
+    pub dry_run: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -167,6 +729,12 @@ pub struct NewParams {
 pub struct TmpParams {
     #[command(flatten)]
     pub work: WorkParams,
+    #[arg(
+        long,
+        help = "Configures the ephemeral workspace from a config file given by the path or git-url//file",
+        alias = "config"
+    )]
+    pub config_path: Option<String>,
     #[arg(short, long)]
     pub root: bool,
     #[arg(short, long, default_value = "bash", env = "ROOZ_SHELL")]
@@ -179,45 +747,237 @@ pub struct TmpParams {
     alias = "jump"
 )]
 pub struct EnterParams {
-    pub name: String,
+    #[arg(help = "Picks the workspace interactively if omitted")]
+    pub name: Option<String>,
     #[arg(short, long)]
     pub shell: Option<String>,
+    #[arg(
+        long,
+        conflicts_with_all = ["mux", "no_mux", "no_reconnect", "retries", "backoff"],
+        help = "Runs the command in the workspace's shell and exits with its exit status"
+    )]
+    pub command: Option<String>,
     #[arg(short, long)]
     pub root: bool,
     #[arg(short, long)]
     pub work_dir: Option<String>,
     #[arg(short, long)]
     pub container: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Attaches to a terminal multiplexer session so processes survive disconnecting"
+    )]
+    pub mux: Option<RoozMux>,
+    #[arg(
+        long,
+        help = "Enters without the multiplexer configured for the workspace"
+    )]
+    pub no_mux: bool,
+    #[arg(long, default_value = "rooz", help = "Multiplexer session name")]
+    pub session: String,
+    #[arg(long, help = "Exits instead of reconnecting when the session drops")]
+    pub no_reconnect: bool,
+    #[arg(
+        long,
+        default_value = "10",
+        conflicts_with = "no_reconnect",
+        help = "Reconnect attempts before giving up"
+    )]
+    pub retries: u32,
+    #[arg(
+        long,
+        default_value = "1",
+        conflicts_with = "no_reconnect",
+        help = "Initial delay between reconnect attempts in seconds. Doubles with every attempt up to 30s"
+    )]
+    pub backoff: u64,
+    #[arg(
+        long,
+        help = "Creates the workspace from ./.rooz.toml and the git remote of the current directory if it doesn't exist, without asking"
+    )]
+    pub create: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Enters the workspace of a local clone, matched by its git remote. Creates the workspace if there is none"
+)]
+pub struct OpenParams {
+    #[arg(default_value = ".", help = "A directory of the local clone")]
+    pub path: String,
+    #[arg(
+        long,
+        help = "The name of the workspace to create. Defaults to the repo name"
+    )]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum SecretsKey {
+    #[value(help = "The age identity created by rooz system init")]
+    Age,
+    #[value(help = "An age identity derived from the ed25519 SSH key created by rooz system init")]
+    Ssh,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+#[derive(Parser, Debug, Clone, clap::ValueEnum)]
+pub enum OutputFormat {
+    Yaml,
+    Json,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Shows everything rooz knows about a workspace: metadata, configs, containers, volumes and networks"
+)]
+pub struct InspectParams {
+    pub name: String,
+    #[arg(long, short, value_enum, default_value = "yaml")]
+    pub output: OutputFormat,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Shows the state and health of the workspace containers")]
+pub struct StatusParams {
+    pub name: String,
+    #[arg(
+        long,
+        short,
+        help = "Prints a one-line summary (e.g. work:running db:healthy) for shell prompts and status bars"
+    )]
+    pub short: bool,
+    #[arg(
+        long,
+        default_value = "5",
+        help = "Seconds to reuse the cached --short summary for. 0 disables caching"
+    )]
+    pub cache_ttl: u64,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Shows CPU, memory, network and block I/O usage of running workspace containers")]
+pub struct StatsParams {
+    #[arg(help = "Shows only the containers of the workspace")]
+    pub name: Option<String>,
+    #[arg(long, short, help = "Keeps refreshing the stats")]
+    pub watch: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Runs the clean.commands of the workspace config and reports the disk space reclaimed"
+)]
+pub struct CleanParams {
+    pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Checks the host setup rooz relies on")]
+pub struct DoctorParams {
+    #[arg(
+        long,
+        help = "Also checks a workspace. Signs a throwaway commit when git.sign is configured"
+    )]
+    pub workspace: Option<String>,
 }
 
 #[derive(Parser, Debug)]
 #[command(about = "Starts a workspace")]
 pub struct StartParams {
-    pub name: String,
+    #[arg(required_unless_present = "project")]
+    pub name: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "name",
+        help = "Starts all workspaces of the project"
+    )]
+    pub project: Option<String>,
 }
 
 #[derive(Parser, Debug)]
-#[command(about = "Lists workspaces", alias = "ls")]
-pub struct ListParams {}
+#[command(about = "Lists workspaces", aliases = ["ls", "ps"])]
+pub struct ListParams {
+    #[arg(long, help = "Shows the disk usage of each workspace's volumes")]
+    pub sizes: bool,
+    #[arg(long, help = "Lists only the workspaces of the project")]
+    pub project: Option<String>,
+    #[arg(long, value_enum, help = "Groups the workspaces")]
+    pub group_by: Option<GroupBy>,
+    #[arg(
+        long,
+        short,
+        help = "Lists only the matching workspaces: status=running|stopped, project=<name>, repo=<part of the url> or branch=<name>. Can be repeated"
+    )]
+    pub filter: Vec<String>,
+    #[arg(long, value_enum, help = "Sorts the workspaces. Defaults to name")]
+    pub sort: Option<SortBy>,
+    #[arg(
+        long,
+        conflicts_with_all = ["sizes", "project", "group_by", "filter", "sort"],
+        help = "Lists the archived workspaces instead"
+    )]
+    pub archived: bool,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum GroupBy {
+    Repo,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum SortBy {
+    Name,
+    /// Newest first
+    Created,
+    /// Most recently entered first
+    LastUsed,
+}
 
 #[derive(Parser, Debug)]
 #[command(about = "Removes a workspace", alias = "rm")]
 pub struct RemoveParams {
-    #[arg(required_unless_present = "all")]
+    #[arg(help = "Picks the workspace interactively if omitted")]
     pub name: Option<String>,
-    #[arg(short, long, help = "Kill running containers")]
+    #[arg(
+        short,
+        long,
+        help = "Kill running containers, even with sessions attached"
+    )]
     pub force: bool,
     #[arg(short, long, conflicts_with = "name", help = "Remove all workspaces")]
     pub all: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["name", "all"],
+        help = "Removes all workspaces of the project"
+    )]
+    pub project: Option<String>,
+    #[arg(long, help = "Also remove data volumes marked with keep")]
+    pub purge_data: bool,
 }
 
 #[derive(Parser, Debug)]
 #[command(about = "Stops a workspace")]
 pub struct StopParams {
-    #[arg(required_unless_present = "all")]
+    #[arg(help = "Picks the workspace interactively if omitted")]
     pub name: Option<String>,
     #[arg(short, long, conflicts_with = "name")]
     pub all: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["name", "all"],
+        help = "Stops all workspaces of the project"
+    )]
+    pub project: Option<String>,
+    #[arg(short, long, help = "Stops the workspace even with sessions attached")]
+    pub force: bool,
 }
 
 #[derive(Parser, Debug, Clone, clap::ValueEnum)]
@@ -225,6 +985,8 @@ pub enum ConfigPart {
     Origin,
     Body,
     Runtime,
+    /// What the runtime config changes compared to the body (vars, extends, CLI overrides)
+    Diff,
 }
 
 #[derive(Parser, Debug, Clone, clap::ValueEnum)]
@@ -242,6 +1004,11 @@ pub struct ShowConfigParams {
     pub part: ConfigPart,
     #[arg(long, short)]
     pub output: Option<ConfigFormat>,
+    #[arg(
+        long,
+        help = "Shows the decrypted secret values instead of masking them (reads the age identity)"
+    )]
+    pub reveal_secrets: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -260,8 +1027,32 @@ pub struct EditConfigParams {
 #[derive(Parser, Debug)]
 #[command(about = "Updates a workspace created from a config file")]
 pub struct UpdateParams {
-    #[arg()]
-    pub name: String,
+    #[arg(required_unless_present_any = ["check", "all", "project", "filter"])]
+    pub name: Option<String>,
+    #[arg(
+        long,
+        conflicts_with_all = ["name", "tweak", "purge", "config_path", "check"],
+        help = "Applies the stored configs of all the workspaces, one at a time"
+    )]
+    pub all: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["name", "tweak", "purge", "config_path", "check"],
+        help = "Applies the stored configs of the workspaces of the project"
+    )]
+    pub project: Option<String>,
+    #[arg(
+        long,
+        short,
+        conflicts_with_all = ["name", "tweak", "purge", "config_path", "check"],
+        help = "Applies the stored configs of the matching workspaces: origin=<part of the config origin>, repo=<part of the url> or branch=<name>. Can be repeated"
+    )]
+    pub filter: Vec<String>,
+    #[arg(
+        long,
+        help = "Keeps updating the other workspaces when one fails (with --all, --project or --filter)"
+    )]
+    pub continue_on_error: bool,
     #[command(flatten)]
     pub env: WorkEnvParams,
     #[arg(
@@ -276,8 +1067,44 @@ pub struct UpdateParams {
         help = "If set it removes the workspace volumes. WARNING: potential data loss ahead"
     )]
     pub purge: bool,
+    #[arg(
+        long,
+        requires = "purge",
+        help = "Purges the workspace even with sessions attached"
+    )]
+    pub force: bool,
+    #[arg(
+        long,
+        conflicts_with = "tweak",
+        help = "Replaces the config of the workspace with the one given by the path or git-url//file. Use - to read it from stdin",
+        alias = "config"
+    )]
+    pub config_path: Option<String>,
+    #[arg(long, value_enum, help = "The format of the config read from stdin")]
+    pub format: Option<ConfigFormat>,
     #[arg(long, help = "If set it skips pulling new images")]
     pub no_pull: bool,
+    #[arg(
+        long,
+        conflicts_with = "purge",
+        help = "Syncs the home volume from the image even if the image has not changed (requires home_from_image)"
+    )]
+    pub reset_home: bool,
+    #[arg(long, help = "Shows the config changes without applying them")]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        value_name = "TARGET",
+        conflicts_with = "purge",
+        help = "Re-creates only the given containers, keeping the others (and the shell sessions in them) running: work, sidecars or a container name. Can be repeated"
+    )]
+    pub only: Vec<String>,
+    #[arg(
+        long,
+        conflicts_with_all = ["tweak", "purge", "config_path", "reset_home", "dry_run", "only"],
+        help = "Reports the workspaces whose images have newer versions in the registry, without re-creating anything. Checks all workspaces if the name is omitted"
+    )]
+    pub check: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -285,24 +1112,99 @@ pub struct UpdateParams {
     about = "Attaches VsCode to a workspace. (requires VsCode installed and 'code' in $PATH)"
 )]
 pub struct CodeParams {
+    #[arg(help = "Picks the workspace interactively if omitted")]
+    pub name: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Attaches JetBrains Gateway to a workspace over SSH. (requires Gateway installed and sshd running in the workspace)"
+)]
+pub struct IdeaParams {
     #[arg()]
     pub name: String,
+    #[arg(long, help = "Prints the Gateway link instead of opening it")]
+    pub print: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Runs a command in a fresh container of the workspace. The container gets removed when the command completes"
+)]
+pub struct RunParams {
+    pub name: String,
+    #[arg(short, long, help = "The workspace container to base the run on")]
+    pub container: Option<String>,
+    #[arg(short, long)]
+    pub work_dir: Option<String>,
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Compares the configuration of two workspaces")]
+pub struct DiffConfigParams {
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Prints lifecycle events of workspace containers and volumes")]
+pub struct WatchParams {
+    #[arg(help = "Only prints events of the given workspace")]
+    pub name: Option<String>,
+    #[arg(long, help = "Prints events as JSON lines")]
+    pub json: bool,
+    #[arg(
+        long,
+        help = "Sends OOM kills and non-zero exits as notifications (see [notifications] in the system config)"
+    )]
+    pub notify: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Prints the rooz metadata of a workspace container. Without a name prints the current one"
+)]
+pub struct ContextParams {
+    pub name: Option<String>,
+    #[arg(short, long, requires = "name")]
+    pub container: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     New(NewParams),
     Enter(EnterParams),
+    Open(OpenParams),
     Code(CodeParams),
+    Idea(IdeaParams),
+    Inspect(InspectParams),
+    Status(StatusParams),
+    Stats(StatsParams),
+    Clean(CleanParams),
+    Doctor(DoctorParams),
+    Run(RunParams),
     Start(StartParams),
     Stop(StopParams),
     Remove(RemoveParams),
     Update(UpdateParams),
     List(ListParams),
     Config(Config),
+    DiffConfig(DiffConfigParams),
     Tmp(TmpParams),
     Remote(RemoteParams),
+    K8s(K8s),
     System(System),
+    Cache(Cache),
+    Image(Image),
+    Snapshot(Snapshot),
+    Backup(BackupParams),
+    Restore(RestoreParams),
+    Archive(ArchiveParams),
+    Unarchive(UnarchiveParams),
+    Watch(WatchParams),
+    Context(ContextParams),
 }
 
 #[derive(Parser, Debug)]
@@ -310,4 +1212,80 @@ pub enum Commands {
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    #[arg(
+        long,
+        global = true,
+        env = "ROOZ_OFFLINE",
+        help = "Skips all registry and git network access"
+    )]
+    pub offline: bool,
+    #[arg(
+        long,
+        global = true,
+        env = "ROOZ_HELPER_IMAGE",
+        help = "The image of the containers rooz runs for git clones, volume population and config reads. Overrides helper_image of the system config"
+    )]
+    pub helper_image: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "ROOZ_HOST_SSH",
+        help = "Mounts ~/.ssh/known_hosts and ~/.ssh/config of the host read-only into the git clone and work containers"
+    )]
+    pub host_ssh: bool,
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "ROOZ_QUIET",
+        help = "Hides the step-by-step progress of long-running operations like rooz new"
+    )]
+    pub quiet: bool,
+    #[arg(
+        long,
+        global = true,
+        env = "ROOZ_NON_INTERACTIVE",
+        help = "Never allocates a TTY, opens an editor or prompts. Configs to edit are read from stdin"
+    )]
+    pub non_interactive: bool,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "age",
+        env = "ROOZ_SECRETS_KEY",
+        help = "The key secrets get encrypted and decrypted with"
+    )]
+    pub secrets_key: SecretsKey,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "pretty",
+        env = "ROOZ_LOG_FORMAT",
+        help = "Format of the logs written to stderr"
+    )]
+    pub log_format: LogFormat,
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Logs more: -v info, -vv debug, -vvv trace. Overrides RUST_LOG for rooz"
+    )]
+    pub verbose: u8,
+    #[arg(
+        long,
+        global = true,
+        help = "Container engine host (e.g. unix:///run/podman/podman.sock). Defaults to DOCKER_HOST, then the Podman user socket, then the Docker socket"
+    )]
+    pub host: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "ROOZ_ENGINE",
+        conflicts_with = "host",
+        help = "Named container engine from [engines] in ~/.config/rooz/config.toml. Defaults to the engine the workspace was created on"
+    )]
+    pub engine: Option<String>,
 }
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use bollard::{
+    models::{Mount, MountTypeEnum},
+    volume::{CreateVolumeOptions, ListVolumesOptions},
+};
+use colored::Colorize;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{
+    api::WorkspaceApi,
+    cli::WorkParams,
+    cmd::snapshot,
+    config::config::{ConfigSource, FileFormat, RoozCfg},
+    constants,
+    model::{
+        error::RoozError,
+        types::AnyError,
+        volume::{CACHE_ROLE, WORKSPACE_CACHE_ROLE},
+    },
+    util::{
+        id::to_safe_id,
+        labels::{self, Labels, KEEP, ROLE, WORKSPACE_KEY},
+        time::DateTime,
+    },
+};
+
+const ARCHIVE_ROLE: &str = "archive";
+// the labels of the work container and of the archived volumes, so both can be re-created as they were
+const ARCHIVE_CONTAINER: &str = "dev.rooz.archive.container";
+const ARCHIVE_VOLUMES: &str = "dev.rooz.archive.volumes";
+const ARCHIVE_CREATED: &str = "dev.rooz.archive.created";
+const ARCHIVE_DIR: &str = "/tmp/rooz-archive";
+const DATA_DIR: &str = "/tmp/rooz-data";
+
+#[derive(Debug, Tabled)]
+struct ArchiveView {
+    #[tabled(rename = "WORKSPACE")]
+    workspace: String,
+    #[tabled(rename = "VOLUMES")]
+    volumes: usize,
+    #[tabled(rename = "ARCHIVED")]
+    created: String,
+}
+
+fn volume_name(workspace_key: &str) -> String {
+    format!("rooz_{}_{}", to_safe_id(workspace_key), ARCHIVE_ROLE)
+}
+
+fn data_mounts(volumes: &[&String]) -> Vec<(String, Mount)> {
+    volumes
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let dir = format!("{}/{}", DATA_DIR, i);
+            let mount = snapshot::mount(MountTypeEnum::VOLUME, v, &dir);
+            (dir, mount)
+        })
+        .collect()
+}
+
+impl<'a> WorkspaceApi<'a> {
+    // everything `rooz rm` would delete: kept volumes and shared caches stay where they are
+    async fn archived_volumes(
+        &self,
+        workspace_key: &str,
+        volumes_key: Option<&String>,
+    ) -> Result<HashMap<String, HashMap<String, String>>, AnyError> {
+        let mut keys = vec![workspace_key];
+        keys.extend(volumes_key.map(|k| k.as_str()));
+        let mut volumes = HashMap::new();
+        for key in keys {
+            let labels = Labels::new(Some(key), None);
+            for v in self
+                .api
+                .client
+                .list_volumes(Some(ListVolumesOptions {
+                    filters: (&labels).into(),
+                }))
+                .await?
+                .volumes
+                .unwrap_or_default()
+            {
+                let skip = v.labels.contains_key(KEEP)
+                    || matches!(
+                        v.labels.get(ROLE).map(|r| r.as_str()),
+                        Some(CACHE_ROLE | WORKSPACE_CACHE_ROLE | ARCHIVE_ROLE)
+                    );
+                if !skip {
+                    volumes.insert(v.name, v.labels);
+                }
+            }
+        }
+        Ok(volumes)
+    }
+
+    pub async fn archive(&self, workspace_key: &str, force: bool) -> Result<(), AnyError> {
+        let container_labels = self
+            .api
+            .container
+            .get_single(
+                &Labels::new(Some(workspace_key), Some(labels::ROLE_WORK))
+                    .with_container(Some(constants::DEFAULT_CONTAINER_NAME)),
+            )
+            .await?
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?
+            .labels
+            .unwrap_or_default();
+        if !labels::has_config(&container_labels) {
+            return Err(format!(
+                "Workspace {} was created without a config file so it could not be re-created. Only workspaces created with a config can be archived",
+                workspace_key
+            )
+            .into());
+        }
+        let archive = volume_name(workspace_key);
+        if self.api.client.inspect_volume(&archive).await.is_ok() {
+            return Err(format!("Workspace {} is already archived", workspace_key).into());
+        }
+        self.guard_sessions(workspace_key, "archive", force).await?;
+
+        let volumes = self
+            .archived_volumes(workspace_key, container_labels.get(labels::VOLUMES_KEY))
+            .await?;
+        let container_json = serde_json::to_string(&container_labels)?;
+        let volumes_json = serde_json::to_string(&volumes)?;
+        let created = DateTime::now().to_rfc3339();
+        let labels = Labels::new(Some(workspace_key), Some(ARCHIVE_ROLE)).with_keep(true);
+        let mut labels: HashMap<&str, &str> = (&labels).into();
+        labels.insert(ARCHIVE_CONTAINER, &container_json);
+        labels.insert(ARCHIVE_VOLUMES, &volumes_json);
+        labels.insert(ARCHIVE_CREATED, &created);
+        self.api
+            .client
+            .create_volume(CreateVolumeOptions {
+                name: archive.as_str(),
+                labels,
+                ..Default::default()
+            })
+            .await?;
+
+        let names = volumes.keys().collect::<Vec<_>>();
+        let mut mounts = vec![snapshot::mount(
+            MountTypeEnum::VOLUME,
+            &archive,
+            ARCHIVE_DIR,
+        )];
+        let mut script = vec!["set -e".to_string()];
+        for ((dir, mount), name) in data_mounts(&names).into_iter().zip(&names) {
+            mounts.push(mount);
+            script.push(format!(
+                "tar -czpf {}/{}.tar.gz --numeric-owner -C {} .",
+                ARCHIVE_DIR, name, dir
+            ));
+        }
+
+        println!("Archiving {} ...", workspace_key);
+        self.stop(workspace_key).await?;
+        if let Err(e) = self
+            .run_snapshot_script(
+                "archive",
+                workspace_key,
+                &self.api.image.helper,
+                mounts,
+                None,
+                &script.join("\n"),
+            )
+            .await
+        {
+            self.api.volume.remove_volume(&archive, true).await?;
+            return Err(e);
+        }
+        self.remove(workspace_key, true, false).await?;
+        println!(
+            "Archived {}. Run 'rooz unarchive {}' to bring it back",
+            workspace_key.bold(),
+            workspace_key
+        );
+        Ok(())
+    }
+
+    pub async fn unarchive(&self, workspace_key: &str) -> Result<(), AnyError> {
+        let archive = volume_name(workspace_key);
+        let archive_labels = self
+            .api
+            .client
+            .inspect_volume(&archive)
+            .await
+            .map_err(|_| format!("No archive found for: {}", workspace_key))?
+            .labels;
+        let work_labels = Labels::new(Some(workspace_key), Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+        if self.api.container.get_single(&work_labels).await?.is_some() {
+            return Err(format!("Workspace {} already exists", workspace_key).into());
+        }
+        let container_labels = serde_json::from_str::<HashMap<String, String>>(
+            archive_labels
+                .get(ARCHIVE_CONTAINER)
+                .ok_or("Invalid archive")?,
+        )?;
+        let volumes = serde_json::from_str::<HashMap<String, HashMap<String, String>>>(
+            archive_labels
+                .get(ARCHIVE_VOLUMES)
+                .ok_or("Invalid archive")?,
+        )?;
+
+        // volumes left from an interrupted unarchive get overwritten so it can be retried
+        for (name, labels) in &volumes {
+            if self.api.client.inspect_volume(name).await.is_err() {
+                self.api
+                    .client
+                    .create_volume(CreateVolumeOptions {
+                        name: name.as_str(),
+                        labels: labels
+                            .iter()
+                            .map(|(k, v)| (k.as_str(), v.as_str()))
+                            .collect(),
+                        ..Default::default()
+                    })
+                    .await?;
+            }
+        }
+
+        let names = volumes.keys().collect::<Vec<_>>();
+        let mut mounts = vec![snapshot::mount(
+            MountTypeEnum::VOLUME,
+            &archive,
+            ARCHIVE_DIR,
+        )];
+        let mut script = vec!["set -e".to_string()];
+        for ((dir, mount), name) in data_mounts(&names).into_iter().zip(&names) {
+            mounts.push(mount);
+            script.push(format!(
+                "find {} -mindepth 1 -delete && tar -xzpf {}/{}.tar.gz -C {}",
+                dir, ARCHIVE_DIR, name, dir
+            ));
+        }
+
+        println!("Unarchiving {} ...", workspace_key);
+        self.run_snapshot_script(
+            "unarchive",
+            workspace_key,
+            &self.api.image.helper,
+            mounts,
+            None,
+            &script.join("\n"),
+        )
+        .await?;
+
+        // the workspace-config volume has just been restored
+        let identity = self.crypt.read_age_identity().await?;
+        let stored = self
+            .config
+            .load(workspace_key, &container_labels, &identity)
+            .await?
+            .ok_or("Invalid archive")?;
+        let origin = &stored.origin;
+        let format = FileFormat::from_path(origin)?;
+        let config = RoozCfg::deserialize_config(&stored.body, format)?
+            .ok_or(RoozError::Config(format!("invalid config: {}", origin)))?;
+        // like in rooz update the repo, the project and the branch are not part of the config
+        let params = WorkParams {
+            git_ssh_url: stored.git_ssh_url(&container_labels),
+            project: container_labels.get(labels::PROJECT).cloned(),
+            branch: container_labels.get(labels::BRANCH).cloned(),
+            uid: Some(
+                container_labels
+                    .get(labels::UID)
+                    .cloned()
+                    .unwrap_or(constants::DEFAULT_UID.to_string()),
+            ),
+            generated_secrets: stored.generated.clone(),
+            ..Default::default()
+        };
+        self.new(
+            workspace_key,
+            &params,
+            Some(ConfigSource::Body {
+                value: config,
+                origin: origin.to_string(),
+                format,
+            }),
+            false,
+            &identity,
+            container_labels
+                .get(labels::VOLUMES_KEY)
+                .map(|k| k.as_str()),
+        )
+        .await?;
+
+        self.api.volume.remove_volume(&archive, true).await?;
+        println!(
+            "Unarchived {}. Run 'rooz enter {}' to enter",
+            workspace_key.bold(),
+            workspace_key
+        );
+        Ok(())
+    }
+
+    pub async fn archive_list(&self) -> Result<(), AnyError> {
+        let labels = Labels::new(None, Some(ARCHIVE_ROLE));
+        let mut views = self
+            .api
+            .client
+            .list_volumes(Some(ListVolumesOptions {
+                filters: (&labels).into(),
+            }))
+            .await?
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|v| ArchiveView {
+                workspace: v.labels.get(WORKSPACE_KEY).cloned().unwrap_or_default(),
+                volumes: v
+                    .labels
+                    .get(ARCHIVE_VOLUMES)
+                    .and_then(|j| {
+                        serde_json::from_str::<HashMap<String, serde_json::Value>>(j).ok()
+                    })
+                    .map(|m| m.len())
+                    .unwrap_or_default(),
+                created: v.labels.get(ARCHIVE_CREATED).cloned().unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        views.sort_by(|a, b| a.workspace.cmp(&b.workspace));
+        println!("{}", Table::new(views).with(Style::blank()));
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::{env, fs};
+
+use base64::{engine::general_purpose, Engine as _};
+use bollard::models::{Mount, MountTypeEnum};
+use clap::{parser::ValueSource, ArgMatches, CommandFactory};
+use serde::{Deserialize, Serialize};
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{
+    api::{container, Api},
+    cli::{Cli, Commands, Config, ConfigCommands, System, SystemCommands},
+    constants,
+    model::types::{AnyError, ContainerResult, RunSpec},
+    util::{id, redact, time::DateTime},
+};
+
+// lives on the container engine host so a shared server keeps one log for all its users
+pub const VOLUME_NAME: &str = "rooz-audit-vol";
+const DIR: &str = "/tmp/rooz-audit";
+const FILE: &str = "audit.log";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub time: String,
+    pub user: String,
+    pub host: String,
+    pub operation: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    pub params: Vec<String>,
+    // missing in entries written before the operations were recorded once done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<String>,
+}
+
+#[derive(Debug, Tabled)]
+struct AuditView {
+    #[tabled(rename = "TIME")]
+    time: String,
+    #[tabled(rename = "USER")]
+    user: String,
+    #[tabled(rename = "HOST")]
+    host: String,
+    #[tabled(rename = "OPERATION")]
+    operation: String,
+    #[tabled(rename = "WORKSPACE")]
+    workspace: String,
+    #[tabled(rename = "PARAMS")]
+    params: String,
+    #[tabled(rename = "OUTCOME")]
+    outcome: String,
+}
+
+pub fn current_user() -> String {
+    env::var("USER")
+        .or(env::var("USERNAME"))
+        .unwrap_or("unknown".into())
+}
+
+pub fn current_host() -> String {
+    env::var("HOSTNAME")
+        .ok()
+        .or(fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or("unknown".into())
+}
+
+impl AuditEntry {
+    pub fn new(operation: &str, workspace: Option<&str>, matches: &ArgMatches) -> AuditEntry {
+        AuditEntry {
+            time: DateTime::now().to_rfc3339(),
+            user: current_user(),
+            host: current_host(),
+            operation: operation.into(),
+            workspace: workspace.map(|w| w.into()),
+            params: flags(matches),
+            outcome: None,
+        }
+    }
+
+    pub fn finish(mut self, result: &Result<(), AnyError>) -> AuditEntry {
+        self.outcome = Some(match result {
+            Ok(()) => "ok".into(),
+            Err(e) => format!("failed: {}", redact::redact(&e.to_string())),
+        });
+        self
+    }
+}
+
+// only the names of the flags given. Their values (e.g. --env KEY=secret) may be secrets so are masked
+fn flags(matches: &ArgMatches) -> Vec<String> {
+    let mut command = Cli::command();
+    command.build();
+    let (mut command, mut matches) = (&command, matches);
+    while let Some((name, sub)) = matches.subcommand() {
+        match command.find_subcommand(name) {
+            Some(c) => (command, matches) = (c, sub),
+            None => break,
+        }
+    }
+    command
+        .get_arguments()
+        .filter(|a| matches.value_source(a.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .filter_map(|a| {
+            let long = a.get_long()?;
+            Some(match a.get_action().takes_values() {
+                true => format!("--{}={}", long, redact::MASK),
+                false => format!("--{}", long),
+            })
+        })
+        .collect()
+}
+
+// the commands changing workspaces along with the workspace they act on, if named
+pub fn operation(command: &Commands) -> Option<(&'static str, Option<&str>)> {
+    match command {
+        Commands::New(p) if !p.dry_run => Some(("new", p.name.as_deref())),
+        Commands::Update(p) if !p.check => Some(("update", p.name.as_deref())),
+        Commands::Remove(p) => Some(("rm", p.name.as_deref())),
+        Commands::Start(p) => Some(("start", p.name.as_deref())),
+        Commands::Stop(p) => Some(("stop", p.name.as_deref())),
+        Commands::Clean(p) => Some(("clean", Some(&p.name))),
+        Commands::Restore(p) => Some(("restore", Some(&p.name))),
+        Commands::Archive(p) => Some(("archive", Some(&p.name))),
+        Commands::Unarchive(p) => Some(("unarchive", Some(&p.name))),
+        Commands::Config(Config {
+            command: ConfigCommands::Edit(_),
+        }) => Some(("config edit", None)),
+        Commands::System(System {
+            command: SystemCommands::Migrate(p),
+        }) if !p.dry_run => Some(("system migrate", None)),
+        _ => None,
+    }
+}
+
+fn mount() -> Mount {
+    Mount {
+        typ: Some(MountTypeEnum::VOLUME),
+        source: Some(VOLUME_NAME.into()),
+        target: Some(DIR.into()),
+        ..Default::default()
+    }
+}
+
+impl<'a> Api<'a> {
+    // one JSON line per operation. Appends of a single line don't interleave so concurrent rooz runs are fine
+    pub async fn audit(&self, entry: &AuditEntry) -> Result<(), AnyError> {
+        let line = format!("{}\n", serde_json::to_string(entry)?);
+        let script = format!(
+            "echo '{}' | base64 -d >> {}/{}",
+            general_purpose::STANDARD.encode(line),
+            DIR,
+            FILE
+        );
+        let entrypoint = container::inject(&script, "audit.sh");
+        let result = self
+            .container
+            .create(RunSpec {
+                reason: "audit",
+                image: &self.image.helper,
+                uid: constants::ROOT_UID,
+                container_name: &id::random_suffix("rooz-audit"),
+                workspace_key: &id::random_suffix("tmp"),
+                mounts: Some(vec![mount()]),
+                entrypoint: Some(entrypoint.iter().map(String::as_str).collect()),
+                ..Default::default()
+            })
+            .await?;
+        self.container.start(result.id()).await?;
+        self.container.logs_to_stdout(result.id()).await?;
+        self.container.remove(result.id(), true).await?;
+        Ok(())
+    }
+
+    async fn audit_entries(&self) -> Result<Vec<AuditEntry>, AnyError> {
+        let result = self
+            .container
+            .create(RunSpec {
+                reason: "read-audit",
+                image: &self.image.helper,
+                uid: constants::ROOT_UID,
+                container_name: &id::random_suffix("rooz-audit"),
+                workspace_key: &id::random_suffix("tmp"),
+                mounts: Some(vec![mount()]),
+                entrypoint: Some(vec!["cat"]),
+                auto_remove: true,
+                ..Default::default()
+            })
+            .await?;
+        let ContainerResult::Created { id } = result else {
+            return Err("Could not read the audit log".into());
+        };
+        self.container.start(&id).await?;
+        let path = format!("{}/{}", DIR, FILE);
+        let body = self
+            .exec
+            .output(
+                "read audit log",
+                &id,
+                None,
+                Some(vec![
+                    "sh",
+                    "-c",
+                    &format!("cat {} 2>/dev/null || true", path),
+                ]),
+            )
+            .await;
+        self.container.kill(&id).await?;
+        Ok(body?
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .filter_map(|l| match serde_json::from_str(l) {
+                Ok(e) => Some(e),
+                Err(e) => {
+                    log::debug!("Skipping an invalid audit entry: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    pub async fn audit_list(
+        &self,
+        workspace_key: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<(), AnyError> {
+        let entries = self
+            .audit_entries()
+            .await?
+            .into_iter()
+            .filter(|e| workspace_key.is_none() || e.workspace.as_deref() == workspace_key)
+            .collect::<Vec<_>>();
+        let skip = limit.map_or(0, |l| entries.len().saturating_sub(l));
+        let views = entries.into_iter().skip(skip).map(|e| AuditView {
+            time: e.time,
+            user: e.user,
+            host: e.host,
+            operation: e.operation,
+            workspace: e.workspace.unwrap_or_default(),
+            params: e.params.join(" "),
+            outcome: e.outcome.unwrap_or_default(),
+        });
+        println!("{}", Table::new(views).with(Style::blank()));
+        Ok(())
+    }
+}
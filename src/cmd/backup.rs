@@ -0,0 +1,233 @@
+use std::{collections::HashMap, env};
+
+use bollard::models::{Mount, MountTypeEnum};
+use colored::Colorize;
+
+use crate::{
+    api::WorkspaceApi,
+    cmd::snapshot::{self, SnapshotTarget},
+    model::types::AnyError,
+    util::{ssh, time::DateTime},
+};
+
+const BACKUP_DIR: &str = "/tmp/rooz-backup";
+const TARGET_DIR: &str = "/tmp/rooz-backup-target";
+const AWS_IMAGE: &str = "docker.io/amazon/aws-cli:latest";
+const RSYNC_IMAGE: &str = "docker.io/instrumentisto/rsync-ssh:latest";
+// passed through from the host so S3-compatible stores work via AWS_ENDPOINT_URL
+const AWS_ENV: [&str; 6] = [
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "AWS_REGION",
+    "AWS_DEFAULT_REGION",
+    "AWS_ENDPOINT_URL",
+];
+const SSH_COMMAND: &str = "ssh -i /tmp/.ssh/id_ed25519 -o UserKnownHostsFile=/tmp/.ssh/known_hosts -o StrictHostKeyChecking=accept-new";
+
+// a backup is a directory named after the backup holding the tar.gz files of a snapshot
+pub enum BackupTarget {
+    File { dir: String },
+    S3 { url: String },
+    Rsync { host: String, path: String },
+}
+
+impl BackupTarget {
+    pub fn parse(target: &str) -> Result<Self, AnyError> {
+        let target = target.trim_end_matches('/');
+        match target.split_once("://") {
+            None => Ok(BackupTarget::File { dir: target.into() }),
+            Some(("file", path)) => Ok(BackupTarget::File { dir: path.into() }),
+            Some(("s3", bucket)) if !bucket.is_empty() => {
+                Ok(BackupTarget::S3 { url: target.into() })
+            }
+            Some(("ssh", rest)) => match rest.split_once('/') {
+                Some((host, path)) if !host.is_empty() => Ok(BackupTarget::Rsync {
+                    host: host.into(),
+                    path: format!("/{}", path),
+                }),
+                _ => Err(format!(
+                    "Invalid backup target: {}. Use ssh://user@host/path",
+                    target
+                )
+                .into()),
+            },
+            Some(_) => Err(format!(
+                "Unsupported backup target: {}. Use a path, file://, s3:// or ssh://",
+                target
+            )
+            .into()),
+        }
+    }
+
+    // splits the location of a single backup into its target and the backup name
+    pub fn parse_location(location: &str) -> Result<(Self, String), AnyError> {
+        match location.trim_end_matches('/').rsplit_once('/') {
+            Some((target, name)) if !name.is_empty() => Ok((Self::parse(target)?, name.into())),
+            _ => Err(format!("Invalid backup location: {}", location).into()),
+        }
+    }
+
+    pub fn location(&self, name: &str) -> String {
+        match self {
+            BackupTarget::File { dir } => format!("{}/{}", dir, name),
+            BackupTarget::S3 { url } => format!("{}/{}", url, name),
+            BackupTarget::Rsync { host, path } => format!("ssh://{}{}/{}", host, path, name),
+        }
+    }
+
+    fn image<'b>(&self, helper: &'b str) -> &'b str {
+        match self {
+            BackupTarget::File { .. } => helper,
+            BackupTarget::S3 { .. } => AWS_IMAGE,
+            BackupTarget::Rsync { .. } => RSYNC_IMAGE,
+        }
+    }
+
+    fn env(&self) -> Option<HashMap<String, String>> {
+        match self {
+            BackupTarget::S3 { .. } => Some(
+                AWS_ENV
+                    .iter()
+                    .filter_map(|k| env::var(k).ok().map(|v| (k.to_string(), v)))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    // a file target is a path on the container engine host, the same as for snapshot files
+    fn mounts(&self) -> Result<Vec<Mount>, AnyError> {
+        Ok(match self {
+            BackupTarget::File { dir } => vec![snapshot::mount(
+                MountTypeEnum::BIND,
+                &std::path::absolute(dir)?.to_string_lossy(),
+                TARGET_DIR,
+            )],
+            BackupTarget::S3 { .. } => vec![],
+            BackupTarget::Rsync { .. } => vec![ssh::mount("/tmp/.ssh")],
+        })
+    }
+
+    fn upload(&self, name: &str) -> String {
+        match self {
+            BackupTarget::File { .. } => format!(
+                "mkdir -p {}/{} && cp -a {}/. {}/{}/",
+                TARGET_DIR, name, BACKUP_DIR, TARGET_DIR, name
+            ),
+            BackupTarget::S3 { url } => {
+                format!("aws s3 cp --recursive {} {}/{}/", BACKUP_DIR, url, name)
+            }
+            BackupTarget::Rsync { host, path } => format!(
+                "rsync -a --mkpath -e '{}' {}/ '{}:{}/{}/'",
+                SSH_COMMAND, BACKUP_DIR, host, path, name
+            ),
+        }
+    }
+
+    fn download(&self, name: &str) -> String {
+        match self {
+            BackupTarget::File { .. } => {
+                format!("cp -a {}/{}/. {}/", TARGET_DIR, name, BACKUP_DIR)
+            }
+            BackupTarget::S3 { url } => {
+                format!("aws s3 cp --recursive {}/{}/ {}", url, name, BACKUP_DIR)
+            }
+            BackupTarget::Rsync { host, path } => format!(
+                "rsync -a -e '{}' '{}:{}/{}/' {}/",
+                SSH_COMMAND, host, path, name, BACKUP_DIR
+            ),
+        }
+    }
+}
+
+impl<'a> WorkspaceApi<'a> {
+    async fn transfer(
+        &self,
+        reason: &str,
+        workspace_key: &str,
+        target: &BackupTarget,
+        volume: &str,
+        script: &str,
+    ) -> Result<(), AnyError> {
+        let mut mounts = vec![snapshot::mount(MountTypeEnum::VOLUME, volume, BACKUP_DIR)];
+        mounts.extend(target.mounts()?);
+        self.run_snapshot_script(
+            reason,
+            workspace_key,
+            target.image(&self.api.image.helper),
+            mounts,
+            target.env(),
+            script,
+        )
+        .await
+    }
+
+    // the backup is staged in a snapshot volume removed once uploaded
+    pub async fn backup(
+        &self,
+        workspace_key: &str,
+        target: &str,
+        home: bool,
+        all: bool,
+    ) -> Result<(), AnyError> {
+        let target = BackupTarget::parse(target)?;
+        let name = format!("{}-{}", workspace_key, DateTime::now().to_compact());
+        self.snapshot_create(
+            workspace_key,
+            SnapshotTarget::Volume { name: Some(&name) },
+            home,
+            all,
+        )
+        .await?;
+        let volume = snapshot::volume_name(workspace_key, &name);
+
+        println!("Uploading backup to {} ...", target.location(&name));
+        let result = self
+            .transfer(
+                "backup-upload",
+                workspace_key,
+                &target,
+                &volume,
+                &target.upload(&name),
+            )
+            .await;
+        self.api.volume.remove_volume(&volume, true).await?;
+        result?;
+        println!("Created backup: {}", target.location(&name).bold());
+        Ok(())
+    }
+
+    pub async fn restore(&self, workspace_key: &str, location: &str) -> Result<(), AnyError> {
+        let (target, name) = BackupTarget::parse_location(location)?;
+        let staging = format!("restore-{}", DateTime::now().to_compact());
+        let volume = self
+            .create_snapshot_volume(workspace_key, &staging, "")
+            .await?;
+
+        println!("Downloading backup from {} ...", target.location(&name));
+        let result = match self
+            .transfer(
+                "backup-download",
+                workspace_key,
+                &target,
+                &volume,
+                &target.download(&name),
+            )
+            .await
+        {
+            Ok(_) => {
+                self.snapshot_restore(
+                    workspace_key,
+                    SnapshotTarget::Volume {
+                        name: Some(&staging),
+                    },
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        self.api.volume.remove_volume(&volume, true).await?;
+        result
+    }
+}
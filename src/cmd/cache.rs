@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use bollard::{
+    container::ListContainersOptions,
+    models::{Mount, MountTypeEnum},
+    service::Volume,
+    volume::{ListVolumesOptions, RemoveVolumeOptions},
+};
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{
+    api::{container, Api},
+    constants,
+    model::{
+        types::{AnyError, RunSpec},
+        volume::{RoozVolume, CACHE_ROLE},
+    },
+    util::{
+        id,
+        labels::{Labels, WORKSPACE_KEY},
+        size,
+    },
+};
+
+#[derive(Debug, Tabled)]
+struct CacheView {
+    #[tabled(rename = "CACHE")]
+    name: String,
+    #[tabled(rename = "PATHS")]
+    paths: String,
+    #[tabled(rename = "USED BY")]
+    used_by: String,
+}
+
+#[derive(Debug, Tabled)]
+struct CacheSizeView {
+    #[tabled(rename = "CACHE")]
+    name: String,
+    #[tabled(rename = "SIZE")]
+    size: String,
+}
+
+#[derive(Debug, Default)]
+struct CacheUsage {
+    paths: BTreeSet<String>,
+    workspaces: BTreeSet<String>,
+}
+
+impl<'a> Api<'a> {
+    async fn caches(&self) -> Result<Vec<Volume>, AnyError> {
+        let labels = Labels::new(None, Some(CACHE_ROLE));
+        let mut volumes = self
+            .client
+            .list_volumes(Some(ListVolumesOptions {
+                filters: (&labels).into(),
+            }))
+            .await?
+            .volumes
+            .unwrap_or_default();
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(volumes)
+    }
+
+    async fn cache_usage(&self) -> Result<BTreeMap<String, CacheUsage>, AnyError> {
+        let containers = self
+            .client
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters: (&Labels::default()).into(),
+                ..Default::default()
+            }))
+            .await?;
+
+        let mut usage = BTreeMap::<String, CacheUsage>::new();
+        for c in containers {
+            let workspace = c
+                .labels
+                .as_ref()
+                .and_then(|l| l.get(WORKSPACE_KEY))
+                .cloned();
+            for m in c.mounts.unwrap_or_default() {
+                if let Some(name) = m.name {
+                    let entry = usage.entry(name).or_default();
+                    entry.paths.extend(m.destination);
+                    entry.workspaces.extend(workspace.clone());
+                }
+            }
+        }
+        Ok(usage)
+    }
+
+    async fn resolve_cache(&self, cache: &str) -> Result<String, AnyError> {
+        let name = if cache.starts_with("rooz_") {
+            cache.to_string()
+        } else {
+            RoozVolume::cache(cache).safe_volume_name()
+        };
+        match self.caches().await?.iter().find(|v| v.name == name) {
+            Some(v) => Ok(v.name.to_string()),
+            None => Err(format!("Cache not found: {}", cache).into()),
+        }
+    }
+
+    pub async fn cache_list(&self) -> Result<(), AnyError> {
+        let usage = self.cache_usage().await?;
+        let views = self
+            .caches()
+            .await?
+            .into_iter()
+            .map(|v| {
+                let u = usage.get(&v.name);
+                let join = |set: Option<&BTreeSet<String>>| {
+                    set.map(|s| s.iter().cloned().collect::<Vec<_>>().join(", "))
+                        .unwrap_or_default()
+                };
+                CacheView {
+                    paths: join(u.map(|u| &u.paths)),
+                    used_by: join(u.map(|u| &u.workspaces)),
+                    name: v.name,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        println!("{}", Table::new(views).with(Style::blank()));
+        Ok(())
+    }
+
+    pub async fn cache_inspect(&self, cache: &str) -> Result<(), AnyError> {
+        let name = self.resolve_cache(cache).await?;
+        let volume = self.client.inspect_volume(&name).await?;
+        let usage = self.cache_usage().await?;
+        let u = usage.get(&name);
+
+        println!("Name:       {}", volume.name);
+        println!("Driver:     {}", volume.driver);
+        println!("Mountpoint: {}", volume.mountpoint);
+        if let Some(created) = volume.created_at {
+            println!("Created:    {}", created);
+        }
+        println!("Mounted at:");
+        for p in u
+            .map(|u| u.paths.iter().collect::<Vec<_>>())
+            .unwrap_or_default()
+        {
+            println!("  {}", p);
+        }
+        println!("Used by:");
+        for w in u
+            .map(|u| u.workspaces.iter().collect::<Vec<_>>())
+            .unwrap_or_default()
+        {
+            println!("  {}", w);
+        }
+        Ok(())
+    }
+
+    pub async fn cache_remove(&self, cache: &str) -> Result<(), AnyError> {
+        let name = self.resolve_cache(cache).await?;
+        if let Some(u) = self.cache_usage().await?.get(&name) {
+            if !u.workspaces.is_empty() {
+                return Err(format!(
+                    "Cache {} is in use by: {}",
+                    name,
+                    u.workspaces.iter().cloned().collect::<Vec<_>>().join(", ")
+                )
+                .into());
+            }
+        }
+        self.client
+            .remove_volume(&name, Some(RemoveVolumeOptions { force: false }))
+            .await?;
+        println!("Removed {}", name);
+        Ok(())
+    }
+
+    pub async fn cache_size(&self, cache: Option<&str>) -> Result<(), AnyError> {
+        let names = match cache {
+            Some(c) => vec![self.resolve_cache(c).await?],
+            None => self.caches().await?.into_iter().map(|v| v.name).collect(),
+        };
+        if names.is_empty() {
+            return Ok(());
+        }
+
+        let root = "/tmp/rooz-caches";
+        let mounts = names
+            .iter()
+            .map(|n| Mount {
+                typ: Some(MountTypeEnum::VOLUME),
+                source: Some(n.to_string()),
+                target: Some(format!("{}/{}", root, n)),
+                read_only: Some(true),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let workspace_key = id::random_suffix("tmp");
+        let run_spec = RunSpec {
+            reason: "cache-size",
+            image: &self.image.helper,
+            uid: constants::ROOT_UID,
+            container_name: &id::random_suffix("cache-size"),
+            workspace_key: &workspace_key,
+            mounts: Some(mounts),
+            entrypoint: Some(vec!["cat"]),
+            auto_remove: true,
+            ..Default::default()
+        };
+
+        let container_result = self.container.create(run_spec).await?;
+        let container_id = container_result.id();
+        self.container.start(container_id).await?;
+        let script = container::inject(&format!("du -sk {}/*", root), "du.sh");
+        let output = self
+            .exec
+            .output(
+                "cache size",
+                container_id,
+                None,
+                Some(script.iter().map(String::as_str).collect()),
+            )
+            .await?;
+        self.container.kill(container_id).await?;
+
+        let views = output
+            .lines()
+            .filter_map(|l| match l.split_whitespace().collect::<Vec<_>>()[..] {
+                [kilobytes, path] => Some(CacheSizeView {
+                    name: path.trim_start_matches(root).trim_start_matches('/').into(),
+                    size: size::human(kilobytes.parse::<i64>().ok()? * 1024),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        println!("{}", Table::new(views).with(Style::blank()));
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::{hash_map::Entry, BTreeSet, HashMap};
+
+use colored::Colorize;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{
+    api::WorkspaceApi,
+    model::{error::RoozError, types::AnyError},
+    util::labels::{Labels, CONTAINER, IMAGE_DIGEST, ROLE, ROLE_SIDECAR, ROLE_WORK, WORKSPACE_KEY},
+};
+
+const UP_TO_DATE: &str = "up to date";
+const STALE: &str = "stale";
+
+#[derive(Debug, Tabled)]
+struct ImageCheckView {
+    #[tabled(rename = "WORKSPACE")]
+    workspace: String,
+    #[tabled(rename = "CONTAINER")]
+    container: String,
+    #[tabled(rename = "IMAGE")]
+    image: String,
+    #[tabled(rename = "STATUS")]
+    status: String,
+}
+
+impl<'a> WorkspaceApi<'a> {
+    // compares the digests the containers were created from with the current ones of their tags.
+    // Nothing gets pulled or re-created
+    pub async fn update_check(&self, workspace_key: Option<&str>) -> Result<(), AnyError> {
+        if self.api.image.offline {
+            return Err(
+                "Checking for newer images requires registry access (drop --offline)".into(),
+            );
+        }
+        let containers = self
+            .api
+            .container
+            .get_all(&Labels::new(workspace_key, None))
+            .await?
+            .into_iter()
+            .filter(|c| {
+                c.labels
+                    .as_ref()
+                    .and_then(|l| l.get(ROLE))
+                    .is_some_and(|r| r == ROLE_WORK || r == ROLE_SIDECAR)
+            })
+            .collect::<Vec<_>>();
+        if let (Some(key), true) = (workspace_key, containers.is_empty()) {
+            return Err(RoozError::WorkspaceNotFound(key.to_string()).into());
+        }
+
+        let mut registry = HashMap::<String, Result<Option<String>, String>>::new();
+        let mut views = Vec::new();
+        for c in containers {
+            let labels = c.labels.unwrap_or_default();
+            let image = c.image.unwrap_or_default();
+            let local = match labels.get(IMAGE_DIGEST) {
+                Some(d) => Some(d.to_string()),
+                // workspaces created before the digest was recorded
+                None => match c.image_id.as_deref() {
+                    Some(id) => self.api.image.digest(id).await.ok().flatten(),
+                    None => None,
+                },
+            };
+            let status = if image.contains('@') {
+                "pinned".to_string()
+            } else {
+                if let Entry::Vacant(e) = registry.entry(image.to_string()) {
+                    e.insert(
+                        self.api
+                            .image
+                            .registry_digest(&image)
+                            .await
+                            .map_err(|e| e.to_string()),
+                    );
+                }
+                match (local, &registry[&image]) {
+                    (Some(l), Ok(Some(r))) if &l == r => UP_TO_DATE.to_string(),
+                    (Some(_), Ok(Some(_))) => STALE.to_string(),
+                    (None, _) => "unknown (no digest recorded)".to_string(),
+                    (_, Ok(None)) => "unknown (no digest in the registry)".to_string(),
+                    (_, Err(e)) => format!("unknown ({})", e),
+                }
+            };
+            views.push(ImageCheckView {
+                workspace: labels.get(WORKSPACE_KEY).cloned().unwrap_or_default(),
+                container: labels.get(CONTAINER).cloned().unwrap_or_default(),
+                image,
+                status,
+            });
+        }
+        views.sort_by(|a, b| (&a.workspace, &a.container).cmp(&(&b.workspace, &b.container)));
+
+        let stale = views
+            .iter()
+            .filter(|v| v.status == STALE)
+            .map(|v| v.workspace.to_string())
+            .collect::<BTreeSet<_>>();
+        println!("{}", Table::new(views).with(Style::blank()));
+        if !stale.is_empty() {
+            println!(
+                "{}",
+                format!(
+                    "Newer images are available for: {}. Run 'rooz update <workspace>' to take them",
+                    stale.into_iter().collect::<Vec<_>>().join(", ")
+                )
+                .yellow()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use colored::Colorize;
+
+use crate::{
+    api::WorkspaceApi,
+    config::runtime::RuntimeConfig,
+    constants,
+    model::{error::RoozError, types::AnyError, volume::RoozVolume},
+    util::{
+        labels::{self, Labels},
+        size,
+    },
+};
+
+impl<'a> WorkspaceApi<'a> {
+    // du runs as root so directories the workspace user can't read still count
+    async fn disk_usage(&self, container_id: &str, paths: &[String]) -> Result<i64, AnyError> {
+        let script = format!(
+            "du -skx {} 2>/dev/null | awk '{{ s += $1 }} END {{ print s + 0 }}'",
+            paths
+                .iter()
+                .map(|p| format!("'{}'", p))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        let output = self
+            .api
+            .exec
+            .output(
+                "disk usage",
+                container_id,
+                Some(constants::ROOT_UID),
+                Some(vec!["sh", "-c", &script]),
+            )
+            .await?;
+        Ok(output.trim().parse::<i64>().unwrap_or_default() * 1024)
+    }
+
+    pub async fn clean(&self, workspace_key: &str) -> Result<(), AnyError> {
+        let cfg = self
+            .runtime_config(workspace_key)
+            .await?
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+        let commands = cfg
+            .clean
+            .as_ref()
+            .and_then(|c| c.commands.clone())
+            .unwrap_or_default();
+        if commands.is_empty() {
+            return Err(RoozError::Config(
+                "no clean.commands configured. Add them to the config and run rooz update".into(),
+            )
+            .into());
+        }
+
+        let labels = Labels::new(Some(workspace_key), Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+        let container = self
+            .api
+            .container
+            .get_single(&labels)
+            .await?
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+        let was_running = container.state.as_deref() == Some("running");
+
+        self.start(workspace_key).await?;
+        let result = self
+            .run_clean(&container.id.unwrap_or_default(), &cfg, &commands)
+            .await;
+        // a workspace stopped before gets stopped again, even if cleaning failed
+        if !was_running {
+            self.stop(workspace_key).await?;
+        }
+        result
+    }
+
+    async fn run_clean(
+        &self,
+        container_id: &str,
+        cfg: &RuntimeConfig,
+        commands: &[String],
+    ) -> Result<(), AnyError> {
+        let home = cfg.home_dir();
+        let paths = [constants::WORK_DIR.to_string(), home.clone(), "/tmp".into()]
+            .into_iter()
+            .chain(
+                cfg.caches
+                    .iter()
+                    .map(|c| RoozVolume::cache_path(c).replacen('~', &home, 1)),
+            )
+            .collect::<Vec<_>>();
+        let before = self.disk_usage(container_id, &paths).await?;
+
+        for command in commands {
+            println!("{} {}", "Running:".bold(), command);
+            if let Err(e) = self
+                .api
+                .exec
+                .tty(
+                    "clean",
+                    container_id,
+                    false,
+                    None,
+                    None,
+                    Some(vec!["sh", "-c", command]),
+                )
+                .await
+            {
+                eprintln!("{}", format!("{} failed: {}", command, e).yellow());
+            }
+        }
+
+        let after = self.disk_usage(container_id, &paths).await?;
+        println!(
+            "Reclaimed {} ({} -> {} in {})",
+            size::human((before - after).max(0)).green(),
+            size::human(before),
+            size::human(after),
+            paths.join(", ")
+        );
+        Ok(())
+    }
+}
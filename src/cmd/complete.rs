@@ -0,0 +1,127 @@
+use std::collections::BTreeSet;
+
+use clap::Command;
+use clap_complete::Shell;
+
+use crate::{
+    api::Api,
+    constants,
+    model::types::AnyError,
+    util::labels::{Labels, CONTAINER, ROLE, ROLE_SIDECAR, ROLE_WORK, WORKSPACE_KEY},
+};
+
+// the scripts wrap the static clap completions and fall back to them for anything but workspace and container names.
+// The workspace is assumed to be the first argument after the subcommand
+const BASH: &str = r#"
+_%BIN%_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    if [[ "${COMP_WORDS[COMP_CWORD-1]}" == "--container" ]]; then
+        COMPREPLY=($(compgen -W "$(%BIN% system complete containers "${COMP_WORDS[2]}" 2>/dev/null)" -- "$cur"))
+        return 0
+    fi
+    if [[ $COMP_CWORD -eq 2 && "$cur" != -* ]]; then
+        case "${COMP_WORDS[1]}" in
+            %COMMANDS%)
+                COMPREPLY=($(compgen -W "$(%BIN% system complete workspaces 2>/dev/null)" -- "$cur"))
+                return 0
+                ;;
+        esac
+    fi
+    _%BIN% "$@"
+}
+
+complete -F _%BIN%_dynamic -o bashdefault -o default %BIN%
+"#;
+
+const ZSH: &str = r#"
+_%BIN%_dynamic() {
+    if [[ "${words[CURRENT-1]}" == "--container" ]]; then
+        compadd -- ${(f)"$(%BIN% system complete containers "${words[3]}" 2>/dev/null)"}
+        return
+    fi
+    if (( CURRENT == 3 )) && [[ "${words[CURRENT]}" != -* ]]; then
+        case "${words[2]}" in
+            (%COMMANDS%)
+                compadd -- ${(f)"$(%BIN% system complete workspaces 2>/dev/null)"}
+                return
+                ;;
+        esac
+    fi
+    _%BIN% "$@"
+}
+
+compdef _%BIN%_dynamic %BIN%
+"#;
+
+const FISH: &str = r#"
+complete -c %BIN% -n "__fish_seen_subcommand_from %COMMANDS%; and test (count (commandline -opc)) -eq 2" -f -a "(%BIN% system complete workspaces 2>/dev/null)"
+complete -c %BIN% -l container -x -a "(%BIN% system complete containers (commandline -opc)[3] 2>/dev/null)"
+"#;
+
+// subcommands taking an existing workspace name as their first argument
+pub fn workspace_commands(cli: &Command) -> Vec<String> {
+    cli.get_subcommands()
+        .filter(|c| c.get_name() != "new")
+        .filter(|c| {
+            c.get_positionals()
+                .next()
+                .is_some_and(|a| a.get_id() == "name")
+        })
+        .flat_map(|c| {
+            std::iter::once(c.get_name())
+                .chain(c.get_all_aliases())
+                .map(|n| n.to_string())
+        })
+        .collect()
+}
+
+pub fn dynamic_script(shell: Shell, bin: &str, workspace_commands: &[String]) -> Option<String> {
+    let (script, separator) = match shell {
+        Shell::Bash => (BASH, "|"),
+        Shell::Zsh => (ZSH, "|"),
+        Shell::Fish => (FISH, " "),
+        _ => return None,
+    };
+    Some(
+        script
+            .replace("%BIN%", bin)
+            .replace("%COMMANDS%", &workspace_commands.join(separator)),
+    )
+}
+
+impl<'a> Api<'a> {
+    pub async fn complete_workspaces(&self) -> Result<(), AnyError> {
+        let labels = Labels::new(None, Some(ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+        let names = self
+            .container
+            .get_all(&labels)
+            .await?
+            .into_iter()
+            .filter_map(|c| c.labels.and_then(|l| l.get(WORKSPACE_KEY).cloned()))
+            .collect::<BTreeSet<_>>();
+        for n in names {
+            println!("{}", n);
+        }
+        Ok(())
+    }
+
+    pub async fn complete_containers(&self, workspace_key: &str) -> Result<(), AnyError> {
+        let names = self
+            .container
+            .get_all(&Labels::new(Some(workspace_key), None))
+            .await?
+            .into_iter()
+            .filter_map(|c| c.labels)
+            .filter(|l| {
+                l.get(ROLE)
+                    .is_some_and(|r| r == ROLE_WORK || r == ROLE_SIDECAR)
+            })
+            .filter_map(|l| l.get(CONTAINER).cloned())
+            .collect::<BTreeSet<_>>();
+        for n in names {
+            println!("{}", n);
+        }
+        Ok(())
+    }
+}
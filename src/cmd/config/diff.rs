@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use colored::Colorize;
+
+use crate::{
+    api::ConfigApi,
+    config::{diff, runtime::RuntimeConfig},
+    model::{error::RoozError, types::AnyError},
+    util::labels::{self, Labels},
+};
+
+impl<'a> ConfigApi<'a> {
+    async fn diff_snapshot(
+        &self,
+        workspace_key: &str,
+    ) -> Result<BTreeMap<String, String>, AnyError> {
+        let containers = self
+            .api
+            .container
+            .get_all(&Labels::new(Some(workspace_key), None))
+            .await?;
+
+        if containers.is_empty() {
+            return Err(RoozError::WorkspaceNotFound(workspace_key.to_string()).into());
+        }
+
+        let mut snapshot = BTreeMap::new();
+
+        for c in containers {
+            let labels = c.labels.unwrap_or_default();
+            let name = labels
+                .get(labels::CONTAINER)
+                .cloned()
+                .unwrap_or_else(|| c.id.clone().unwrap_or_default());
+
+            if let Some(runtime_config) = labels.get(labels::RUNTIME_CONFIG) {
+                let cfg = RuntimeConfig::from_string(runtime_config.to_string())?;
+                for (path, value) in diff::flatten(&cfg)? {
+                    snapshot.insert(format!("runtime.{}", path), value);
+                }
+            }
+
+            if let Some(image) = c.image {
+                snapshot.insert(format!("containers.{}.image", name), image);
+            }
+
+            for m in c.mounts.unwrap_or_default() {
+                if let Some(destination) = m.destination {
+                    let source = m
+                        .name
+                        .unwrap_or_default()
+                        .replace(workspace_key, "{workspace}");
+                    snapshot.insert(
+                        format!("containers.{}.volumes.{}", name, destination),
+                        source,
+                    );
+                }
+            }
+        }
+        Ok(snapshot)
+    }
+
+    pub async fn diff(&self, left: &str, right: &str) -> Result<(), AnyError> {
+        let left_snapshot = self.diff_snapshot(left).await?;
+        let right_snapshot = self.diff_snapshot(right).await?;
+        println!("{}", format!("--- {}", left).red());
+        println!("{}", format!("+++ {}", right).green());
+        diff::print(&diff::diff(&left_snapshot, &right_snapshot));
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ use crate::{
 
 impl<'a> ConfigApi<'a> {
     pub async fn edit(&self, config_path: &str) -> Result<(), AnyError> {
-        let format = FileFormat::from_path(config_path);
+        let format = FileFormat::from_path(config_path)?;
         let body = fs::read_to_string(&config_path)?;
         let mut config = RoozCfg::deserialize_config(&body, format)?.unwrap();
         let identity = self.crypt.read_age_identity().await?;
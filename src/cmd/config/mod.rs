@@ -1,3 +1,5 @@
+pub mod diff;
 pub mod edit;
 pub mod show;
+pub mod store;
 pub mod template;
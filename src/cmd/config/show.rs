@@ -1,61 +1,158 @@
+use age::x25519::Identity;
+use colored::Colorize;
+
 use crate::{
     api::ConfigApi,
     cli::{ConfigFormat, ConfigPart},
+    cmd::config::store::StoredConfig,
     config::{
         config::{FileFormat, RoozCfg},
+        diff,
         runtime::RuntimeConfig,
     },
-    model::{types::AnyError, volume::WORK_ROLE},
-    util::labels::{self, Labels},
+    constants,
+    model::{error::RoozError, types::AnyError, volume::WORK_ROLE},
+    util::{
+        labels::{self, Labels},
+        redact,
+    },
 };
 
 impl<'a> ConfigApi<'a> {
+    // decrypts the stored secrets so their values can be either revealed or masked
+    pub async fn stored_config(
+        &self,
+        stored: &StoredConfig,
+        identity: &Identity,
+    ) -> Result<(RoozCfg, FileFormat), AnyError> {
+        let format = FileFormat::from_path(&stored.origin)?;
+        let mut cfg = RoozCfg::from_string(&stored.body, format)?;
+        if Self::has_secrets(&cfg) {
+            self.decrypt(&mut cfg, identity).await?;
+        }
+        Ok((cfg, format))
+    }
+
+    pub async fn register_stored_secrets(
+        &self,
+        stored: &StoredConfig,
+        identity: &Identity,
+    ) -> Result<(), AnyError> {
+        let cfg = RoozCfg::from_string(&stored.body, FileFormat::from_path(&stored.origin)?)?;
+        self.register_secrets(&cfg, identity).await
+    }
+
+    // the body goes through the same defaults as on rooz new, so only what the expansion,
+    // extends and CLI overrides changed shows up
+    async fn show_diff(
+        &self,
+        stored: Option<&StoredConfig>,
+        reveal_secrets: bool,
+        identity: &Identity,
+    ) -> Result<bool, AnyError> {
+        let Some(
+            stored @ StoredConfig {
+                origin,
+                body,
+                runtime: Some(runtime),
+                ..
+            },
+        ) = stored
+        else {
+            return Ok(false);
+        };
+        let mut file = RoozCfg::default();
+        file.from_config(&RoozCfg::from_string(body, FileFormat::from_path(origin)?)?);
+        let runtime = RuntimeConfig::from_string(runtime.to_string())?;
+        if !reveal_secrets {
+            self.register_stored_secrets(stored, identity).await?;
+        }
+        println!("{}", format!("--- {}", origin).red());
+        println!("{}", "+++ runtime".green());
+        diff::print(&diff::runtime_config(
+            &RuntimeConfig::from(&file),
+            &runtime,
+            !reveal_secrets,
+        )?);
+        Ok(true)
+    }
+
     pub async fn show(
         &self,
         workspace_key: &str,
         part: ConfigPart,
         output: Option<ConfigFormat>,
+        reveal_secrets: bool,
     ) -> Result<(), AnyError> {
-        let labels = Labels::new(Some(workspace_key), Some(WORK_ROLE));
+        let labels = Labels::new(Some(workspace_key), Some(WORK_ROLE))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
 
         let container = self
             .api
             .container
             .get_single(&labels)
             .await?
-            .ok_or(format!("Workspace not found: {}", &workspace_key))?;
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
 
         if let Some(labels) = container.labels {
+            let identity = self.crypt.read_age_identity().await?;
+            let stored = self.load(workspace_key, &labels, &identity).await?;
+            let new_format = output.clone().map(|c| match c {
+                ConfigFormat::Toml => FileFormat::Toml,
+                ConfigFormat::Yaml => FileFormat::Yaml,
+            });
             let content: Option<String> = match part {
                 ConfigPart::Origin => labels.get(labels::CONFIG_ORIGIN).cloned(),
+                ConfigPart::Diff
+                    if self
+                        .show_diff(stored.as_ref(), reveal_secrets, &identity)
+                        .await? =>
+                {
+                    return Ok(())
+                }
+                ConfigPart::Diff => None,
+                ConfigPart::Body if reveal_secrets => match &stored {
+                    Some(stored) => {
+                        let (cfg, format) = self.stored_config(stored, &identity).await?;
+                        Some(cfg.to_string(new_format.unwrap_or(format))?)
+                    }
+                    None => None,
+                },
                 ConfigPart::Body => {
-                    let new_format = output.map(|c| match c {
-                        ConfigFormat::Toml => FileFormat::Toml,
-                        ConfigFormat::Yaml => FileFormat::Yaml,
-                    });
-
-                    let maybe_body = labels.get(labels::CONFIG_BODY);
-                    if let Some(body) = maybe_body {
+                    if let Some(stored) = &stored {
                         if let Some(format) = new_format {
-                            let origin_path = labels.get(labels::CONFIG_ORIGIN).unwrap();
-                            let original_format = FileFormat::from_path(&origin_path);
-                            let cfg = RoozCfg::from_string(&body, original_format)?;
+                            let original_format = FileFormat::from_path(&stored.origin)?;
+                            let cfg = RoozCfg::from_string(&stored.body, original_format)?;
                             Some(cfg.to_string(format)?.to_string())
                         } else {
-                            Some(body.to_string())
+                            Some(stored.body.to_string())
                         }
                     } else {
                         None
                     }
                 }
                 ConfigPart::Runtime => {
-                    if let Some(runtime_config) = labels.get(labels::RUNTIME_CONFIG) {
-                        match output {
+                    // the labels only have the secret values masked
+                    let runtime_config = stored
+                        .as_ref()
+                        .and_then(|s| s.runtime.as_ref())
+                        .or(labels.get(labels::RUNTIME_CONFIG));
+                    if let Some(runtime_config) = runtime_config {
+                        let runtime = match output {
                             Some(ConfigFormat::Yaml) => {
                                 let cfg = RuntimeConfig::from_string(runtime_config.to_string())?;
-                                Some(serde_yaml::to_string(&cfg)?)
+                                serde_yaml::to_string(&cfg)?
+                            }
+                            _ => runtime_config.to_string(),
+                        };
+                        if reveal_secrets {
+                            Some(runtime)
+                        } else {
+                            // the runtime config holds the values the secrets got expanded into
+                            if let Some(stored) = &stored {
+                                self.register_stored_secrets(stored, &identity).await?;
                             }
-                            _ => Some(runtime_config.to_string()),
+                            Some(redact::redact(&runtime))
                         }
                     } else {
                         None
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use age::x25519::Identity;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    api::ConfigApi,
+    config::{crypt, runtime::RuntimeConfig},
+    constants,
+    model::{
+        error::RoozError,
+        types::{AnyError, ContainerResult, RunSpec},
+        volume::RoozVolume,
+    },
+    util::{id, labels},
+};
+
+const CONFIG_FILE: &str = "config.age";
+
+// what used to be in the labels of the work container, where anyone with access to the
+// container engine could read it. The runtime config holds the decrypted secret values
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredConfig {
+    pub origin: String,
+    pub body: String,
+    pub runtime: Option<String>,
+    #[serde(default)]
+    pub generated: HashMap<String, String>,
+}
+
+impl StoredConfig {
+    // the body only has encrypted secrets so its hash gives nothing away
+    pub fn hash(&self) -> String {
+        Sha256::digest(self.body.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    // the repo labels only have the redacted url. Workspaces stored before the runtime config fall back to it
+    pub fn git_ssh_url(&self, labels: &HashMap<String, String>) -> Option<String> {
+        self.runtime
+            .as_ref()
+            .and_then(|r| RuntimeConfig::from_string(r.to_string()).ok())
+            .and_then(|c| c.git_ssh_url)
+            .or(labels.get(labels::REPO).cloned())
+    }
+}
+
+impl<'a> ConfigApi<'a> {
+    // the file is encrypted so the helper container writing it never sees the values
+    pub async fn store(
+        &self,
+        workspace_key: &str,
+        stored: &StoredConfig,
+        identity: &Identity,
+    ) -> Result<String, AnyError> {
+        let volume = RoozVolume::workspace_config(workspace_key);
+        self.api
+            .volume
+            .ensure_volume(
+                &volume.safe_volume_name(),
+                &volume.role,
+                volume.key(),
+                false,
+            )
+            .await?;
+        let encrypted = self
+            .crypt
+            .encrypt(serde_json::to_string(stored)?, &identity.to_public())?;
+        self.api
+            .write_files(
+                "store-config",
+                &volume,
+                constants::ROOT_UID,
+                &[(CONFIG_FILE.to_string(), encrypted)],
+            )
+            .await?;
+        Ok(stored.hash())
+    }
+
+    async fn read_stored(&self, workspace_key: &str) -> Result<String, AnyError> {
+        let volume = RoozVolume::workspace_config(workspace_key);
+        if self
+            .api
+            .client
+            .inspect_volume(&volume.safe_volume_name())
+            .await
+            .is_err()
+        {
+            return Err(RoozError::Config(format!(
+                "the stored config of {} is missing. Re-create the workspace",
+                workspace_key
+            ))
+            .into());
+        }
+        let mut mount = volume.to_mount(None);
+        mount.read_only = Some(true);
+        let run_spec = RunSpec {
+            reason: "read-config",
+            image: &self.api.image.helper,
+            uid: constants::ROOT_UID,
+            work_dir: None,
+            container_name: &id::random_suffix("read-config"),
+            workspace_key: &id::random_suffix("tmp"),
+            mounts: Some(vec![mount]),
+            entrypoint: Some(vec!["cat"]),
+            privileged: false,
+            force_recreate: false,
+            auto_remove: true,
+            ..Default::default()
+        };
+        let container_result = self.api.container.create(run_spec).await?;
+        let container_id = container_result.id();
+        self.api.container.start(container_id).await?;
+        let path = format!("{}/{}", volume.path, CONFIG_FILE);
+        match container_result {
+            ContainerResult::Created { .. } => {
+                let data = self
+                    .api
+                    .exec
+                    .output("read config", container_id, None, Some(vec!["cat", &path]))
+                    .await?;
+                self.api.container.kill(container_id).await?;
+                Ok(data)
+            }
+            _ => Err("Could not read the stored config".into()),
+        }
+    }
+
+    // older workspaces have the config in the labels. 'rooz system migrate' moves it to the volume
+    pub async fn load(
+        &self,
+        workspace_key: &str,
+        labels: &HashMap<String, String>,
+        identity: &Identity,
+    ) -> Result<Option<StoredConfig>, AnyError> {
+        let Some(origin) = labels.get(labels::CONFIG_ORIGIN) else {
+            return Ok(None);
+        };
+        if let Some(body) = labels.get(labels::CONFIG_BODY) {
+            return Ok(Some(StoredConfig {
+                origin: origin.to_string(),
+                body: body.to_string(),
+                runtime: labels.get(labels::RUNTIME_CONFIG).cloned(),
+                generated: crypt::stored_generated(labels)?,
+            }));
+        }
+        let Some(hash) = labels.get(labels::CONFIG_HASH) else {
+            return Ok(None);
+        };
+        let data = self.read_stored(workspace_key).await?;
+        let stored = serde_json::from_str::<StoredConfig>(
+            &self.crypt.decrypt(identity, data.trim()).map_err(|e| {
+                RoozError::Config(format!(
+                    "could not read the stored config of {}: {}",
+                    workspace_key, e
+                ))
+            })?,
+        )?;
+        if &stored.hash() != hash {
+            log::debug!(
+                "The stored config of {} changed since the work container got created",
+                workspace_key
+            );
+        }
+        Ok(Some(stored))
+    }
+}
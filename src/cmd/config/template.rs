@@ -1,8 +1,406 @@
-use crate::{api::ConfigApi, config::config::FileFormat, model::types::AnyError};
+use linked_hash_map::LinkedHashMap;
+
+use crate::{
+    api::ConfigApi,
+    config::config::{
+        FileFormat, RoozCfg, RoozCleanCfg, RoozCloneCfg, RoozContainer, RoozDocker, RoozDockerMode,
+        RoozDotfilesCfg, RoozExposeCfg, RoozGitCfg, RoozGitSigning, RoozIdMap, RoozIdeCfg,
+        RoozInitContainer, RoozMux, RoozProxy, RoozProxyCfg, RoozPullPolicy, RoozRestart,
+        RoozSidecar, RoozSyncCfg, RoozVolumeCfg,
+    },
+    constants,
+    model::types::AnyError,
+};
+
+fn map(entries: &[(&str, &str)]) -> LinkedHashMap<String, String> {
+    entries
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+// Every field is set on purpose so the template follows the schema:
+// adding a field to the config structs fails to compile until it gets an example here.
+fn example() -> RoozCfg {
+    RoozCfg {
+        extends: Some(strings(&[
+            "git@github.com:my-org/rooz-configs.git//base.rooz.toml",
+            "./local.rooz.toml",
+        ])),
+        vars: Some(map(&[("project", "my-project")])),
+        secrets: Some(map(&[(
+            "db_password",
+            "-----BEGIN AGE ENCRYPTED FILE-----|...|-----END AGE ENCRYPTED FILE-----",
+        )])),
+        git_ssh_url: Some("git@github.com:my-org/my-project.git".into()),
+        clone_dir: Some(".".into()),
+        clone: Some(RoozCloneCfg {
+            depth: Some(1),
+            single_branch: Some(true),
+            branch: Some("main".into()),
+            commit: Some("0123456789abcdef".into()),
+            submodules: Some(true),
+            filter: Some("blob:none".into()),
+        }),
+        extra_repos: Some(strings(&["git@github.com:my-org/my-library.git"])),
+        engine: Some("buildbox".into()),
+        image: Some(constants::DEFAULT_IMAGE.into()),
+        pull: Some(RoozPullPolicy::Missing),
+        platform: Some("linux/amd64".into()),
+        caches: Some(strings(&["~/.cargo/registry"])),
+        volumes: Some(
+            [(
+                "models".to_string(),
+                RoozVolumeCfg {
+                    path: "~/models".into(),
+                    shared: Some(false),
+                    keep: Some(true),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        shell: Some(strings(&[constants::DEFAULT_SHELL])),
+        user: Some(constants::DEFAULT_USER.into()),
+        ports: Some(strings(&["8080:8080"])),
+        auto_ports: Some(false),
+        bind_address: Some("127.0.0.1".into()),
+        privileged: Some(false),
+        docker: Some(RoozDocker::Mode(RoozDockerMode::Dind)),
+        security_opts: Some(strings(&["label=disable"])),
+        cap_add: Some(strings(&["SYS_PTRACE"])),
+        cap_drop: Some(strings(&["NET_RAW"])),
+        read_only_rootfs: Some(false),
+        dns: Some(strings(&["10.0.0.53"])),
+        dns_search: Some(strings(&["corp.example.com"])),
+        extra_hosts: Some(strings(&["git.corp.example.com:10.0.0.10"])),
+        idmap: Some(RoozIdMap::Auto),
+        mux: Some(RoozMux::Tmux),
+        prompt: Some(true),
+        shell_init: Some("alias ll='ls -la'\nexport PATH=\"$HOME/.local/bin:$PATH\"\n".into()),
+        home_from_image: Some(true),
+        restart: Some(RoozRestart::UnlessStopped),
+        network: None,
+        networks: Some(strings(&["proxy"])),
+        aliases: Some(strings(&["my-project.local"])),
+        expose: Some(RoozExposeCfg {
+            proxy: Some(RoozProxy::Traefik),
+            network: Some("proxy".into()),
+            domain: Some("dev.example.com".into()),
+            hosts: Some([("my-project".to_string(), 8080)].into_iter().collect()),
+        }),
+        labels: Some(map(&[(
+            "traefik.http.routers.my-project.rule",
+            "Host(`{{ project }}.localhost`)",
+        )])),
+        env: Some(map(&[
+            ("PROJECT", "{{ project }}"),
+            ("DB_PASSWORD", "{{ db_password }}"),
+        ])),
+        sidecars: Some(
+            [(
+                "db".to_string(),
+                RoozSidecar {
+                    image: "docker.io/library/postgres:16".into(),
+                    shell: Some(strings(&["bash"])),
+                    env: Some(map(&[("POSTGRES_PASSWORD", "{{ db_password }}")])),
+                    command: Some(strings(&["postgres", "-c", "log_statement=all"])),
+                    mounts: Some(strings(&["/var/lib/postgresql/data"])),
+                    ports: Some(strings(&["5432:5432"])),
+                    mount_work: Some(false),
+                    work_dir: Some("/".into()),
+                    user: Some("postgres".into()),
+                    restart: Some(RoozRestart::OnFailure),
+                    networks: Some(strings(&["monitoring"])),
+                    aliases: Some(strings(&["postgres"])),
+                    labels: Some(map(&[("com.centurylinklabs.watchtower.enable", "false")])),
+                    expose: None,
+                    security_opts: Some(strings(&["no-new-privileges"])),
+                    cap_add: Some(Vec::new()),
+                    cap_drop: Some(strings(&["ALL"])),
+                    read_only_rootfs: Some(true),
+                    dns: Some(Vec::new()),
+                    dns_search: Some(Vec::new()),
+                    extra_hosts: Some(Vec::new()),
+                    replicas: Some(1),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        containers: Some(
+            [(
+                "node".to_string(),
+                RoozContainer {
+                    image: "docker.io/library/node:22".into(),
+                    shell: Some(strings(&["bash"])),
+                    user: Some("node".into()),
+                    env: Some(map(&[("NODE_ENV", "development")])),
+                    ports: Some(strings(&["3000:3000"])),
+                    caches: Some(strings(&["~/.npm"])),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        init_containers: Some(
+            [(
+                "migrate".to_string(),
+                RoozInitContainer {
+                    image: Some("docker.io/flyway/flyway:10".into()),
+                    command: Some(strings(&["migrate"])),
+                    user: None,
+                    work_dir: Some("/work".into()),
+                    env: Some(map(&[("FLYWAY_URL", "jdbc:sqlserver://sql:1433")])),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        git: Some(RoozGitCfg {
+            name: Some("Jane Doe".into()),
+            email: Some("jane@example.com".into()),
+            signing_key: Some("~/.ssh/id_ed25519.pub".into()),
+            sign: Some(RoozGitSigning::Ssh),
+            url: Some(map(&[(
+                "git@github.com:my-org/",
+                "https://github.com/my-org/",
+            )])),
+        }),
+        ide: Some(RoozIdeCfg {
+            product: Some("IU".into()),
+            project: Some("/work/my-project".into()),
+        }),
+        dotfiles: Some(RoozDotfilesCfg {
+            repo: Some("git@github.com:jane/dotfiles.git".into()),
+            volume: None,
+            install: Some("install.sh".into()),
+        }),
+        sync: Some(RoozSyncCfg {
+            path: "~/src/my-project".into(),
+            ignore: Some(strings(&["node_modules", "target", "build/cache"])),
+            image: None,
+        }),
+        clean: Some(RoozCleanCfg {
+            commands: Some(strings(&[
+                "cargo clean --manifest-path /work/my-project/Cargo.toml",
+                "npm cache clean --force",
+                "rm -rf /tmp/*",
+            ])),
+        }),
+        proxy: Some(RoozProxyCfg {
+            http: Some("http://proxy.example.com:3128".into()),
+            https: Some("http://proxy.example.com:3128".into()),
+            no_proxy: Some(strings(&[".example.com"])),
+        }),
+    }
+}
+
+fn describe(path: &[String]) -> Option<&'static str> {
+    let path = path.iter().map(String::as_str).collect::<Vec<_>>();
+    Some(match path[..] {
+        ["extends"] => "Configs this one is merged onto, in order. Relative paths resolve against this file",
+        ["vars"] => "Variables usable in other values via the {{ name }} syntax",
+        ["secrets"] => {
+            "Age-encrypted values usable like vars. Encrypt with: rooz config edit <path>"
+        }
+        ["git_ssh_url"] => "The root git repository of the workspace",
+        ["clone_dir"] => {
+            "Where the root repository gets cloned. Relative to /work, '.' means /work itself"
+        }
+        ["clone"] => "Controls how the root repository gets cloned",
+        ["clone", "depth"] => "Creates a shallow clone with the given depth",
+        ["clone", "single_branch"] => "Clones the history of a single branch only",
+        ["clone", "branch"] => "The branch or tag to clone",
+        ["clone", "commit"] => "The commit to check out after cloning",
+        ["clone", "submodules"] => "Initializes and clones submodules",
+        ["clone", "filter"] => "Partial clone filter. Set to 'none' to do a full clone",
+        ["extra_repos"] => "Additional git repositories cloned into /work",
+        ["engine"] => "The named container engine (see [engines] in ~/.config/rooz/config.toml) to create the workspace on",
+        ["image"] => "The work container image",
+        ["pull"] => "Image pull policy: always, missing (default), or never",
+        ["platform"] => "Platform of the work container images (e.g. linux/amd64 on an arm64 host). Foreign ones run emulated, which is slow. Defaults to the one of the container engine host",
+        ["caches"] => "Paths backed by volumes shared across all workspaces",
+        ["volumes"] => "Named data volumes mounted into the workspace",
+        ["volumes", _, "path"] => "Where the volume gets mounted",
+        ["volumes", _, "shared"] => "Shares the volume across all workspaces instead",
+        ["volumes", _, "keep"] => {
+            "Keeps the volume on rooz rm. Remove it with: rooz rm --purge-data"
+        }
+        ["shell"] => "The shell to enter the work container with",
+        ["user"] => "The user to run the work container as",
+        ["ports"] => "Port mappings of the work container in the container:host format, optionally prefixed with the host address to bind, e.g. 0.0.0.0:80:8080 or *:80:8080",
+        ["auto_ports"] => "Remaps host ports already in use to free ones instead of failing",
+        ["bind_address"] => "The host address ports get published on unless set per port. Defaults to 127.0.0.1, use 0.0.0.0 or * for all interfaces",
+        ["privileged"] => "Runs the work container in privileged mode",
+        ["docker"] => "Docker inside the workspace: dind (or true) runs a Docker daemon sidecar, socket mounts the socket of the container engine host",
+        ["security_opts"] => {
+            "Security options of the work container, e.g. label=disable on SELinux hosts"
+        }
+        ["cap_add"] => "Linux capabilities added to the work container",
+        ["cap_drop"] => "Linux capabilities dropped from the work container",
+        ["read_only_rootfs"] => {
+            "Mounts the root filesystem read-only. The image must contain the workspace user"
+        }
+        ["dns"] => "DNS servers of the work and init containers",
+        ["dns_search"] => "DNS search domains of the work and init containers",
+        ["extra_hosts"] => {
+            "Extra /etc/hosts entries of the work and init containers in the host:ip format"
+        }
+        ["idmap"] => "User namespace mapping: auto (keep-id on rootless Podman), keep-id (Podman only), or none",
+        ["prompt"] => "Shows the workspace context in the shell prompt. Enabled by default",
+        ["shell_init"] => "Shell code sourced by every shell of the workspace, e.g. aliases, PATH tweaks and exports",
+        ["home_from_image"] => "Syncs the image's home directory into the home volume whenever the image changes, keeping files not in the image",
+        ["mux"] => "Terminal multiplexer (tmux or zellij) to enter the workspace with. Must be installed in the image",
+        ["restart"] => "Restart policy of the work container: no, on-failure, always, or unless-stopped",
+        ["network"] => {
+            "An existing network to attach the workspace to instead of the per-workspace one"
+        }
+        ["networks"] => "Existing networks the work container is additionally connected to",
+        ["aliases"] => "Network aliases of the work container",
+        ["labels"] => {
+            "Labels of the work container, e.g. for Traefik. The dev.rooz namespace is reserved"
+        }
+        ["expose"] => "Routes host names to the work container through a reverse proxy",
+        ["expose", "proxy"] => "The proxy the labels are generated for: traefik or caddy",
+        ["expose", "network"] => "An existing network the proxy is connected to",
+        ["expose", "domain"] => "The domain of host names without a dot. Defaults to localhost",
+        ["expose", "hosts"] => "Maps host names to container ports",
+        ["env"] => "Environment variables of the work container",
+        ["sidecars"] => "Additional containers sharing a network with the work container",
+        ["sidecars", _] => "The sidecar name, also used as its hostname on the network",
+        ["sidecars", _, "image"] => "The sidecar image",
+        ["sidecars", _, "shell"] => "The shell rooz enter --container uses. Defaults to sh",
+        ["sidecars", _, "env"] => "Environment variables of the sidecar",
+        ["sidecars", _, "command"] => "Overrides the sidecar command",
+        ["sidecars", _, "mounts"] => "Paths backed by volumes surviving sidecar re-creation",
+        ["sidecars", _, "ports"] => "Port mappings of the sidecar in the container:host format, optionally prefixed with the host address to bind",
+        ["sidecars", _, "mount_work"] => "Mounts the work volume at /work",
+        ["sidecars", _, "work_dir"] => "The sidecar working directory",
+        ["sidecars", _, "user"] => "The user to run the sidecar as",
+        ["sidecars", _, "networks"] => "Existing networks the sidecar is additionally connected to",
+        ["sidecars", _, "aliases"] => "Network aliases of the sidecar besides its name",
+        ["sidecars", _, "labels"] => "Labels of the sidecar",
+        ["sidecars", _, "security_opts"] => "Security options of the sidecar",
+        ["sidecars", _, "cap_add"] => "Linux capabilities added to the sidecar",
+        ["sidecars", _, "cap_drop"] => "Linux capabilities dropped from the sidecar",
+        ["sidecars", _, "read_only_rootfs"] => "Mounts the root filesystem of the sidecar read-only",
+        ["sidecars", _, "dns"] => "DNS servers of the sidecar",
+        ["sidecars", _, "dns_search"] => "DNS search domains of the sidecar",
+        ["sidecars", _, "extra_hosts"] => "Extra /etc/hosts entries of the sidecar in the host:ip format",
+        ["sidecars", _, "expose"] => {
+            "Maps host names to sidecar ports. Uses the proxy settings of expose"
+        }
+        ["sidecars", _, "replicas"] => {
+            "Number of instances. Replicas are named <name>-1..<name>-N and share the <name> alias"
+        }
+        ["sidecars", _, "restart"] => {
+            "Restart policy of the sidecar: no, on-failure, always, or unless-stopped"
+        }
+        ["containers"] => "Additional work containers sharing the work volume and network",
+        ["containers", _] => {
+            "The container name. Enter it with: rooz enter <workspace> --container <name>"
+        }
+        ["containers", _, "image"] => "The container image",
+        ["containers", _, "shell"] => "The shell to enter the container with",
+        ["containers", _, "user"] => {
+            "The user to run the container as. Defaults to the workspace user"
+        }
+        ["containers", _, "env"] => "Environment variables added to the workspace ones",
+        ["containers", _, "ports"] => "Port mappings of the container in the container:host format, optionally prefixed with the host address to bind",
+        ["containers", _, "caches"] => "Shared cache paths of the container",
+        ["init_containers"] => {
+            "One-time setup jobs run in order, each to completion, before the work container starts"
+        }
+        ["init_containers", _] => "An init container named by its key",
+        ["init_containers", _, "image"] => "The container image. Defaults to the workspace image",
+        ["init_containers", _, "command"] => "The command to run",
+        ["init_containers", _, "user"] => "The user to run the command as. Defaults to the workspace user",
+        ["init_containers", _, "work_dir"] => "The working directory of the command",
+        ["init_containers", _, "env"] => "Environment variables added to the workspace ones",
+        ["git"] => "Git identity written to ~/.config/git/config",
+        ["git", "name"] => "user.name",
+        ["git", "email"] => "user.email",
+        ["git", "signing_key"] => "user.signingkey. With sign = \"ssh\" a public key file of the host gets inlined. Defaults to the first key of the agent",
+        ["git", "sign"] => "Signs commits and tags through the agent of the host: ssh (ssh-agent) or gpg (gpg-agent). Local engines only",
+        ["git", "url"] => "Maps base URLs to their insteadOf prefixes",
+        ["ide"] => "JetBrains Gateway settings used by rooz idea",
+        ["ide", "product"] => "JetBrains product code of the IDE to deploy (e.g. IU, PY, GO). Defaults to IU",
+        ["ide", "project"] => "Project path opened by the IDE. Defaults to the container working directory",
+        ["dotfiles"] => "Dotfiles following you across workspaces, available at ~/.dotfiles",
+        ["dotfiles", "repo"] => "Git repository cloned into ~/.dotfiles of new workspaces",
+        ["dotfiles", "volume"] => "Shared volume mounted at ~/.dotfiles instead of cloning a repository",
+        ["dotfiles", "install"] => "Script in ~/.dotfiles run as the workspace user whenever the workspace gets created",
+        ["sync"] => "Mirrors a host folder with /work both ways while the workspace runs. Faster than bind mounts on Docker Desktop",
+        ["sync", "path"] => "The host folder, on the container engine host",
+        ["sync", "ignore"] => "Never synced. Names match at any depth, entries with a slash are paths from the root",
+        ["clean"] => "Cleanup run by rooz clean to get disk space back without re-creating the workspace",
+        ["clean", "commands"] => "Shell commands run in order in the work container as the workspace user",
+        ["proxy"] => "Proxy for the git clones, work containers and sidecars. Overrides the [proxy] of the system config and the host's HTTP(S)_PROXY/NO_PROXY field by field",
+        ["proxy", "http"] => "HTTP_PROXY. An empty string unsets the inherited one",
+        ["proxy", "https"] => "HTTPS_PROXY. An empty string unsets the inherited one",
+        ["proxy", "no_proxy"] => "NO_PROXY hosts. The workspace's sidecars and localhost get added",
+        ["sync", "image"] => "The image running the sync, needs unison (with unison-fsmonitor) and su-exec. Defaults to alpine, installing them on first start",
+        _ => return None,
+    })
+}
+
+fn toml_key(raw: &str) -> String {
+    raw.trim().trim_matches('"').to_string()
+}
+
+fn annotate(body: &str, format: FileFormat) -> String {
+    let mut out = String::new();
+    let mut section = Vec::<String>::new();
+    let mut stack = Vec::<(usize, String)>::new();
+
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        let path = match format {
+            FileFormat::Toml if trimmed.starts_with('[') => {
+                section = trimmed
+                    .trim_matches(|c| c == '[' || c == ']')
+                    .split('.')
+                    .map(toml_key)
+                    .collect();
+                Some(section.clone())
+            }
+            FileFormat::Toml => trimmed.split_once(" = ").map(|(key, _)| {
+                let mut path = section.clone();
+                path.push(toml_key(key));
+                path
+            }),
+            FileFormat::Yaml => match trimmed.split_once(':') {
+                Some((key, _)) if !trimmed.starts_with('-') => {
+                    while stack.last().is_some_and(|(i, _)| *i >= indent) {
+                        stack.pop();
+                    }
+                    stack.push((indent, key.to_string()));
+                    Some(stack.iter().map(|(_, k)| k.to_string()).collect())
+                }
+                _ => None,
+            },
+        };
+
+        if let Some(comment) = path.as_deref().and_then(describe) {
+            out.push_str(&format!("{}# {}\n", &line[..indent], comment));
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
 
 impl<'a> ConfigApi<'a> {
-    pub async fn template(&self, _format: FileFormat) -> Result<(), AnyError> {
-        println!("{}", "# not implemented yet");
+    pub async fn template(&self, format: FileFormat) -> Result<(), AnyError> {
+        let body = example().to_string(format)?;
+        print!("{}", annotate(&body, format));
         Ok(())
     }
 }
@@ -0,0 +1,56 @@
+use std::{collections::HashMap, env};
+
+use crate::{
+    api::WorkspaceApi,
+    constants,
+    model::{error::RoozError, types::AnyError},
+    util::{labels::Labels, prompt},
+};
+
+fn print(meta: Vec<(String, String)>) {
+    for (k, v) in meta {
+        println!("{}={}", k, v);
+    }
+}
+
+pub fn current() -> Result<(), AnyError> {
+    let meta = prompt::meta(env::vars());
+    if meta.is_empty() {
+        return Err("Not in a rooz workspace. Specify the workspace name to inspect".into());
+    }
+    print(meta);
+    Ok(())
+}
+
+impl<'a> WorkspaceApi<'a> {
+    pub async fn context(
+        &self,
+        workspace_key: &str,
+        container: Option<&str>,
+    ) -> Result<(), AnyError> {
+        let labels = Labels::new(Some(workspace_key), None)
+            .with_container(Some(container.unwrap_or(constants::DEFAULT_CONTAINER_NAME)));
+        let id = self
+            .api
+            .container
+            .get_single(&labels)
+            .await?
+            .and_then(|c| c.id)
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+
+        let env = self
+            .api
+            .client
+            .inspect_container(&id, None)
+            .await?
+            .config
+            .and_then(|c| c.env)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        print(prompt::meta(env));
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use colored::Colorize;
+
+use crate::{
+    api::{
+        workspace::{enter::FALLBACK_SHELLS, signing},
+        WorkspaceApi,
+    },
+    config::{config::RoozGitSigning, runtime::RuntimeConfig},
+    constants,
+    model::{error::RoozError, types::AnyError},
+    util::labels::{self, Labels},
+};
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    message: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, message: impl Into<String>) -> Self {
+        Check {
+            name,
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn print(&self) {
+        let status = match self.status {
+            Status::Ok => format!("{:<5}", "ok").green(),
+            Status::Warn => format!("{:<5}", "warn").yellow(),
+            Status::Fail => format!("{:<5}", "fail").red(),
+        };
+        println!("{} {:<10} {}", status, self.name, self.message);
+    }
+}
+
+// signs a commit of the empty tree in a throwaway repo, so nothing in the workspace changes
+const SIGNING_SCRIPT: &str = r#"
+[ "$(git config --get commit.gpgsign)" = true ] || { echo 'commit.gpgsign is not set. Run rooz update'; exit; }
+d=$(mktemp -d)
+cd "$d" && git init -q && git commit-tree -S "$(git hash-object -t tree /dev/null)" -m rooz-doctor 2>&1 >/dev/null && echo rooz-doctor-ok
+rm -rf "$d"
+"#;
+
+fn agent_check(name: &'static str, socket: Option<String>, missing: &str) -> Check {
+    match socket {
+        Some(s) => Check::new(name, Status::Ok, s),
+        None => Check::new(name, Status::Warn, missing),
+    }
+}
+
+impl<'a> WorkspaceApi<'a> {
+    async fn signing_check(
+        &self,
+        workspace_key: &str,
+        signing: RoozGitSigning,
+    ) -> Result<Check, AnyError> {
+        let container = self
+            .api
+            .container
+            .get_single(
+                &Labels::new(Some(workspace_key), Some(labels::ROLE_WORK))
+                    .with_container(Some(constants::DEFAULT_CONTAINER_NAME)),
+            )
+            .await?
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+        if container.state.as_deref() != Some("running") {
+            return Ok(Check::new(
+                "signing",
+                Status::Warn,
+                format!("not running. Run rooz start {} to check it", workspace_key),
+            ));
+        }
+        let container_id = container.id.unwrap_or_default();
+        let output = self
+            .api
+            .exec
+            .output(
+                "doctor signing",
+                &container_id,
+                None,
+                Some(vec!["sh", "-c", SIGNING_SCRIPT]),
+            )
+            .await?;
+        let agent = match signing {
+            RoozGitSigning::Ssh => "ssh-agent",
+            RoozGitSigning::Gpg => "gpg-agent",
+        };
+        Ok(match output.contains("rooz-doctor-ok") {
+            true => Check::new("signing", Status::Ok, format!("signed through {}", agent)),
+            false => Check::new(
+                "signing",
+                Status::Fail,
+                output
+                    .lines()
+                    .rfind(|l| !l.trim().is_empty())
+                    .unwrap_or("signing failed")
+                    .trim(),
+            ),
+        })
+    }
+
+    // a missing agent only warns. A workspace signing through it fails its signing check instead
+    // sidecars on scratch-ish images often have no shell at all, so there is nothing to enter.
+    // Doctor never starts anything, stopped containers only get reported
+    async fn shell_checks(
+        &self,
+        workspace_key: &str,
+        cfg: &RuntimeConfig,
+    ) -> Result<Vec<Check>, AnyError> {
+        let mut checks = Vec::new();
+        for c in self
+            .api
+            .container
+            .get_all(&Labels::new(Some(workspace_key), None))
+            .await?
+        {
+            let (Some(id), Some(container_labels)) = (c.id.as_deref(), c.labels.as_ref()) else {
+                continue;
+            };
+            let name = container_labels
+                .get(labels::CONTAINER)
+                .map(|n| n.as_str())
+                .unwrap_or(constants::DEFAULT_CONTAINER_NAME);
+            let (shell, user) = match container_labels.get(labels::ROLE).map(|r| r.as_str()) {
+                Some(labels::ROLE_WORK) if name == constants::DEFAULT_CONTAINER_NAME => {
+                    (Some(cfg.shell.clone()), None)
+                }
+                Some(labels::ROLE_WORK) => {
+                    (cfg.containers.get(name).and_then(|c| c.shell.clone()), None)
+                }
+                Some(labels::ROLE_SIDECAR) => cfg
+                    .sidecars
+                    .iter()
+                    .find(|(n, s)| s.instances(n).iter().any(|i| i == name))
+                    .map(|(_, s)| (s.shell.clone(), s.user.as_deref()))
+                    .unwrap_or_default(),
+                _ => continue,
+            };
+            if c.state.as_deref() != Some("running") {
+                checks.push(Check::new(
+                    "shell",
+                    Status::Warn,
+                    format!("{}: not running", name),
+                ));
+                continue;
+            }
+            let shell = shell.unwrap_or(vec![constants::DEFAULT_SHELL.to_string()]);
+            let configured = shell.first().cloned().unwrap_or_default();
+            checks.push(match self.probe_shell(id, user, &shell).await? {
+                Some(found) if found == shell => {
+                    Check::new("shell", Status::Ok, format!("{}: {}", name, configured))
+                }
+                Some(found) => Check::new(
+                    "shell",
+                    Status::Warn,
+                    format!(
+                        "{}: {} not found. rooz enter falls back to {}",
+                        name, configured, found[0]
+                    ),
+                ),
+                None => Check::new(
+                    "shell",
+                    Status::Fail,
+                    format!(
+                        "{}: no shell. Tried {}, {}",
+                        name,
+                        configured,
+                        FALLBACK_SHELLS.join(", ")
+                    ),
+                ),
+            });
+        }
+        Ok(checks)
+    }
+
+    pub async fn doctor(&self, workspace_key: Option<&str>) -> Result<(), AnyError> {
+        let mut checks = vec![
+            Check::new(
+                "engine",
+                Status::Ok,
+                format!("{:?}", self.api.container.backend),
+            ),
+            agent_check(
+                "ssh-agent",
+                signing::ssh_agent_socket(),
+                "SSH_AUTH_SOCK is not set",
+            ),
+            agent_check(
+                "gpg-agent",
+                signing::gpg_agent_socket(),
+                "gpgconf not found or no agent socket",
+            ),
+        ];
+
+        if let Some(workspace_key) = workspace_key {
+            let cfg = self
+                .runtime_config(workspace_key)
+                .await?
+                .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+            checks.push(Check::new("workspace", Status::Ok, workspace_key));
+            match cfg.git.as_ref().and_then(|g| g.sign) {
+                Some(signing) => match self.signing_check(workspace_key, signing).await {
+                    Ok(check) => checks.push(check),
+                    Err(e) => checks.push(Check::new("signing", Status::Fail, e.to_string())),
+                },
+                None => checks.push(Check::new(
+                    "signing",
+                    Status::Warn,
+                    "git.sign is not configured",
+                )),
+            }
+            match self.shell_checks(workspace_key, &cfg).await {
+                Ok(shell_checks) => checks.extend(shell_checks),
+                Err(e) => checks.push(Check::new("shell", Status::Fail, e.to_string())),
+            }
+        }
+
+        for check in &checks {
+            check.print();
+        }
+        if checks.iter().any(|c| matches!(c.status, Status::Fail)) {
+            return Err("Some checks failed".into());
+        }
+        Ok(())
+    }
+}
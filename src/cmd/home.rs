@@ -0,0 +1,87 @@
+use crate::{
+    api::{container, WorkspaceApi},
+    constants,
+    model::{
+        error::RoozError,
+        types::{AnyError, RunSpec},
+        volume::RoozVolume,
+    },
+    util::{id, labels::Labels},
+};
+
+const HOME_ROLE: &str = "home-sync";
+const HOME_DIR: &str = "/tmp/rooz-home";
+// holds the id of the image the home volume was last synced from
+const MARKER: &str = ".rooz-home-image";
+
+impl<'a> WorkspaceApi<'a> {
+    // copies the image's home directory over the home volume like `rsync -a` without `--delete`:
+    // files from the image are refreshed while files created in the workspace are kept
+    #[tracing::instrument(name = "home.sync", level = "debug", skip_all, fields(id = %workspace_key))]
+    pub async fn sync_home(
+        &self,
+        workspace_key: &str,
+        volumes_key: &str,
+        image: &str,
+        home_dir: &str,
+        reset: bool,
+    ) -> Result<(), AnyError> {
+        let image_id = self.api.client.inspect_image(image).await?.id;
+        let image_id = image_id.as_deref().unwrap_or(image);
+
+        let mounts = self
+            .api
+            .volume
+            .ensure_mounts(&vec![RoozVolume::home(volumes_key, HOME_DIR)], None)
+            .await?;
+
+        let script = format!(
+            r#"set -e
+               if [ ! -d '{src}' ]; then
+                 echo 'The image has no {src} directory. Skipping home sync'
+                 exit 0
+               fi
+               if [ '{reset}' != 'true' ] && [ "$(cat '{dst}/{marker}' 2>/dev/null)" = '{id}' ]; then
+                 exit 0
+               fi
+               echo 'Syncing {src} from the image'
+               cp -a '{src}/.' '{dst}/'
+               echo '{id}' > '{dst}/{marker}'
+            "#,
+            src = home_dir,
+            dst = HOME_DIR,
+            marker = MARKER,
+            id = image_id,
+            reset = reset,
+        );
+        let entrypoint = container::inject(&script, "home.sh");
+        let run_spec = RunSpec {
+            reason: "home-sync",
+            image,
+            uid: constants::ROOT_UID,
+            container_name: &id::random_suffix(&format!("{}-home", workspace_key)),
+            workspace_key,
+            mounts: Some(mounts),
+            entrypoint: Some(entrypoint.iter().map(String::as_str).collect()),
+            labels: Labels::new(Some(workspace_key), Some(HOME_ROLE)),
+            ..Default::default()
+        };
+
+        let id = self.api.container.create(run_spec).await?.id().to_string();
+        self.api.container.start(&id).await?;
+        self.api.container.logs_to_stdout(&id).await?;
+        let exit_code = self
+            .api
+            .client
+            .inspect_container(&id, None)
+            .await?
+            .state
+            .and_then(|s| s.exit_code)
+            .unwrap_or_default();
+        self.api.container.remove(&id, true).await?;
+        if exit_code != 0 {
+            return Err(RoozError::ExitCode(exit_code).into());
+        }
+        Ok(())
+    }
+}
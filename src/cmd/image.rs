@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+
+use bollard::{container::ListContainersOptions, errors::Error, image::RemoveImageOptions};
+use colored::Colorize;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{api::Api, config::system::PulledImages, model::types::AnyError, util::size};
+
+#[derive(Debug, Tabled)]
+struct ImageView {
+    #[tabled(rename = "IMAGE")]
+    image: String,
+    #[tabled(rename = "SIZE")]
+    size: String,
+    #[tabled(rename = "STATUS")]
+    status: String,
+    #[tabled(rename = "PULLED FOR")]
+    workspaces: String,
+}
+
+// with the image size
+enum ImageStatus {
+    InUse(i64),
+    Unused(i64),
+    Missing,
+}
+
+impl<'a> Api<'a> {
+    // any container counts, not only the rooz ones. The engine wouldn't remove the image anyway
+    async fn used_images(&self) -> Result<HashSet<String>, AnyError> {
+        Ok(self
+            .client
+            .list_containers(Some(ListContainersOptions::<String> {
+                all: true,
+                ..Default::default()
+            }))
+            .await?
+            .into_iter()
+            .flat_map(|c| [c.image, c.image_id])
+            .flatten()
+            .collect())
+    }
+
+    async fn image_status(
+        &self,
+        image: &str,
+        used: &HashSet<String>,
+    ) -> Result<ImageStatus, AnyError> {
+        match self.client.inspect_image(image).await {
+            Ok(i) => {
+                let size = i.size.unwrap_or_default();
+                let in_use =
+                    used.contains(image) || i.id.as_ref().is_some_and(|id| used.contains(id));
+                Ok(match in_use {
+                    true => ImageStatus::InUse(size),
+                    false => ImageStatus::Unused(size),
+                })
+            }
+            Err(Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(ImageStatus::Missing),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn image_list(&self) -> Result<(), AnyError> {
+        let used = self.used_images().await?;
+        let mut views = Vec::new();
+        for (image, workspaces) in PulledImages::load()?.get(self.container.engine) {
+            let (status, size) = match self.image_status(&image, &used).await? {
+                ImageStatus::InUse(s) => ("in use", size::human(s)),
+                ImageStatus::Unused(s) => ("unused", size::human(s)),
+                ImageStatus::Missing => ("removed", String::new()),
+            };
+            views.push(ImageView {
+                image,
+                size,
+                status: status.to_string(),
+                workspaces: workspaces.join(", "),
+            });
+        }
+        views.sort_by(|a, b| a.image.cmp(&b.image));
+        println!("{}", Table::new(views).with(Style::blank()));
+        Ok(())
+    }
+
+    // only images rooz pulled itself get removed. Removed ones leave the ledger either way
+    pub async fn image_prune(&self, dry_run: bool) -> Result<(), AnyError> {
+        let used = self.used_images().await?;
+        let mut forgotten = Vec::new();
+        let mut freed = 0;
+        for (image, _) in PulledImages::load()?.get(self.container.engine) {
+            match self.image_status(&image, &used).await? {
+                ImageStatus::InUse(_) => (),
+                ImageStatus::Missing => forgotten.push(image),
+                ImageStatus::Unused(size) if dry_run => {
+                    println!("{} image {}", "Would remove".yellow(), image);
+                    freed += size;
+                }
+                ImageStatus::Unused(size) => {
+                    println!("{} image {}", "Removing".red(), image);
+                    match self
+                        .client
+                        .remove_image(&image, None::<RemoveImageOptions>, None)
+                        .await
+                    {
+                        Ok(_) => {
+                            freed += size;
+                            forgotten.push(image);
+                        }
+                        Err(e) => {
+                            eprintln!("{}", format!("Could not remove {}: {}", image, e).yellow())
+                        }
+                    }
+                }
+            }
+        }
+        if !dry_run {
+            PulledImages::forget(self.container.engine, &forgotten)?;
+        }
+        println!(
+            "{} {}",
+            if dry_run { "Would free" } else { "Freed" },
+            size::human(freed).green()
+        );
+        Ok(())
+    }
+}
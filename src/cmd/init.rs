@@ -3,6 +3,8 @@ use std::str::FromStr;
 use crate::{
     api::{self, container, Api},
     cli::InitParams,
+    cmd::key,
+    config::config::RoozPullPolicy,
     constants,
     model::{
         types::{AnyError, RunSpec, VolumeResult},
@@ -10,12 +12,12 @@ use crate::{
     },
     util::{id, labels::Labels, ssh},
 };
-use age::secrecy::ExposeSecret;
+use age::{secrecy::ExposeSecret, x25519::Identity};
 use bollard::models::MountTypeEnum::VOLUME;
 use bollard::service::Mount;
 
 impl<'a> Api<'a> {
-    async fn execute_init(
+    pub async fn execute_init(
         &self,
         container_name: &str,
         entrypoint: &str,
@@ -58,7 +60,7 @@ impl<'a> Api<'a> {
     }
 
     pub async fn init(&self, image: &str, uid: &str, spec: &InitParams) -> Result<(), AnyError> {
-        let image_id = self.image.ensure(&image, false).await?;
+        let image_id = self.image.ensure(&image, RoozPullPolicy::Missing).await?;
         match self
             .volume
             .ensure_volume(
@@ -95,29 +97,34 @@ impl<'a> Api<'a> {
             }
         }
 
+        let key = match (&spec.age_identity, &spec.from_backup) {
+            (Some(identity), _) => Some(Identity::from_str(identity)?),
+            (None, Some(path)) => Some(key::read_backup(path, self.exec.non_interactive)?),
+            (None, None) => None,
+        };
+        self.init_age_key(&image_id, uid, key, spec.force).await
+    }
+
+    pub async fn init_age_key(
+        &self,
+        image_id: &str,
+        uid: &str,
+        key: Option<Identity>,
+        force: bool,
+    ) -> Result<(), AnyError> {
         match self
             .volume
             .ensure_volume(
                 api::crypt::VOLUME_NAME.into(),
                 &RoozVolumeRole::AgeKey,
                 Some("age-key".into()),
-                spec.force,
+                force,
             )
             .await?
         {
             VolumeResult::Created { .. } => {
-                let (key, pubkey) = match spec.age_identity.clone() {
-                    None => {
-                        let key = age::x25519::Identity::generate();
-                        let pubkey = key.to_public();
-                        (key, pubkey)
-                    }
-                    Some(identity) => {
-                        let key = age::x25519::Identity::from_str(&identity)?;
-                        let pubkey = key.to_public();
-                        (key, pubkey)
-                    }
-                };
+                let key = key.unwrap_or_else(Identity::generate);
+                let pubkey = key.to_public();
 
                 let entrypoint = &format!(
                     r#"mkdir -p /tmp/.age && \
@@ -136,7 +143,7 @@ impl<'a> Api<'a> {
                     entrypoint,
                     api::crypt::VOLUME_NAME,
                     "/tmp/.age",
-                    &image_id,
+                    image_id,
                 )
                 .await?;
                 println!("{}", pubkey);
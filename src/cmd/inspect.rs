@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+
+use bollard::{
+    secret::{ContainerInspectResponse, ContainerState, Health, Port},
+    service::ContainerSummary,
+    volume::ListVolumesOptions,
+};
+use serde::Serialize;
+
+use crate::{
+    api::WorkspaceApi,
+    cli::OutputFormat,
+    config::runtime::RuntimeConfig,
+    constants,
+    model::{error::RoozError, types::AnyError},
+    util::{
+        labels::{
+            self, Labels, CONFIG_BODY, CONFIG_ORIGIN, CONTAINER, CREATED, ENGINE, ORIGIN_HOST,
+            PROJECT, ROLE, ROOZ_VERSION, RUNTIME_CONFIG, VOLUMES_KEY,
+        },
+        redact,
+    },
+};
+
+#[derive(Debug, Serialize)]
+struct WorkspaceMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rooz_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    engine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volumes_key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ConfigDump {
+    origin: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runtime: Option<RuntimeConfig>,
+}
+
+#[derive(Debug, Serialize)]
+struct ContainerDump {
+    name: String,
+    id: String,
+    image: Option<String>,
+    role: Option<String>,
+    state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health: Option<String>,
+    ports: Vec<String>,
+    networks: Vec<String>,
+    mounts: Vec<String>,
+    labels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct VolumeDump {
+    name: String,
+    role: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct WorkspaceDump {
+    workspace: String,
+    metadata: WorkspaceMetadata,
+    config: ConfigDump,
+    containers: Vec<ContainerDump>,
+    volumes: Vec<VolumeDump>,
+    networks: Vec<String>,
+}
+
+fn format_port(port: &Port) -> String {
+    let typ = port.typ.map(|t| t.to_string()).unwrap_or("tcp".into());
+    match (&port.ip, port.public_port) {
+        (Some(ip), Some(public)) => format!("{}:{}->{}/{}", ip, public, port.private_port, typ),
+        _ => format!("{}/{}", port.private_port, typ),
+    }
+}
+
+impl<'a> WorkspaceApi<'a> {
+    async fn health(&self, container_id: &str) -> Result<Option<String>, RoozError> {
+        Ok(
+            match self
+                .api
+                .client
+                .inspect_container(container_id, None)
+                .await?
+            {
+                ContainerInspectResponse {
+                    state:
+                        Some(ContainerState {
+                            health:
+                                Some(Health {
+                                    status: Some(status),
+                                    ..
+                                }),
+                            ..
+                        }),
+                    ..
+                } => Some(status.to_string()),
+                _ => None,
+            },
+        )
+    }
+
+    async fn container_dump(&self, c: ContainerSummary) -> Result<ContainerDump, RoozError> {
+        let id = c.id.unwrap_or_default();
+        let mut labels = c.labels.unwrap_or_default();
+        // the stored configs are already part of the config section
+        for key in [CONFIG_BODY, RUNTIME_CONFIG] {
+            labels.remove(key);
+        }
+        let mut ports = c
+            .ports
+            .iter()
+            .flatten()
+            .map(format_port)
+            .collect::<Vec<_>>();
+        ports.sort();
+        ports.dedup();
+        let mut networks = c
+            .network_settings
+            .and_then(|n| n.networks)
+            .map(|n| n.into_keys().collect::<Vec<_>>())
+            .unwrap_or_default();
+        networks.sort();
+        let mounts = c
+            .mounts
+            .iter()
+            .flatten()
+            .map(|m| {
+                format!(
+                    "{}:{}",
+                    m.name
+                        .as_deref()
+                        .or(m.source.as_deref())
+                        .unwrap_or_default(),
+                    m.destination.as_deref().unwrap_or_default()
+                )
+            })
+            .collect();
+
+        Ok(ContainerDump {
+            name: labels
+                .get(CONTAINER)
+                .cloned()
+                .or(c
+                    .names
+                    .and_then(|n| n.first().map(|n| n.trim_start_matches('/').into())))
+                .unwrap_or_default(),
+            health: self.health(&id).await?,
+            id,
+            image: c.image,
+            role: labels.get(ROLE).cloned(),
+            state: c.state,
+            ports,
+            networks,
+            mounts,
+            labels: labels.into_iter().collect(),
+        })
+    }
+
+    pub async fn inspect(&self, workspace_key: &str, output: OutputFormat) -> Result<(), AnyError> {
+        let work_labels = Labels::new(Some(workspace_key), Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+        let Some(ContainerSummary {
+            labels: Some(labels),
+            ..
+        }) = self.api.container.get_single(&work_labels).await?
+        else {
+            return Err(RoozError::WorkspaceNotFound(workspace_key.to_string()).into());
+        };
+
+        let volumes_key = labels.get(VOLUMES_KEY).cloned();
+        // workspaces created by older versions of rooz lack the creation metadata
+        let metadata = WorkspaceMetadata {
+            created: labels.get(CREATED).cloned(),
+            rooz_version: labels.get(ROOZ_VERSION).cloned(),
+            engine: labels.get(ENGINE).cloned(),
+            origin_host: labels.get(ORIGIN_HOST).cloned(),
+            project: labels.get(PROJECT).cloned(),
+            volumes_key: volumes_key.clone(),
+        };
+
+        let identity = self.crypt.read_age_identity().await?;
+        let stored = self.config.load(workspace_key, &labels, &identity).await?;
+        let config = ConfigDump {
+            origin: labels
+                .get(CONFIG_ORIGIN)
+                .cloned()
+                .unwrap_or("cli".to_string()),
+            body: stored.as_ref().map(|s| s.body.clone()),
+            runtime: match stored
+                .as_ref()
+                .and_then(|s| s.runtime.as_ref())
+                .or(labels.get(RUNTIME_CONFIG))
+            {
+                Some(runtime) => Some(RuntimeConfig::from_string(runtime.clone())?),
+                None => None,
+            },
+        };
+
+        let mut containers = Vec::new();
+        for c in self
+            .api
+            .container
+            .get_all(&Labels::new(Some(workspace_key), None))
+            .await?
+        {
+            containers.push(self.container_dump(c).await?);
+        }
+        containers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut networks = containers
+            .iter()
+            .flat_map(|c| c.networks.iter().cloned())
+            .collect::<Vec<_>>();
+        networks.sort();
+        networks.dedup();
+
+        let volume_labels =
+            Labels::new(Some(volumes_key.as_deref().unwrap_or(workspace_key)), None);
+        let mut volumes = self
+            .api
+            .client
+            .list_volumes(Some(ListVolumesOptions {
+                filters: (&volume_labels).into(),
+            }))
+            .await?
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|v| VolumeDump {
+                role: v.labels.get(ROLE).cloned(),
+                name: v.name,
+            })
+            .collect::<Vec<_>>();
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let dump = WorkspaceDump {
+            workspace: workspace_key.to_string(),
+            metadata,
+            config,
+            containers,
+            volumes,
+            networks,
+        };
+
+        // the runtime config holds the values the secrets got expanded into
+        if let Some(stored) = &stored {
+            self.config
+                .register_stored_secrets(stored, &identity)
+                .await?;
+        }
+        let rendered = match output {
+            OutputFormat::Yaml => serde_yaml::to_string(&dump)?,
+            OutputFormat::Json => format!("{}\n", serde_json::to_string_pretty(&dump)?),
+        };
+        print!("{}", redact::redact(&rendered));
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    process::{Child, Command, Stdio},
+};
+
+use colored::Colorize;
+use serde_json::{json, Value};
+
+use crate::{
+    config::{
+        config::{FileFormat, RoozCfg},
+        runtime::RuntimeConfig,
+    },
+    constants,
+    model::{error::RoozError, types::AnyError},
+    util::{
+        git,
+        id::to_safe_id,
+        labels::{ROLE, ROLE_WORK, RUNTIME_CONFIG, WORKSPACE_KEY},
+        ssh, uid,
+    },
+};
+
+// experimental: the workspace is a single pod (work container + sidecars) with PVCs standing in for
+// the rooz volumes. Everything goes through kubectl so its contexts, auth plugins and port-forwarding apply
+pub struct Kubectl {
+    pub context: Option<String>,
+    pub namespace: Option<String>,
+    // clones the repo in an init container
+    pub helper_image: String,
+}
+
+impl Kubectl {
+    fn command(&self) -> Command {
+        let mut cmd = Command::new("kubectl");
+        if let Some(context) = &self.context {
+            cmd.args(["--context", context]);
+        }
+        if let Some(namespace) = &self.namespace {
+            cmd.args(["--namespace", namespace]);
+        }
+        cmd
+    }
+
+    fn spawn(cmd: &mut Command) -> Result<Child, AnyError> {
+        cmd.spawn()
+            .map_err(|e| format!("Could not run kubectl: {}", e).into())
+    }
+
+    fn run(&self, args: &[&str]) -> Result<(), AnyError> {
+        let status = Self::spawn(self.command().args(args))?.wait()?;
+        match status.code() {
+            Some(0) => Ok(()),
+            code => Err(RoozError::ExitCode(code.unwrap_or(1).into()).into()),
+        }
+    }
+
+    fn output(&self, args: &[&str]) -> Result<Option<String>, AnyError> {
+        let output = self
+            .command()
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| format!("Could not run kubectl: {}", e))?;
+        Ok(output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    fn apply(&self, manifest: &Value) -> Result<(), AnyError> {
+        let mut child = Self::spawn(
+            self.command()
+                .args(["apply", "-f", "-"])
+                .stdin(Stdio::piped()),
+        )?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(manifest.to_string().as_bytes())?;
+        }
+        if !child.wait()?.success() {
+            return Err("kubectl apply failed".into());
+        }
+        Ok(())
+    }
+}
+
+// pods, containers and volumes need DNS label names
+fn dns_name(value: &str) -> String {
+    to_safe_id(value).replace(['_', '.'], "-")
+}
+
+fn pod_name(workspace_key: &str) -> String {
+    format!("rooz-{}", dns_name(workspace_key))
+}
+
+fn env(vars: &BTreeMap<String, String>) -> Value {
+    vars.iter()
+        .map(|(k, v)| json!({ "name": k, "value": v }))
+        .collect()
+}
+
+fn claim(name: &str, storage: &str) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "PersistentVolumeClaim",
+        "metadata": { "name": name },
+        "spec": {
+            "accessModes": ["ReadWriteOnce"],
+            "resources": { "requests": { "storage": storage } }
+        }
+    })
+}
+
+fn work_dir(cfg: &RuntimeConfig) -> String {
+    match &cfg.git_ssh_url {
+        Some(url) => format!("{}/{}", constants::WORK_DIR, git::repo_name(url)),
+        None => constants::WORK_DIR.to_string(),
+    }
+}
+
+const SSH_VOLUME: &str = "ssh-key";
+const SSH_DIR: &str = "/tmp/.ssh";
+
+// PVCs get labelled with the workspace so rooz k8s rm can find them
+pub fn manifest(
+    workspace_key: &str,
+    cfg: &RuntimeConfig,
+    storage: &str,
+    helper_image: &str,
+    uid: &str,
+    ssh_secret: &str,
+) -> Result<Value, AnyError> {
+    let pod = pod_name(workspace_key);
+    let labels = json!({ WORKSPACE_KEY: to_safe_id(workspace_key), ROLE: ROLE_WORK });
+    let (uid, gid) = uid::split(uid);
+    let (uid, gid) = (uid.parse::<u32>()?, gid.parse::<u32>()?);
+    let home = cfg.home_dir();
+
+    // volume name -> mount path
+    let mut work_mounts = vec![
+        ("home".to_string(), home.clone()),
+        ("work".to_string(), constants::WORK_DIR.to_string()),
+    ];
+    let mut user_volumes = cfg.volumes.iter().collect::<Vec<_>>();
+    user_volumes.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, v) in user_volumes {
+        let path = match v.path.strip_prefix('~') {
+            Some(rest) => format!("{}{}", home, rest),
+            None => v.path.to_string(),
+        };
+        work_mounts.push((format!("vol-{}", dns_name(name)), path));
+    }
+    let mut claims = work_mounts
+        .iter()
+        .map(|(n, _)| n.clone())
+        .collect::<Vec<_>>();
+
+    let mut work_env = cfg
+        .env
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect::<BTreeMap<_, _>>();
+    work_env.insert("HOME".into(), home.clone());
+    let mounts = |mounts: &[(String, String)]| -> Value {
+        mounts
+            .iter()
+            .map(|(name, path)| json!({ "name": name, "mountPath": path }))
+            .collect()
+    };
+
+    // next to ~/.ssh rather than over it so known_hosts stays on the home PVC
+    work_env.entry("GIT_SSH_COMMAND".into()).or_insert(format!(
+        "ssh -i {}/{}",
+        SSH_DIR,
+        ssh::PRIVATE_KEY
+    ));
+    let mut work_volume_mounts = mounts(&work_mounts);
+    if let Some(m) = work_volume_mounts.as_array_mut() {
+        m.push(json!({ "name": SSH_VOLUME, "mountPath": SSH_DIR, "readOnly": true }));
+    }
+    let mut containers = vec![json!({
+        "name": constants::DEFAULT_CONTAINER_NAME,
+        "image": cfg.image,
+        "command": ["cat"],
+        "stdin": true,
+        "workingDir": constants::WORK_DIR,
+        "env": env(&work_env),
+        "volumeMounts": work_volume_mounts,
+        "securityContext": { "runAsUser": uid, "runAsGroup": gid }
+    })];
+
+    let mut sidecars = cfg.sidecars.iter().collect::<Vec<_>>();
+    sidecars.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, s) in sidecars {
+        let mut sidecar_mounts = Vec::new();
+        for (i, path) in s.mounts.iter().flatten().enumerate() {
+            let claim = format!("{}-{}", dns_name(name), i);
+            claims.push(claim.clone());
+            sidecar_mounts.push((claim, path.to_string()));
+        }
+        if s.mount_work == Some(true) {
+            sidecar_mounts.push(("work".into(), constants::WORK_DIR.into()));
+        }
+        let mut container = json!({
+            "name": dns_name(name),
+            "image": s.image,
+            "env": env(&s.env.iter().flatten().map(|(k, v)| (k.clone(), v.clone())).collect()),
+            "volumeMounts": mounts(&sidecar_mounts),
+        });
+        if let Some(command) = &s.command {
+            container["args"] = json!(command);
+        }
+        if let Some(dir) = &s.work_dir {
+            container["workingDir"] = json!(dir);
+        }
+        containers.push(container);
+    }
+
+    let init_containers = match &cfg.git_ssh_url {
+        Some(url) => vec![json!({
+            "name": "clone",
+            "image": helper_image,
+            "command": ["sh", "-c", format!("[ -d '{dir}/.git' ] || git clone '{url}' '{dir}'", dir = work_dir(cfg), url = url)],
+            "env": [{
+                "name": "GIT_SSH_COMMAND",
+                "value": format!("ssh -i {}/{} -o UserKnownHostsFile=/tmp/known_hosts -o StrictHostKeyChecking=accept-new", SSH_DIR, ssh::PRIVATE_KEY)
+            }],
+            "volumeMounts": [
+                json!({ "name": "work", "mountPath": constants::WORK_DIR }),
+                json!({ "name": SSH_VOLUME, "mountPath": SSH_DIR, "readOnly": true })
+            ],
+            "securityContext": { "runAsUser": uid, "runAsGroup": gid }
+        })],
+        None => Vec::new(),
+    };
+
+    let mut volumes = claims
+        .iter()
+        .map(|c| json!({ "name": c, "persistentVolumeClaim": { "claimName": format!("{}-{}", pod, c) } }))
+        .collect::<Vec<_>>();
+    // root-owned and readable by the pod's group, which ssh accepts. Optional so pods of public repos start without it
+    volumes.push(json!({
+        "name": SSH_VOLUME,
+        "secret": { "secretName": ssh_secret, "optional": true, "defaultMode": 0o440 }
+    }));
+
+    let mut items = claims
+        .iter()
+        .map(|c| {
+            let mut pvc = claim(&format!("{}-{}", pod, c), storage);
+            pvc["metadata"]["labels"] = labels.clone();
+            pvc
+        })
+        .collect::<Vec<_>>();
+    items.push(json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": pod,
+            "labels": labels,
+            "annotations": { RUNTIME_CONFIG: cfg.to_string()? }
+        },
+        "spec": {
+            "securityContext": { "fsGroup": gid },
+            "initContainers": init_containers,
+            "containers": containers,
+            "volumes": volumes
+        }
+    }));
+    Ok(json!({ "apiVersion": "v1", "kind": "List", "items": items }))
+}
+
+fn read_config(config_path: Option<&str>) -> Result<RuntimeConfig, AnyError> {
+    let mut builder = RoozCfg::default();
+    if let Some(path) = config_path {
+        let body = fs::read_to_string(path)?;
+        let config = RoozCfg::from_string(&body, FileFormat::from_path(path)?)?;
+        if config.extends.as_ref().is_some_and(|e| !e.is_empty()) {
+            return Err(
+                RoozError::Config("extends is not supported on Kubernetes yet".into()).into(),
+            );
+        }
+        if config.secrets.as_ref().is_some_and(|s| !s.is_empty()) {
+            return Err(
+                RoozError::Config("secrets are not supported on Kubernetes yet".into()).into(),
+            );
+        }
+        builder.from_config(&config);
+    }
+    builder.expand_vars()?;
+    let cfg = RuntimeConfig::from(&builder);
+    if !cfg.caches.is_empty() {
+        eprintln!(
+            "{}",
+            "Caches are shared across workspaces which PVCs can't do. Ignoring them".yellow()
+        );
+    }
+    Ok(cfg)
+}
+
+impl Kubectl {
+    pub fn create(
+        &self,
+        workspace_key: &str,
+        config_path: Option<&str>,
+        storage: &str,
+        uid: &str,
+        ssh_secret: &str,
+        dry_run: bool,
+    ) -> Result<(), AnyError> {
+        let cfg = read_config(config_path)?;
+        let manifest = manifest(
+            workspace_key,
+            &cfg,
+            storage,
+            &self.helper_image,
+            uid,
+            ssh_secret,
+        )?;
+        if dry_run {
+            println!("{}", serde_json::to_string_pretty(&manifest)?);
+            return Ok(());
+        }
+        let pod = pod_name(workspace_key);
+        if self.output(&["get", "pod", &pod, "-o", "name"])?.is_some() {
+            return Err(format!("Workspace {} already exists", workspace_key).into());
+        }
+        self.apply(&manifest)?;
+        println!("Waiting for {} to start ...", pod);
+        self.run(&[
+            "wait",
+            "--for=condition=Ready",
+            &format!("pod/{}", pod),
+            "--timeout=10m",
+        ])?;
+        println!(
+            "Created {}. Run 'rooz k8s enter {}' to enter",
+            workspace_key.bold(),
+            workspace_key
+        );
+        Ok(())
+    }
+
+    fn runtime_config(&self, pod: &str, workspace_key: &str) -> Result<RuntimeConfig, AnyError> {
+        let json = self
+            .output(&["get", "pod", pod, "-o", "json"])?
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+        let pod = serde_json::from_str::<Value>(&json)?;
+        match pod["metadata"]["annotations"][RUNTIME_CONFIG].as_str() {
+            Some(config) => RuntimeConfig::from_string(config.to_string()),
+            None => Ok(RuntimeConfig::default()),
+        }
+    }
+
+    // the config's host ports get forwarded for as long as the shell runs
+    pub fn enter(&self, workspace_key: &str) -> Result<(), AnyError> {
+        let pod = pod_name(workspace_key);
+        let cfg = self.runtime_config(&pod, workspace_key)?;
+        let forwards = cfg
+            .ports
+            .iter()
+            .filter_map(|(container, host)| host.as_ref().map(|h| format!("{}:{}", h, container)))
+            .collect::<Vec<_>>();
+        let mut forwarder = match forwards.is_empty() {
+            true => None,
+            false => Some(Self::spawn(
+                self.command()
+                    .arg("port-forward")
+                    .arg(format!("pod/{}", pod))
+                    .args(&forwards)
+                    .stdout(Stdio::null()),
+            )?),
+        };
+        let shell = format!(
+            "cd '{}' 2>/dev/null; exec {}",
+            work_dir(&cfg),
+            cfg.shell.join(" ")
+        );
+        let result = self.run(&[
+            "exec",
+            "-it",
+            &pod,
+            "-c",
+            constants::DEFAULT_CONTAINER_NAME,
+            "--",
+            "sh",
+            "-c",
+            &shell,
+        ]);
+        if let Some(f) = &mut forwarder {
+            f.kill()?;
+        }
+        result
+    }
+
+    pub fn remove(&self, workspace_key: &str) -> Result<(), AnyError> {
+        self.run(&[
+            "delete",
+            "pod,pvc",
+            "-l",
+            &format!("{}={}", WORKSPACE_KEY, to_safe_id(workspace_key)),
+        ])
+    }
+
+    pub fn list(&self) -> Result<(), AnyError> {
+        self.run(&[
+            "get",
+            "pods",
+            "-l",
+            &format!("{}={}", ROLE, ROLE_WORK),
+            "-L",
+            WORKSPACE_KEY,
+        ])
+    }
+}
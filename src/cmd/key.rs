@@ -0,0 +1,99 @@
+use std::{fs, str::FromStr};
+
+use age::{
+    secrecy::{ExposeSecret, SecretString},
+    x25519::Identity,
+};
+use colored::Colorize;
+
+use crate::{
+    api::{Api, CryptApi},
+    config::config::RoozPullPolicy,
+    model::{error::RoozError, types::AnyError},
+    util::term,
+};
+
+const ARMOR_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+
+fn prompt_passphrase(prompt: &str, non_interactive: bool) -> Result<SecretString, AnyError> {
+    if non_interactive {
+        return Err(RoozError::Interactive("Prompting for a passphrase".into()).into());
+    }
+    eprint!("{}", prompt);
+    match term::read_passwd()? {
+        Some(p) => {
+            eprintln!();
+            Ok(SecretString::from(p))
+        }
+        None => Err("Aborted".into()),
+    }
+}
+
+pub fn read_backup(path: &str, non_interactive: bool) -> Result<Identity, AnyError> {
+    let mut content = fs::read_to_string(path)?;
+    if content.trim_start().starts_with(ARMOR_HEADER) {
+        let passphrase = prompt_passphrase("Backup passphrase: ", non_interactive)?;
+        let decrypted = age::decrypt(&age::scrypt::Identity::new(passphrase), content.as_bytes())
+            .map_err(|e| format!("Could not decrypt the backup: {}", e))?;
+        content = String::from_utf8(decrypted)?;
+    }
+    let key = content
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with("AGE-SECRET-KEY-"))
+        .ok_or(format!("No age identity found in: {}", path))?;
+    Ok(Identity::from_str(key)?)
+}
+
+impl<'a> CryptApi<'a> {
+    pub async fn key_export(&self, passphrase: bool, yes: bool) -> Result<(), AnyError> {
+        if self.non_interactive && !yes {
+            return Err(RoozError::Interactive("Confirming the export (use --yes)".into()).into());
+        }
+        if !yes
+            && !term::confirm(
+                &"This prints the secret key decrypting all workspace secrets. Continue?"
+                    .yellow()
+                    .to_string(),
+            )?
+        {
+            return Ok(());
+        }
+
+        let identity = self.read_age_identity().await?;
+        let backup = format!(
+            "# public key: {}\n{}\n",
+            identity.to_public(),
+            identity.to_string().expose_secret()
+        );
+
+        if passphrase {
+            let first = prompt_passphrase("Passphrase: ", self.non_interactive)?;
+            let second = prompt_passphrase("Confirm passphrase: ", self.non_interactive)?;
+            if first.expose_secret() != second.expose_secret() {
+                return Err("Passphrases do not match".into());
+            }
+            print!(
+                "{}",
+                age::encrypt_and_armor(&age::scrypt::Recipient::new(first), backup.as_bytes())?
+            );
+        } else {
+            print!("{}", backup);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Api<'a> {
+    pub async fn key_import(
+        &self,
+        image: &str,
+        uid: &str,
+        path: &str,
+        force: bool,
+    ) -> Result<(), AnyError> {
+        let key = read_backup(path, self.exec.non_interactive)?;
+        let image_id = self.image.ensure(image, RoozPullPolicy::Missing).await?;
+        self.init_age_key(&image_id, uid, Some(key), force).await
+    }
+}
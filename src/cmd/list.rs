@@ -1,12 +1,28 @@
+use std::collections::{BTreeMap, HashMap};
+
+use colored::Colorize;
+
 use crate::{
     api::Api,
-    model::types::AnyError,
-    util::labels::{self, Labels, CONFIG_ORIGIN},
+    cli::{GroupBy, SortBy},
+    config::system::LastUsed,
+    constants,
+    model::{error::RoozError, types::AnyError},
+    util::{
+        labels::{
+            self, LabelFilter, Labels, BRANCH, CONFIG_ORIGIN, CREATED, PORTS, REPO, ROLE,
+            ROLE_SIDECAR, ROLE_WORK, ROOZ_VERSION, VOLUMES_KEY, WORKSPACE_KEY,
+        },
+        size, term,
+    },
 };
 
 use bollard::{container::ListContainersOptions, service::ContainerSummary};
 
-use tabled::{settings::Style, Table, Tabled};
+use tabled::{
+    settings::{object::Columns, Remove, Style},
+    Table, Tabled,
+};
 
 #[derive(Debug, Tabled)]
 struct WorkspaceView {
@@ -14,13 +30,81 @@ struct WorkspaceView {
     name: String,
     #[tabled(rename = "RUNNING", format("{}", if self.running {"true"} else {""}))]
     running: bool,
+    // running/all of the work containers and sidecars
+    #[tabled(rename = "CONTAINERS")]
+    containers: String,
+    #[tabled(rename = "IMAGE")]
+    image: String,
     #[tabled(rename = "CONFIG")]
     origin: String,
+    #[tabled(rename = "REPO")]
+    repo: String,
+    #[tabled(rename = "BRANCH")]
+    branch: String,
+    #[tabled(rename = "PORTS")]
+    ports: String,
+    #[tabled(rename = "CREATED")]
+    created: String,
+    #[tabled(rename = "LAST USED")]
+    last_used: String,
+    #[tabled(rename = "ROOZ")]
+    version: String,
+    #[tabled(rename = "SIZE")]
+    size: String,
 }
 
+const FILTER_KEYS: [&str; 4] = ["status", "project", "repo", "branch"];
+
 impl<'a> Api<'a> {
-    pub async fn list(&self) -> Result<(), AnyError> {
-        let labels = Labels::new(None, Some(labels::ROLE_WORK));
+    async fn container_counts(&self) -> Result<HashMap<String, (usize, usize)>, AnyError> {
+        let mut counts = HashMap::<String, (usize, usize)>::new();
+        for c in self
+            .client
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters: (&Labels::default()).into(),
+                ..Default::default()
+            }))
+            .await?
+        {
+            let labels = c.labels.unwrap_or_default();
+            let (Some(workspace), Some(role)) = (labels.get(WORKSPACE_KEY), labels.get(ROLE))
+            else {
+                continue;
+            };
+            if role == ROLE_WORK || role == ROLE_SIDECAR {
+                let count = counts.entry(workspace.to_string()).or_default();
+                count.1 += 1;
+                if c.state.as_deref() == Some("running") {
+                    count.0 += 1;
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn volume_sizes(&self) -> Result<HashMap<String, i64>, AnyError> {
+        let mut sizes = HashMap::<String, i64>::new();
+        for v in self.client.df().await?.volumes.unwrap_or_default() {
+            let (Some(workspace), Some(usage)) = (v.labels.get(WORKSPACE_KEY), v.usage_data) else {
+                continue;
+            };
+            if usage.size >= 0 {
+                *sizes.entry(workspace.to_string()).or_default() += usage.size;
+            }
+        }
+        Ok(sizes)
+    }
+
+    async fn workspace_views(
+        &self,
+        sizes: bool,
+        project: Option<&str>,
+        filter: &LabelFilter,
+    ) -> Result<Vec<WorkspaceView>, AnyError> {
+        let labels = Labels::new(None, Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME))
+            .with_project(project);
         let list_options = ListContainersOptions {
             filters: (&labels).into(),
             all: true,
@@ -29,6 +113,14 @@ impl<'a> Api<'a> {
 
         let container_summary = self.client.list_containers(Some(list_options)).await?;
 
+        let volume_sizes = if sizes {
+            self.volume_sizes().await?
+        } else {
+            HashMap::new()
+        };
+        let counts = self.container_counts().await?;
+        let last_used = LastUsed::load()?;
+
         let mut views = Vec::<WorkspaceView>::new();
 
         for c in container_summary {
@@ -42,22 +134,115 @@ impl<'a> Api<'a> {
                     "running" => true,
                     _ => false,
                 };
+                if !filter.matches(&labels, is_running) {
+                    continue;
+                }
+                let workspace_key = labels.get(WORKSPACE_KEY).cloned().unwrap_or_default();
+                let volumes_key = labels.get(VOLUMES_KEY).or(labels.get(WORKSPACE_KEY));
+                let size = volumes_key
+                    .and_then(|k| volume_sizes.get(k))
+                    .map(|s| size::human(*s))
+                    .unwrap_or_default();
                 views.push(WorkspaceView {
                     name: c.names.unwrap().join(", ")[1..].to_string(),
                     running: is_running,
+                    containers: counts
+                        .get(&workspace_key)
+                        .map(|(running, all)| format!("{}/{}", running, all))
+                        .unwrap_or_default(),
+                    image: c.image.unwrap_or_default(),
                     origin: labels
                         .get(CONFIG_ORIGIN)
                         .unwrap_or(&"cli".to_string())
                         .to_string(),
+                    repo: labels.get(REPO).cloned().unwrap_or_default(),
+                    branch: labels.get(BRANCH).cloned().unwrap_or_default(),
+                    ports: labels.get(PORTS).cloned().unwrap_or_default(),
+                    created: labels.get(CREATED).cloned().unwrap_or_default(),
+                    last_used: last_used
+                        .get(&workspace_key)
+                        .unwrap_or_default()
+                        .to_string(),
+                    version: labels.get(ROOZ_VERSION).cloned().unwrap_or_default(),
+                    size,
                 });
             }
         }
 
         views.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(views)
+    }
 
-        let table = Table::new(views).with(Style::blank()).to_string();
+    pub async fn list(
+        &self,
+        sizes: bool,
+        project: Option<&str>,
+        group_by: Option<GroupBy>,
+        filters: &[String],
+        sort: Option<SortBy>,
+    ) -> Result<(), AnyError> {
+        let filter = LabelFilter::parse(filters, &FILTER_KEYS)?;
+        let mut views = self
+            .workspace_views(sizes, filter.project.as_deref().or(project), &filter)
+            .await?;
+        // RFC 3339 timestamps sort chronologically as strings
+        match sort {
+            None | Some(SortBy::Name) => (),
+            Some(SortBy::Created) => views.sort_by(|a, b| b.created.cmp(&a.created)),
+            Some(SortBy::LastUsed) => views.sort_by(|a, b| b.last_used.cmp(&a.last_used)),
+        }
+        let table = |views: Vec<WorkspaceView>| {
+            let mut table = Table::new(views);
+            table.with(Style::blank());
+            if !sizes {
+                table.with(Remove::column(Columns::last()));
+            }
+            table.to_string()
+        };
 
-        println!("{}", table);
+        match group_by {
+            None => println!("{}", table(views)),
+            Some(GroupBy::Repo) => {
+                let mut groups = BTreeMap::<String, Vec<WorkspaceView>>::new();
+                for v in views {
+                    groups.entry(v.repo.to_string()).or_default().push(v);
+                }
+                for (repo, views) in groups {
+                    let header = match repo.as_str() {
+                        "" => "(no repository)".to_string(),
+                        _ => repo,
+                    };
+                    println!("{}\n{}\n", header.bold(), table(views));
+                }
+            }
+        }
         Ok(())
     }
+
+    // lets the user pick a workspace when its name is omitted
+    pub async fn pick_workspace(&self) -> Result<String, AnyError> {
+        if self.exec.non_interactive || !term::is_interactive() {
+            return Err(RoozError::Interactive(
+                "Picking a workspace (pass its name instead)".into(),
+            )
+            .into());
+        }
+        let views = self
+            .workspace_views(false, None, &LabelFilter::default())
+            .await?;
+        if views.is_empty() {
+            return Err("No workspaces found".into());
+        }
+        let items = views
+            .iter()
+            .map(|v| match v.running {
+                true => format!("{} (running)", v.name),
+                false => v.name.to_string(),
+            })
+            .collect::<Vec<_>>();
+        match term::pick("Workspace:", &items)? {
+            Some(i) => Ok(views[i].name.to_string()),
+            None => Err("Aborted".into()),
+        }
+    }
 }
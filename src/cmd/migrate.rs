@@ -0,0 +1,90 @@
+use colored::Colorize;
+
+use crate::{
+    api::WorkspaceApi,
+    cli::WorkEnvParams,
+    cmd::update::{UpdateConfig, UpdateMode},
+    constants,
+    model::types::AnyError,
+    util::labels::{self, Labels, ROLE_WORK},
+};
+
+impl<'a> WorkspaceApi<'a> {
+    // labels can't be changed in place so migrating means re-creating the containers from the stored config,
+    // like rooz update does, just without fetching a newer config or pulling images
+    pub async fn migrate(&self, dry_run: bool, force: bool) -> Result<(), AnyError> {
+        let containers = self
+            .api
+            .container
+            .get_all(
+                &Labels::new(None, Some(ROLE_WORK))
+                    .with_container(Some(constants::DEFAULT_CONTAINER_NAME)),
+            )
+            .await?;
+
+        let mut outdated = Vec::new();
+        for c in containers {
+            let labels = c.labels.unwrap_or_default();
+            let Some(key) = labels.get(labels::WORKSPACE_KEY) else {
+                continue;
+            };
+            let schema = labels
+                .get(labels::SCHEMA)
+                .and_then(|s| s.parse::<u32>().ok())
+                .unwrap_or(0);
+            if schema < labels::SCHEMA_VERSION {
+                outdated.push((key.to_string(), schema, labels::has_config(&labels)));
+            }
+        }
+        outdated.sort();
+
+        if outdated.is_empty() {
+            println!("All workspaces are up to date");
+            return Ok(());
+        }
+
+        let mut failed = Vec::new();
+        for (key, schema, has_config) in outdated {
+            let from = format!("schema {} -> {}", schema, labels::SCHEMA_VERSION);
+            if !has_config {
+                println!(
+                    "{} {} ({}): created without a config, so there is nothing to re-create it from. Re-create it manually",
+                    "Skipping".yellow(),
+                    key.bold(),
+                    from
+                );
+                continue;
+            }
+            if dry_run {
+                println!("{} {} ({})", "Would migrate".yellow(), key.bold(), from);
+                continue;
+            }
+            println!("{} {} ({})", "Migrating".green(), key.bold(), from);
+            let result = match self.guard_sessions(&key, "migrate", force).await {
+                Ok(_) => {
+                    self.update(
+                        &key,
+                        &WorkEnvParams::default(),
+                        UpdateConfig::Keep,
+                        UpdateMode::Apply {
+                            reset_home: false,
+                            only: Vec::new(),
+                        },
+                        true,
+                        false,
+                    )
+                    .await
+                }
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                eprintln!("{}", format!("Could not migrate {}: {}", key, e).red());
+                failed.push(key);
+            }
+        }
+        if !failed.is_empty() {
+            return Err(format!("Failed to migrate: {}", failed.join(", ")).into());
+        }
+        Ok(())
+    }
+}
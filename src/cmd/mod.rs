@@ -1,7 +1,33 @@
+pub mod archive;
+pub mod audit;
+pub mod backup;
+pub mod cache;
+pub mod check;
+pub mod clean;
+pub mod complete;
 pub mod config;
+pub mod context;
+pub mod doctor;
+pub mod home;
+pub mod image;
 pub mod init;
+pub mod inspect;
+pub mod k8s;
+pub mod key;
 pub mod list;
+pub mod migrate;
 pub mod new;
+pub mod notify;
+pub mod open;
+pub mod plan;
 pub mod prune;
+#[cfg(unix)]
 pub mod remote;
+pub mod snapshot;
+pub mod ssh_key;
+pub mod stats;
+pub mod status;
 pub mod update;
+pub mod upgrade;
+pub mod user;
+pub mod watch;
@@ -1,24 +1,143 @@
-use std::fs;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::Path,
+    process::{Command, Stdio},
+};
 
 use age::x25519::Identity;
+use bollard::volume::ListVolumesOptions;
+use colored::Colorize;
+use futures::future::{FutureExt, LocalBoxFuture};
 
 use crate::{
-    api::WorkspaceApi,
+    api::{workspace::docker, WorkspaceApi},
     cli::WorkParams,
+    cmd::config::store::StoredConfig,
     config::{
-        config::{ConfigPath, ConfigSource, FileFormat, RoozCfg},
+        config::{ConfigPath, ConfigSource, FileFormat, RoozCfg, RoozContainer, RoozDockerMode},
         runtime::RuntimeConfig,
+        system::{PulledImages, SystemConfig, WorkspaceEngines},
     },
     constants,
-    model::types::{AnyError, EnterSpec, WorkSpec},
+    model::{
+        error::{Context, RoozError},
+        types::{AnyError, EnterSpec, ReconnectPolicy, WorkSpec},
+        volume::{RoozVolume, HOME_ROLE, WORK_ROLE},
+    },
     util::{
-        git::{CloneEnv, RootRepoCloneResult},
+        git::{self, CloneEnv, RootRepoCloneResult},
         id,
-        labels::{self, Labels},
+        labels::{self, Labels, ROLE},
+        progress, prompt, ssh, term, uid,
     },
 };
 
+pub const LOCAL_CONFIG: &str = ".rooz.toml";
+
+// the origin of the current directory becomes the root repo of workspaces created from it
+pub fn local_remote(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["remote", "get-url", "origin"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|url| output.status.success() && !url.is_empty())
+}
+
+// branches are often named like feature/x so the key is made safe for container and volume names
+pub fn branch_workspace_key(prefix: Option<&str>, git_ssh_url: &str, branch: &str) -> String {
+    let prefix = prefix
+        .map(|p| p.to_string())
+        .unwrap_or_else(|| git::repo_name(git_ssh_url));
+    id::to_safe_id(&format!("{}-{}", prefix, branch))
+}
+
+// the uid is the one of the host user unless configured otherwise
+pub fn workspace_clone_env(
+    workspace_key: &str,
+    volumes_key: Option<&str>,
+    cli_params: &WorkParams,
+) -> Result<CloneEnv, AnyError> {
+    let uid = uid::resolve(
+        cli_params
+            .uid
+            .clone()
+            .or(SystemConfig::load()?.uid)
+            .as_deref(),
+    )?;
+    Ok(CloneEnv {
+        uid: uid.to_string(),
+        workspace_key: workspace_key.to_string(),
+        volumes_key: volumes_key.map(|k| k.to_string()),
+        working_dir: constants::WORK_DIR.to_string(),
+        ..Default::default()
+    })
+}
+
+// the extra containers share the env and volumes of the work container
+pub fn container_spec<'b>(
+    cfg: &'b RuntimeConfig,
+    work_spec: &WorkSpec<'b>,
+    name: &str,
+    c: &'b RoozContainer,
+    container_name: &'b str,
+) -> WorkSpec<'b> {
+    let mut env = work_spec.env_vars.clone().unwrap_or_default();
+    env.extend(c.env.iter().flatten().map(|(k, v)| (k.clone(), v.clone())));
+    let mut ports = HashMap::<String, Option<String>>::new();
+    RoozCfg::parse_ports(&mut ports, c.ports.clone());
+    WorkSpec {
+        image: &c.image,
+        user: c.user.as_deref().unwrap_or(&cfg.user),
+        home_dir: work_spec.home_dir.filter(|_| c.user.is_none()),
+        container_name,
+        volumes_key: Some(work_spec.volumes_key.unwrap_or(work_spec.workspace_key)),
+        caches: c.caches.clone(),
+        env_vars: Some(env),
+        ports: Some(ports),
+        labels: Labels {
+            volumes: work_spec.labels.volumes.clone(),
+            ..Labels::new(Some(work_spec.workspace_key), Some(labels::ROLE_WORK))
+        }
+        .with_container(Some(name)),
+        network_aliases: None,
+        extra_networks: None,
+        ..work_spec.clone()
+    }
+}
+
+// engines report x86_64 and aarch64 as well as the Go names used in platforms
+fn go_arch(arch: &str) -> &str {
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        a => a,
+    }
+}
+
 impl<'a> WorkspaceApi<'a> {
+    async fn warn_emulation(&self, platform: &str) -> Result<(), AnyError> {
+        let host = self.api.client.version().await?.arch.unwrap_or_default();
+        let arch = platform.split('/').nth(1).unwrap_or_default();
+        if !host.is_empty() && go_arch(arch) != go_arch(&host) {
+            eprintln!(
+                "{}",
+                format!(
+                    "{} runs emulated on the {} container engine host. Expect it to be several times slower",
+                    platform, host
+                )
+                .yellow()
+            );
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "workspace.create", level = "debug", skip_all, fields(id = %workspace_key))]
     async fn new_core(
         &self,
         cfg_builder: &mut RoozCfg,
@@ -36,54 +155,193 @@ impl<'a> WorkspaceApi<'a> {
             cfg_builder.from_config(c);
         }
         cfg_builder.from_cli(cli_params, None);
+        let generated =
+            self.config
+                .generate_secrets(cfg_builder, &cli_params.generated_secrets, identity)?;
         self.config.decrypt(cfg_builder, identity).await?;
         cfg_builder.expand_vars()?;
 
-        let cfg = RuntimeConfig::from(&*cfg_builder);
+        let mut cfg = RuntimeConfig::from(&*cfg_builder);
+        let volumes_key = work_spec.volumes_key.unwrap_or(workspace_key);
+        if let Some(engine) = cfg.engine.as_deref() {
+            if self.api.container.engine != Some(engine) {
+                return Err(RoozError::Config(format!(
+                    "the workspace belongs to the '{}' engine. Run the command with --engine {}",
+                    engine, engine
+                ))
+                .into());
+            }
+        }
 
-        self.api
-            .image
-            .ensure(&cfg.image, cli_params.pull_image)
+        // the platform only applies to the work containers. Sidecars and helpers stay native
+        let platform = cfg.platform.as_deref();
+        if let Some(p) = platform {
+            self.warn_emulation(p).await?;
+        }
+        let images = [cfg.image.as_str()]
+            .into_iter()
+            .chain(cfg.containers.values().map(|c| c.image.as_str()))
+            .map(|i| (i, platform))
+            .chain(
+                cfg.sidecars
+                    .values()
+                    .map(|s| s.image.as_str())
+                    .chain(cfg.sync.as_ref().map(|s| s.image()))
+                    .chain((cfg.docker == Some(RoozDockerMode::Dind)).then_some(docker::DIND_IMAGE))
+                    .map(|i| (i, None)),
+            )
+            .collect::<Vec<_>>();
+        let pulled = self.api.image.ensure_all(&images, cfg.pull).await?;
+        let used = images.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        if let Err(e) =
+            PulledImages::record(self.api.container.engine, workspace_key, &pulled, &used)
+        {
+            log::warn!("Could not record the pulled images: {}", e);
+        }
+
+        // the user of the image is only known once it got pulled
+        let explicit_uid = cli_params.uid.is_some() || SystemConfig::load()?.uid.is_some();
+        let image_user = self
+            .image_user(workspace_key, &cfg, work_spec.uid, explicit_uid)
             .await?;
+        cfg.user = image_user.name;
+        cfg.home = image_user.home;
+        let work_spec = &WorkSpec {
+            uid: &image_user.uid,
+            ..work_spec.clone()
+        };
+        let home_dir = cfg.home_dir();
 
-        let network = self
-            .ensure_sidecars(
-                &cfg.sidecars,
+        if cfg.home_from_image {
+            let step = progress::step(self.api.progress, "Populating the home volume");
+            self.sync_home(
                 workspace_key,
-                force,
-                cli_params.pull_image,
-                &work_dir,
+                volumes_key,
+                &cfg.image,
+                &home_dir,
+                cli_params.reset_home,
             )
             .await?;
+            step.done();
+        }
+
+        let network = self
+            .ensure_sidecars(&cfg, workspace_key, volumes_key, force, &work_dir)
+            .await?;
+
+        let expose = cfg.expose.clone().unwrap_or_default();
+        let expose_labels =
+            expose.labels(workspace_key, &expose.hosts.clone().unwrap_or_default())?;
 
-        let labels = work_spec
+        let mut labels = work_spec
             .labels
             .clone()
             .with_container(Some(constants::DEFAULT_CONTAINER_NAME))
-            .with_runtime_config(cfg.clone());
+            .with_metadata(self.api.container.backend)
+            .with_engine(self.api.container.engine)
+            .with_uid(work_spec.uid)
+            .with_image_digest(self.api.image.digest(&cfg.image).await?)
+            .with_custom(&cfg.labels)?
+            .with_custom(expose_labels.iter().map(|(k, v)| (k, v)))?
+            .with_runtime_config(cfg.masked());
+        if let (Some(origin), Some(body)) = (&labels.config_source, &labels.config_body) {
+            let stored = StoredConfig {
+                origin: origin.value.to_string(),
+                body: body.value.to_string(),
+                runtime: Some(cfg.to_string()?),
+                generated,
+            };
+            let hash = self.config.store(workspace_key, &stored, identity).await?;
+            labels = labels.with_config_hash(&hash);
+        }
 
-        let work_spec = WorkSpec {
-            image: &cfg.image,
-            user: &cfg.user,
-            caches: Some(cfg.caches),
-            env_vars: Some(cfg.env),
-            ports: Some(cfg.ports),
-            container_working_dir: &root_git_repo
-                .clone()
-                .map(|r| r.dir)
-                .unwrap_or(constants::WORK_DIR.to_string()),
-            network: network.as_deref(),
-            labels,
-            privileged: cfg.privileged,
-            ..*work_spec
-        };
+        let prompt = cfg.prompt.unwrap_or(true);
+        let shell_init = cfg.shell_init.as_deref().filter(|s| !s.trim().is_empty());
+        let working_dir = root_git_repo
+            .as_ref()
+            .map(|r| r.dir.clone())
+            .unwrap_or(constants::WORK_DIR.to_string());
+        let work_spec = self.work_spec(
+            &cfg,
+            network.as_deref(),
+            &working_dir,
+            WorkSpec {
+                labels,
+                keep_existing: cli_params.keep_existing,
+                ..work_spec.clone()
+            },
+        )?;
 
+        let step = progress::step(self.api.progress, "Creating the work container");
         let ws = self.create(&work_spec).await?;
+        step.done();
+
+        for (name, c) in &cfg.containers {
+            let step = progress::step(self.api.progress, format!("Creating container {}", name));
+            let container_name = format!("{}-{}", workspace_key, name);
+            let spec = container_spec(&cfg, &work_spec, name, c, &container_name);
+            self.create(&WorkSpec {
+                labels: spec
+                    .labels
+                    .clone()
+                    .with_image_digest(self.api.image.digest(&c.image).await?),
+                ..spec
+            })
+            .await?;
+            step.done();
+        }
+        if let Some(sync) = &cfg.sync {
+            self.ensure_sync(sync, workspace_key, volumes_key, work_spec.uid, force)
+                .await?;
+        }
+        let mut home_files = Vec::new();
+        if let Some(git) = &cfg.git {
+            home_files.push((
+                ".config/git/config".into(),
+                git.with_host_signing_key().to_gitconfig(),
+            ));
+        }
+        if prompt || shell_init.is_some() {
+            home_files.push(prompt::init_script(prompt, shell_init));
+        }
+        self.api
+            .write_files(
+                "home files",
+                &RoozVolume::home(volumes_key, &home_dir),
+                work_spec.uid,
+                &home_files,
+            )
+            .await?;
+        let clone_spec = CloneEnv {
+            token: cfg_builder
+                .secrets
+                .as_ref()
+                .and_then(|s| s.get(git::TOKEN_SECRET).cloned())
+                .or(clone_spec.token.clone()),
+            proxy: cfg.proxy.clone(),
+            uid: work_spec.uid.to_string(),
+            ..clone_spec.clone()
+        };
         if !cfg.extra_repos.is_empty() {
+            let step = progress::step(self.api.progress, "Cloning the extra repos");
             self.git
-                .clone_extra_repos(clone_spec.clone(), cfg.extra_repos)
+                .clone_extra_repos(clone_spec.clone(), cfg.extra_repos.clone())
                 .await?;
+            step.done();
         }
+        if let Some(url) = cfg.dotfiles.as_ref().and_then(|d| d.repo.as_deref()) {
+            let step = progress::step(self.api.progress, "Cloning the dotfiles");
+            self.git.clone_dotfiles(clone_spec, url, &home_dir).await?;
+            step.done();
+        }
+        self.run_init_containers(
+            &cfg.with_dotfiles_install(),
+            workspace_key,
+            work_spec.uid,
+            &ws.volumes,
+            network.as_deref(),
+        )
+        .await?;
         Ok(EnterSpec {
             workspace: ws,
             git_spec: root_git_repo,
@@ -91,7 +349,118 @@ impl<'a> WorkspaceApi<'a> {
         })
     }
 
-    async fn get_cli_config(
+    // the work container spec, built the same way for the dry-run plan
+    pub fn work_spec<'b>(
+        &self,
+        cfg: &'b RuntimeConfig,
+        network: Option<&'b str>,
+        working_dir: &'b str,
+        base: WorkSpec<'b>,
+    ) -> Result<WorkSpec<'b>, AnyError> {
+        let expose = cfg.expose.clone().unwrap_or_default();
+        let mut extra_networks = cfg.networks.clone();
+        extra_networks
+            .extend(expose.extra_network(&expose.hosts.clone().unwrap_or_default(), network));
+        let prompt = cfg.prompt.unwrap_or(true);
+        let shell_init = cfg.shell_init.as_deref().filter(|s| !s.trim().is_empty());
+        let mut env = self.proxy_env(cfg);
+        env.extend(cfg.env.clone());
+        if prompt || shell_init.is_some() {
+            prompt::extend_env(&mut env);
+        }
+        let (mut mounts, docker_env) = match cfg.docker {
+            Some(mode) => self.docker_client(mode, base.workspace_key)?,
+            None => Default::default(),
+        };
+        env.extend(docker_env);
+        if let Some(signing) = cfg.git.as_ref().and_then(|g| g.sign) {
+            let (agent_mounts, agent_env) = self.signing_agent(signing, &cfg.home_dir())?;
+            mounts.extend(agent_mounts);
+            env.extend(agent_env);
+        }
+        if self.git.host_ssh {
+            mounts.extend(ssh::host_mounts());
+        }
+        Ok(WorkSpec {
+            image: &cfg.image,
+            user: &cfg.user,
+            home_dir: cfg.home.as_deref(),
+            caches: Some(cfg.caches.clone()),
+            volumes: Some({
+                let mut volumes = cfg.volumes.clone();
+                volumes.extend(cfg.dotfiles.as_ref().and_then(|d| d.volume()));
+                volumes
+            }),
+            env_vars: Some(env),
+            mounts,
+            ports: Some(cfg.ports.clone()),
+            auto_ports: cfg.auto_ports,
+            bind_address: cfg.bind_address.as_deref(),
+            container_working_dir: working_dir,
+            network,
+            network_aliases: Some(cfg.aliases.clone()).filter(|a| !a.is_empty()),
+            extra_networks: Some(extra_networks),
+            privileged: cfg.privileged,
+            security: cfg.security(),
+            dns: cfg.dns(),
+            idmap: cfg.idmap,
+            restart: cfg.restart,
+            platform: cfg.platform.as_deref(),
+            ..base
+        })
+    }
+
+    // the token and proxy of the config also apply to cloning the repos
+    pub async fn read_cli_config(
+        &self,
+        cli_config_path: Option<ConfigSource>,
+        clone_env: &mut CloneEnv,
+        labels: &mut Labels,
+        identity: &Identity,
+    ) -> Result<Option<RoozCfg>, AnyError> {
+        let cli_cfg = self
+            .get_cli_config(cli_config_path, clone_env, labels)
+            .await?;
+        if let Some(c) = &cli_cfg {
+            clone_env.token = self.config.secret(c, git::TOKEN_SECRET, identity).await?;
+            clone_env.proxy = c.proxy.clone();
+        }
+        Ok(cli_cfg)
+    }
+
+    // the .rooz.toml or .rooz.yaml of the cloned repo, the labels get to record where the config came from
+    pub async fn apply_repo_config(
+        &self,
+        cfg_builder: &mut RoozCfg,
+        url: &str,
+        config: Option<&(String, FileFormat)>,
+        clone_env: &CloneEnv,
+        labels: Labels,
+    ) -> Result<Labels, AnyError> {
+        let Some((body, format)) = config else {
+            log::debug!("No valid config file found in the repository.");
+            return Ok(labels);
+        };
+        let Some(c) = RoozCfg::deserialize_config(body, *format)? else {
+            log::debug!("No valid config file found in the repository.");
+            return Ok(labels);
+        };
+        let origin = ConfigPath::Git {
+            url: url.to_string(),
+            file_path: format!(".rooz.{}", format.to_string()),
+        };
+        let c = self.resolve_extends(c, &origin, clone_env).await?;
+        cfg_builder.from_config(&c);
+        log::debug!("Config file applied.");
+        let source = format!("{}//.rooz.{}", url, format.to_string());
+        Ok(Labels {
+            config_source: Labels::config_origin(&source),
+            config_body: Labels::config_body(body),
+            ..labels
+        })
+    }
+
+    pub async fn get_cli_config(
         &self,
         cli_config_path: Option<ConfigSource>,
         clone_env: &CloneEnv,
@@ -109,53 +478,191 @@ impl<'a> WorkspaceApi<'a> {
                         config_body: Labels::config_body(&value.to_string(format.clone())?),
                         ..labels.clone()
                     };
-                    Some(value.clone())
+                    Some((value.clone(), ConfigPath::from_str(origin)?))
                 }
-                ConfigSource::Path { value: path } => match path {
-                    ConfigPath::File { path } => {
-                        let body = fs::read_to_string(&path)?;
-                        let absolute_path =
-                            std::path::absolute(path)?.to_string_lossy().into_owned();
-                        *labels = Labels {
-                            config_source: Labels::config_origin(&absolute_path),
-                            config_body: Labels::config_body(&body),
-                            ..labels.clone()
-                        };
-                        RoozCfg::deserialize_config(&body, FileFormat::from_path(&path))?
-                    }
-                    ConfigPath::Git { url, file_path } => {
-                        let body = self
-                            .git
-                            .clone_config_repo(clone_env.clone(), &url, &file_path)
-                            .await?;
-
-                        *labels = Labels {
-                            config_source: Labels::config_origin(&path.to_string()),
-                            ..labels.clone()
-                        };
-
-                        if let Some(b) = &body {
+                ConfigSource::Path { value: path } => {
+                    let (origin, body) = self.read_config(path, clone_env).await?;
+                    *labels = Labels {
+                        config_source: Labels::config_origin(&origin.to_string()),
+                        ..labels.clone()
+                    };
+
+                    match body {
+                        Some(body) => {
                             *labels = Labels {
-                                config_body: Labels::config_body(&b),
+                                config_body: Labels::config_body(&body),
                                 ..labels.clone()
-                            }
-                        };
-
-                        match body {
-                            Some(body) => {
-                                let fmt = FileFormat::from_path(&file_path);
-                                RoozCfg::deserialize_config(&body, fmt)?
-                            }
-                            None => None,
+                            };
+                            let fmt = FileFormat::from_path(&origin.to_string())?;
+                            RoozCfg::deserialize_config(&body, fmt)?.map(|c| (c, origin))
                         }
+                        None => None,
                     }
-                },
+                }
             }
         } else {
             None
         };
 
-        Ok(val)
+        Ok(match val {
+            Some((cfg, origin)) => Some(self.resolve_extends(cfg, &origin, clone_env).await?),
+            None => None,
+        })
+    }
+
+    // returns the origin to record along with the config body. A git repo may have no config file
+    pub async fn read_config(
+        &self,
+        path: &ConfigPath,
+        clone_env: &CloneEnv,
+    ) -> Result<(ConfigPath, Option<String>), AnyError> {
+        Ok(match path {
+            ConfigPath::File { path } => (
+                ConfigPath::File {
+                    path: std::path::absolute(path)?.to_string_lossy().into_owned(),
+                },
+                Some(fs::read_to_string(path).context(path)?),
+            ),
+            ConfigPath::Git { url, file_path } => (
+                path.clone(),
+                self.git
+                    .clone_config_repo(clone_env.clone(), url, file_path)
+                    .await?,
+            ),
+            ConfigPath::Stdin { .. } => {
+                let mut body = String::new();
+                io::stdin().read_to_string(&mut body)?;
+                (path.clone(), Some(body))
+            }
+        })
+    }
+
+    async fn read_extended_config(
+        &self,
+        path: &ConfigPath,
+        clone_env: &CloneEnv,
+    ) -> Result<RoozCfg, AnyError> {
+        let format = FileFormat::from_path(&path.to_string())?;
+        if let ConfigPath::Stdin { .. } = path {
+            return Err(RoozError::Config("stdin can't be extended".into()).into());
+        }
+        let body = self
+            .read_config(path, clone_env)
+            .await?
+            .1
+            .ok_or(RoozError::Config(format!(
+                "extended config not found: {}",
+                path.to_string()
+            )))?;
+        Ok(RoozCfg::from_string(&body, format)
+            .map_err(|e| RoozError::Config(format!("{}: {}", path.to_string(), e)))?)
+    }
+
+    // depth-first: each extended config gets its own extends applied before it is merged
+    fn extend_chain<'b>(
+        &'b self,
+        config: RoozCfg,
+        origin: ConfigPath,
+        clone_env: &'b CloneEnv,
+        stack: Vec<String>,
+        resolved: &'b mut Vec<String>,
+    ) -> LocalBoxFuture<'b, Result<RoozCfg, AnyError>> {
+        async move {
+            let mut base: Option<RoozCfg> = None;
+            for entry in config.extends.iter().flatten() {
+                let path = origin.join(entry)?;
+                let id = path.to_string();
+                if stack.contains(&id) {
+                    return Err(RoozError::Config(format!(
+                        "circular extends: {} -> {}",
+                        stack.join(" -> "),
+                        id
+                    ))
+                    .into());
+                }
+                log::debug!("{} extends {}", origin.to_string(), id);
+                let parent = self.read_extended_config(&path, clone_env).await?;
+                let mut parent_stack = stack.clone();
+                parent_stack.push(id.clone());
+                let parent = self
+                    .extend_chain(parent, path, clone_env, parent_stack, resolved)
+                    .await?;
+                if !resolved.contains(&id) {
+                    resolved.push(id);
+                }
+                match &mut base {
+                    Some(b) => b.from_config(&parent),
+                    None => base = Some(parent),
+                }
+            }
+            Ok(match base {
+                Some(mut b) => {
+                    b.from_config(&config);
+                    b
+                }
+                None => config,
+            })
+        }
+        .boxed_local()
+    }
+
+    // the resolved chain replaces extends so it ends up in the runtime config
+    pub async fn resolve_extends(
+        &self,
+        config: RoozCfg,
+        origin: &ConfigPath,
+        clone_env: &CloneEnv,
+    ) -> Result<RoozCfg, AnyError> {
+        if config.extends.as_ref().is_none_or(|e| e.is_empty()) {
+            return Ok(config);
+        }
+        let mut resolved = Vec::new();
+        let mut cfg = self
+            .extend_chain(
+                config,
+                origin.clone(),
+                clone_env,
+                vec![origin.to_string()],
+                &mut resolved,
+            )
+            .await?;
+        cfg.extends = Some(resolved);
+        Ok(cfg)
+    }
+
+    async fn ensure_adoptable(&self, volumes_key: &str) -> Result<(), AnyError> {
+        let labels = Labels::new(Some(volumes_key), None);
+        if !self.api.container.get_all(&labels).await?.is_empty() {
+            return Err(format!(
+                "Volumes of '{}' are still in use by its workspace. Only orphaned volumes can be adopted.",
+                volumes_key
+            )
+            .into());
+        }
+
+        let ls_vol_options = ListVolumesOptions {
+            filters: (&labels).into(),
+        };
+        let volumes = self
+            .api
+            .client
+            .list_volumes(Some(ls_vol_options))
+            .await?
+            .volumes
+            .unwrap_or_default();
+
+        let adoptable = volumes.iter().any(|v| {
+            matches!(
+                v.labels.get(ROLE).map(|r| r.as_str()),
+                Some(HOME_ROLE) | Some(WORK_ROLE)
+            )
+        });
+
+        if !adoptable {
+            return Err(format!("No home or work volumes found for: {}", volumes_key).into());
+        }
+        log::debug!("Adopting volumes of: {}", volumes_key);
+        Ok(())
     }
 
     pub async fn new(
@@ -165,44 +672,59 @@ impl<'a> WorkspaceApi<'a> {
         cli_config_path: Option<ConfigSource>,
         ephemeral: bool,
         identity: &Identity,
+        volumes_key: Option<&str>,
     ) -> Result<EnterSpec, AnyError> {
-        let orig_uid = constants::DEFAULT_UID.to_string();
+        let step = progress::step(
+            self.api.progress,
+            format!("Creating workspace {}", workspace_key),
+        );
+        let volumes_key = volumes_key.filter(|k| *k != workspace_key);
+        if let Some(key) = volumes_key {
+            self.ensure_adoptable(key).await?;
+        }
 
         let mut labels = Labels {
             workspace: Labels::workspace(&workspace_key),
             role: Labels::role(labels::ROLE_WORK),
+            volumes: volumes_key.and_then(Labels::volumes),
             ..Default::default()
-        };
+        }
+        .with_project(cli_params.project.as_deref());
 
         self.api
             .image
-            .ensure(constants::DEFAULT_IMAGE, cli_params.pull_image)
+            .ensure(&self.api.image.helper, cli_params.pull.unwrap_or_default())
             .await?;
 
         let work_dir = constants::WORK_DIR;
-
-        let clone_env = CloneEnv {
-            uid: orig_uid.to_string(),
-            workspace_key: workspace_key.to_string(),
-            working_dir: work_dir.to_string(),
-            ..Default::default()
-        };
+        let mut clone_env = workspace_clone_env(workspace_key, volumes_key, cli_params)?;
+        let orig_uid = clone_env.uid.clone();
 
         let cli_cfg = self
-            .get_cli_config(cli_config_path, &clone_env, &mut labels)
+            .read_cli_config(cli_config_path, &mut clone_env, &mut labels, identity)
             .await?;
 
+        let git_ssh_url = RoozCfg::git_ssh_url(cli_params, &cli_cfg);
+        let labels = labels.with_repo(
+            git_ssh_url.as_deref().map(git::redact_url).as_deref(),
+            RoozCfg::clone_options(cli_params, &cli_cfg)
+                .branch
+                .as_deref()
+                .filter(|_| git_ssh_url.is_some()),
+        );
+
         let work_spec = WorkSpec {
             uid: &orig_uid,
             container_working_dir: &work_dir,
             container_name: &workspace_key,
             workspace_key: &workspace_key,
+            volumes_key,
             ephemeral,
             force_recreate: false,
             ..Default::default()
         };
 
-        let enter_spec = match &RoozCfg::git_ssh_url(cli_params, &cli_cfg) {
+        let enter_spec = match &git_ssh_url {
             None => {
                 let mut cfg_builder = RoozCfg::default().from_cli_env(cli_params.clone());
                 self.new_core(
@@ -223,63 +745,149 @@ impl<'a> WorkspaceApi<'a> {
                 .await
             }
 
-            Some(url) => match self.git.clone_root_repo(&url, &clone_env).await? {
-                root_repo_result => {
-                    let mut cfg_builder = RoozCfg::default().from_cli_env(cli_params.clone());
-                    match &root_repo_result.config {
-                        Some((body, format)) => match RoozCfg::deserialize_config(body, *format)? {
-                            Some(c) => {
-                                cfg_builder.from_config(&c);
-                                log::debug!("Config file applied.");
-                                let source = format!("{}//.rooz.{}", url, format.to_string());
-                                labels = Labels {
-                                    config_source: Labels::config_origin(&source),
-                                    config_body: Labels::config_body(&body),
-                                    ..labels
-                                };
-                            }
-                            None => {
-                                log::debug!("No valid config file found in the repository.");
-                            }
-                        },
-                        None => {
-                            log::debug!("No valid config file found in the repository.");
-                        }
-                    }
-
-                    self.new_core(
+            Some(url) => {
+                let clone_step = progress::step(
+                    self.api.progress,
+                    format!("Cloning {}", git::redact_url(url)),
+                );
+                let root_repo_result = self
+                    .git
+                    .clone_root_repo(
+                        &url,
+                        RoozCfg::clone_dir(cli_params, &cli_cfg).as_deref(),
+                        &RoozCfg::clone_options(cli_params, &cli_cfg),
+                        &clone_env,
+                    )
+                    .await?;
+                clone_step.done();
+                let mut cfg_builder = RoozCfg::default().from_cli_env(cli_params.clone());
+                let labels = self
+                    .apply_repo_config(
                         &mut cfg_builder,
-                        cli_cfg,
-                        cli_params,
-                        &WorkSpec {
-                            labels,
-                            ..work_spec
-                        },
+                        url,
+                        root_repo_result.config.as_ref(),
                         &clone_env,
-                        Some(root_repo_result),
-                        &workspace_key,
-                        false,
-                        work_dir,
-                        identity,
+                        labels,
                     )
-                    .await
-                }
-            },
+                    .await?;
+
+                self.new_core(
+                    &mut cfg_builder,
+                    cli_cfg,
+                    cli_params,
+                    &WorkSpec {
+                        labels,
+                        ..work_spec
+                    },
+                    &clone_env,
+                    Some(root_repo_result),
+                    &workspace_key,
+                    false,
+                    work_dir,
+                    identity,
+                )
+                .await
+            }
         };
+        if let (Ok(_), Some(engine), false) = (&enter_spec, self.api.container.engine, ephemeral) {
+            WorkspaceEngines::record(workspace_key, engine)?;
+        }
         if let Some(true) = cli_params.start {
             self.start(&workspace_key).await?;
         }
+        if enter_spec.is_ok() {
+            step.done();
+        }
         enter_spec
     }
 
-    pub async fn tmp(&self, spec: &WorkParams, root: bool, shell: &str) -> Result<(), AnyError> {
+    // rooz enter on a missing workspace falls back to the config of the current directory,
+    // like opening a folder with a devcontainer. Returns whether the workspace got created
+    pub async fn new_from_local(
+        &self,
+        workspace_key: &str,
+        create: bool,
+    ) -> Result<bool, AnyError> {
+        if !Path::new(LOCAL_CONFIG).is_file() {
+            return Ok(false);
+        }
+        let config = RoozCfg::from_string(&fs::read_to_string(LOCAL_CONFIG)?, FileFormat::Toml)?;
+        let remote = match config.git_ssh_url {
+            Some(_) => None,
+            None => local_remote(Path::new(".")),
+        };
+        if !create {
+            if self.config.non_interactive || !term::is_interactive() {
+                eprintln!(
+                    "Run with --create to create the workspace from ./{}",
+                    LOCAL_CONFIG
+                );
+                return Ok(false);
+            }
+            let question = format!(
+                "Workspace {} not found. Create it from ./{}{}?",
+                workspace_key,
+                LOCAL_CONFIG,
+                remote
+                    .as_deref()
+                    .map(|r| format!(" and {}", git::redact_url(r)))
+                    .unwrap_or_default()
+            );
+            if !term::confirm(&question)? {
+                return Ok(false);
+            }
+        }
+        let work = WorkParams {
+            git_ssh_url: remote,
+            ..Default::default()
+        };
+        let identity = self.crypt.read_age_identity().await?;
+        self.new(
+            workspace_key,
+            &work,
+            Some(ConfigSource::Path {
+                value: ConfigPath::from_str(LOCAL_CONFIG)?,
+            }),
+            false,
+            &identity,
+            None,
+        )
+        .await?;
+        Ok(true)
+    }
+
+    pub async fn tmp(
+        &self,
+        spec: &WorkParams,
+        cli_config_path: Option<ConfigSource>,
+        root: bool,
+        shell: &str,
+    ) -> Result<(), AnyError> {
+        let workspace_key = id::random_suffix("tmp");
+        let result = self
+            .tmp_core(&workspace_key, spec, cli_config_path, root, shell)
+            .await;
+        // sidecars, networks and volumes go too, also when creating or entering failed
+        let teardown = self.remove(&workspace_key, true, true).await;
+        result?;
+        Ok(teardown?)
+    }
+
+    async fn tmp_core(
+        &self,
+        workspace_key: &str,
+        spec: &WorkParams,
+        cli_config_path: Option<ConfigSource>,
+        root: bool,
+        shell: &str,
+    ) -> Result<(), AnyError> {
         let identity = self.crypt.read_age_identity().await?;
         let EnterSpec {
             workspace,
             git_spec,
             config,
         } = self
-            .new(&id::random_suffix("tmp"), spec, None, true, &identity)
+            .new(workspace_key, spec, cli_config_path, false, &identity, None)
             .await?;
 
         let working_dir = git_spec
@@ -291,16 +899,20 @@ impl<'a> WorkspaceApi<'a> {
             ..config
         });
 
-        self.enter(
-            &workspace.workspace_key,
-            working_dir.as_deref(),
-            Some(cfg.shell.iter().map(|v| v.as_str()).collect::<Vec<_>>()),
-            None,
-            workspace.volumes,
-            &workspace.orig_uid,
-            root,
-            true,
-        )
-        .await
+        Ok(self
+            .enter(
+                &workspace.workspace_key,
+                working_dir.as_deref(),
+                Some(cfg.shell.iter().map(|v| v.as_str()).collect::<Vec<_>>()),
+                None,
+                None,
+                workspace.volumes,
+                &workspace.orig_uid,
+                root,
+                false,
+                None,
+                ReconnectPolicy::default(),
+            )
+            .await?)
     }
 }
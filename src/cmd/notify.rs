@@ -0,0 +1,129 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use serde::Serialize;
+
+use crate::{
+    cli::{Commands, RemoveParams},
+    cmd::audit,
+    config::system::NotificationsCfg,
+    model::types::AnyError,
+    util::time::DateTime,
+};
+
+pub const CREATED: &str = "workspace.created";
+pub const UPDATED: &str = "workspace.updated";
+pub const REMOVED: &str = "workspace.removed";
+pub const ARCHIVED: &str = "workspace.archived";
+pub const UNARCHIVED: &str = "workspace.unarchived";
+pub const OOM: &str = "container.oom";
+pub const DIED: &str = "container.died";
+
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    pub time: String,
+    pub user: String,
+    pub host: String,
+    // a one-line summary. Slack's incoming webhooks post it as is
+    pub text: String,
+}
+
+impl Notification {
+    pub fn new(event: &str, workspace: Option<&str>, container: Option<&str>) -> Notification {
+        let user = audit::current_user();
+        let host = audit::current_host();
+        let subject = match (workspace, container) {
+            (Some(w), Some(c)) => format!(" {}/{}", w, c),
+            (Some(w), None) => format!(" {}", w),
+            _ => String::new(),
+        };
+        Notification {
+            event: event.into(),
+            workspace: workspace.map(|w| w.into()),
+            container: container.map(|c| c.into()),
+            time: DateTime::now().to_rfc3339(),
+            text: format!("[rooz] {}{} ({}@{})", event, subject, user, host),
+            user,
+            host,
+        }
+    }
+}
+
+// the events of the commands changing workspaces. They fire once the command succeeds
+pub fn event(command: &Commands) -> Option<(&'static str, Option<String>)> {
+    match command {
+        Commands::New(p) if !p.dry_run => Some((CREATED, p.name.clone())),
+        Commands::Update(p) if !p.check && !p.dry_run => Some((UPDATED, p.name.clone())),
+        Commands::Remove(RemoveParams { name, .. }) => Some((REMOVED, name.clone())),
+        Commands::Archive(p) => Some((ARCHIVED, Some(p.name.clone()))),
+        Commands::Unarchive(p) => Some((UNARCHIVED, Some(p.name.clone()))),
+        _ => None,
+    }
+}
+
+fn pipe(mut command: Command, payload: &str) -> Result<(), AnyError> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(payload.as_bytes())?;
+    }
+    match child.wait()? {
+        s if s.success() => Ok(()),
+        s => Err(format!("exited with {}", s).into()),
+    }
+}
+
+impl NotificationsCfg {
+    fn wants(&self, event: &str) -> bool {
+        self.events
+            .as_ref()
+            .is_none_or(|events| events.iter().any(|e| e == event))
+    }
+
+    // the webhook gets posted with curl so rooz needs no HTTP client. Failures only warn,
+    // a notification never fails the command
+    pub fn send(&self, notification: &Notification) {
+        if !self.wants(&notification.event) {
+            return;
+        }
+        let payload = match serde_json::to_string(notification) {
+            Ok(p) => p,
+            Err(e) => return log::warn!("Could not serialize the notification: {}", e),
+        };
+        if let Some(url) = &self.webhook {
+            let mut curl = Command::new("curl");
+            curl.args(["-fsS", "--max-time", "10", "-X", "POST"])
+                .args([
+                    "-H",
+                    "Content-Type: application/json",
+                    "--data-binary",
+                    "@-",
+                ])
+                .arg(url);
+            if let Err(e) = pipe(curl, &payload) {
+                log::warn!("Could not post the notification to the webhook: {}", e);
+            }
+        }
+        if let Some(hook) = &self.command {
+            let mut sh = Command::new("sh");
+            sh.args(["-c", hook])
+                .env("ROOZ_EVENT", &notification.event)
+                .env(
+                    "ROOZ_WORKSPACE",
+                    notification.workspace.as_deref().unwrap_or_default(),
+                );
+            if let Err(e) = pipe(sh, &payload) {
+                log::warn!("The notification command failed: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    api::WorkspaceApi,
+    cli::WorkParams,
+    config::runtime::RuntimeConfig,
+    constants,
+    model::{error::RoozError, types::AnyError},
+    util::{
+        git,
+        labels::{self, Labels},
+        term,
+    },
+};
+
+use super::new::local_remote;
+
+fn local_branch(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|b| output.status.success() && !b.is_empty() && b != "HEAD")
+}
+
+struct Candidate {
+    name: String,
+    branch: Option<String>,
+}
+
+impl<'a> WorkspaceApi<'a> {
+    // workspaces created before the repo label only have the url in their runtime config
+    async fn workspaces_of(&self, remote: &str) -> Result<Vec<Candidate>, AnyError> {
+        let wanted = git::normalize_url(remote);
+        let mut candidates = Vec::new();
+        for c in self
+            .api
+            .container
+            .get_all(&Labels::new(None, Some(labels::ROLE_WORK)))
+            .await?
+        {
+            let container_labels = c.labels.unwrap_or_default();
+            if container_labels.get(labels::CONTAINER).map(|n| n.as_str())
+                != Some(constants::DEFAULT_CONTAINER_NAME)
+            {
+                continue;
+            }
+            let Some(name) = container_labels.get(labels::WORKSPACE_KEY) else {
+                continue;
+            };
+            let repo = match container_labels.get(labels::REPO) {
+                Some(repo) => Some(repo.to_string()),
+                None => container_labels
+                    .get(labels::RUNTIME_CONFIG)
+                    .and_then(|cfg| RuntimeConfig::from_string(cfg.to_string()).ok())
+                    .and_then(|cfg| cfg.git_ssh_url),
+            };
+            if repo.is_some_and(|r| git::normalize_url(&r) == wanted) {
+                candidates.push(Candidate {
+                    name: name.to_string(),
+                    branch: container_labels.get(labels::BRANCH).cloned(),
+                });
+            }
+        }
+        candidates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(candidates)
+    }
+
+    fn pick_candidate(&self, candidates: &[Candidate], remote: &str) -> Result<String, AnyError> {
+        let names = candidates
+            .iter()
+            .map(|c| c.name.to_string())
+            .collect::<Vec<_>>();
+        if self.config.non_interactive || !term::is_interactive() {
+            return Err(RoozError::Interactive(format!(
+                "Picking one of the workspaces of {} ({}). Run rooz enter <name> instead",
+                git::redact_url(remote),
+                names.join(", ")
+            ))
+            .into());
+        }
+        match term::pick("Workspace:", &names)? {
+            Some(i) => Ok(names[i].to_string()),
+            None => Err("Aborted".into()),
+        }
+    }
+
+    // the workspace on the checked-out branch wins, e.g. one created with --name-from-branch
+    pub async fn open(&self, path: &str, name: Option<&str>) -> Result<String, AnyError> {
+        let dir = Path::new(path);
+        let remote = local_remote(dir).ok_or(format!(
+            "{} is not a git clone with an origin remote",
+            dir.display()
+        ))?;
+        let candidates = self.workspaces_of(&remote).await?;
+        let branch = local_branch(dir);
+        if let Some(c) = candidates
+            .iter()
+            .find(|c| branch.is_some() && c.branch == branch)
+        {
+            return Ok(c.name.to_string());
+        }
+        match candidates.as_slice() {
+            [] => (),
+            [c] => return Ok(c.name.to_string()),
+            _ => return self.pick_candidate(&candidates, &remote),
+        }
+
+        let workspace_key = name
+            .map(|n| n.to_string())
+            .unwrap_or_else(|| git::repo_name(&remote));
+        let existing = Labels::new(Some(&workspace_key), Some(labels::ROLE_WORK))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+        if self.api.container.get_single(&existing).await?.is_some() {
+            return Err(format!(
+                "Workspace {} already exists for another repo. Pass --name to create one for {}",
+                workspace_key,
+                git::redact_url(&remote)
+            )
+            .into());
+        }
+        println!(
+            "No workspace found for {}. Creating {}",
+            git::redact_url(&remote),
+            workspace_key
+        );
+        let work = WorkParams {
+            git_ssh_url: Some(remote),
+            ..Default::default()
+        };
+        let identity = self.crypt.read_age_identity().await?;
+        self.new(&workspace_key, &work, None, false, &identity, None)
+            .await?;
+        Ok(workspace_key)
+    }
+}
@@ -0,0 +1,349 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+use age::x25519::Identity;
+use bollard::models::Mount;
+use colored::Colorize;
+
+use crate::{
+    api::{
+        sidecar::{self, sidecar_instances},
+        workspace::{create::work_volumes, docker},
+        WorkspaceApi,
+    },
+    cli::WorkParams,
+    cmd::new::{container_spec, workspace_clone_env},
+    config::{
+        config::{ConfigSource, RoozCfg, RoozDockerMode, RoozDotfilesCfg},
+        runtime::RuntimeConfig,
+    },
+    constants,
+    model::{
+        types::{AnyError, RunSpec, WorkSpec},
+        volume::RoozVolume,
+    },
+    util::{git, labels::Labels},
+};
+
+// what the container engine would get asked to create
+struct PlannedContainer {
+    name: String,
+    image: String,
+    networks: Vec<String>,
+    ports: HashMap<String, Option<String>>,
+    mounts: Vec<Mount>,
+    env: Vec<String>,
+}
+
+impl From<&RunSpec<'_>> for PlannedContainer {
+    fn from(spec: &RunSpec) -> Self {
+        let mut env = spec
+            .env
+            .iter()
+            .flat_map(|e| e.keys().cloned())
+            .collect::<Vec<_>>();
+        env.sort();
+        PlannedContainer {
+            name: spec.container_name.to_string(),
+            image: spec.image.to_string(),
+            networks: spec
+                .network
+                .map(|n| n.to_string())
+                .into_iter()
+                .chain(spec.extra_networks.iter().flatten().cloned())
+                .collect(),
+            ports: spec.ports.clone().unwrap_or_default(),
+            mounts: spec.mounts.clone().unwrap_or_default(),
+            env,
+        }
+    }
+}
+
+impl PlannedContainer {
+    fn print(&self) {
+        println!("  {} ({})", self.name.bold(), self.image);
+        for network in &self.networks {
+            println!("    network: {}", network);
+        }
+        let mut ports = self
+            .ports
+            .iter()
+            .map(|(source, target)| match target {
+                Some(t) => match t.parse::<SocketAddr>() {
+                    Ok(addr) => format!("{}:{}:{}", addr.ip(), source, addr.port()),
+                    Err(_) => format!("{}:{}", source, t),
+                },
+                None => source.to_string(),
+            })
+            .collect::<Vec<_>>();
+        ports.sort();
+        for p in ports {
+            println!("    port: {}", p);
+        }
+        for m in &self.mounts {
+            println!(
+                "    mount: {} -> {}",
+                m.source.as_deref().unwrap_or_default(),
+                m.target.as_deref().unwrap_or_default()
+            );
+        }
+        for e in &self.env {
+            println!("    env: {}", e);
+        }
+    }
+}
+
+impl<'a> WorkspaceApi<'a> {
+    // the same builders as rooz new, minus anything that creates containers, volumes or networks.
+    // The repos only get cloned into throwaway containers to read their config files
+    pub async fn plan(
+        &self,
+        workspace_key: &str,
+        cli_params: &WorkParams,
+        cli_config_path: Option<ConfigSource>,
+        identity: &Identity,
+        volumes_key: Option<&str>,
+    ) -> Result<(), AnyError> {
+        let volumes_key = volumes_key.filter(|k| *k != workspace_key);
+        let mut notes = Vec::<String>::new();
+
+        let mut clone_env = workspace_clone_env(workspace_key, volumes_key, cli_params)?;
+        let cli_cfg = self
+            .read_cli_config(
+                cli_config_path,
+                &mut clone_env,
+                &mut Labels::default(),
+                identity,
+            )
+            .await?;
+
+        let git_ssh_url = RoozCfg::git_ssh_url(cli_params, &cli_cfg);
+        let clone_dir = RoozCfg::clone_dir(cli_params, &cli_cfg);
+        let mut cfg_builder = RoozCfg::default().from_cli_env(cli_params.clone());
+        if let Some(url) = &git_ssh_url {
+            if self.git.offline {
+                notes.push(
+                    "The .rooz config file of the git repository is not resolved in offline mode"
+                        .into(),
+                );
+            } else {
+                let config = self
+                    .git
+                    .read_root_config(
+                        url,
+                        clone_dir.as_deref(),
+                        &RoozCfg::clone_options(cli_params, &cli_cfg),
+                        &clone_env,
+                    )
+                    .await?;
+                self.apply_repo_config(
+                    &mut cfg_builder,
+                    url,
+                    config.as_ref(),
+                    &clone_env,
+                    Labels::default(),
+                )
+                .await?;
+            }
+        }
+
+        if let Some(c) = &cli_cfg {
+            cfg_builder.from_config(c);
+        }
+        cfg_builder.from_cli(cli_params, None);
+        if let Some(secrets) = cfg_builder.secrets.as_mut() {
+            for (_, v) in secrets.iter_mut() {
+                *v = "***".into();
+            }
+        }
+        cfg_builder.expand_vars()?;
+        let cfg = RuntimeConfig::from(&cfg_builder);
+
+        let network = match &cfg.network {
+            Some(n) => {
+                notes.push(format!("The external network {} must already exist", n));
+                Some(n.to_string())
+            }
+            None if sidecar::needs_network(&cfg) => Some(workspace_key.to_string()),
+            None => None,
+        };
+
+        let working_dir = match &git_ssh_url {
+            Some(url) => git::resolve_clone_dir(constants::WORK_DIR, url, clone_dir.as_deref())?,
+            None => constants::WORK_DIR.to_string(),
+        };
+        let work_spec = self.work_spec(
+            &cfg,
+            network.as_deref(),
+            &working_dir,
+            WorkSpec {
+                uid: &clone_env.uid,
+                container_name: workspace_key,
+                workspace_key,
+                volumes_key,
+                ..Default::default()
+            },
+        )?;
+
+        let mut container_names = cfg
+            .containers
+            .keys()
+            .map(|name| (name, format!("{}-{}", workspace_key, name)))
+            .collect::<Vec<_>>();
+        container_names.sort();
+        let work_specs = [work_spec.clone()]
+            .into_iter()
+            .chain(container_names.iter().map(|(name, container_name)| {
+                container_spec(
+                    &cfg,
+                    &work_spec,
+                    name,
+                    &cfg.containers[*name],
+                    container_name,
+                )
+            }))
+            .collect::<Vec<_>>();
+
+        let mut volumes = Vec::<RoozVolume>::new();
+        let mut containers = Vec::<PlannedContainer>::new();
+        for spec in &work_specs {
+            let (home_dir, work_volumes) = work_volumes(spec);
+            let mounts = work_volumes
+                .iter()
+                .map(|v| v.to_mount(Some(&home_dir)))
+                .collect();
+            containers.push((&self.work_run_spec(spec, &home_dir, mounts)).into());
+            volumes.extend(work_volumes);
+        }
+
+        let volumes_key = volumes_key.unwrap_or(workspace_key);
+        let mut instances = sidecar_instances(&cfg, workspace_key);
+        instances.sort_by(|a, b| a.name.cmp(&b.name));
+        for instance in &instances {
+            let sidecar_volumes = instance.volumes(workspace_key, volumes_key, constants::WORK_DIR);
+            containers.push(
+                (&RunSpec {
+                    mounts: Some(sidecar_volumes.iter().map(|v| v.to_mount(None)).collect()),
+                    ..self.sidecar_run_spec(
+                        &cfg,
+                        workspace_key,
+                        instance,
+                        network.as_deref(),
+                        constants::WORK_DIR,
+                    )
+                })
+                    .into(),
+            );
+            volumes.extend(sidecar_volumes);
+        }
+
+        let init_cfg = cfg.with_dotfiles_install();
+        let home_dir = cfg.home_dir();
+        let (_, work_volumes) = work_volumes(&work_spec);
+        let init_mounts = work_volumes
+            .iter()
+            .map(|v| v.to_mount(Some(&home_dir)))
+            .collect::<Vec<_>>();
+        let init_containers = init_cfg
+            .init_containers
+            .iter()
+            .map(|(name, init)| {
+                let container_name = format!("{}-init-{}", workspace_key, name);
+                (&RunSpec {
+                    mounts: Some(init_mounts.clone()),
+                    network: network.as_deref(),
+                    ..self.init_run_spec(
+                        &init_cfg,
+                        workspace_key,
+                        &clone_env.uid,
+                        &container_name,
+                        init,
+                        &home_dir,
+                    )
+                })
+                    .into()
+            })
+            .collect::<Vec<PlannedContainer>>();
+
+        let mut volume_names = volumes
+            .iter()
+            .map(|v| v.safe_volume_name())
+            .collect::<Vec<_>>();
+        volume_names.sort();
+        volume_names.dedup();
+
+        println!("{} {}", "Workspace:".bold(), workspace_key);
+        if let Some(url) = &git_ssh_url {
+            println!(
+                "{} {} -> {}",
+                "Repository:".bold(),
+                git::redact_url(url),
+                working_dir
+            );
+        }
+        for r in &cfg.extra_repos {
+            println!("{} {}", "Extra repository:".bold(), git::redact_url(r));
+        }
+        if let Some(r) = cfg.dotfiles.as_ref().and_then(|d| d.repo.as_deref()) {
+            println!(
+                "{} {} -> {}",
+                "Dotfiles repository:".bold(),
+                git::redact_url(r),
+                RoozDotfilesCfg::DIR
+            );
+        }
+        if cfg.home_from_image {
+            println!(
+                "{} /home/{} of {}",
+                "Home synced from:".bold(),
+                cfg.user,
+                cfg.image
+            );
+        }
+        if let Some(sync) = &cfg.sync {
+            println!(
+                "{} {} <-> {} (via {})",
+                "Synced with:".bold(),
+                sync.host_path()?,
+                constants::WORK_DIR,
+                sync.image()
+            );
+        }
+        match cfg.docker {
+            Some(RoozDockerMode::Dind) => println!(
+                "{} {}-{} ({}, privileged)",
+                "Docker daemon:".bold(),
+                workspace_key,
+                docker::DIND_NAME,
+                docker::DIND_IMAGE
+            ),
+            Some(RoozDockerMode::Socket) => {
+                println!("{} the container engine host socket", "Docker:".bold())
+            }
+            None => (),
+        }
+        if let Some(p) = &cfg.platform {
+            println!("{} {} (work containers)", "Platform:".bold(), p);
+        }
+        println!("{}", "Containers:".bold());
+        for c in &containers {
+            c.print();
+        }
+        if !init_containers.is_empty() {
+            println!("{}", "Init containers (in order):".bold());
+            for c in &init_containers {
+                c.print();
+            }
+        }
+        println!("{}", "Volumes:".bold());
+        for v in volume_names {
+            println!("  {}", v);
+        }
+        if let Some(n) = &network {
+            println!("{} {}", "Network:".bold(), n);
+        }
+        for n in notes {
+            println!("{}", n.yellow());
+        }
+        Ok(())
+    }
+}
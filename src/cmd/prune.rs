@@ -1,12 +1,29 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bollard::{
     container::ListContainersOptions,
+    network::ListNetworksOptions,
     service::ContainerSummary,
     volume::{ListVolumesOptions, RemoveVolumeOptions},
 };
+use colored::Colorize;
 
-use crate::{api::Api, model::types::AnyError, util::labels::Labels};
+use crate::{
+    api::Api,
+    model::{
+        types::AnyError,
+        volume::{DATA_ROLE, HOME_ROLE, VOLUME_ROLE, WORK_ROLE},
+    },
+    util::labels::{Labels, KEEP, ROLE, ROLE_SIDECAR, ROLE_WORK, VOLUMES_KEY, WORKSPACE_KEY},
+};
+
+fn report(kind: &str, name: &str, dry_run: bool) {
+    if dry_run {
+        println!("{} {} {}", "Would remove".yellow(), kind, name);
+    } else {
+        println!("{} {} {}", "Removing".red(), kind, name);
+    }
+}
 
 impl<'a> Api<'a> {
     async fn prune(
@@ -57,6 +74,87 @@ impl<'a> Api<'a> {
         Ok(())
     }
 
+    pub async fn gc(&self, dry_run: bool) -> Result<(), AnyError> {
+        let filters: HashMap<String, Vec<String>> = (&Labels::default()).into();
+        let containers = self
+            .client
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters: filters.clone(),
+                ..Default::default()
+            }))
+            .await?;
+
+        let mut in_use = HashSet::<String>::new();
+        for c in containers {
+            let labels = c.labels.unwrap_or_default();
+            let role = labels.get(ROLE).map(|r| r.as_str());
+            let running = c.state.as_deref() == Some("running");
+            if running || matches!(role, Some(ROLE_WORK) | Some(ROLE_SIDECAR)) {
+                in_use.extend(labels.get(WORKSPACE_KEY).cloned());
+                in_use.extend(labels.get(VOLUMES_KEY).cloned());
+                continue;
+            }
+            if let Some(id) = c.id {
+                let name = c
+                    .names
+                    .and_then(|n| n.first().cloned())
+                    .unwrap_or(id.to_string());
+                report("container", name.trim_start_matches('/'), dry_run);
+                if !dry_run {
+                    self.container.remove(&id, true).await?;
+                }
+            }
+        }
+
+        let orphaned = |labels: &HashMap<String, String>| match labels.get(WORKSPACE_KEY) {
+            Some(key) => !in_use.contains(key),
+            None => false,
+        };
+
+        for v in self
+            .client
+            .list_volumes(Some(ListVolumesOptions {
+                filters: filters.clone(),
+            }))
+            .await?
+            .volumes
+            .unwrap_or_default()
+        {
+            let exclusive = matches!(
+                v.labels.get(ROLE).map(|r| r.as_str()),
+                Some(HOME_ROLE) | Some(WORK_ROLE) | Some(DATA_ROLE) | Some(VOLUME_ROLE)
+            );
+            if exclusive && !v.labels.contains_key(KEEP) && orphaned(&v.labels) {
+                report("volume", &v.name, dry_run);
+                if !dry_run {
+                    self.client
+                        .remove_volume(&v.name, Some(RemoveVolumeOptions { force: false }))
+                        .await?;
+                }
+            }
+        }
+
+        for n in self
+            .client
+            .list_networks(Some(ListNetworksOptions {
+                filters: filters.clone(),
+            }))
+            .await?
+        {
+            if let (Some(name), Some(labels)) = (n.name, n.labels) {
+                if orphaned(&labels) {
+                    report("network", &name, dry_run);
+                    if !dry_run {
+                        self.client.remove_network(&name).await?;
+                    }
+                }
+            }
+        }
+        log::debug!("Gc success");
+        Ok(())
+    }
+
     pub async fn prune_system(&self) -> Result<(), AnyError> {
         let labels = Labels::default();
         self.prune((&labels).into(), true).await
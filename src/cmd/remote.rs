@@ -1,29 +1,197 @@
 use bollard::{
     container::ListContainersOptions,
-    models::{Port, PortTypeEnum},
+    errors::Error,
+    models::{EventMessage, Port, PortTypeEnum},
     Docker,
 };
 
+use futures::{channel::mpsc, future::join_all, Stream, StreamExt};
 use openssh::{ForwardType, KnownHosts, Session, SessionBuilder};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
-    fs,
-    net::{Ipv4Addr, TcpListener},
-    path::Path,
-    sync::{
-        mpsc::{self, Sender},
-        Mutex,
-    },
-    time::Duration,
+    collections::{hash_map::Entry, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::{self, Command, Stdio},
+    time::{Duration, Instant},
 };
+use tokio::time::{interval, sleep};
 
-use crate::{model::types::AnyError, util::labels};
+use crate::{
+    api::events,
+    model::types::AnyError,
+    util::{labels, time::DateTime},
+};
+
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+pub struct DaemonSpec {
+    pub pid_file: Option<String>,
+    pub log_file: Option<String>,
+}
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+const TLS_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum HealthCheck {
+    Tcp,
+    Tls,
+    None,
+}
+
+// narrows down the auto-tunnelled ports, adds fixed local:remote tunnels and sets how tunnels get checked
+pub struct TunnelSpec {
+    only: Vec<String>,
+    exclude_ports: Vec<(u16, u16)>,
+    maps: Vec<(u16, u16)>,
+    checks: HashMap<u16, HealthCheck>,
+}
+
+fn parse_port(value: &str) -> Result<u16, AnyError> {
+    value
+        .trim()
+        .parse::<u16>()
+        .map_err(|_| format!("Invalid port: {}", value).into())
+}
+
+impl TunnelSpec {
+    pub fn parse(
+        only: &[String],
+        exclude_ports: &[String],
+        maps: &[String],
+        checks: &[String],
+    ) -> Result<Self, AnyError> {
+        let exclude_ports = exclude_ports
+            .iter()
+            .map(|p| match p.split_once('-') {
+                Some((from, to)) => Ok((parse_port(from)?, parse_port(to)?)),
+                None => parse_port(p).map(|p| (p, p)),
+            })
+            .collect::<Result<Vec<_>, AnyError>>()?;
+        let maps = maps
+            .iter()
+            .map(|m| match m.split_once(':') {
+                Some((local, remote)) => Ok((parse_port(local)?, parse_port(remote)?)),
+                None => Err(format!("Invalid port mapping: {}. Use local:remote", m).into()),
+            })
+            .collect::<Result<Vec<_>, AnyError>>()?;
+        let checks = checks
+            .iter()
+            .map(|c| match c.split_once('=') {
+                Some((port, "tcp")) => Ok((parse_port(port)?, HealthCheck::Tcp)),
+                Some((port, "tls")) => Ok((parse_port(port)?, HealthCheck::Tls)),
+                Some((port, "none")) => Ok((parse_port(port)?, HealthCheck::None)),
+                _ => Err(format!("Invalid health check: {}. Use port=tcp|tls|none", c).into()),
+            })
+            .collect::<Result<HashMap<_, _>, AnyError>>()?;
+        Ok(TunnelSpec {
+            only: only.to_vec(),
+            exclude_ports,
+            maps,
+            checks,
+        })
+    }
+
+    fn check(&self, local_port: u16) -> HealthCheck {
+        self.checks
+            .get(&local_port)
+            .copied()
+            .unwrap_or(HealthCheck::Tcp)
+    }
+
+    fn includes_workspace(&self, workspace_key: Option<&String>) -> bool {
+        self.only.is_empty() || workspace_key.is_some_and(|k| self.only.contains(k))
+    }
+
+    fn excludes_port(&self, port: u16) -> bool {
+        self.exclude_ports
+            .iter()
+            .any(|(from, to)| (*from..=*to).contains(&port))
+    }
+
+    // remote ports with a manual mapping are never auto-tunnelled
+    fn is_mapped(&self, remote_port: u16) -> bool {
+        self.maps.iter().any(|(_, r)| *r == remote_port)
+    }
+}
+
+async fn forward(session: &Session, local_port: u16, remote_port: u16) -> Result<(), AnyError> {
+    session
+        .request_port_forward(
+            ForwardType::Local,
+            (Ipv4Addr::new(127, 0, 0, 1), local_port),
+            (Ipv4Addr::new(127, 0, 0, 1), remote_port),
+        )
+        .await?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct TunnelStatus {
+    local: u16,
+    remote: u16,
+    check: HealthCheck,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    healthy: Option<bool>,
+}
+
+// written next to the local socket on every reconcile so `rooz remote status` can report on the tunnel
+#[derive(Serialize, Deserialize)]
+struct RemoteStatus {
+    pid: u32,
+    ssh_url: String,
+    socket: String,
+    connected: bool,
+    updated: String,
+    tunnels: Vec<TunnelStatus>,
+}
+
+fn socket_path(local_docker_host: &str) -> PathBuf {
+    let re = Regex::new(r"^unix://").unwrap();
+    PathBuf::from(shellexpand::tilde(&re.replace(local_docker_host, "")).into_owned())
+}
+
+fn status_path(local_socket_path: &Path) -> PathBuf {
+    local_socket_path.with_extension("json")
+}
+
+// prints to the terminal or, when running as a daemon, appends timestamped lines to the log file
+struct Output {
+    log: Option<File>,
+}
+
+impl Output {
+    fn line(&self, message: &str) {
+        match &self.log {
+            Some(f) => {
+                let mut f: &File = f;
+                writeln!(f, "{} {}", DateTime::now().to_rfc3339(), message).ok();
+            }
+            None => println!("{}", message),
+        }
+    }
+
+    fn error(&self, message: &str) {
+        match &self.log {
+            Some(_) => self.line(message),
+            None => eprintln!("{}", message),
+        }
+    }
+}
 
 async fn connect(
     builder: &SessionBuilder,
     ssh_url: &str,
     local_socket_path: &Path,
+    out: &Output,
 ) -> Result<Session, AnyError> {
     if local_socket_path.exists() {
         fs::remove_file(local_socket_path)?;
@@ -31,7 +199,7 @@ async fn connect(
 
     let session = builder.connect_mux(&ssh_url).await?;
 
-    println!("SSH: connected to {}", &ssh_url);
+    out.line(&format!("SSH: connected to {}", &ssh_url));
 
     let socket_url = String::from_utf8(
         session
@@ -44,7 +212,10 @@ async fn connect(
     )?;
 
     if socket_url.is_empty() {
-        panic!("Env var DOCKER_HOST is not set on the remote host. Can't get docker.socket path.")
+        return Err(
+            "Env var DOCKER_HOST is not set on the remote host. Can't get docker.socket path."
+                .into(),
+        );
     }
 
     log::debug!(
@@ -58,37 +229,261 @@ async fn connect(
         .request_port_forward(ForwardType::Local, local_socket_path, remote_socket)
         .await?;
 
-    println!(
+    out.line(&format!(
         "Forwarding: {} -> {}:{}",
         local_socket_path.display(),
         &ssh_url,
         &remote_socket.display()
-    );
-    println!(
+    ));
+    out.line(&format!(
         "Run 'export DOCKER_HOST=unix://{}' to make the socket useful for local tools",
         local_socket_path.display()
-    );
+    ));
     Ok(session)
 }
 
-pub async fn remote(ssh_url: &str, local_docker_host: &str) -> Result<(), AnyError> {
-    let (sender, receiver) = mpsc::channel::<()>();
+fn subscribe(docker: &Docker) -> Pin<Box<impl Stream<Item = Result<EventMessage, Error>>>> {
+    let mut filters: HashMap<String, Vec<String>> = (&labels::Labels::default()).into();
+    filters.insert("type".into(), vec!["container".into()]);
+    filters.insert("event".into(), vec!["start".into(), "die".into()]);
+    events::subscribe(docker, filters)
+}
 
-    let tx_mutex = Mutex::<Option<Sender<()>>>::new(Some(sender));
+async fn manage_tunnels(
+    session: &Session,
+    docker: &Docker,
+    tunnels: &mut HashMap<u16, u16>,
+    spec: &TunnelSpec,
+    out: &Output,
+) -> Result<(), AnyError> {
+    for (local_port, remote_port) in &spec.maps {
+        if let Entry::Vacant(tunnel) = tunnels.entry(*remote_port) {
+            forward(session, *local_port, *remote_port).await?;
+            out.line(&format!(
+                "Forwarding: 127.0.0.1:{} -> 127.0.0.1:{} (mapped)",
+                local_port, remote_port
+            ));
+            tunnel.insert(*local_port);
+        }
+    }
 
-    ctrlc::set_handler(move || {
-        if let Some(tx) = tx_mutex.lock().unwrap().take() {
-            tx.send(()).unwrap();
+    let containers = match docker
+        .list_containers(Some(ListContainersOptions {
+            filters: (&labels::Labels::default()).into(),
+            ..Default::default()
+        }))
+        .await
+    {
+        Ok(data) => data,
+        Err(e) => {
+            log::debug!("{}", e);
+            vec![]
         }
-    })?;
+    };
 
-    let re = Regex::new(r"^unix://").unwrap();
-    let expanded_socket = shellexpand::tilde(&re.replace(&local_docker_host, "")).into_owned();
-    let local_socket_path = Path::new(&expanded_socket);
+    let containers = containers.iter().filter(|c| {
+        spec.includes_workspace(c.labels.as_ref().and_then(|l| l.get(labels::WORKSPACE_KEY)))
+    });
 
-    if let Some(path) = local_socket_path.parent() {
-        fs::create_dir_all(path)?;
+    for (name, ports) in containers.map(|c| {
+        let names = c
+            .names
+            .as_ref()
+            .map(|n| n.concat())
+            .unwrap_or(c.id.as_ref().unwrap().to_string());
+        let ports = c.clone().ports.unwrap_or(Vec::<_>::new());
+        (names.to_string(), ports)
+    }) {
+        for Port {
+            ip,
+            private_port,
+            public_port,
+            typ,
+        } in ports
+        {
+            let public_port = public_port.unwrap_or(private_port);
+            log::debug!(
+                "{} {} {} {} {}",
+                name,
+                ip.unwrap_or_default(),
+                private_port,
+                public_port,
+                typ.unwrap_or(PortTypeEnum::EMPTY)
+            );
+
+            // SSH only forwards TCP
+            if typ == Some(PortTypeEnum::UDP) {
+                log::debug!("Skipping UDP port {} ({})", public_port, name);
+                continue;
+            }
+            if spec.is_mapped(public_port)
+                || spec.excludes_port(private_port)
+                || spec.excludes_port(public_port)
+            {
+                continue;
+            }
+
+            let listen_socket = format!("127.0.0.1:{}", private_port);
+            let connect_socket = format!("127.0.0.1:{}", public_port);
+
+            if let Entry::Vacant(tunnel) = tunnels.entry(public_port) {
+                if is_available(&private_port) {
+                    forward(session, private_port, public_port).await?;
+                    out.line(&format!(
+                        "Forwarding: {} -> {} ({})",
+                        listen_socket, connect_socket, name
+                    ));
+                } else {
+                    out.line(&format!(
+                        "Already bound, so maybe forwarding: {} -> {} ({})",
+                        listen_socket, connect_socket, name
+                    ));
+                }
+                tunnel.insert(private_port);
+            }
+        }
+    }
+    Ok(())
+}
+
+// SSH accepts connections on the local port even when nothing listens on the remote one,
+// but then closes them right away. A service that is up either waits for the client or greets it
+fn probe_tcp(port: u16) -> bool {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&address, PROBE_TIMEOUT) else {
+        return false;
+    };
+    stream.set_read_timeout(Some(PROBE_TIMEOUT)).ok();
+    match stream.read(&mut [0u8; 1]) {
+        Ok(0) => false,
+        Ok(_) => true,
+        Err(e) => matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut),
     }
+}
+
+// openssl completes the handshake without verifying the certificate, dev services mostly have self-signed ones.
+// Without openssl the tunnel is not checked
+fn probe_tls(port: u16) -> Option<bool> {
+    let child = Command::new("openssl")
+        .args([
+            "s_client",
+            "-brief",
+            "-connect",
+            &format!("127.0.0.1:{}", port),
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            log::warn!(
+                "Could not run openssl to check the tunnel on {}: {}",
+                port,
+                e
+            );
+            return None;
+        }
+    };
+    let deadline = Instant::now() + TLS_PROBE_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status.success()),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            _ => {
+                child.kill().ok();
+                child.wait().ok();
+                return Some(false);
+            }
+        }
+    }
+}
+
+async fn check_tunnels(tunnels: &HashMap<u16, u16>, spec: &TunnelSpec) -> Vec<TunnelStatus> {
+    let checks = tunnels.iter().map(|(remote, local)| {
+        let (remote, local, check) = (*remote, *local, spec.check(*local));
+        async move {
+            let healthy = match check {
+                HealthCheck::Tcp => tokio::task::spawn_blocking(move || probe_tcp(local))
+                    .await
+                    .ok(),
+                HealthCheck::Tls => tokio::task::spawn_blocking(move || probe_tls(local))
+                    .await
+                    .ok()
+                    .flatten(),
+                HealthCheck::None => None,
+            };
+            TunnelStatus {
+                local,
+                remote,
+                check,
+                healthy,
+            }
+        }
+    });
+    let mut statuses = join_all(checks).await;
+    statuses.sort_by_key(|s| s.local);
+    statuses
+}
+
+fn report_health(statuses: &[TunnelStatus], last: &mut HashMap<u16, bool>, out: &Output) {
+    for s in statuses {
+        let Some(healthy) = s.healthy else {
+            continue;
+        };
+        if last.insert(s.local, healthy) != Some(healthy) {
+            out.line(&format!(
+                "Tunnel 127.0.0.1:{} -> 127.0.0.1:{} is {}",
+                s.local,
+                s.remote,
+                if healthy { "UP" } else { "DOWN" }
+            ));
+        }
+    }
+}
+
+// only informs rooz remote status, so failing to write it must not stop the tunnel
+fn write_status(
+    path: &Path,
+    ssh_url: &str,
+    local_socket_path: &Path,
+    connected: bool,
+    tunnels: Vec<TunnelStatus>,
+    out: &Output,
+) {
+    let status = RemoteStatus {
+        pid: process::id(),
+        ssh_url: ssh_url.to_string(),
+        socket: local_socket_path.to_string_lossy().into_owned(),
+        connected,
+        updated: DateTime::now().to_rfc3339(),
+        tunnels,
+    };
+    let result = serde_json::to_string_pretty(&status)
+        .map_err(AnyError::from)
+        .and_then(|body| Ok(fs::write(path, body)?));
+    if let Err(e) = result {
+        out.error(&format!(
+            "Could not write the status to {}: {}",
+            path.display(),
+            e
+        ));
+    }
+}
+
+async fn serve(
+    ssh_url: &str,
+    local_socket_path: &Path,
+    spec: &TunnelSpec,
+    out: &Output,
+) -> Result<(), AnyError> {
+    let (sender, mut receiver) = mpsc::unbounded::<()>();
+
+    // also catches SIGTERM and SIGHUP so it stops cleanly under systemd or launchd
+    ctrlc::set_handler(move || {
+        sender.unbounded_send(()).ok();
+    })?;
 
     let mut builder = SessionBuilder::default();
     builder
@@ -96,102 +491,206 @@ pub async fn remote(ssh_url: &str, local_docker_host: &str) -> Result<(), AnyErr
         .connect_timeout(Duration::from_secs(5))
         .server_alive_interval(Duration::from_secs(5));
 
-    let mut session = connect(&builder, ssh_url, local_socket_path).await?;
+    let status_file = status_path(local_socket_path);
+    let mut session = connect(&builder, ssh_url, local_socket_path, out).await?;
     let docker = Docker::connect_with_local_defaults()?.with_timeout(Duration::from_secs(10));
-    let mut tunnels = HashSet::<u16>::new();
+    let mut tunnels = HashMap::<u16, u16>::new();
+    let mut health = HashMap::<u16, bool>::new();
+    let mut events = subscribe(&docker);
+    let mut reconcile = interval(RECONCILE_INTERVAL);
 
     loop {
-        match session.check().await {
-            Ok(_) => (),
-            Err(_) => {
-                eprintln!("SSH connection lost. Reconnecting...");
-                match connect(&builder, ssh_url, local_socket_path).await {
-                    Ok(s) => session = s,
-                    Err(error) => {
-                        eprintln!("ERROR: {}", error);
-                        if let Some(()) = receiver.recv_timeout(Duration::from_secs(3)).ok() {
-                            break;
+        tokio::select! {
+            _ = receiver.next() => break,
+            _ = reconcile.tick() => {
+                if session.check().await.is_err() {
+                    out.error("SSH connection lost. Reconnecting...");
+                    write_status(&status_file, ssh_url, local_socket_path, false, Vec::new(), out);
+                    match connect(&builder, ssh_url, local_socket_path, out).await {
+                        // the forwards died with the previous session. Forgetting them makes manage_tunnels
+                        // below forward every port again, auto-tunnelled and --map ones alike
+                        Ok(s) => {
+                            session = s;
+                            tunnels.clear();
+                            health.clear();
+                            events = subscribe(&docker);
+                        }
+                        Err(error) => {
+                            out.error(&format!("ERROR: {}", error));
+                            reconcile.reset_after(RECONNECT_DELAY);
+                            continue;
                         }
-                        continue;
                     }
                 }
             }
-        }
-
-        let containers = match docker
-            .list_containers(Some(ListContainersOptions {
-                filters: (&labels::Labels::default()).into(),
-                ..Default::default()
-            }))
-            .await
-        {
-            Ok(data) => data,
-            Err(e) => {
-                log::debug!("{}", e);
-                vec![]
-            }
-        };
-
-        for (name, ports) in containers.iter().map(|c| {
-            let names = c
-                .names
-                .as_ref()
-                .map(|n| n.concat())
-                .unwrap_or(c.id.as_ref().unwrap().to_string());
-            let ports = c.clone().ports.unwrap_or(Vec::<_>::new());
-            (names.to_string(), ports)
-        }) {
-            for Port {
-                ip,
-                private_port,
-                public_port,
-                typ,
-            } in ports
-            {
-                let public_port = public_port.unwrap_or(private_port);
-                log::debug!(
-                    "{} {} {} {} {}",
-                    name,
-                    ip.unwrap_or_default(),
-                    private_port,
-                    public_port,
-                    typ.unwrap_or(PortTypeEnum::EMPTY)
-                );
-
-                let listen_socket = format!("127.0.0.1:{}", private_port);
-                let connect_socket = format!("127.0.0.1:{}", public_port);
-
-                if !tunnels.contains(&public_port) {
-                    if is_available(&private_port) {
-                        session
-                            .request_port_forward(
-                                ForwardType::Local,
-                                (Ipv4Addr::new(127, 0, 0, 1), private_port),
-                                (Ipv4Addr::new(127, 0, 0, 1), public_port),
-                            )
-                            .await?;
-                        println!(
-                            "Forwarding: {} -> {} ({})",
-                            listen_socket, connect_socket, name
-                        );
-                        tunnels.insert(public_port);
-                    } else {
-                        println!(
-                            "Already bound, so maybe forwarding: {} -> {} ({})",
-                            listen_socket, connect_socket, name
-                        );
-                        tunnels.insert(public_port);
-                    }
+            event = events.next() => match event {
+                Some(Ok(EventMessage { action, actor, .. })) => {
+                    log::debug!(
+                        "Container event: {} {}",
+                        action.unwrap_or_default(),
+                        actor.and_then(|a| a.id).unwrap_or_default()
+                    );
+                }
+                Some(Err(e)) => {
+                    log::debug!("Events stream error: {}", e);
+                    sleep(RECONNECT_DELAY).await;
+                    events = subscribe(&docker);
+                    reconcile.reset_immediately();
+                    continue;
+                }
+                None => {
+                    log::debug!("Events stream ended. Resubscribing...");
+                    events = subscribe(&docker);
+                    continue;
                 }
             }
         }
-        if let Some(()) = receiver.recv_timeout(Duration::from_secs(10)).ok() {
-            break;
-        }
+
+        manage_tunnels(&session, &docker, &mut tunnels, spec, out).await?;
+        let statuses = check_tunnels(&tunnels, spec).await;
+        report_health(&statuses, &mut health, out);
+        write_status(
+            &status_file,
+            ssh_url,
+            local_socket_path,
+            true,
+            statuses,
+            out,
+        );
     }
     //TODO: store and close port forwards here
     session.close().await?;
-    std::process::exit(0);
+    Ok(())
+}
+
+pub async fn remote(
+    ssh_url: &str,
+    local_docker_host: &str,
+    daemon: Option<DaemonSpec>,
+    spec: TunnelSpec,
+) -> Result<(), AnyError> {
+    let local_socket_path = socket_path(local_docker_host);
+
+    if let Some(path) = local_socket_path.parent() {
+        fs::create_dir_all(path)?;
+    }
+
+    let pid_file = daemon
+        .as_ref()
+        .and_then(|d| d.pid_file.as_ref())
+        .map(PathBuf::from)
+        .unwrap_or(local_socket_path.with_extension("pid"));
+    let _lock = lock_pid_file(&pid_file, &local_socket_path)?;
+    let log = match daemon.as_ref().and_then(|d| d.log_file.as_ref()) {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+    let out = Output { log };
+
+    let result = serve(ssh_url, &local_socket_path, &spec, &out).await;
+    // in the foreground the error gets reported on exit as for any other command
+    if let (Err(e), Some(_)) = (&result, &out.log) {
+        out.error(&format!("ERROR: {}", e));
+    }
+
+    // the forwarded socket is left dangling otherwise and a stale status would claim it is up
+    for path in [
+        local_socket_path.clone(),
+        status_path(&local_socket_path),
+        pid_file,
+    ] {
+        if path.exists() {
+            fs::remove_file(&path).ok();
+        }
+    }
+    result?;
+    process::exit(0);
+}
+
+// signal 0 only checks the process exists. EPERM means it does but belongs to another user
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// held for as long as the tunnel runs so a second one for the same socket can't start.
+// The kernel releases it when the process exits, even if it gets killed
+fn lock_pid_file(path: &Path, local_socket_path: &Path) -> Result<File, AnyError> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let error = std::io::Error::last_os_error();
+        if error.kind() != ErrorKind::WouldBlock {
+            return Err(format!("Could not lock {}: {}", path.display(), error).into());
+        }
+        let mut pid = String::new();
+        file.read_to_string(&mut pid).ok();
+        return Err(format!(
+            "rooz remote is already running for {} (pid {})",
+            local_socket_path.display(),
+            pid.trim()
+        )
+        .into());
+    }
+    file.set_len(0)?;
+    file.write_all(format!("{}\n", process::id()).as_bytes())?;
+    Ok(file)
+}
+
+pub fn status(local_docker_host: &str) -> Result<(), AnyError> {
+    let local_socket_path = socket_path(local_docker_host);
+    let status = match fs::read_to_string(status_path(&local_socket_path)) {
+        Ok(body) => serde_json::from_str::<RemoteStatus>(&body)?,
+        Err(_) => {
+            return Err(format!(
+                "rooz remote is not running for {}",
+                local_socket_path.display()
+            )
+            .into())
+        }
+    };
+    if !is_alive(status.pid) {
+        return Err(format!(
+            "rooz remote (pid {}) is not running anymore. The status is stale",
+            status.pid
+        )
+        .into());
+    }
+    println!("pid:       {}", status.pid);
+    println!("remote:    {}", status.ssh_url);
+    println!("socket:    {}", status.socket);
+    println!(
+        "state:     {}",
+        if status.connected {
+            "connected"
+        } else {
+            "reconnecting"
+        }
+    );
+    println!("updated:   {}", status.updated);
+    for t in &status.tunnels {
+        println!(
+            "tunnel:    127.0.0.1:{} -> 127.0.0.1:{} {}",
+            t.local,
+            t.remote,
+            match t.healthy {
+                Some(true) => "UP",
+                Some(false) => "DOWN",
+                None => "(not checked)",
+            }
+        );
+    }
+    if !status.connected {
+        return Err("rooz remote is not connected".into());
+    }
+    Ok(())
 }
 
 fn is_available(port: &u16) -> bool {
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+
+use bollard::{
+    models::{Mount, MountTypeEnum},
+    volume::{CreateVolumeOptions, ListVolumesOptions},
+};
+use colored::Colorize;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{
+    api::{container, WorkspaceApi},
+    constants,
+    model::{
+        error::RoozError,
+        types::{AnyError, RunSpec},
+        volume::RoozVolume,
+    },
+    util::{
+        id::{self, to_safe_id},
+        labels::{Labels, ROLE_WORK, VOLUMES_KEY, WORKSPACE_KEY},
+        time::DateTime,
+    },
+};
+
+const SNAPSHOT_ROLE: &str = "snapshot";
+const SNAPSHOT_NAME: &str = "dev.rooz.snapshot.name";
+const SNAPSHOT_PARTS: &str = "dev.rooz.snapshot.parts";
+const SNAPSHOT_DIR: &str = "/tmp/rooz-snapshot";
+const DATA_DIR: &str = "/tmp/rooz-data";
+const IGNORE_FILE: &str = ".roozignore";
+
+// GNU tar reads the ignore files itself. .roozignore uses the .gitignore syntax
+fn exclusions(all: bool) -> String {
+    match all {
+        true => String::new(),
+        false => format!(
+            " --exclude-vcs-ignores --exclude-ignore-recursive={}",
+            IGNORE_FILE
+        ),
+    }
+}
+
+#[derive(Debug, Tabled)]
+struct SnapshotView {
+    #[tabled(rename = "SNAPSHOT")]
+    name: String,
+    #[tabled(rename = "WORKSPACE")]
+    workspace: String,
+    #[tabled(rename = "PARTS")]
+    parts: String,
+    #[tabled(rename = "CREATED")]
+    created: String,
+}
+
+pub enum SnapshotTarget<'a> {
+    Volume { name: Option<&'a str> },
+    File { path: &'a str },
+}
+
+pub fn volume_name(workspace_key: &str, name: &str) -> String {
+    format!(
+        "rooz_{}_{}_{}",
+        to_safe_id(workspace_key),
+        SNAPSHOT_ROLE,
+        to_safe_id(name)
+    )
+}
+
+pub fn mount(typ: MountTypeEnum, source: &str, target: &str) -> Mount {
+    Mount {
+        typ: Some(typ),
+        source: Some(source.to_string()),
+        target: Some(target.to_string()),
+        read_only: Some(false),
+        ..Default::default()
+    }
+}
+
+// a file target gets bind-mounted via its directory so it is a path on the container engine host
+fn target_mount(target: &SnapshotTarget, volume: &str) -> Result<(Mount, String), AnyError> {
+    match target {
+        SnapshotTarget::Volume { .. } => Ok((
+            mount(MountTypeEnum::VOLUME, volume, SNAPSHOT_DIR),
+            SNAPSHOT_DIR.to_string(),
+        )),
+        SnapshotTarget::File { path } => {
+            let path = std::path::absolute(path)?;
+            let dir = path
+                .parent()
+                .ok_or(format!("Invalid snapshot file: {}", path.display()))?;
+            let file = path
+                .file_name()
+                .ok_or(format!("Invalid snapshot file: {}", path.display()))?;
+            Ok((
+                mount(MountTypeEnum::BIND, &dir.to_string_lossy(), SNAPSHOT_DIR),
+                format!("{}/{}", SNAPSHOT_DIR, file.to_string_lossy()),
+            ))
+        }
+    }
+}
+
+impl<'a> WorkspaceApi<'a> {
+    async fn snapshot_parts(
+        &self,
+        workspace_key: &str,
+        home: bool,
+    ) -> Result<Vec<(&'static str, String)>, AnyError> {
+        let container = self
+            .api
+            .container
+            .get_single(
+                &Labels::new(Some(workspace_key), Some(ROLE_WORK))
+                    .with_container(Some(constants::DEFAULT_CONTAINER_NAME)),
+            )
+            .await?
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
+        let volumes_key = container
+            .labels
+            .and_then(|l| l.get(VOLUMES_KEY).cloned())
+            .unwrap_or(workspace_key.to_string());
+
+        let mut parts = vec![(
+            "work",
+            RoozVolume::work(&volumes_key, "").safe_volume_name(),
+        )];
+        if home {
+            parts.push((
+                "home",
+                RoozVolume::home(&volumes_key, "").safe_volume_name(),
+            ));
+        }
+        Ok(parts)
+    }
+
+    pub async fn run_snapshot_script(
+        &self,
+        reason: &str,
+        workspace_key: &str,
+        image: &str,
+        mounts: Vec<Mount>,
+        env: Option<HashMap<String, String>>,
+        script: &str,
+    ) -> Result<(), AnyError> {
+        let entrypoint = container::inject(script, "snapshot.sh");
+        let run_spec = RunSpec {
+            reason,
+            image,
+            uid: constants::ROOT_UID,
+            container_name: &id::random_suffix("rooz-snapshot"),
+            workspace_key,
+            mounts: Some(mounts),
+            entrypoint: Some(entrypoint.iter().map(String::as_str).collect()),
+            labels: Labels::new(Some(workspace_key), Some(SNAPSHOT_ROLE)),
+            env,
+            ..Default::default()
+        };
+
+        let id = self.api.container.create(run_spec).await?.id().to_string();
+        self.api.container.start(&id).await?;
+        self.api.container.logs_to_stdout(&id).await?;
+        let exit_code = self
+            .api
+            .client
+            .inspect_container(&id, None)
+            .await?
+            .state
+            .and_then(|s| s.exit_code)
+            .unwrap_or_default();
+        self.api.container.remove(&id, true).await?;
+        if exit_code != 0 {
+            return Err(RoozError::ExitCode(exit_code).into());
+        }
+        Ok(())
+    }
+
+    pub async fn create_snapshot_volume(
+        &self,
+        workspace_key: &str,
+        name: &str,
+        part_names: &str,
+    ) -> Result<String, AnyError> {
+        let volume = volume_name(workspace_key, name);
+        if self.api.client.inspect_volume(&volume).await.is_ok() {
+            return Err(format!("Snapshot already exists: {}", name).into());
+        }
+        let labels = Labels::new(Some(workspace_key), Some(SNAPSHOT_ROLE));
+        let mut labels: HashMap<&str, &str> = (&labels).into();
+        labels.insert(SNAPSHOT_NAME, name);
+        labels.insert(SNAPSHOT_PARTS, part_names);
+        self.api
+            .client
+            .create_volume(CreateVolumeOptions {
+                name: volume.as_str(),
+                labels,
+                ..Default::default()
+            })
+            .await?;
+        Ok(volume)
+    }
+
+    pub async fn snapshot_create(
+        &self,
+        workspace_key: &str,
+        target: SnapshotTarget<'_>,
+        home: bool,
+        all: bool,
+    ) -> Result<(), AnyError> {
+        let parts = self.snapshot_parts(workspace_key, home).await?;
+        let name = match target {
+            SnapshotTarget::Volume { name: Some(n) } => n.to_string(),
+            _ => DateTime::now().to_compact(),
+        };
+        let volume = volume_name(workspace_key, &name);
+
+        if let SnapshotTarget::Volume { .. } = target {
+            let part_names = parts.iter().map(|(p, _)| *p).collect::<Vec<_>>().join(",");
+            self.create_snapshot_volume(workspace_key, &name, &part_names)
+                .await?;
+        }
+
+        let (target_mount, archive) = target_mount(&target, &volume)?;
+        let mut mounts = vec![target_mount];
+        let mut dirs = Vec::new();
+        for (part, source) in &parts {
+            let dir = format!("{}/{}", DATA_DIR, part);
+            mounts.push(mount(MountTypeEnum::VOLUME, source, &dir));
+            dirs.push(part.to_string());
+        }
+        let script = match target {
+            SnapshotTarget::Volume { .. } => dirs
+                .iter()
+                .map(|d| {
+                    format!(
+                        "tar -czpf {}/{}.tar.gz --numeric-owner{} -C {}/{} .",
+                        SNAPSHOT_DIR,
+                        d,
+                        exclusions(all),
+                        DATA_DIR,
+                        d
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" && "),
+            SnapshotTarget::File { .. } => format!(
+                "tar -czpf {} --numeric-owner{} -C {} {}",
+                archive,
+                exclusions(all),
+                DATA_DIR,
+                dirs.join(" ")
+            ),
+        };
+
+        println!("Creating snapshot of {} ...", workspace_key);
+        self.stop(workspace_key).await?;
+        if let Err(e) = self
+            .run_snapshot_script(
+                "snapshot-create",
+                workspace_key,
+                &self.api.image.helper,
+                mounts,
+                None,
+                &script,
+            )
+            .await
+        {
+            if let SnapshotTarget::Volume { .. } = target {
+                self.api.volume.remove_volume(&volume, true).await?;
+            }
+            return Err(e);
+        }
+        match target {
+            SnapshotTarget::Volume { .. } => println!("Created snapshot: {}", name.bold()),
+            SnapshotTarget::File { path } => println!("Created snapshot: {}", path.bold()),
+        };
+        Ok(())
+    }
+
+    pub async fn snapshot_restore(
+        &self,
+        workspace_key: &str,
+        target: SnapshotTarget<'_>,
+    ) -> Result<(), AnyError> {
+        let volume = match target {
+            SnapshotTarget::Volume { name: Some(n) } => {
+                let volume = volume_name(workspace_key, n);
+                self.api
+                    .client
+                    .inspect_volume(&volume)
+                    .await
+                    .map_err(|_| format!("Snapshot not found: {}", n))?;
+                volume
+            }
+            SnapshotTarget::Volume { name: None } => {
+                return Err("Specify the snapshot to restore".into())
+            }
+            SnapshotTarget::File { .. } => String::new(),
+        };
+
+        let (target_mount, archive) = target_mount(&target, &volume)?;
+        let mut mounts = vec![target_mount];
+        let mut script = vec!["set -e".to_string()];
+        for (part, source) in self.snapshot_parts(workspace_key, true).await? {
+            let dir = format!("{}/{}", DATA_DIR, part);
+            mounts.push(mount(MountTypeEnum::VOLUME, &source, &dir));
+            let (contains, extract) = match target {
+                SnapshotTarget::Volume { .. } => (
+                    format!("[ -f {}/{}.tar.gz ]", SNAPSHOT_DIR, part),
+                    format!("tar -xzpf {}/{}.tar.gz -C {}", SNAPSHOT_DIR, part, dir),
+                ),
+                SnapshotTarget::File { .. } => (
+                    format!("tar -tzf {} {} > /dev/null 2>&1", archive, part),
+                    format!("tar -xzpf {} -C {} {}", archive, DATA_DIR, part),
+                ),
+            };
+            script.push(format!(
+                "if {}; then find {} -mindepth 1 -delete && {}; echo 'Restored {}'; fi",
+                contains, dir, extract, part
+            ));
+        }
+
+        println!("Restoring {} ...", workspace_key);
+        self.stop(workspace_key).await?;
+        self.run_snapshot_script(
+            "snapshot-restore",
+            workspace_key,
+            &self.api.image.helper,
+            mounts,
+            None,
+            &script.join("\n"),
+        )
+        .await?;
+        println!("Restored {}", workspace_key.bold());
+        Ok(())
+    }
+
+    pub async fn snapshot_list(&self, workspace_key: Option<&str>) -> Result<(), AnyError> {
+        let labels = Labels::new(workspace_key, Some(SNAPSHOT_ROLE));
+        let mut volumes = self
+            .api
+            .client
+            .list_volumes(Some(ListVolumesOptions {
+                filters: (&labels).into(),
+            }))
+            .await?
+            .volumes
+            .unwrap_or_default();
+        volumes.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+        let label =
+            |v: &HashMap<String, String>, key: &str| v.get(key).cloned().unwrap_or_default();
+        let views = volumes
+            .into_iter()
+            .map(|v| SnapshotView {
+                name: label(&v.labels, SNAPSHOT_NAME),
+                workspace: label(&v.labels, WORKSPACE_KEY),
+                parts: label(&v.labels, SNAPSHOT_PARTS),
+                created: v.created_at.unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+
+        println!("{}", Table::new(views).with(Style::blank()));
+        Ok(())
+    }
+
+    pub async fn snapshot_remove(&self, workspace_key: &str, name: &str) -> Result<(), AnyError> {
+        let volume = volume_name(workspace_key, name);
+        self.api
+            .client
+            .inspect_volume(&volume)
+            .await
+            .map_err(|_| format!("Snapshot not found: {}", name))?;
+        self.api.volume.remove_volume(&volume, false).await?;
+        println!("Removed snapshot: {}", name);
+        Ok(())
+    }
+}
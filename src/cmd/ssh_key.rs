@@ -0,0 +1,152 @@
+use std::fs;
+
+use base64::{engine::general_purpose, Engine as _};
+use colored::Colorize;
+
+use crate::{
+    api::CryptApi,
+    cli::SecretsKey,
+    config::config::RoozPullPolicy,
+    model::{error::RoozError, types::AnyError, volume::RoozVolumeRole},
+    util::{id, ssh, term},
+};
+
+const SSH_DIR: &str = "/tmp/.ssh";
+
+impl<'a> CryptApi<'a> {
+    async fn run_on_ssh_volume(&self, name: &str, script: &str) -> Result<(), AnyError> {
+        let image_id = self
+            .api
+            .image
+            .ensure(&self.api.image.helper, RoozPullPolicy::Missing)
+            .await?;
+        self.api
+            .execute_init(
+                &id::random_suffix(name),
+                script,
+                ssh::VOLUME_NAME,
+                SSH_DIR,
+                &image_id,
+            )
+            .await
+    }
+
+    async fn ensure_ssh_volume(&self) -> Result<(), AnyError> {
+        if self
+            .api
+            .client
+            .inspect_volume(ssh::VOLUME_NAME)
+            .await
+            .is_err()
+        {
+            return Err("There is no SSH key yet. Run 'rooz system init' first".into());
+        }
+        Ok(())
+    }
+
+    // the key is all git servers and, with --secrets-key ssh, the secrets know rooz by
+    fn replace_warning(&self) -> String {
+        let mut warning =
+            "The current SSH key stops working wherever it was added (e.g. GitHub).".to_string();
+        if self.key == SecretsKey::Ssh {
+            warning.push_str(" Secrets encrypted with the age identity derived from it can't be decrypted anymore.");
+        }
+        warning
+    }
+
+    pub async fn ssh_show_key(&self) -> Result<(), AnyError> {
+        self.ensure_ssh_volume().await?;
+        let keyfile = format!("{}/{}", SSH_DIR, ssh::PRIVATE_KEY);
+        self.run_on_ssh_volume(
+            "rooz-ssh-show",
+            &format!(
+                "cat '{keyfile}.pub' 2>/dev/null || ssh-keygen -y -f '{keyfile}'",
+                keyfile = keyfile
+            ),
+        )
+        .await
+    }
+
+    pub async fn ssh_regenerate(&self, uid: &str, yes: bool) -> Result<(), AnyError> {
+        self.ensure_ssh_volume().await?;
+        if self.non_interactive && !yes {
+            return Err(
+                RoozError::Interactive("Confirming the regeneration (use --yes)".into()).into(),
+            );
+        }
+        if !yes
+            && !term::confirm(
+                &format!("{} Continue?", self.replace_warning())
+                    .yellow()
+                    .to_string(),
+            )?
+        {
+            return Ok(());
+        }
+        let hostname = self
+            .api
+            .client
+            .info()
+            .await?
+            .name
+            .unwrap_or("unknown".into());
+        let script = format!(
+            r#"KEYFILE={dir}/{key}
+               rm -f "$KEYFILE" "$KEYFILE.pub"
+               ssh-keygen -q -t ed25519 -N '' -f "$KEYFILE" -C rooz@{hostname}
+               cat "$KEYFILE.pub"
+               chmod 400 "$KEYFILE" && chown -R {uid} {dir}
+            "#,
+            dir = SSH_DIR,
+            key = ssh::PRIVATE_KEY,
+            hostname = hostname,
+            uid = uid
+        );
+        self.run_on_ssh_volume("rooz-ssh-regenerate", &script).await
+    }
+
+    // the public key gets derived from the private one, so a missing .pub file doesn't matter
+    pub async fn ssh_import(&self, uid: &str, path: &str, force: bool) -> Result<(), AnyError> {
+        let private_key = fs::read_to_string(path)?;
+        // rooz clones with it and may derive the age identity from it, both need ed25519
+        ssh::age_identity(&private_key)?;
+
+        let exists = self
+            .api
+            .client
+            .inspect_volume(ssh::VOLUME_NAME)
+            .await
+            .is_ok();
+        if exists && !force {
+            return Err("An SSH key already exists. Use --force to replace it".into());
+        }
+        if exists {
+            eprintln!("{}", self.replace_warning().yellow());
+        }
+        self.api
+            .volume
+            .ensure_volume(
+                ssh::VOLUME_NAME,
+                &RoozVolumeRole::SshKey,
+                Some("ssh-key".into()),
+                false,
+            )
+            .await?;
+        let script = format!(
+            r#"KEYFILE={dir}/{key}
+               mkdir -p {dir}
+               rm -f "$KEYFILE" "$KEYFILE.pub"
+               echo '{content}' | base64 -d > "$KEYFILE"
+               chmod 400 "$KEYFILE"
+               ssh-keygen -y -f "$KEYFILE" > "$KEYFILE.pub"
+               cat "$KEYFILE.pub"
+               chown -R {uid} {dir}
+            "#,
+            dir = SSH_DIR,
+            key = ssh::PRIVATE_KEY,
+            content = general_purpose::STANDARD.encode(&private_key),
+            uid = uid
+        );
+        self.run_on_ssh_volume("rooz-ssh-import", &script).await
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use bollard::{
+    container::{MemoryStatsStats, Stats, StatsOptions},
+    service::ContainerSummary,
+};
+use futures::{stream, StreamExt};
+use tabled::{settings::Style, Table, Tabled};
+use tokio::time::sleep;
+
+use crate::{
+    api::Api,
+    constants,
+    model::types::AnyError,
+    util::{
+        labels::{Labels, CONTAINER, WORKSPACE_KEY},
+        size, term,
+    },
+};
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Tabled)]
+struct StatsView {
+    #[tabled(rename = "WORKSPACE")]
+    workspace: String,
+    #[tabled(rename = "CONTAINER")]
+    container: String,
+    #[tabled(rename = "CPU %")]
+    cpu: String,
+    #[tabled(rename = "MEM USAGE / LIMIT")]
+    memory: String,
+    #[tabled(rename = "MEM %")]
+    memory_percent: String,
+    #[tabled(rename = "NET I/O")]
+    network: String,
+    #[tabled(rename = "BLOCK I/O")]
+    block: String,
+}
+
+fn cpu_percent(stats: &Stats) -> f64 {
+    let cpu_delta = stats.cpu_stats.cpu_usage.total_usage as f64
+        - stats.precpu_stats.cpu_usage.total_usage as f64;
+    let system_delta = stats.cpu_stats.system_cpu_usage.unwrap_or_default() as f64
+        - stats.precpu_stats.system_cpu_usage.unwrap_or_default() as f64;
+    let cpus = stats.cpu_stats.online_cpus.unwrap_or(1) as f64;
+    if cpu_delta > 0.0 && system_delta > 0.0 {
+        cpu_delta / system_delta * cpus * 100.0
+    } else {
+        0.0
+    }
+}
+
+// the same as docker stats: the page cache is not counted as used memory
+fn memory_usage(stats: &Stats) -> u64 {
+    let usage = stats.memory_stats.usage.unwrap_or_default();
+    let cache = match stats.memory_stats.stats {
+        Some(MemoryStatsStats::V1(s)) => s.total_inactive_file,
+        Some(MemoryStatsStats::V2(s)) => s.inactive_file,
+        None => 0,
+    };
+    usage.saturating_sub(cache)
+}
+
+fn io(read: u64, write: u64) -> String {
+    format!(
+        "{} / {}",
+        size::human(read as i64),
+        size::human(write as i64)
+    )
+}
+
+fn to_view(workspace: String, container: String, stats: &Stats) -> StatsView {
+    let memory = memory_usage(stats);
+    let limit = stats.memory_stats.limit.unwrap_or_default();
+    let (rx, tx) = stats
+        .networks
+        .iter()
+        .flatten()
+        .fold((0, 0), |(rx, tx), (_, n)| {
+            (rx + n.rx_bytes, tx + n.tx_bytes)
+        });
+    let (read, write) = stats
+        .blkio_stats
+        .io_service_bytes_recursive
+        .iter()
+        .flatten()
+        .fold((0, 0), |(read, write), e| {
+            match e.op.to_ascii_lowercase().as_str() {
+                "read" => (read + e.value, write),
+                "write" => (read, write + e.value),
+                _ => (read, write),
+            }
+        });
+    StatsView {
+        workspace,
+        container,
+        cpu: format!("{:.2}%", cpu_percent(stats)),
+        memory: io(memory, limit),
+        memory_percent: if limit > 0 {
+            format!("{:.2}%", memory as f64 / limit as f64 * 100.0)
+        } else {
+            "-".into()
+        },
+        network: io(rx, tx),
+        block: io(read, write),
+    }
+}
+
+impl<'a> Api<'a> {
+    async fn stats_views(&self, workspace: Option<&str>) -> Result<Vec<StatsView>, AnyError> {
+        let containers = self
+            .container
+            .get_running(&Labels::new(workspace, None))
+            .await?;
+
+        // a single sample takes about a second as the engine needs two cycles to compute CPU usage
+        let samples = stream::iter(containers)
+            .map(|c| async move {
+                let ContainerSummary {
+                    id: Some(id),
+                    labels: Some(labels),
+                    ..
+                } = c
+                else {
+                    return None;
+                };
+                let options = StatsOptions {
+                    stream: false,
+                    one_shot: false,
+                };
+                let stats = self.client.stats(&id, Some(options)).next().await?;
+                match stats {
+                    Ok(stats) => Some(to_view(
+                        labels.get(WORKSPACE_KEY).cloned().unwrap_or_default(),
+                        labels.get(CONTAINER).cloned().unwrap_or_default(),
+                        &stats,
+                    )),
+                    Err(e) => {
+                        log::debug!("Could not get the stats of {}: {}", id, e);
+                        None
+                    }
+                }
+            })
+            .buffer_unordered(constants::MAX_PARALLEL)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut views = samples.into_iter().flatten().collect::<Vec<_>>();
+        views.sort_by(|a, b| (&a.workspace, &a.container).cmp(&(&b.workspace, &b.container)));
+        Ok(views)
+    }
+
+    pub async fn stats(&self, workspace: Option<&str>, watch: bool) -> Result<(), AnyError> {
+        loop {
+            let views = self.stats_views(workspace).await?;
+            let mut table = Table::new(views);
+            table.with(Style::blank());
+            if watch {
+                term::clear();
+            }
+            println!("{}", table);
+            if !watch {
+                return Ok(());
+            }
+            sleep(WATCH_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use bollard::service::ContainerSummary;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{
+    api::WorkspaceApi,
+    model::{error::RoozError, types::AnyError},
+    util::{
+        id::to_safe_id,
+        labels::{Labels, CONTAINER, ROLE, ROLE_SIDECAR, ROLE_WORK},
+    },
+};
+
+#[derive(Debug, Tabled)]
+struct StatusView {
+    #[tabled(rename = "CONTAINER")]
+    name: String,
+    #[tabled(rename = "ROLE")]
+    role: String,
+    #[tabled(rename = "STATE")]
+    state: String,
+    #[tabled(rename = "STATUS")]
+    status: String,
+}
+
+impl StatusView {
+    // the health is part of the status text, so no extra inspect call per container is needed
+    fn short(&self) -> String {
+        let health = ["healthy", "unhealthy", "starting"]
+            .into_iter()
+            .find(|h| self.status.contains(&format!("({})", h)))
+            .or(self
+                .status
+                .contains("(health: starting)")
+                .then_some("starting"));
+        format!("{}:{}", self.name, health.unwrap_or(&self.state))
+    }
+}
+
+// prompts and status bars call this every few seconds so the summary gets cached per user
+fn cache_path(workspace_key: &str) -> PathBuf {
+    env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or(env::temp_dir())
+        .join(format!("rooz-status-{}", to_safe_id(workspace_key)))
+}
+
+fn read_cache(path: &PathBuf, ttl: Duration) -> Option<String> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let age = SystemTime::now().duration_since(modified).ok()?;
+    if age >= ttl {
+        return None;
+    }
+    fs::read_to_string(path).ok()
+}
+
+fn view(c: ContainerSummary) -> StatusView {
+    let labels = c.labels.unwrap_or_default();
+    StatusView {
+        name: labels.get(CONTAINER).cloned().unwrap_or_default(),
+        role: labels.get(ROLE).cloned().unwrap_or_default(),
+        state: c.state.unwrap_or_default(),
+        status: c.status.unwrap_or_default(),
+    }
+}
+
+impl<'a> WorkspaceApi<'a> {
+    async fn status_views(&self, workspace_key: &str) -> Result<Vec<StatusView>, AnyError> {
+        let mut views = self
+            .api
+            .container
+            .get_all(&Labels::new(Some(workspace_key), None))
+            .await?
+            .into_iter()
+            .map(view)
+            .filter(|v| v.role == ROLE_WORK || v.role == ROLE_SIDECAR)
+            .collect::<Vec<_>>();
+        if views.is_empty() {
+            return Err(RoozError::WorkspaceNotFound(workspace_key.to_string()).into());
+        }
+        // the work containers go first
+        views.sort_by(|a, b| (a.role != ROLE_WORK, &a.name).cmp(&(b.role != ROLE_WORK, &b.name)));
+        Ok(views)
+    }
+
+    pub async fn status(
+        &self,
+        workspace_key: &str,
+        short: bool,
+        cache_ttl: u64,
+    ) -> Result<(), AnyError> {
+        if !short {
+            let views = self.status_views(workspace_key).await?;
+            println!("{}", Table::new(views).with(Style::blank()));
+            return Ok(());
+        }
+
+        let path = cache_path(workspace_key);
+        if let Some(line) = read_cache(&path, Duration::from_secs(cache_ttl)) {
+            print!("{}", line);
+            return Ok(());
+        }
+        let line = format!(
+            "{}\n",
+            self.status_views(workspace_key)
+                .await?
+                .iter()
+                .map(StatusView::short)
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        if cache_ttl > 0 {
+            if let Err(e) = fs::write(&path, &line) {
+                log::debug!("Could not cache the status of {}: {}", workspace_key, e);
+            }
+        }
+        print!("{}", line);
+        Ok(())
+    }
+}
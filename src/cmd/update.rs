@@ -1,60 +1,305 @@
+use std::collections::HashSet;
+
+use age::x25519::Identity;
+use bollard::service::ContainerSummary;
+use colored::Colorize;
+
 use crate::{
     api::WorkspaceApi,
     cli::{WorkEnvParams, WorkParams},
-    config::config::{ConfigPath, ConfigSource, FileFormat, RoozCfg},
-    model::{types::AnyError, volume::WORK_ROLE},
+    cmd::config::store::StoredConfig,
+    config::{
+        config::{ConfigPath, ConfigSource, FileFormat, RoozCfg, RoozPullPolicy},
+        diff,
+        runtime::RuntimeConfig,
+    },
+    constants,
+    model::{error::RoozError, types::AnyError, volume::WORK_ROLE},
     util::{
         git::CloneEnv,
-        labels::{self, Labels},
+        labels::{self, LabelFilter, Labels, CONTAINER, ROLE, ROLE_SIDECAR, ROLE_WORK},
     },
 };
 
+const REPLACED_SUFFIX: &str = "-replaced";
+
+// a container being re-created, kept under another name until its successor got created
+struct SetAside {
+    id: String,
+    name: String,
+    container: Option<String>,
+    running: bool,
+}
+
+// where the config to apply comes from
+pub enum UpdateConfig {
+    Stored,
+    // the stored body as is, even if it came from a git repo
+    Keep,
+    Tweak,
+    Replace(ConfigPath),
+}
+
+// rooz update --all, --project and --filter
+pub struct UpdateBatch {
+    pub no_pull: bool,
+    pub dry_run: bool,
+    pub reset_home: bool,
+    pub only: Vec<String>,
+    pub continue_on_error: bool,
+}
+
+const FILTER_KEYS: [&str; 3] = ["origin", "repo", "branch"];
+
 pub enum UpdateMode {
-    Apply,
-    Purge,
+    // only re-creates the containers matching the targets unless empty
+    Apply { reset_home: bool, only: Vec<String> },
+    Purge { force: bool },
+}
+
+// replicas match by their sidecar name
+fn is_target(targets: &[String], name: &str, role: &str) -> bool {
+    targets.iter().any(|t| {
+        name == t
+            || (t == "sidecars" && role == ROLE_SIDECAR)
+            || (role == ROLE_SIDECAR
+                && name
+                    .strip_prefix(&format!("{}-", t))
+                    .is_some_and(|i| i.parse::<u32>().is_ok()))
+    })
 }
 
 impl<'a> WorkspaceApi<'a> {
+    async fn show_changes(
+        &self,
+        workspace_key: &str,
+        stored: &StoredConfig,
+        config: &RoozCfg,
+        origin: &str,
+        params: &WorkParams,
+        identity: &Identity,
+    ) -> Result<(), AnyError> {
+        let clone_env = CloneEnv {
+            workspace_key: workspace_key.to_string(),
+            ..Default::default()
+        };
+        let resolved = self
+            .resolve_extends(config.clone(), &ConfigPath::from_str(origin)?, &clone_env)
+            .await?;
+        let mut cfg_builder = RoozCfg::default().from_cli_env(params.clone());
+        cfg_builder.from_config(&resolved);
+        cfg_builder.from_cli(params, None);
+        self.config.decrypt(&mut cfg_builder, identity).await?;
+        cfg_builder.expand_vars()?;
+        let new = RuntimeConfig::from(&cfg_builder);
+
+        // the stored runtime config has the secret values the labels only have masked
+        let old = match &stored.runtime {
+            Some(runtime) => Some(RuntimeConfig::from_string(runtime.to_string())?),
+            None => self.runtime_config(workspace_key).await?,
+        };
+        let old = match old {
+            Some(c) => c,
+            None => {
+                println!(
+                    "{}",
+                    "The workspace has no stored runtime config. Showing the full config".yellow()
+                );
+                RuntimeConfig::default()
+            }
+        };
+
+        let stored_body =
+            RoozCfg::from_string(&stored.body, FileFormat::from_path(&stored.origin)?)?;
+        // the new secrets got registered by the decryption above, the previous ones only are in the stored body
+        self.config.register_secrets(&stored_body, identity).await?;
+
+        println!("{} {}", "Changes to".bold(), workspace_key.bold());
+        diff::print(&diff::runtime_config(&old, &new, true)?);
+        Ok(())
+    }
+
+    // no targets means all of them. The containers being re-created get stopped and renamed, which frees
+    // their names and ports. Staging the new ones under other names instead would bake those into their env and labels
+    async fn set_aside(
+        &self,
+        workspace_key: &str,
+        before: &[ContainerSummary],
+        targets: &[String],
+    ) -> Result<Vec<SetAside>, AnyError> {
+        let mut set_aside = Vec::new();
+        for c in before {
+            let labels = c.labels.clone().unwrap_or_default();
+            let (Some(id), Some(name)) = (&c.id, c.names.as_ref().and_then(|n| n.first())) else {
+                continue;
+            };
+            let (container, role) = (labels.get(CONTAINER), labels.get(ROLE));
+            if !targets.is_empty() {
+                let (Some(container), Some(role)) = (container, role) else {
+                    continue;
+                };
+                if !(role == ROLE_WORK || role == ROLE_SIDECAR)
+                    || !is_target(targets, container, role)
+                {
+                    continue;
+                }
+            }
+            let container = SetAside {
+                id: id.to_string(),
+                name: name.trim_start_matches('/').to_string(),
+                container: container.cloned(),
+                running: c.state.as_deref() == Some("running"),
+            };
+            let result = match container.running {
+                true => self.api.container.kill(id).await,
+                false => Ok(()),
+            };
+            let result = match result {
+                Ok(()) => {
+                    self.api
+                        .container
+                        .rename(id, &format!("{}{}", container.name, REPLACED_SUFFIX))
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                set_aside.push(container);
+                self.roll_back(workspace_key, before, &set_aside).await;
+                return Err(e.into());
+            }
+            set_aside.push(container);
+        }
+        Ok(set_aside)
+    }
+
+    // removes what the failed apply created and puts the set aside containers back
+    async fn restore_set_aside(
+        &self,
+        workspace_key: &str,
+        before: &[ContainerSummary],
+        set_aside: &[SetAside],
+    ) -> Result<(), AnyError> {
+        let existed = before
+            .iter()
+            .filter_map(|c| c.id.as_deref())
+            .collect::<HashSet<_>>();
+        for c in self
+            .api
+            .container
+            .get_all(&Labels::new(Some(workspace_key), None))
+            .await?
+        {
+            if let Some(id) = c.id.as_deref().filter(|id| !existed.contains(id)) {
+                self.api.container.remove(id, true).await?;
+            }
+        }
+        for c in set_aside {
+            let current = self.api.client.inspect_container(&c.id, None).await?.name;
+            if current.as_deref().map(|n| n.trim_start_matches('/')) != Some(c.name.as_str()) {
+                self.api.container.rename(&c.id, &c.name).await?;
+            }
+            if c.running {
+                self.api.container.start(&c.id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn roll_back(
+        &self,
+        workspace_key: &str,
+        before: &[ContainerSummary],
+        set_aside: &[SetAside],
+    ) {
+        match self
+            .restore_set_aside(workspace_key, before, set_aside)
+            .await
+        {
+            Ok(()) => eprintln!("{}", "Rolled back to the previous containers".yellow()),
+            Err(e) => eprintln!(
+                "{}",
+                format!("Could not roll back to the previous containers: {}", e).red()
+            ),
+        }
+    }
+
+    // the kept containers are running so the re-created ones have to join them
+    async fn start_stopped(&self, workspace_key: &str) -> Result<(), AnyError> {
+        for c in self
+            .api
+            .container
+            .get_all(&Labels::new(Some(workspace_key), None))
+            .await?
+        {
+            let role = c.labels.as_ref().and_then(|l| l.get(ROLE));
+            let is_member = role.is_some_and(|r| r == ROLE_WORK || r == ROLE_SIDECAR);
+            if let (true, Some(id), false) =
+                (is_member, &c.id, c.state.as_deref() == Some("running"))
+            {
+                self.api.container.start(id).await?;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn update(
         &self,
         workspace_key: &str,
         spec: &WorkEnvParams,
-        interactive: bool,
+        config: UpdateConfig,
         mode: UpdateMode,
         no_pull: bool,
+        dry_run: bool,
     ) -> Result<(), AnyError> {
-        let labels = Labels::new(Some(workspace_key), Some(WORK_ROLE));
+        let labels = Labels::new(Some(workspace_key), Some(WORK_ROLE))
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
 
         let container = self
             .api
             .container
             .get_single(&labels)
             .await?
-            .ok_or(format!("Workspace not found: {}", &workspace_key))?;
-
-        match mode {
-            UpdateMode::Apply => self.remove_containers_only(&workspace_key, true).await?,
-            UpdateMode::Purge => self.remove(&workspace_key, true).await?,
-        };
+            .ok_or(RoozError::WorkspaceNotFound(workspace_key.to_string()))?;
 
         let identity = self.crypt.read_age_identity().await?;
 
         if let Some(labels) = &container.labels {
-            let config_source = &labels[labels::CONFIG_ORIGIN];
-            let format = FileFormat::from_path(config_source);
-            let mut original_body = labels[labels::CONFIG_BODY].clone();
+            let Some(stored) = self.config.load(workspace_key, labels, &identity).await? else {
+                return Err(RoozError::Config(format!(
+                    "{} was created without a config so there is nothing to update it from",
+                    workspace_key
+                ))
+                .into());
+            };
+            let stored_format = FileFormat::from_path(&stored.origin)?;
+            let stored_body = stored.body.clone();
+            let mut config_source = stored.origin.clone();
+            let mut original_body = stored_body.clone();
+            let clone_env = CloneEnv {
+                workspace_key: workspace_key.to_string(),
+                use_volume: false,
+                depth_override: Some(1),
+                ..Default::default()
+            };
 
-            if !interactive {
-                match ConfigPath::from_str(&config_source)? {
-                    ConfigPath::File { .. } => (),
+            let interactive = matches!(config, UpdateConfig::Tweak);
+            match config {
+                UpdateConfig::Tweak | UpdateConfig::Keep => (),
+                UpdateConfig::Replace(path) => {
+                    let (origin, body) = self.read_config(&path, &clone_env).await?;
+                    original_body = body.ok_or(RoozError::Config(format!(
+                        "config not found: {}",
+                        origin.to_string()
+                    )))?;
+                    config_source = origin.to_string();
+                }
+                UpdateConfig::Stored => match ConfigPath::from_str(&config_source)? {
+                    ConfigPath::File { .. } | ConfigPath::Stdin { .. } => (),
+                    ConfigPath::Git { .. } if self.git.offline => {
+                        log::debug!("Offline mode: using the config stored in the workspace")
+                    }
                     ConfigPath::Git { url, file_path } => {
-                        let clone_env = CloneEnv {
-                            workspace_key: workspace_key.to_string(),
-                            use_volume: false,
-                            depth_override: Some(1),
-                            ..Default::default()
-                        };
-
                         match self
                             .git
                             .clone_config_repo(clone_env, &url, &file_path)
@@ -64,10 +309,14 @@ impl<'a> WorkspaceApi<'a> {
                             None => (),
                         };
                     }
-                };
-            }
+                },
+            };
+            let config_source = &config_source;
+            let format = FileFormat::from_path(config_source)?;
 
-            let mut original_config = RoozCfg::deserialize_config(&original_body, format)?.unwrap();
+            let mut original_config = RoozCfg::deserialize_config(&original_body, format)?.ok_or(
+                RoozError::Config(format!("invalid config: {}", config_source)),
+            )?;
 
             let config_to_apply = if interactive {
                 self.config.decrypt(&mut original_config, &identity).await?;
@@ -82,23 +331,229 @@ impl<'a> WorkspaceApi<'a> {
                 original_config
             };
 
-            self.new(
-                &labels[labels::WORKSPACE_KEY],
-                &WorkParams {
-                    env: spec.clone(),
-                    pull_image: if no_pull || interactive { false } else { true },
-                    ..Default::default()
+            let params = WorkParams {
+                env: spec.clone(),
+                pull: if no_pull || interactive {
+                    None
+                } else {
+                    Some(RoozPullPolicy::Always)
                 },
-                Some(ConfigSource::Body {
-                    value: config_to_apply,
-                    origin: config_source.to_string(),
-                    format,
-                }),
-                false,
+                // the repo, the project and the branch are not part of the config so they have to be carried over
+                git_ssh_url: stored.git_ssh_url(labels),
+                project: labels.get(labels::PROJECT).cloned(),
+                branch: labels.get(labels::BRANCH).cloned(),
+                // workspaces created before the uid was recorded have the default one
+                uid: Some(
+                    labels
+                        .get(labels::UID)
+                        .cloned()
+                        .unwrap_or(constants::DEFAULT_UID.to_string()),
+                ),
+                generated_secrets: stored.generated.clone(),
+                reset_home: matches!(
+                    mode,
+                    UpdateMode::Apply {
+                        reset_home: true,
+                        ..
+                    }
+                ),
+                keep_existing: matches!(&mode, UpdateMode::Apply { only, .. } if !only.is_empty()),
+                ..Default::default()
+            };
+
+            let stored_config = RoozCfg::deserialize_config(&stored_body, stored_format)?.ok_or(
+                RoozError::Config(format!("invalid stored config: {}", stored.origin)),
+            )?;
+            self.show_changes(
+                workspace_key,
+                &stored,
+                &config_to_apply,
+                config_source,
+                &params,
                 &identity,
             )
             .await?;
+
+            if dry_run {
+                return Ok(());
+            }
+
+            let was_running = container.state.as_deref() == Some("running");
+            let before = self
+                .api
+                .container
+                .get_all(&Labels::new(Some(workspace_key), None))
+                .await?;
+            let (set_aside, kept_work) = match mode {
+                UpdateMode::Apply { only, .. } if !only.is_empty() => {
+                    let set_aside = self.set_aside(workspace_key, &before, &only).await?;
+                    let mut recreated = set_aside
+                        .iter()
+                        .filter_map(|c| c.container.as_deref())
+                        .collect::<Vec<_>>();
+                    recreated.sort();
+                    println!("Re-creating: {}", recreated.join(", "));
+                    let kept_work = !recreated.contains(&constants::DEFAULT_CONTAINER_NAME);
+                    (set_aside, kept_work)
+                }
+                UpdateMode::Apply { .. } => {
+                    (self.set_aside(workspace_key, &before, &[]).await?, false)
+                }
+                UpdateMode::Purge { force } => {
+                    self.guard_sessions(workspace_key, "purge", force).await?;
+                    self.remove(&workspace_key, true, false).await?;
+                    (Vec::new(), false)
+                }
+            };
+            // the labels of the work container can't be changed in place
+            let config_changed =
+                config_to_apply.to_string(format)? != stored_config.to_string(format)?;
+
+            let result = self
+                .new(
+                    &labels[labels::WORKSPACE_KEY],
+                    &params,
+                    Some(ConfigSource::Body {
+                        value: config_to_apply,
+                        origin: config_source.to_string(),
+                        format,
+                    }),
+                    false,
+                    &identity,
+                    labels.get(labels::VOLUMES_KEY).map(|k| k.as_str()),
+                )
+                .await;
+            if let Err(e) = result {
+                if !set_aside.is_empty() {
+                    self.roll_back(workspace_key, &before, &set_aside).await;
+                }
+                return Err(e);
+            }
+            for c in &set_aside {
+                self.api.container.remove(&c.id, true).await?;
+            }
+
+            if kept_work && was_running {
+                self.start_stopped(workspace_key).await?;
+            }
+            if kept_work && config_changed {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "The work container was kept so {} still runs with its previous config. \
+                         Re-create it (--only work) to apply the new one",
+                        workspace_key
+                    )
+                    .yellow()
+                );
+            }
         }
         Ok(())
     }
 }
+
+impl<'a> WorkspaceApi<'a> {
+    // one workspace at a time so the output and failures stay attributable
+    pub async fn update_all(
+        &self,
+        project: Option<&str>,
+        filters: &[String],
+        spec: &WorkEnvParams,
+        batch: UpdateBatch,
+    ) -> Result<(), AnyError> {
+        let filter = LabelFilter::parse(filters, &FILTER_KEYS)?;
+        let containers = self
+            .api
+            .container
+            .get_all(
+                &Labels::new(None, Some(ROLE_WORK))
+                    .with_container(Some(constants::DEFAULT_CONTAINER_NAME))
+                    .with_project(project),
+            )
+            .await?;
+
+        let mut selected = Vec::new();
+        let mut skipped = Vec::new();
+        for c in containers {
+            let labels = c.labels.unwrap_or_default();
+            let Some(key) = labels.get(labels::WORKSPACE_KEY) else {
+                continue;
+            };
+            if !filter.matches(&labels, c.state.as_deref() == Some("running")) {
+                continue;
+            }
+            match labels::has_config(&labels) {
+                true => selected.push(key.to_string()),
+                false => skipped.push(key.to_string()),
+            }
+        }
+        selected.sort();
+        skipped.sort();
+
+        if selected.is_empty() && skipped.is_empty() {
+            println!("No matching workspaces");
+            return Ok(());
+        }
+
+        let mut updated = Vec::new();
+        let mut failed = Vec::new();
+        for key in &selected {
+            println!("{} {}", "Updating".green(), key.bold());
+            let result = self
+                .update(
+                    key,
+                    spec,
+                    UpdateConfig::Stored,
+                    UpdateMode::Apply {
+                        reset_home: batch.reset_home,
+                        only: batch.only.clone(),
+                    },
+                    batch.no_pull,
+                    batch.dry_run,
+                )
+                .await;
+            match result {
+                Ok(_) => updated.push(key.as_str()),
+                Err(e) => {
+                    eprintln!("{}", format!("Could not update {}: {}", key, e).red());
+                    failed.push(key.as_str());
+                    if !batch.continue_on_error {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let not_attempted = selected
+            .iter()
+            .map(|k| k.as_str())
+            .filter(|k| !updated.contains(k) && !failed.contains(k))
+            .collect::<Vec<_>>();
+        println!();
+        for (title, keys) in [
+            ("Updated".green(), &updated),
+            ("Failed".red(), &failed),
+            ("Not attempted".yellow(), &not_attempted),
+        ] {
+            if !keys.is_empty() {
+                println!("{}: {}", title, keys.join(", "));
+            }
+        }
+        if !skipped.is_empty() {
+            println!(
+                "{}: {} (created without a config)",
+                "Skipped".yellow(),
+                skipped.join(", ")
+            );
+        }
+        match failed.len() {
+            0 => Ok(()),
+            n if n == 1 && !batch.continue_on_error && !not_attempted.is_empty() => Err(format!(
+                "Stopped after {} failed. Re-run with --continue-on-error to update the rest",
+                failed[0]
+            )
+            .into()),
+            n => Err(format!("Failed to update {} workspace(s)", n).into()),
+        }
+    }
+}
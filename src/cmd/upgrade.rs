@@ -0,0 +1,206 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use colored::Colorize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::model::types::AnyError;
+
+const RELEASES: &str = "https://github.com/queil/rooz/releases";
+const LATEST_API: &str = "https://api.github.com/repos/queil/rooz/releases/latest";
+
+// the targets the release workflow builds
+fn target() -> Option<&'static str> {
+    match (env::consts::OS, env::consts::ARCH) {
+        ("linux", "x86_64") => Some("x86_64-unknown-linux-gnu"),
+        ("macos", "aarch64") => Some("aarch64-apple-darwin"),
+        _ => None,
+    }
+}
+
+// curl is there wherever the install one-liner worked, so rooz needs no HTTP client
+fn curl(url: &str, output: Option<&Path>) -> Result<Vec<u8>, AnyError> {
+    let mut cmd = Command::new("curl");
+    cmd.args(["-fsSL", "--max-time", "300", url]);
+    if let Some(path) = output {
+        cmd.arg("-o").arg(path);
+    }
+    let result = cmd
+        .output()
+        .map_err(|e| format!("Could not run curl: {}", e))?;
+    if !result.status.success() {
+        return Err(format!(
+            "Could not download {}: {}",
+            url,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )
+        .into());
+    }
+    Ok(result.stdout)
+}
+
+fn version_of(tag: &str) -> &str {
+    tag.trim_start_matches('v')
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split(['.', '-'])
+        .map_while(|p| p.parse().ok())
+        .collect()
+}
+
+fn latest_tag() -> Result<String, AnyError> {
+    let release = serde_json::from_slice::<Value>(&curl(LATEST_API, None)?)?;
+    release["tag_name"]
+        .as_str()
+        .map(|t| t.to_string())
+        .ok_or("The latest release has no tag".into())
+}
+
+// the previous binary is kept next to the current one until the next upgrade
+fn paths() -> Result<(PathBuf, PathBuf, PathBuf), AnyError> {
+    let current = env::current_exe()?.canonicalize()?;
+    let with_suffix = |suffix: &str| {
+        let mut name = current.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        current.with_file_name(name)
+    };
+    Ok((current.clone(), with_suffix(".new"), with_suffix(".old")))
+}
+
+fn verify(binary: &Path, checksum_file: &str) -> Result<(), AnyError> {
+    let expected = checksum_file
+        .split_whitespace()
+        .next()
+        .ok_or("The checksum file is empty")?
+        .to_lowercase();
+    let actual = Sha256::digest(fs::read(binary)?)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch (expected {}, got {}). Not upgrading",
+            expected, actual
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), AnyError> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn make_executable(_path: &Path) -> Result<(), AnyError> {
+    Ok(())
+}
+
+// swaps the files with renames, which only works within a directory, hence .new and .old next to the binary
+fn replace(current: &Path, new: &Path, old: &Path) -> Result<(), AnyError> {
+    fs::rename(current, old)?;
+    if let Err(e) = fs::rename(new, current) {
+        fs::rename(old, current)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn install(asset: &str, current: &Path, new: &Path, old: &Path) -> Result<(), AnyError> {
+    curl(asset, Some(new))?;
+    let checksum = curl(&format!("{}.sha256", asset), None).map_err(|e| {
+        format!(
+            "{}. Releases published before the checksums can't be verified",
+            e
+        )
+    })?;
+    verify(new, &String::from_utf8_lossy(&checksum))?;
+    make_executable(new)?;
+    // a binary that doesn't start is not worth switching to
+    let output = Command::new(new).arg("--version").output()?;
+    if !output.status.success() {
+        return Err("The downloaded binary does not run".into());
+    }
+    replace(current, new, old)
+}
+
+fn writable_error(dir: &Path, e: impl std::fmt::Display) -> AnyError {
+    format!(
+        "Could not write to {} ({}). Re-run with the permissions to replace the rooz binary, e.g. with sudo",
+        dir.display(),
+        e
+    )
+    .into()
+}
+
+pub fn upgrade(check: bool, version: Option<&str>) -> Result<(), AnyError> {
+    let current_version = env!("CARGO_PKG_VERSION");
+    let tag = match version {
+        Some(v) => v.to_string(),
+        None => latest_tag()?,
+    };
+    let newer = parse_version(version_of(&tag)) > parse_version(current_version);
+
+    if check {
+        match newer {
+            true => println!(
+                "rooz {} is available (current: {}). Run 'rooz system upgrade' to install it",
+                version_of(&tag).green(),
+                current_version
+            ),
+            false => println!("rooz {} is up to date", current_version),
+        }
+        return Ok(());
+    }
+    if version.is_none() && !newer {
+        println!("rooz {} is up to date", current_version);
+        return Ok(());
+    }
+
+    let target = target().ok_or(format!(
+        "There are no release binaries for {}/{}. Build rooz from source instead",
+        env::consts::OS,
+        env::consts::ARCH
+    ))?;
+    let (current, new, old) = paths()?;
+    let dir = current.parent().unwrap_or(Path::new("."));
+    let asset = format!("{}/download/{}/rooz-{}", RELEASES, tag, target);
+
+    fs::File::create(&new).map_err(|e| writable_error(dir, e))?;
+    println!("Downloading rooz {} ...", version_of(&tag));
+    let result = install(&asset, &current, &new, &old);
+    if result.is_err() {
+        fs::remove_file(&new).ok();
+    }
+    result?;
+    println!(
+        "Upgraded rooz {} -> {}. Run 'rooz system upgrade --rollback' to go back",
+        current_version,
+        version_of(&tag).green()
+    );
+    Ok(())
+}
+
+pub fn rollback() -> Result<(), AnyError> {
+    let (current, _, old) = paths()?;
+    if !old.exists() {
+        return Err(format!(
+            "There is no previous binary to roll back to ({})",
+            old.display()
+        )
+        .into());
+    }
+    let dir = current.parent().unwrap_or(Path::new("."));
+    fs::rename(&old, &current).map_err(|e| writable_error(dir, e))?;
+    println!("Rolled back to the previous rooz binary");
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use crate::{
+    api::WorkspaceApi,
+    config::runtime::RuntimeConfig,
+    constants,
+    model::types::{AnyError, RunSpec},
+    util::{
+        id,
+        labels::Labels,
+        passwd::{self, Entry},
+    },
+};
+
+const PASSWD_ROLE: &str = "passwd";
+
+#[derive(Debug)]
+pub struct ImageUser {
+    pub name: String,
+    pub uid: String,
+    pub home: Option<String>,
+}
+
+// a user of the image brings its own ids and home, e.g. node in the node images.
+// A numeric user not in the image is the uid of the default user. An explicit uid always wins
+fn resolve(user: &str, uid: &str, explicit_uid: bool, entries: &[Entry]) -> ImageUser {
+    let numeric = user.parse::<u32>().is_ok();
+    let entry = passwd::find(entries, user);
+    let name = match (entry, numeric) {
+        (Some(e), _) => e.name.to_string(),
+        (None, true) => constants::DEFAULT_USER.to_string(),
+        (None, false) => user.to_string(),
+    };
+    let uid = match (explicit_uid, entry) {
+        (true, _) => uid.to_string(),
+        (false, Some(e)) => format!("{}:{}", e.uid, e.gid),
+        (false, None) if numeric => user.to_string(),
+        (false, None) => uid.to_string(),
+    };
+    let home = entry
+        .map(|e| e.home.to_string())
+        .filter(|h| *h != format!("/home/{}", name));
+    ImageUser { name, uid, home }
+}
+
+impl<'a> WorkspaceApi<'a> {
+    // a one-shot container reads it, so images without cat (e.g. distroless ones) have none
+    async fn image_passwd(
+        &self,
+        workspace_key: &str,
+        image: &str,
+        platform: Option<&str>,
+    ) -> Result<Option<String>, AnyError> {
+        let run_spec = RunSpec {
+            reason: "passwd",
+            image,
+            uid: constants::ROOT_UID,
+            container_name: &id::random_suffix(&format!("{}-passwd", workspace_key)),
+            workspace_key,
+            entrypoint: Some(vec!["cat", "/etc/passwd"]),
+            labels: Labels::new(Some(workspace_key), Some(PASSWD_ROLE)),
+            platform,
+            ..Default::default()
+        };
+        let id = self.api.container.create(run_spec).await?.id().to_string();
+        let passwd = match self.api.container.start(&id).await {
+            Ok(()) => self.api.container.logs_to_string(&id).await,
+            Err(e) => Err(e),
+        };
+        self.api.container.remove(&id, true).await?;
+        match passwd {
+            Ok(passwd) => Ok(Some(passwd)),
+            Err(e) => {
+                log::debug!("Could not read /etc/passwd of {}: {}", image, e);
+                Ok(None)
+            }
+        }
+    }
+
+    // the default user is rooz's own so it is never looked up
+    pub async fn image_user(
+        &self,
+        workspace_key: &str,
+        cfg: &RuntimeConfig,
+        uid: &str,
+        explicit_uid: bool,
+    ) -> Result<ImageUser, AnyError> {
+        let entries = match cfg.user == constants::DEFAULT_USER {
+            true => Vec::new(),
+            false => self
+                .image_passwd(workspace_key, &cfg.image, cfg.platform.as_deref())
+                .await?
+                .map(|p| passwd::parse(&p))
+                .unwrap_or_default(),
+        };
+        let user = resolve(&cfg.user, uid, explicit_uid, &entries);
+        log::debug!("Resolved the user of {}: {:?}", cfg.image, user);
+        Ok(user)
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use bollard::{
+    models::{EventMessage, EventMessageTypeEnum},
+    volume::ListVolumesOptions,
+};
+use colored::Colorize;
+use futures::StreamExt;
+use serde::Serialize;
+
+use crate::{
+    api::Api,
+    cmd::notify::{self, Notification},
+    config::system::NotificationsCfg,
+    model::types::AnyError,
+    util::{
+        labels::{self, Labels},
+        time::DateTime,
+    },
+};
+
+#[derive(Debug, Serialize)]
+struct WatchEvent {
+    time: i64,
+    kind: String,
+    action: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workspace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<String>,
+}
+
+impl WatchEvent {
+    fn from_message(
+        message: &EventMessage,
+        workspace: Option<&str>,
+        volumes: &HashMap<String, String>,
+    ) -> Option<WatchEvent> {
+        let actor = message.actor.as_ref()?;
+        let attributes = actor.attributes.clone().unwrap_or_default();
+        let action = message.action.clone()?;
+        let time = message.time.unwrap_or_default();
+
+        match message.typ? {
+            EventMessageTypeEnum::CONTAINER => {
+                let workspace_key = attributes.get(labels::WORKSPACE_KEY)?;
+                if workspace.is_some_and(|w| w != workspace_key) {
+                    return None;
+                }
+                Some(WatchEvent {
+                    time,
+                    kind: "container".into(),
+                    action,
+                    name: attributes.get("name").cloned().unwrap_or_default(),
+                    workspace: Some(workspace_key.to_string()),
+                    container: attributes.get(labels::CONTAINER).cloned(),
+                    exit_code: attributes.get("exitCode").cloned(),
+                })
+            }
+            EventMessageTypeEnum::VOLUME => {
+                let name = actor.id.clone()?;
+                let workspace_key = attributes
+                    .get(labels::WORKSPACE_KEY)
+                    .or(volumes.get(&name))?;
+                if workspace.is_some_and(|w| w != workspace_key) {
+                    return None;
+                }
+                Some(WatchEvent {
+                    time,
+                    kind: "volume".into(),
+                    action,
+                    workspace: Some(workspace_key.to_string()),
+                    name,
+                    container: None,
+                    exit_code: None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // only the events someone should look into
+    fn notification(&self) -> Option<&'static str> {
+        match (&self.kind[..], &self.action[..], self.exit_code.as_deref()) {
+            ("container", "oom", _) => Some(notify::OOM),
+            ("container", "die", Some(code)) if code != "0" => Some(notify::DIED),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let subject = match (&self.kind[..], &self.workspace, &self.container) {
+            ("container", Some(w), Some(c)) => format!("{}/{}", w, c),
+            ("container", _, _) => self.name.to_string(),
+            _ => format!("volume {}", self.name),
+        };
+        let what = match &self.action[..] {
+            "create" => "created".normal(),
+            "start" => "started".green(),
+            "stop" => "stopped".normal(),
+            "destroy" => "removed".yellow(),
+            "oom" => "OOM-killed".red(),
+            "die" => match self.exit_code.as_deref() {
+                Some("0") | None => "exited".normal(),
+                Some(code) => format!("died (exit code {})", code).red(),
+            },
+            other => other.normal(),
+        };
+        format!(
+            "{} {} {}",
+            DateTime::from_unix(self.time).to_rfc3339(),
+            subject.bold(),
+            what
+        )
+    }
+}
+
+impl<'a> Api<'a> {
+    // volume events don't carry the labels, so the workspace of each volume gets looked up
+    async fn volume_workspaces(&self) -> Result<HashMap<String, String>, AnyError> {
+        let options = ListVolumesOptions {
+            filters: (&Labels::default()).into(),
+        };
+        Ok(self
+            .client
+            .list_volumes(Some(options))
+            .await?
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| {
+                let workspace_key = v.labels.get(labels::WORKSPACE_KEY).cloned()?;
+                Some((v.name, workspace_key))
+            })
+            .collect())
+    }
+
+    pub async fn watch(
+        &self,
+        workspace: Option<&str>,
+        json: bool,
+        notifications: Option<&NotificationsCfg>,
+    ) -> Result<(), AnyError> {
+        let mut filters = HashMap::<String, Vec<String>>::new();
+        filters.insert("type".into(), vec!["container".into(), "volume".into()]);
+        filters.insert(
+            "event".into(),
+            ["create", "start", "stop", "die", "oom", "destroy"]
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+        );
+
+        let mut volumes = self.volume_workspaces().await?;
+        let mut events = self.events(filters);
+        while let Some(message) = events.next().await {
+            let message = message?;
+            let volume = match (message.typ, message.actor.as_ref()) {
+                (Some(EventMessageTypeEnum::VOLUME), Some(actor)) => actor.id.clone(),
+                _ => None,
+            };
+            if let Some(name) = volume.as_ref().filter(|n| !volumes.contains_key(*n)) {
+                // a volume created since the watch started
+                if let Some(workspace_key) = self
+                    .client
+                    .inspect_volume(name)
+                    .await
+                    .ok()
+                    .and_then(|v| v.labels.get(labels::WORKSPACE_KEY).cloned())
+                {
+                    volumes.insert(name.to_string(), workspace_key);
+                }
+            }
+            let event = WatchEvent::from_message(&message, workspace, &volumes);
+            if let (Some(name), Some("destroy")) = (&volume, message.action.as_deref()) {
+                volumes.remove(name);
+            }
+            let Some(event) = event else {
+                continue;
+            };
+            if json {
+                println!("{}", serde_json::to_string(&event)?);
+            } else {
+                println!("{}", event.describe());
+            }
+            if let (Some(notifications), Some(name)) = (notifications, event.notification()) {
+                notifications.send(&Notification::new(
+                    name,
+                    event.workspace.as_deref(),
+                    event.container.as_deref(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,23 @@
-use crate::model::types::AnyError;
-use crate::{cli::WorkParams, constants};
+use crate::model::{
+    error::RoozError,
+    types::{AnyError, DnsSpec, SecuritySpec},
+};
+use crate::{
+    cli::{ConfigFormat, WorkParams},
+    config::interpolation::SecretMarkers,
+    constants,
+};
 use colored::Colorize;
 use handlebars::{no_escape, Handlebars};
 use linked_hash_map::LinkedHashMap;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, ffi::OsStr, fs, path::Path};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    net::{IpAddr, SocketAddr},
+    path::{Component, Path},
+};
 
 #[derive(Debug, Clone)]
 pub enum ConfigSource {
@@ -22,15 +35,36 @@ pub enum ConfigSource {
 pub enum ConfigPath {
     File { path: String },
     Git { url: String, file_path: String },
+    Stdin { format: FileFormat },
 }
 
 impl<'a> ConfigPath {
+    pub const STDIN: &'static str = "-";
+
+    // `-` reads the config from stdin, which has no extension to tell the format by
+    pub fn from_cli(value: &'a str, format: Option<FileFormat>) -> Result<Self, AnyError> {
+        match (value, format) {
+            (Self::STDIN, Some(format)) => Ok(Self::Stdin { format }),
+            (Self::STDIN, None) => {
+                Err("Reading the config from stdin requires --format toml|yaml".into())
+            }
+            _ => Self::from_str(value),
+        }
+    }
+
     pub fn from_str(value: &'a str) -> Result<Self, AnyError> {
-        if value.starts_with("git@") || value.starts_with("ssh://") {
-            let chunks = value.split("//").collect::<Vec<_>>();
+        if value.starts_with("-.") {
+            return Ok(Self::Stdin {
+                format: FileFormat::from_path(value)?,
+            });
+        }
+        if value.starts_with("git@") || value.contains("://") {
+            let scheme_len = value.find("://").map(|i| i + 3).unwrap_or(0);
+            let (scheme, rest) = value.split_at(scheme_len);
+            let chunks = rest.split("//").collect::<Vec<_>>();
             match chunks.as_slice() {
                 &[url, file_path] => Ok(Self::Git {
-                    url: url.to_string(),
+                    url: format!("{}{}", scheme, url),
                     file_path: file_path.to_string(),
                 }),
                 _ => Err(format!("Invalid repo URL {}", value).into()),
@@ -46,8 +80,62 @@ impl<'a> ConfigPath {
         match self {
             ConfigPath::File { path } => path.to_string(),
             ConfigPath::Git { url, file_path } => format!("{}//{}", url, file_path),
+            // recorded as the origin, so the stored body can still be told apart by its format
+            ConfigPath::Stdin { format } => format!("{}.{}", Self::STDIN, format.to_string()),
+        }
+    }
+
+    // resolves a path relative to the directory of this config (in the same repo for git configs)
+    pub fn join(&self, value: &str) -> Result<ConfigPath, AnyError> {
+        let relative = match ConfigPath::from_str(value)? {
+            ConfigPath::File { path } if !Path::new(&path).is_absolute() => path,
+            ConfigPath::File { path } => {
+                return Ok(ConfigPath::File {
+                    path: std::path::absolute(path)?.to_string_lossy().into_owned(),
+                })
+            }
+            git => return Ok(git),
+        };
+        Ok(match self {
+            ConfigPath::Stdin { .. } => ConfigPath::File {
+                path: std::path::absolute(normalize(Path::new(&relative)))?
+                    .to_string_lossy()
+                    .into_owned(),
+            },
+            ConfigPath::File { path } => {
+                let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+                ConfigPath::File {
+                    path: std::path::absolute(normalize(&dir.join(relative)))?
+                        .to_string_lossy()
+                        .into_owned(),
+                }
+            }
+            ConfigPath::Git { url, file_path } => {
+                let dir = Path::new(file_path).parent().unwrap_or(Path::new(""));
+                ConfigPath::Git {
+                    url: url.to_string(),
+                    // a path within the repo, so always with forward slashes
+                    file_path: normalize(&dir.join(relative))
+                        .to_string_lossy()
+                        .replace('\\', "/"),
+                }
+            }
+        })
+    }
+}
+
+fn normalize(path: &Path) -> std::path::PathBuf {
+    let mut out = std::path::PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
         }
     }
+    out
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -56,6 +144,15 @@ pub enum FileFormat {
     Yaml,
 }
 
+impl From<ConfigFormat> for FileFormat {
+    fn from(value: ConfigFormat) -> Self {
+        match value {
+            ConfigFormat::Toml => FileFormat::Toml,
+            ConfigFormat::Yaml => FileFormat::Yaml,
+        }
+    }
+}
+
 impl FileFormat {
     pub fn to_string(&self) -> String {
         match self {
@@ -64,12 +161,12 @@ impl FileFormat {
         }
     }
 
-    pub fn from_path(path: &str) -> FileFormat {
+    pub fn from_path(path: &str) -> Result<FileFormat, RoozError> {
         match Path::new(path).extension().and_then(OsStr::to_str) {
-            Some("yaml") => FileFormat::Yaml,
-            Some("toml") => FileFormat::Toml,
-            Some(other) => panic!("Config file format: {} is not supported", other),
-            None => panic!("Only toml and yaml config file formats are supported."),
+            Some("yaml") => Ok(FileFormat::Yaml),
+            Some("toml") => Ok(FileFormat::Toml),
+            Some(other) => Err(RoozError::ConfigFormat(other.to_string())),
+            None => Err(RoozError::ConfigFormat(path.to_string())),
         }
     }
 }
@@ -79,6 +176,8 @@ impl FileFormat {
 pub struct RoozSidecar {
     pub image: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub env: Option<LinkedHashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub command: Option<Vec<String>>,
@@ -92,11 +191,537 @@ pub struct RoozSidecar {
     pub work_dir: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RoozRestart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub networks: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<LinkedHashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expose: Option<LinkedHashMap<String, u16>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_opts: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_add: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_drop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only_rootfs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_search: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_hosts: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoozVolumeCfg {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoozContainer {
+    pub image: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<LinkedHashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ports: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caches: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoozInitContainer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<LinkedHashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RoozMux {
+    Tmux,
+    Zellij,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RoozPullPolicy {
+    /// Pulls the image every time
+    Always,
+    /// Pulls the image only if not present locally
+    #[default]
+    Missing,
+    /// Never pulls the image
+    Never,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoozRestart {
+    No,
+    OnFailure,
+    Always,
+    UnlessStopped,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoozDockerMode {
+    /// Mounts the socket of the container engine host
+    Socket,
+    /// Runs a privileged Docker daemon sidecar reached over TLS
+    Dind,
+}
+
+/// `true` picks dind as it doesn't hand the workspace the host engine
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum RoozDocker {
+    Enabled(bool),
+    Mode(RoozDockerMode),
+}
+
+impl RoozDocker {
+    pub fn mode(&self) -> Option<RoozDockerMode> {
+        match self {
+            RoozDocker::Enabled(true) => Some(RoozDockerMode::Dind),
+            RoozDocker::Enabled(false) => None,
+            RoozDocker::Mode(mode) => Some(*mode),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoozIdMap {
+    /// keep-id on rootless Podman, no mapping otherwise
+    #[default]
+    Auto,
+    /// Maps the host user to the workspace uid (Podman only)
+    KeepId,
+    None,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RoozCloneCfg {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub single_branch: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submodules: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+}
+
+impl RoozCloneCfg {
+    pub fn merge(&self, other: &RoozCloneCfg) -> RoozCloneCfg {
+        RoozCloneCfg {
+            depth: other.depth.or(self.depth),
+            single_branch: other.single_branch.or(self.single_branch),
+            branch: other.branch.clone().or(self.branch.clone()),
+            commit: other.commit.clone().or(self.commit.clone()),
+            submodules: other.submodules.or(self.submodules),
+            filter: other.filter.clone().or(self.filter.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RoozProxy {
+    #[default]
+    Traefik,
+    Caddy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RoozExposeCfg {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<RoozProxy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<LinkedHashMap<String, u16>>,
+}
+
+impl RoozExposeCfg {
+    pub fn merge(&self, other: &RoozExposeCfg) -> RoozExposeCfg {
+        RoozExposeCfg {
+            proxy: other.proxy.or(self.proxy),
+            network: other.network.clone().or(self.network.clone()),
+            domain: other.domain.clone().or(self.domain.clone()),
+            hosts: match (&self.hosts, &other.hosts) {
+                (Some(a), Some(b)) => {
+                    let mut hosts = a.clone();
+                    hosts.extend(b.clone());
+                    Some(hosts)
+                }
+                (a, b) => b.clone().or(a.clone()),
+            },
+        }
+    }
+
+    /// The proxy network to connect a container with exposed hosts to,
+    /// unless it's the container's network already.
+    pub fn extra_network(
+        &self,
+        hosts: &LinkedHashMap<String, u16>,
+        network: Option<&str>,
+    ) -> Option<String> {
+        self.network
+            .clone()
+            .filter(|n| !hosts.is_empty() && Some(n.as_str()) != network)
+    }
+
+    /// Host names without a dot are treated as subdomains of `domain`
+    pub fn host(&self, name: &str) -> String {
+        match name.contains('.') {
+            true => name.to_string(),
+            false => format!("{}.{}", name, self.domain.as_deref().unwrap_or("localhost")),
+        }
+    }
+
+    /// Renders the proxy labels routing the hosts to the container ports.
+    /// `id` must be unique per container as Traefik routers are global.
+    pub fn labels(
+        &self,
+        id: &str,
+        hosts: &LinkedHashMap<String, u16>,
+    ) -> Result<Vec<(String, String)>, AnyError> {
+        if hosts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let network = self
+            .network
+            .as_ref()
+            .ok_or("expose: network must be set to route hosts through the proxy")?;
+        let mut labels = Vec::new();
+        match self.proxy.unwrap_or_default() {
+            RoozProxy::Traefik => {
+                labels.push(("traefik.enable".into(), "true".into()));
+                labels.push(("traefik.docker.network".into(), network.to_string()));
+                for (name, port) in hosts {
+                    let router = format!("{}-{}", id, name).replace('.', "-");
+                    labels.push((
+                        format!("traefik.http.routers.{}.rule", router),
+                        format!("Host(`{}`)", self.host(name)),
+                    ));
+                    labels.push((
+                        format!("traefik.http.routers.{}.service", router),
+                        router.to_string(),
+                    ));
+                    labels.push((
+                        format!("traefik.http.services.{}.loadbalancer.server.port", router),
+                        port.to_string(),
+                    ));
+                }
+            }
+            RoozProxy::Caddy => {
+                for (i, (name, port)) in hosts.iter().enumerate() {
+                    labels.push((format!("caddy_{}", i), self.host(name)));
+                    labels.push((
+                        format!("caddy_{}.reverse_proxy", i),
+                        format!("{{{{upstreams {}}}}}", port),
+                    ));
+                }
+            }
+        }
+        Ok(labels)
+    }
+}
+
+impl From<&WorkParams> for RoozCloneCfg {
+    fn from(cli: &WorkParams) -> Self {
+        RoozCloneCfg {
+            depth: cli.clone_depth,
+            single_branch: cli.single_branch.then_some(true),
+            branch: cli.branch.clone(),
+            commit: cli.commit.clone(),
+            submodules: cli.recurse_submodules.then_some(true),
+            filter: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoozGitCfg {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sign: Option<RoozGitSigning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<LinkedHashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoozGitSigning {
+    /// Signs with a key held by the host ssh-agent
+    Ssh,
+    /// Signs through the host gpg-agent
+    Gpg,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoozIdeCfg {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoozDotfilesCfg {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install: Option<String>,
+}
+
+impl RoozDotfilesCfg {
+    pub const DIR: &'static str = "~/.dotfiles";
+
+    // the shared volume is a regular shared user volume mounted at the dotfiles dir
+    pub fn volume(&self) -> Option<(String, RoozVolumeCfg)> {
+        self.volume.as_ref().map(|name| {
+            (
+                name.to_string(),
+                RoozVolumeCfg {
+                    path: Self::DIR.into(),
+                    shared: Some(true),
+                    keep: None,
+                },
+            )
+        })
+    }
+
+    // the install script runs like an init container, ahead of the configured ones
+    pub fn install_container(&self) -> Option<RoozInitContainer> {
+        self.install.as_ref().map(|script| RoozInitContainer {
+            image: None,
+            command: Some(vec![
+                "sh".into(),
+                "-c".into(),
+                format!("cd {} && sh '{}'", Self::DIR, script),
+            ]),
+            user: None,
+            work_dir: None,
+            env: None,
+        })
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RoozProxyCfg {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<Vec<String>>,
+}
+
+impl RoozProxyCfg {
+    fn env_var(name: &str) -> Option<String> {
+        std::env::var(name)
+            .or_else(|_| std::env::var(name.to_lowercase()))
+            .ok()
+            .filter(|v| !v.is_empty())
+    }
+
+    pub fn from_env() -> Self {
+        Self {
+            http: Self::env_var("HTTP_PROXY"),
+            https: Self::env_var("HTTPS_PROXY"),
+            no_proxy: Self::env_var("NO_PROXY")
+                .map(|v| v.split(',').map(|h| h.trim().to_string()).collect()),
+        }
+    }
+
+    // field by field, so a workspace can e.g. only add hosts to no_proxy
+    pub fn or(&self, other: &Self) -> Self {
+        Self {
+            http: self.http.clone().or(other.http.clone()),
+            https: self.https.clone().or(other.https.clone()),
+            no_proxy: self.no_proxy.clone().or(other.no_proxy.clone()),
+        }
+    }
+
+    pub fn with_no_proxy<'b>(&self, hosts: impl IntoIterator<Item = &'b str>) -> Self {
+        let mut no_proxy = self.no_proxy.clone().unwrap_or_default();
+        for h in hosts {
+            if !no_proxy.iter().any(|n| n == h) {
+                no_proxy.push(h.to_string());
+            }
+        }
+        Self {
+            no_proxy: Some(no_proxy),
+            ..self.clone()
+        }
+    }
+
+    /// Tools disagree on the case so both get set. Empty values unset a proxy inherited from the host
+    pub fn env(&self) -> HashMap<String, String> {
+        if self.http.as_deref().unwrap_or_default().is_empty()
+            && self.https.as_deref().unwrap_or_default().is_empty()
+        {
+            return HashMap::new();
+        }
+        [
+            ("HTTP_PROXY", self.http.clone()),
+            ("HTTPS_PROXY", self.https.clone()),
+            ("NO_PROXY", self.no_proxy.as_ref().map(|n| n.join(","))),
+        ]
+        .into_iter()
+        .filter_map(|(k, v)| v.filter(|v| !v.is_empty()).map(|v| (k, v)))
+        .flat_map(|(k, v)| [(k.to_string(), v.clone()), (k.to_lowercase(), v)])
+        .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoozCleanCfg {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commands: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoozSyncCfg {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+impl RoozSyncCfg {
+    pub const IMAGE: &str = "docker.io/library/alpine:latest";
+
+    pub fn image(&self) -> &str {
+        self.image.as_deref().unwrap_or(Self::IMAGE)
+    }
+
+    // the host folder is bind-mounted so it is a path on the container engine host
+    pub fn host_path(&self) -> Result<String, AnyError> {
+        let expanded = shellexpand::tilde(&self.path);
+        Ok(std::path::absolute(expanded.as_ref())?
+            .to_string_lossy()
+            .into_owned())
+    }
+}
+
+fn git_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl RoozGitCfg {
+    /// Renders the config in the gitconfig format.
+    /// Keys of `url` are base URLs and values are their `insteadOf` prefixes.
+    pub fn to_gitconfig(&self) -> String {
+        let mut out = String::new();
+        if self.name.is_some() || self.email.is_some() || self.signing_key.is_some() {
+            out.push_str("[user]\n");
+            if let Some(name) = &self.name {
+                out.push_str(&format!("\tname = {}\n", git_quote(name)));
+            }
+            if let Some(email) = &self.email {
+                out.push_str(&format!("\temail = {}\n", git_quote(email)));
+            }
+            if let Some(key) = &self.signing_key {
+                out.push_str(&format!("\tsigningkey = {}\n", git_quote(key)));
+            }
+        }
+        if let Some(sign) = self.sign {
+            out.push_str("[commit]\n\tgpgsign = true\n[tag]\n\tgpgsign = true\n");
+            if sign == RoozGitSigning::Ssh {
+                out.push_str("[gpg]\n\tformat = ssh\n");
+                if self.signing_key.is_none() {
+                    out.push_str(&format!(
+                        "[gpg \"ssh\"]\n\tdefaultKeyCommand = {}\n",
+                        git_quote("sh -c 'echo key::$(ssh-add -L | head -n1)'")
+                    ));
+                }
+            }
+        }
+        if let Some(urls) = &self.url {
+            for (base, instead_of) in urls {
+                out.push_str(&format!("[url {}]\n", git_quote(base)));
+                out.push_str(&format!("\tinsteadOf = {}\n", git_quote(instead_of)));
+            }
+        }
+        out
+    }
+
+    // ~/.ssh inside the workspace is the rooz ssh volume, so a public key file of the host gets
+    // inlined for git to find it
+    pub fn with_host_signing_key(&self) -> Self {
+        let key = match (self.sign, &self.signing_key) {
+            (Some(RoozGitSigning::Ssh), Some(key)) if !key.starts_with("key::") => {
+                let path = shellexpand::tilde(key);
+                match std::fs::read_to_string(path.as_ref()) {
+                    Ok(public_key) => Some(format!("key::{}", public_key.trim())),
+                    Err(_) => Some(key.to_string()),
+                }
+            }
+            (_, key) => key.clone(),
+        };
+        Self {
+            signing_key: key,
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct RoozCfg {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vars: Option<LinkedHashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -104,40 +729,173 @@ pub struct RoozCfg {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub git_ssh_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub clone_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clone: Option<RoozCloneCfg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub extra_repos: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub image: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub pull: Option<RoozPullPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub caches: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumes: Option<LinkedHashMap<String, RoozVolumeCfg>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub shell: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ports: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_ports: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub privileged: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub docker: Option<RoozDocker>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_opts: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_add: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_drop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only_rootfs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_search: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_hosts: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idmap: Option<RoozIdMap>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mux: Option<RoozMux>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell_init: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home_from_image: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RoozRestart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub networks: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<LinkedHashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expose: Option<RoozExposeCfg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub env: Option<LinkedHashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sidecars: Option<LinkedHashMap<String, RoozSidecar>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub containers: Option<LinkedHashMap<String, RoozContainer>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub init_containers: Option<LinkedHashMap<String, RoozInitContainer>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<RoozGitCfg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ide: Option<RoozIdeCfg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dotfiles: Option<RoozDotfilesCfg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync: Option<RoozSyncCfg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clean: Option<RoozCleanCfg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<RoozProxyCfg>,
 }
 
 impl Default for RoozCfg {
     fn default() -> Self {
         Self {
+            extends: Some(Vec::new()),
             vars: Some(LinkedHashMap::new()),
             secrets: Some(LinkedHashMap::new()),
             git_ssh_url: None,
+            clone_dir: None,
+            clone: None,
             extra_repos: Some(Vec::new()),
+            engine: None,
             image: Some(constants::DEFAULT_IMAGE.into()),
+            pull: None,
+            platform: None,
             caches: Some(Vec::new()),
+            volumes: Some(LinkedHashMap::new()),
             shell: Some(vec![constants::DEFAULT_SHELL.into()]),
             user: Some(constants::DEFAULT_USER.into()),
             ports: Some(Vec::new()),
+            auto_ports: None,
+            bind_address: None,
             privileged: None,
+            docker: None,
+            security_opts: Some(Vec::new()),
+            cap_add: Some(Vec::new()),
+            cap_drop: Some(Vec::new()),
+            read_only_rootfs: None,
+            dns: Some(Vec::new()),
+            dns_search: Some(Vec::new()),
+            extra_hosts: Some(Vec::new()),
+            idmap: None,
+            mux: None,
+            prompt: None,
+            shell_init: None,
+            home_from_image: None,
+            restart: None,
+            network: None,
+            networks: Some(Vec::new()),
+            aliases: Some(Vec::new()),
+            labels: Some(LinkedHashMap::new()),
+            expose: None,
             env: Some(LinkedHashMap::new()),
             sidecars: Some(LinkedHashMap::new()),
+            containers: Some(LinkedHashMap::new()),
+            init_containers: Some(LinkedHashMap::new()),
+            git: None,
+            ide: None,
+            dotfiles: None,
+            sync: None,
+            clean: None,
+            proxy: None,
+        }
+    }
+}
+
+impl RoozSidecar {
+    pub fn security(&self) -> SecuritySpec {
+        SecuritySpec {
+            security_opts: self.security_opts.clone().unwrap_or_default(),
+            cap_add: self.cap_add.clone().unwrap_or_default(),
+            cap_drop: self.cap_drop.clone().unwrap_or_default(),
+            read_only_rootfs: self.read_only_rootfs.unwrap_or_default(),
+        }
+    }
+
+    pub fn dns(&self) -> DnsSpec {
+        DnsSpec {
+            dns: self.dns.clone().unwrap_or_default(),
+            dns_search: self.dns_search.clone().unwrap_or_default(),
+            extra_hosts: self.extra_hosts.clone().unwrap_or_default(),
+        }
+    }
+
+    // a single instance keeps the plain sidecar name
+    pub fn instances(&self, name: &str) -> Vec<String> {
+        match self.replicas.unwrap_or(1) {
+            1 => vec![name.to_string()],
+            n => (1..=n).map(|i| format!("{}-{}", name, i)).collect(),
         }
     }
 }
@@ -151,7 +909,7 @@ impl RoozCfg {
     }
 
     pub fn to_file(&self, path: &str) -> Result<(), AnyError> {
-        let file_format = FileFormat::from_path(path);
+        let file_format = FileFormat::from_path(path)?;
         fs::write(path, self.to_string(file_format)?)?;
         Ok(())
     }
@@ -163,12 +921,12 @@ impl RoozCfg {
         })
     }
 
-    fn extend_if_any<A, T: Extend<A> + IntoIterator<Item = A>>(
+    fn extend_if_any<A, T: Extend<A> + IntoIterator<Item = A> + Default>(
         target: Option<T>,
         other: Option<T>,
     ) -> Option<T> {
         if let Some(caches) = other {
-            let mut ret = target.unwrap();
+            let mut ret = target.unwrap_or_default();
             ret.extend(caches);
             Some(ret)
         } else {
@@ -180,9 +938,19 @@ impl RoozCfg {
         *self = RoozCfg {
             shell: shell.map(|v| vec![v]).or(self.shell.clone()),
             image: cli.image.clone().or(self.image.clone()),
+            pull: cli.pull.or(self.pull),
+            platform: cli.platform.clone().or(self.platform.clone()),
             user: cli.user.clone().or(self.user.clone()),
             git_ssh_url: cli.git_ssh_url.clone().or(self.git_ssh_url.clone()),
+            clone_dir: cli.clone_dir.clone().or(self.clone_dir.clone()),
+            clone: Some(
+                self.clone
+                    .clone()
+                    .unwrap_or_default()
+                    .merge(&RoozCloneCfg::from(cli)),
+            ),
             privileged: cli.privileged.or(self.privileged),
+            auto_ports: cli.auto_ports.then_some(true).or(self.auto_ports),
             caches: Self::extend_if_any(self.caches.clone(), cli.caches.clone()),
             ..self.clone()
         }
@@ -190,18 +958,69 @@ impl RoozCfg {
 
     pub fn from_config(&mut self, config: &RoozCfg) -> () {
         *self = RoozCfg {
+            extends: Self::extend_if_any(self.extends.clone(), config.extends.clone()),
             vars: Self::extend_if_any(self.vars.clone(), config.vars.clone()),
             secrets: Self::extend_if_any(self.secrets.clone(), config.secrets.clone()),
             git_ssh_url: config.git_ssh_url.clone().or(self.git_ssh_url.clone()),
+            clone_dir: config.clone_dir.clone().or(self.clone_dir.clone()),
+            clone: match (&self.clone, &config.clone) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => b.clone().or(a.clone()),
+            },
             extra_repos: Self::extend_if_any(self.extra_repos.clone(), config.extra_repos.clone()),
+            engine: config.engine.clone().or(self.engine.clone()),
             image: config.image.clone().or(self.image.clone()),
+            pull: config.pull.or(self.pull),
+            platform: config.platform.clone().or(self.platform.clone()),
             caches: Self::extend_if_any(self.caches.clone(), config.caches.clone()),
+            volumes: Self::extend_if_any(self.volumes.clone(), config.volumes.clone()),
             shell: config.shell.clone().or(self.shell.clone()),
             user: config.user.clone().or(self.user.clone()),
             ports: Self::extend_if_any(self.ports.clone(), config.ports.clone()),
+            auto_ports: config.auto_ports.or(self.auto_ports),
+            bind_address: config.bind_address.clone().or(self.bind_address.clone()),
             privileged: config.privileged.clone().or(self.privileged.clone()),
+            docker: config.docker.or(self.docker),
+            security_opts: Self::extend_if_any(
+                self.security_opts.clone(),
+                config.security_opts.clone(),
+            ),
+            cap_add: Self::extend_if_any(self.cap_add.clone(), config.cap_add.clone()),
+            cap_drop: Self::extend_if_any(self.cap_drop.clone(), config.cap_drop.clone()),
+            read_only_rootfs: config.read_only_rootfs.or(self.read_only_rootfs),
+            dns: Self::extend_if_any(self.dns.clone(), config.dns.clone()),
+            dns_search: Self::extend_if_any(self.dns_search.clone(), config.dns_search.clone()),
+            extra_hosts: Self::extend_if_any(self.extra_hosts.clone(), config.extra_hosts.clone()),
+            idmap: config.idmap.or(self.idmap),
+            mux: config.mux.or(self.mux),
+            prompt: config.prompt.or(self.prompt),
+            shell_init: config.shell_init.clone().or(self.shell_init.clone()),
+            home_from_image: config.home_from_image.or(self.home_from_image),
+            restart: config.restart.or(self.restart),
+            network: config.network.clone().or(self.network.clone()),
+            networks: Self::extend_if_any(self.networks.clone(), config.networks.clone()),
+            aliases: Self::extend_if_any(self.aliases.clone(), config.aliases.clone()),
+            labels: Self::extend_if_any(self.labels.clone(), config.labels.clone()),
+            expose: match (&self.expose, &config.expose) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => b.clone().or(a.clone()),
+            },
             env: Self::extend_if_any(self.env.clone(), config.env.clone()),
             sidecars: Self::extend_if_any(self.sidecars.clone(), config.sidecars.clone()),
+            containers: Self::extend_if_any(self.containers.clone(), config.containers.clone()),
+            init_containers: Self::extend_if_any(
+                self.init_containers.clone(),
+                config.init_containers.clone(),
+            ),
+            git: config.git.clone().or(self.git.clone()),
+            ide: config.ide.clone().or(self.ide.clone()),
+            dotfiles: config.dotfiles.clone().or(self.dotfiles.clone()),
+            sync: config.sync.clone().or(self.sync.clone()),
+            clean: config.clean.clone().or(self.clean.clone()),
+            proxy: match (&config.proxy, &self.proxy) {
+                (Some(c), Some(s)) => Some(c.or(s)),
+                (c, s) => c.clone().or(s.clone()),
+            },
         }
     }
 
@@ -222,6 +1041,20 @@ impl RoozCfg {
             .or(cli_cfg.clone().map(|c| c.git_ssh_url).flatten())
     }
 
+    pub fn clone_dir(cli: &WorkParams, cli_cfg: &Option<RoozCfg>) -> Option<String> {
+        cli.clone_dir
+            .clone()
+            .or(cli_cfg.as_ref().and_then(|c| c.clone_dir.clone()))
+    }
+
+    pub fn clone_options(cli: &WorkParams, cli_cfg: &Option<RoozCfg>) -> RoozCloneCfg {
+        cli_cfg
+            .as_ref()
+            .and_then(|c| c.clone.clone())
+            .unwrap_or_default()
+            .merge(&RoozCloneCfg::from(cli))
+    }
+
     pub fn parse_ports<'a>(
         map: &'a mut HashMap<String, Option<String>>,
         ports: Option<Vec<String>>,
@@ -229,54 +1062,174 @@ impl RoozCfg {
         match ports {
             None => map,
             Some(ports) => {
-                for (source, target) in ports.iter().map(RoozCfg::parse_port) {
-                    map.insert(source.to_string(), target.map(|p| p.to_string()));
+                // invalid mappings are rejected by validate() before getting here.
+                // Host ports with an address are kept as ip:port
+                for (source, target, address) in
+                    ports.iter().filter_map(|p| RoozCfg::parse_port(p).ok())
+                {
+                    let target = match (target, address) {
+                        (Some(port), Some(ip)) => Some(SocketAddr::new(ip, port).to_string()),
+                        (port, _) => port.map(|p| p.to_string()),
+                    };
+                    map.insert(source.to_string(), target);
                 }
                 map
             }
         }
     }
 
-    fn parse_port(port_mapping: &String) -> (u16, Option<u16>) {
-        match port_mapping.split(":").collect::<Vec<_>>().as_slice() {
-            &[a] => (a.parse::<u16>().unwrap(), None),
-            &[a, b] => (a.parse::<u16>().unwrap(), Some(b.parse::<u16>().unwrap())),
-            _ => panic!("Invalid port mapping specification: {}", port_mapping),
+    // '*' stands for all interfaces. IPv6 addresses may be wrapped in brackets
+    pub fn parse_address(address: &str) -> Result<IpAddr, RoozError> {
+        match address {
+            "*" => Ok(IpAddr::from([0, 0, 0, 0])),
+            a => a
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .map_err(|_| RoozError::Config(format!("invalid bind address: {}", address))),
         }
     }
 
-    pub fn expand_vars(&mut self) -> Result<(), AnyError> {
-        let vars_and_secrets = match (&self.vars, &self.secrets) {
-            (None, None) => LinkedHashMap::<String, String>::new(),
-            (None, Some(secrets)) => secrets.clone(),
-            (Some(vars), None) => vars.clone(),
-            (Some(vars), Some(secrets)) => {
-                if let Some(duplicate_key) =
-                    vars.keys().find(|k| secrets.contains_key(&k.to_string()))
-                {
-                    panic!(
-                        "The key: '{}' can be only defined in either vars or secrets.",
-                        &duplicate_key.to_string()
-                    )
-                }
+    fn parse_port(port_mapping: &String) -> Result<(u16, Option<u16>, Option<IpAddr>), RoozError> {
+        let invalid = || RoozError::Config(format!("invalid port mapping: {}", port_mapping));
+        let port = |p: &str| p.parse::<u16>().map_err(|_| invalid());
+        // split from the right so the address may contain colons
+        match port_mapping.rsplitn(3, ':').collect::<Vec<_>>().as_slice() {
+            &[a] => Ok((port(a)?, None, None)),
+            &[b, a] => Ok((port(a)?, Some(port(b)?), None)),
+            &[b, a, ip] => Ok((
+                port(a)?,
+                Some(port(b)?),
+                Some(Self::parse_address(ip).map_err(|_| invalid())?),
+            )),
+            _ => Err(invalid()),
+        }
+    }
 
-                let mut secrets = secrets.clone();
-                secrets.extend(vars.clone());
-                secrets
+    fn validate(&self) -> Result<(), RoozError> {
+        let ports = self
+            .ports
+            .iter()
+            .flatten()
+            .chain(
+                self.sidecars
+                    .iter()
+                    .flatten()
+                    .flat_map(|(_, s)| s.ports.iter().flatten()),
+            )
+            .chain(
+                self.containers
+                    .iter()
+                    .flatten()
+                    .flat_map(|(_, c)| c.ports.iter().flatten()),
+            );
+        for p in ports {
+            RoozCfg::parse_port(p)?;
+        }
+        if let Some(address) = &self.bind_address {
+            RoozCfg::parse_address(address)?;
+        }
+        for (name, s) in self.sidecars.iter().flatten() {
+            match s.replicas {
+                Some(0) => {
+                    return Err(RoozError::Config(format!(
+                        "sidecar '{}': replicas must be at least 1",
+                        name
+                    )))
+                }
+                Some(1) | None => (),
+                Some(_) => {
+                    for p in s.ports.iter().flatten() {
+                        if let (_, Some(_), _) = RoozCfg::parse_port(p)? {
+                            return Err(RoozError::Config(format!(
+                                "sidecar '{}': host port {} can't be bound by multiple replicas",
+                                name, p
+                            )));
+                        }
+                    }
+                }
             }
-        };
+        }
+        let extra_hosts = self.extra_hosts.iter().flatten().chain(
+            self.sidecars
+                .iter()
+                .flatten()
+                .flat_map(|(_, s)| s.extra_hosts.iter().flatten()),
+        );
+        for h in extra_hosts {
+            match h.split_once(':') {
+                Some((host, ip)) if !host.is_empty() && !ip.is_empty() => (),
+                _ => {
+                    return Err(RoozError::Config(format!(
+                        "invalid extra host: '{}'. Use the host:ip format",
+                        h
+                    )))
+                }
+            }
+        }
+        if let Some(RoozDotfilesCfg {
+            repo: Some(_),
+            volume: Some(_),
+            ..
+        }) = &self.dotfiles
+        {
+            return Err(RoozError::Config(
+                "dotfiles: either repo or volume can be set".into(),
+            ));
+        }
+        if self.docker.and_then(|d| d.mode()) == Some(RoozDockerMode::Dind)
+            && self
+                .sidecars
+                .iter()
+                .flatten()
+                .any(|(name, _)| name == "docker")
+        {
+            return Err(RoozError::Config(
+                "docker: the dind sidecar is named docker, rename the docker sidecar".into(),
+            ));
+        }
+        if self.sync.as_ref().is_some_and(|s| s.path.trim().is_empty()) {
+            return Err(RoozError::Config("sync: path can't be empty".into()));
+        }
+        Ok(())
+    }
+
+    pub fn expand_vars(&mut self) -> Result<(), AnyError> {
+        let vars = self.vars.clone().unwrap_or_default();
+        let secrets = self.secrets.clone().unwrap_or_default();
+        if let Some(duplicate_key) = vars.keys().find(|k| secrets.contains_key(*k)) {
+            return Err(RoozError::Config(format!(
+                "the key: '{}' can be only defined in either vars or secrets",
+                duplicate_key
+            ))
+            .into());
+        }
 
-        let cfg_string = &self.to_string(FileFormat::Yaml)?;
+        let markers = SecretMarkers::new(secrets.keys());
+        let mut built_vars = LinkedHashMap::<String, String>::new();
+        for name in secrets.keys() {
+            built_vars.insert(
+                name.to_string(),
+                markers.marker(name).cloned().unwrap_or_default(),
+            );
+        }
+        let mut masked = self.clone();
+        masked.secrets = self.secrets.as_ref().map(|_| built_vars.clone());
+
+        let cfg_string = &masked.to_string(FileFormat::Yaml)?;
         let mut reg = Handlebars::new();
         reg.register_escape_fn(no_escape);
-        let mut built_vars = LinkedHashMap::<String, String>::new();
+        markers.register_helper(&mut reg);
 
-        for (k, v) in vars_and_secrets {
+        for (k, v) in vars {
             built_vars.insert(k.to_string(), reg.render_template(&v, &built_vars)?);
         }
 
         let rendered = reg.render_template(&cfg_string, &built_vars)?;
-        let s = RoozCfg::from_string(&rendered, FileFormat::Yaml)?;
+        let mut value = serde_yaml::from_str::<serde_yaml::Value>(&rendered)?;
+        markers.reveal_in(&mut value, &secrets.into_iter().collect())?;
+        let s = serde_yaml::from_value::<RoozCfg>(value)?;
+        s.validate()?;
         *self = s;
 
         Ok(())
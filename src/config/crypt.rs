@@ -1,17 +1,102 @@
+use std::collections::HashMap;
+
 use super::config::RoozCfg;
-use crate::{api::ConfigApi, model::types::AnyError};
+use crate::{
+    api::ConfigApi,
+    model::{error::RoozError, types::AnyError},
+    util::{labels, redact},
+};
 use age::x25519::Identity;
 use linked_hash_map::LinkedHashMap;
+use rand::{thread_rng, Rng};
+
+const GENERATE: &str = "generate";
+const DEFAULT_LENGTH: usize = 32;
+
+// e.g. generate, generate:hex or generate:alphanumeric:24
+fn is_generated(value: &str) -> bool {
+    value == GENERATE || value.starts_with("generate:")
+}
+
+fn generate(spec: &str) -> Result<String, RoozError> {
+    let mut parts = spec.split(':').skip(1);
+    let charset = match parts.next().unwrap_or("alphanumeric") {
+        "alphanumeric" => "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+        "hex" => "0123456789abcdef",
+        "numeric" => "0123456789",
+        other => {
+            return Err(RoozError::Config(format!(
+                "unknown charset '{}' in '{}'. Use alphanumeric, hex or numeric",
+                other, spec
+            )))
+        }
+    }
+    .as_bytes();
+    let length = match parts.next() {
+        Some(l) => l
+            .parse::<usize>()
+            .ok()
+            .filter(|l| *l > 0)
+            .ok_or(RoozError::Config(format!("invalid length in '{}'", spec)))?,
+        None => DEFAULT_LENGTH,
+    };
+    let mut rng = thread_rng();
+    Ok((0..length)
+        .map(|_| charset[rng.gen_range(0..charset.len())] as char)
+        .collect())
+}
+
+// the generated secrets, still encrypted, of workspaces created before the workspace-config volume
+pub fn stored_generated(
+    labels: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, AnyError> {
+    Ok(match labels.get(labels::GENERATED_SECRETS) {
+        Some(json) => serde_json::from_str(json)?,
+        None => HashMap::new(),
+    })
+}
 
 impl<'a> ConfigApi<'a> {
+    // swaps generate:... secrets for encrypted values: the ones generated for the workspace before
+    // or fresh ones. Returns them so they can be stored with the workspace
+    pub fn generate_secrets(
+        &self,
+        config: &mut RoozCfg,
+        stored: &HashMap<String, String>,
+        identity: &Identity,
+    ) -> Result<HashMap<String, String>, AnyError> {
+        let mut generated = HashMap::new();
+        for (k, v) in config.secrets.iter_mut().flatten() {
+            if !is_generated(v) {
+                continue;
+            }
+            let encrypted = match stored.get(k) {
+                Some(s) => s.to_string(),
+                None => {
+                    log::debug!("Generating secret: {}", k);
+                    self.crypt.encrypt(generate(v)?, &identity.to_public())?
+                }
+            };
+            *v = encrypted.clone();
+            generated.insert(k.to_string(), encrypted);
+        }
+        Ok(generated)
+    }
+
     pub async fn decrypt(&self, config: &mut RoozCfg, identity: &Identity) -> Result<(), AnyError> {
         config.secrets = match config.secrets.clone() {
             Some(secrets) if secrets.len() > 0 => {
                 log::debug!("Decrypting secrets");
                 let mut ret = LinkedHashMap::<String, String>::new();
                 for (k, v) in secrets.iter() {
-                    ret.insert(k.to_string(), self.crypt.decrypt(identity, v)?);
+                    // the spec stays as is until the workspace gets created
+                    let value = match is_generated(v) {
+                        true => v.to_string(),
+                        false => self.crypt.decrypt(identity, v)?,
+                    };
+                    ret.insert(k.to_string(), value);
                 }
+                redact::register(ret.values().cloned());
                 Some(ret)
             }
             Some(empty) => Some(empty),
@@ -20,14 +105,47 @@ impl<'a> ConfigApi<'a> {
         Ok(())
     }
 
+    // only for redact::redact to mask the values, the config stays encrypted
+    pub async fn register_secrets(
+        &self,
+        config: &RoozCfg,
+        identity: &Identity,
+    ) -> Result<(), AnyError> {
+        if Self::has_secrets(config) {
+            self.decrypt(&mut config.clone(), identity).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn secret(
+        &self,
+        config: &RoozCfg,
+        key: &str,
+        identity: &Identity,
+    ) -> Result<Option<String>, AnyError> {
+        match config.secrets.as_ref().and_then(|s| s.get(key)) {
+            Some(v) if !is_generated(v) => {
+                let secret = self.crypt.decrypt(identity, v)?;
+                redact::register([secret.clone()]);
+                Ok(Some(secret))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn has_secrets(config: &RoozCfg) -> bool {
+        config.secrets.as_ref().is_some_and(|s| !s.is_empty())
+    }
+
     pub async fn encrypt(&self, config: &mut RoozCfg, identity: &Identity) -> Result<(), AnyError> {
         let mut encrypted_secrets = LinkedHashMap::<String, String>::new();
         if let Some(edited_secrets) = config.clone().secrets {
             for (k, v) in edited_secrets {
-                encrypted_secrets.insert(
-                    k.to_string(),
-                    self.crypt.encrypt(v.to_string(), &identity.to_public())?,
-                );
+                let value = match is_generated(&v) {
+                    true => v.to_string(),
+                    false => self.crypt.encrypt(v.to_string(), &identity.to_public())?,
+                };
+                encrypted_secrets.insert(k.to_string(), value);
             }
         };
         config.secrets = if encrypted_secrets.len() > 0 {
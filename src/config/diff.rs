@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use colored::Colorize;
+use serde::Serialize;
+use serde_yaml::Value;
+
+use crate::{config::runtime::RuntimeConfig, model::types::AnyError, util::redact};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        path: String,
+        value: String,
+    },
+    Removed {
+        path: String,
+        value: String,
+    },
+    Modified {
+        path: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. } => path,
+            Change::Removed { path, .. } => path,
+            Change::Modified { path, .. } => path,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        match self {
+            Change::Added { path, value } => format!("+ {}: {}", path, value).green().to_string(),
+            Change::Removed { path, value } => format!("- {}: {}", path, value).red().to_string(),
+            Change::Modified { path, old, new } => {
+                format!("~ {}: {} -> {}", path, old.red(), new.green())
+            }
+        }
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "~".into(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.to_string(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+fn flatten_into(prefix: &str, value: &Value, out: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Mapping(m) if !m.is_empty() => {
+            for (k, v) in m {
+                let key = scalar(k);
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_into(&path, v, out);
+            }
+        }
+        Value::Sequence(s) if !s.is_empty() => {
+            for (i, v) in s.iter().enumerate() {
+                flatten_into(&format!("{}[{}]", prefix, i), v, out);
+            }
+        }
+        Value::Tagged(t) => flatten_into(prefix, &t.value, out),
+        Value::Mapping(_) | Value::Sequence(_) => (),
+        _ => {
+            out.insert(prefix.to_string(), scalar(value));
+        }
+    }
+}
+
+/// Flattens any serializable value into a sorted map of `a.b[0].c` style paths to scalar values
+pub fn flatten<T: Serialize>(value: &T) -> Result<BTreeMap<String, String>, AnyError> {
+    let mut out = BTreeMap::new();
+    flatten_into("", &serde_yaml::to_value(value)?, &mut out);
+    Ok(out)
+}
+
+/// Diffs two runtime configs. With `mask` the registered secret values are masked on both sides
+pub fn runtime_config(
+    old: &RuntimeConfig,
+    new: &RuntimeConfig,
+    mask: bool,
+) -> Result<Vec<Change>, AnyError> {
+    let mut left = flatten(old)?;
+    let mut right = flatten(new)?;
+    if mask {
+        for value in left.values_mut().chain(right.values_mut()) {
+            *value = redact::redact(value);
+        }
+    }
+    Ok(diff(&left, &right))
+}
+
+pub fn diff(left: &BTreeMap<String, String>, right: &BTreeMap<String, String>) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (path, old) in left {
+        match right.get(path) {
+            None => changes.push(Change::Removed {
+                path: path.to_string(),
+                value: old.to_string(),
+            }),
+            Some(new) if new != old => changes.push(Change::Modified {
+                path: path.to_string(),
+                old: old.to_string(),
+                new: new.to_string(),
+            }),
+            Some(_) => (),
+        }
+    }
+    for (path, new) in right {
+        if !left.contains_key(path) {
+            changes.push(Change::Added {
+                path: path.to_string(),
+                value: new.to_string(),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+pub fn print(changes: &[Change]) {
+    if changes.is_empty() {
+        println!("{}", "No differences".green());
+    }
+    for c in changes {
+        println!("{}", c.render());
+    }
+}
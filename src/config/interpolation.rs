@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use rand::{thread_rng, Rng};
+use serde_yaml::Value;
+
+use crate::model::error::RoozError;
+
+pub const SECRET_HELPER: &str = "secret";
+
+// secrets never get rendered into the config text. Templates see a marker instead, which gets swapped
+// for the value once the config is parsed, so the values can't break the syntax and every field a
+// secret ends up in is known
+pub struct SecretMarkers {
+    markers: HashMap<String, String>,
+}
+
+impl SecretMarkers {
+    pub fn new<'a>(names: impl Iterator<Item = &'a String>) -> Self {
+        let nonce = format!("{:016x}", thread_rng().gen::<u64>());
+        SecretMarkers {
+            markers: names
+                .enumerate()
+                .map(|(i, name)| (name.to_string(), format!("__rooz_secret_{}_{}__", nonce, i)))
+                .collect(),
+        }
+    }
+
+    pub fn marker(&self, name: &str) -> Option<&String> {
+        self.markers.get(name)
+    }
+
+    // {{ secret "name" }} fails on names not declared in secrets, unlike {{ name }}
+    pub fn register_helper(&self, reg: &mut Handlebars) {
+        let names = self.markers.keys().cloned().collect::<HashSet<_>>();
+        reg.register_helper(
+            SECRET_HELPER,
+            Box::new(
+                move |h: &Helper,
+                      _: &Handlebars,
+                      ctx: &Context,
+                      _: &mut RenderContext,
+                      out: &mut dyn Output|
+                      -> HelperResult {
+                    let name = h
+                        .param(0)
+                        .and_then(|p| p.value().as_str())
+                        .ok_or(RenderErrorReason::ParamNotFoundForIndex(SECRET_HELPER, 0))?;
+                    if !names.contains(name) {
+                        return Err(RenderErrorReason::Other(format!(
+                            "secret not found: '{}'",
+                            name
+                        ))
+                        .into());
+                    }
+                    let marker = ctx.data().get(name).and_then(|v| v.as_str());
+                    out.write(marker.unwrap_or_default())?;
+                    Ok(())
+                },
+            ),
+        );
+    }
+
+    fn find(&self, text: &str) -> Option<&str> {
+        self.markers
+            .iter()
+            .find(|(_, m)| text.contains(m.as_str()))
+            .map(|(name, _)| name.as_str())
+    }
+
+    fn reveal(&self, text: &str, secrets: &HashMap<String, String>) -> String {
+        self.markers
+            .iter()
+            .fold(text.to_string(), |text, (name, marker)| {
+                match (text.contains(marker.as_str()), secrets.get(name)) {
+                    (true, Some(value)) => text.replace(marker.as_str(), value),
+                    _ => text,
+                }
+            })
+    }
+
+    // replaces the markers with the secret values, failing on fields that are not secret-safe
+    pub fn reveal_in(
+        &self,
+        value: &mut Value,
+        secrets: &HashMap<String, String>,
+    ) -> Result<(), RoozError> {
+        self.walk(value, &mut Vec::new(), secrets)
+    }
+
+    fn walk(
+        &self,
+        value: &mut Value,
+        path: &mut Vec<String>,
+        secrets: &HashMap<String, String>,
+    ) -> Result<(), RoozError> {
+        match value {
+            Value::String(text) => {
+                if let Some(name) = self.find(text) {
+                    if !secret_safe(path) {
+                        return Err(RoozError::Config(format!(
+                            "secret '{}' can't be used in {}. Secrets can only be interpolated into env values",
+                            name,
+                            path.join(".")
+                        )));
+                    }
+                    *text = self.reveal(text, secrets);
+                }
+            }
+            Value::Sequence(items) => {
+                for (i, item) in items.iter_mut().enumerate() {
+                    path.push(i.to_string());
+                    self.walk(item, path, secrets)?;
+                    path.pop();
+                }
+            }
+            Value::Mapping(map) => {
+                for (key, item) in map.iter_mut() {
+                    let key = key.as_str().unwrap_or_default();
+                    path.push(key.to_string());
+                    if let Some(name) = self.find(key) {
+                        return Err(RoozError::Config(format!(
+                            "secret '{}' can't be used as a key ({})",
+                            name,
+                            path.join(".")
+                        )));
+                    }
+                    self.walk(item, path, secrets)?;
+                    path.pop();
+                }
+            }
+            Value::Tagged(tagged) => self.walk(&mut tagged.value, path, secrets)?,
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+// env values end up in the container's environment only. Vars and secrets are just carriers
+fn secret_safe(path: &[String]) -> bool {
+    matches!(
+        path.iter()
+            .map(|p| p.as_str())
+            .collect::<Vec<_>>()
+            .as_slice(),
+        ["vars" | "secrets" | "env", _]
+            | ["sidecars" | "containers" | "init_containers", _, "env", _]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_yaml::Value;
+
+    use super::SecretMarkers;
+    use crate::{
+        config::{
+            config::{FileFormat, RoozCfg},
+            runtime::RuntimeConfig,
+        },
+        util::{
+            labels::Labels,
+            redact::{self, MASK},
+        },
+    };
+
+    // the secrets registry is global, so every test uses values of its own
+    fn expand(config: &str) -> Result<RoozCfg, String> {
+        let mut cfg = RoozCfg::default();
+        cfg.from_config(&RoozCfg::from_string(config, FileFormat::Yaml).unwrap());
+        cfg.expand_vars().map_err(|e| e.to_string())?;
+        Ok(cfg)
+    }
+
+    fn secrets(values: &[(&str, &str)]) -> (SecretMarkers, HashMap<String, String>) {
+        let names = values
+            .iter()
+            .map(|(n, _)| n.to_string())
+            .collect::<Vec<_>>();
+        let secrets = values
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+        (SecretMarkers::new(names.iter()), secrets)
+    }
+
+    #[test]
+    fn reveals_markers_in_env_values_only() {
+        let (markers, secrets) = secrets(&[("token", "t0ken-value")]);
+        let marker = markers.marker("token").unwrap();
+        let mut value = serde_yaml::from_str::<Value>(&format!(
+            "env:\n  TOKEN: Bearer {}\n  PLAIN: marker-free\n",
+            marker
+        ))
+        .unwrap();
+        markers.reveal_in(&mut value, &secrets).unwrap();
+        assert_eq!(value["env"]["TOKEN"].as_str(), Some("Bearer t0ken-value"));
+        assert_eq!(value["env"]["PLAIN"].as_str(), Some("marker-free"));
+    }
+
+    #[test]
+    fn rejects_secrets_outside_env_values() {
+        let (markers, secrets) = secrets(&[("token", "t0ken-value")]);
+        let marker = markers.marker("token").unwrap();
+        for config in [
+            format!("labels:\n  team: {}\n", marker),
+            format!("image: {}\n", marker),
+            format!("sidecars:\n  db:\n    command: [{}]\n", marker),
+            format!("env:\n  {}: value\n", marker),
+        ] {
+            let mut value = serde_yaml::from_str::<Value>(&config).unwrap();
+            let error = markers.reveal_in(&mut value, &secrets).unwrap_err();
+            assert!(error.to_string().contains("secret 'token'"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn masks_secrets_in_env_labels_and_config_show() {
+        let cfg = expand(
+            r#"
+vars:
+  greeting: hello
+secrets:
+  api_key: s3cr3t-api-key
+env:
+  API_KEY: 'key={{ secret "api_key" }}'
+  GREETING: "{{ greeting }} world"
+sidecars:
+  db:
+    image: postgres
+    env:
+      PASSWORD: "{{ api_key }}"
+"#,
+        )
+        .unwrap();
+        let runtime = RuntimeConfig::from(&cfg);
+        assert_eq!(runtime.env["API_KEY"], "key=s3cr3t-api-key");
+
+        // decrypting registers the values, like rooz new does
+        redact::register(["s3cr3t-api-key".to_string()]);
+        let masked = runtime.masked();
+        assert_eq!(masked.env["API_KEY"], format!("key={}", MASK));
+        assert_eq!(masked.env["GREETING"], "hello world");
+        assert_eq!(
+            masked.sidecars["db"].env.as_ref().unwrap()["PASSWORD"],
+            MASK
+        );
+
+        let label = Labels::default()
+            .with_runtime_config(masked)
+            .runtime_config
+            .unwrap()
+            .value;
+        assert!(!label.contains("s3cr3t-api-key"));
+        assert!(label.contains("hello world"));
+
+        // rooz config show --part runtime
+        let shown = redact::redact(&runtime.to_string().unwrap());
+        assert!(!shown.contains("s3cr3t-api-key"));
+        assert!(shown.contains(&format!("key={}", MASK)));
+        assert!(shown.contains("hello world"));
+    }
+
+    #[test]
+    fn passes_values_through_without_secrets() {
+        let cfg = expand(
+            r#"
+vars:
+  name: plain-value-1
+env:
+  NAME: "{{ name }}"
+labels:
+  owner: "{{ name }}"
+"#,
+        )
+        .unwrap();
+        let runtime = RuntimeConfig::from(&cfg);
+        assert_eq!(runtime.env["NAME"], "plain-value-1");
+        assert_eq!(runtime.labels["owner"], "plain-value-1");
+        assert_eq!(runtime.masked().env["NAME"], "plain-value-1");
+        assert!(redact::redact(&runtime.to_string().unwrap()).contains("plain-value-1"));
+    }
+
+    #[test]
+    fn rejects_undeclared_secrets() {
+        let error = expand(
+            r#"
+vars:
+  name: value
+env:
+  NAME: '{{ secret "name" }}'
+"#,
+        )
+        .unwrap_err();
+        assert!(error.contains("secret not found: 'name'"), "{}", error);
+    }
+}
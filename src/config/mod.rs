@@ -1,3 +1,6 @@
 pub mod config;
 pub mod crypt;
+pub mod diff;
+pub mod interpolation;
 pub mod runtime;
+pub mod system;
@@ -1,36 +1,145 @@
-use super::config::{RoozCfg, RoozSidecar};
-use crate::constants;
-use crate::AnyError;
+use super::config::{
+    RoozCfg, RoozCleanCfg, RoozCloneCfg, RoozContainer, RoozDockerMode, RoozDotfilesCfg,
+    RoozExposeCfg, RoozGitCfg, RoozIdMap, RoozIdeCfg, RoozInitContainer, RoozMux, RoozProxyCfg,
+    RoozPullPolicy, RoozRestart, RoozSidecar, RoozSyncCfg, RoozVolumeCfg,
+};
+use crate::{constants, util::redact};
+use crate::{
+    model::types::{DnsSpec, SecuritySpec},
+    AnyError,
+};
+use linked_hash_map::LinkedHashMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RuntimeConfig {
+    #[serde(default)]
+    pub extends: Vec<String>,
     pub git_ssh_url: Option<String>,
+    pub clone_dir: Option<String>,
+    pub clone: Option<RoozCloneCfg>,
     pub extra_repos: Vec<String>,
+    pub engine: Option<String>,
     pub image: String,
+    #[serde(default)]
+    pub pull: RoozPullPolicy,
+    #[serde(default)]
+    pub platform: Option<String>,
     pub caches: Vec<String>,
+    #[serde(default)]
+    pub volumes: HashMap<String, RoozVolumeCfg>,
     pub shell: Vec<String>,
     pub user: String,
+    // the home of an image's own user when it is not /home/<user>
+    #[serde(default)]
+    pub home: Option<String>,
     pub ports: HashMap<String, Option<String>>,
+    #[serde(default)]
+    pub auto_ports: bool,
+    pub bind_address: Option<String>,
     pub privileged: bool,
+    #[serde(default)]
+    pub docker: Option<RoozDockerMode>,
+    #[serde(default)]
+    pub security_opts: Vec<String>,
+    #[serde(default)]
+    pub cap_add: Vec<String>,
+    #[serde(default)]
+    pub cap_drop: Vec<String>,
+    #[serde(default)]
+    pub read_only_rootfs: bool,
+    #[serde(default)]
+    pub dns: Vec<String>,
+    #[serde(default)]
+    pub dns_search: Vec<String>,
+    #[serde(default)]
+    pub extra_hosts: Vec<String>,
+    #[serde(default)]
+    pub idmap: RoozIdMap,
+    pub mux: Option<RoozMux>,
+    pub prompt: Option<bool>,
+    pub shell_init: Option<String>,
+    #[serde(default)]
+    pub home_from_image: bool,
+    pub restart: Option<RoozRestart>,
+    pub network: Option<String>,
+    #[serde(default)]
+    pub networks: Vec<String>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    pub expose: Option<RoozExposeCfg>,
     pub env: HashMap<String, String>,
     pub sidecars: HashMap<String, RoozSidecar>,
+    #[serde(default)]
+    pub containers: HashMap<String, RoozContainer>,
+    #[serde(default)]
+    pub init_containers: LinkedHashMap<String, RoozInitContainer>,
+    pub git: Option<RoozGitCfg>,
+    #[serde(default)]
+    pub ide: Option<RoozIdeCfg>,
+    #[serde(default)]
+    pub dotfiles: Option<RoozDotfilesCfg>,
+    #[serde(default)]
+    pub sync: Option<RoozSyncCfg>,
+    #[serde(default)]
+    pub clean: Option<RoozCleanCfg>,
+    #[serde(default)]
+    pub proxy: Option<RoozProxyCfg>,
 }
 
 impl Default for RuntimeConfig {
     fn default() -> Self {
         Self {
+            extends: Vec::new(),
             git_ssh_url: None,
+            clone_dir: None,
+            clone: None,
             extra_repos: Vec::new(),
+            engine: None,
             image: constants::DEFAULT_IMAGE.into(),
+            pull: RoozPullPolicy::default(),
+            platform: None,
             caches: Vec::new(),
+            volumes: HashMap::new(),
             shell: vec![constants::DEFAULT_SHELL.into()],
             user: constants::DEFAULT_USER.into(),
+            home: None,
             ports: HashMap::new(),
+            auto_ports: false,
+            bind_address: None,
             privileged: false,
+            docker: None,
+            security_opts: Vec::new(),
+            cap_add: Vec::new(),
+            cap_drop: Vec::new(),
+            read_only_rootfs: false,
+            dns: Vec::new(),
+            dns_search: Vec::new(),
+            extra_hosts: Vec::new(),
+            idmap: RoozIdMap::default(),
+            mux: None,
+            prompt: None,
+            shell_init: None,
+            home_from_image: false,
+            restart: None,
+            network: None,
+            networks: Vec::new(),
+            aliases: Vec::new(),
+            labels: HashMap::new(),
+            expose: None,
             sidecars: HashMap::new(),
+            containers: HashMap::new(),
+            init_containers: LinkedHashMap::new(),
             env: HashMap::new(),
+            git: None,
+            ide: None,
+            dotfiles: None,
+            sync: None,
+            clean: None,
+            proxy: None,
         }
     }
 }
@@ -41,10 +150,24 @@ impl RuntimeConfig {
     ) -> Result<RuntimeConfig, Box<dyn std::error::Error + 'static>> {
         match toml::from_str(&config) {
             Ok(val) => Ok(val),
-            Err(e) => Err(Box::new(e)),
+            Err(e) => match Self::migrate(&config) {
+                Some(val) => {
+                    log::debug!("Filled in the runtime config of an older rooz version");
+                    Ok(val)
+                }
+                None => Err(Box::new(e)),
+            },
         }
     }
 
+    // older rooz versions didn't store the fields added since, some of which have no serde default
+    fn migrate(config: &str) -> Option<RuntimeConfig> {
+        let mut value = toml::Value::try_from(RuntimeConfig::default()).ok()?;
+        let stored = toml::from_str::<toml::Table>(config).ok()?;
+        value.as_table_mut()?.extend(stored);
+        value.try_into().ok()
+    }
+
     pub fn to_string(&self) -> Result<String, AnyError> {
         match toml::to_string(&self) {
             Ok(val) => Ok(val),
@@ -53,6 +176,64 @@ impl RuntimeConfig {
     }
 }
 
+impl RuntimeConfig {
+    // secrets can only end up in env values. The masked config is what goes into the labels
+    pub fn home_dir(&self) -> String {
+        self.home.clone().unwrap_or(format!("/home/{}", self.user))
+    }
+
+    // the dotfiles get installed by an init container running before the configured ones
+    pub fn with_dotfiles_install(&self) -> RuntimeConfig {
+        let mut cfg = self.clone();
+        if let Some(install) = self.dotfiles.as_ref().and_then(|d| d.install_container()) {
+            cfg.init_containers = [("dotfiles".to_string(), install)]
+                .into_iter()
+                .chain(self.init_containers.clone())
+                .collect();
+        }
+        cfg
+    }
+
+    pub fn masked(&self) -> RuntimeConfig {
+        let mask = |env: &mut LinkedHashMap<String, String>| {
+            for (_, v) in env.iter_mut() {
+                *v = redact::redact(v);
+            }
+        };
+        let mut cfg = self.clone();
+        for v in cfg.env.values_mut() {
+            *v = redact::redact(v);
+        }
+        cfg.sidecars
+            .values_mut()
+            .for_each(|s| s.env.iter_mut().for_each(mask));
+        cfg.containers
+            .values_mut()
+            .for_each(|c| c.env.iter_mut().for_each(mask));
+        cfg.init_containers
+            .iter_mut()
+            .for_each(|(_, c)| c.env.iter_mut().for_each(mask));
+        cfg
+    }
+
+    pub fn security(&self) -> SecuritySpec {
+        SecuritySpec {
+            security_opts: self.security_opts.clone(),
+            cap_add: self.cap_add.clone(),
+            cap_drop: self.cap_drop.clone(),
+            read_only_rootfs: self.read_only_rootfs,
+        }
+    }
+
+    pub fn dns(&self) -> DnsSpec {
+        DnsSpec {
+            dns: self.dns.clone(),
+            dns_search: self.dns_search.clone(),
+            extra_hosts: self.extra_hosts.clone(),
+        }
+    }
+}
+
 impl<'a> From<&'a RoozCfg> for RuntimeConfig {
     fn from(value: &'a RoozCfg) -> Self {
         let default = RuntimeConfig::default();
@@ -61,20 +242,31 @@ impl<'a> From<&'a RoozCfg> for RuntimeConfig {
         RoozCfg::parse_ports(&mut ports, value.clone().ports);
 
         RuntimeConfig {
+            extends: value.extends.clone().unwrap_or_default(),
             git_ssh_url: value.git_ssh_url.clone(),
+            clone_dir: value.clone_dir.clone(),
+            clone: value.clone.clone(),
             extra_repos: value
                 .extra_repos
                 .as_deref()
                 .unwrap_or(&default.extra_repos)
                 .to_vec(),
+            engine: value.engine.clone(),
             shell: value.shell.as_deref().unwrap_or(&default.shell).into(),
             image: value.image.as_deref().unwrap_or(&default.image).into(),
             user: value.user.as_deref().unwrap_or(&default.user).into(),
+            home: None,
             caches: {
                 let mut val = value.caches.as_deref().unwrap_or(&default.caches).to_vec();
                 val.dedup();
                 val
             },
+            volumes: value
+                .volumes
+                .iter()
+                .flatten()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<HashMap<_, _>>(),
             sidecars: value
                 .sidecars
                 .as_ref()
@@ -82,6 +274,13 @@ impl<'a> From<&'a RoozCfg> for RuntimeConfig {
                 .iter()
                 .map(|(k, v)| (k.clone(), v.clone()))
                 .collect::<HashMap<_, _>>(),
+            containers: value
+                .containers
+                .iter()
+                .flatten()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<HashMap<_, _>>(),
+            init_containers: value.init_containers.clone().unwrap_or_default(),
             env: value
                 .env
                 .as_ref()
@@ -90,7 +289,54 @@ impl<'a> From<&'a RoozCfg> for RuntimeConfig {
                 .map(|(k, v)| (k.clone(), v.clone()))
                 .collect::<HashMap<_, _>>(),
             ports,
+            auto_ports: value.auto_ports.unwrap_or(default.auto_ports),
+            // validated along with the config
+            bind_address: value
+                .bind_address
+                .as_deref()
+                .and_then(|a| RoozCfg::parse_address(a).ok())
+                .map(|a| a.to_string()),
             privileged: value.privileged.unwrap_or(default.privileged),
+            docker: value.docker.and_then(|d| d.mode()),
+            security_opts: value.security_opts.clone().unwrap_or_default(),
+            cap_add: value.cap_add.clone().unwrap_or_default(),
+            cap_drop: value.cap_drop.clone().unwrap_or_default(),
+            read_only_rootfs: value.read_only_rootfs.unwrap_or(default.read_only_rootfs),
+            dns: value.dns.clone().unwrap_or_default(),
+            dns_search: value.dns_search.clone().unwrap_or_default(),
+            extra_hosts: value.extra_hosts.clone().unwrap_or_default(),
+            idmap: value.idmap.unwrap_or(default.idmap),
+            mux: value.mux,
+            prompt: value.prompt,
+            shell_init: value.shell_init.clone(),
+            home_from_image: value.home_from_image.unwrap_or(default.home_from_image),
+            restart: value.restart,
+            pull: value.pull.unwrap_or(default.pull),
+            platform: value.platform.clone(),
+            network: value.network.clone(),
+            networks: {
+                let mut val = value.networks.clone().unwrap_or_default();
+                val.dedup();
+                val
+            },
+            expose: value.expose.clone(),
+            labels: value
+                .labels
+                .iter()
+                .flatten()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<HashMap<_, _>>(),
+            aliases: {
+                let mut val = value.aliases.clone().unwrap_or_default();
+                val.dedup();
+                val
+            },
+            git: value.git.clone(),
+            ide: value.ide.clone(),
+            dotfiles: value.dotfiles.clone(),
+            sync: value.sync.clone(),
+            clean: value.clean.clone(),
+            proxy: value.proxy.clone(),
             ..default
         }
     }
@@ -0,0 +1,285 @@
+use std::{env, fs, path::PathBuf};
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+
+use super::config::{FileFormat, RoozCfg, RoozProxyCfg};
+use crate::{
+    constants,
+    model::{error::RoozError, types::AnyError},
+    util::time::DateTime,
+};
+
+fn user_path(var: &str, fallback: &str, file: &str) -> PathBuf {
+    env::var(var)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(shellexpand::tilde(fallback).as_ref()))
+        .join("rooz")
+        .join(file)
+}
+
+fn read_toml<T: Default + for<'de> Deserialize<'de>>(path: &PathBuf) -> Result<T, AnyError> {
+    match fs::read_to_string(path) {
+        Ok(body) => Ok(toml::from_str(&body)
+            .map_err(|e| RoozError::Config(format!("{}: {}", path.display(), e)))?),
+        Err(_) => Ok(T::default()),
+    }
+}
+
+// the user-level config shared by all workspaces: ~/.config/rooz/config.toml
+#[derive(Debug, Default, Deserialize)]
+pub struct SystemConfig {
+    // name -> engine host, in the --host format
+    #[serde(default)]
+    pub engines: LinkedHashMap<String, String>,
+    // operations changing workspaces get recorded unless set to false
+    pub audit: Option<bool>,
+    // overrides the proxy env vars of the host
+    pub proxy: Option<RoozProxyCfg>,
+    // the default of --uid
+    pub uid: Option<String>,
+    pub notifications: Option<NotificationsCfg>,
+    // the image of the helper containers, e.g. from a registry mirror
+    pub helper_image: Option<String>,
+    // the default of --host-ssh
+    pub host_ssh: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationsCfg {
+    // gets the notification POSTed as JSON
+    pub webhook: Option<String>,
+    // runs via sh -c with the notification JSON on stdin
+    pub command: Option<String>,
+    // all events unless set
+    pub events: Option<Vec<String>>,
+}
+
+impl SystemConfig {
+    pub fn path() -> PathBuf {
+        user_path("XDG_CONFIG_HOME", "~/.config", "config.toml")
+    }
+
+    pub fn load() -> Result<Self, AnyError> {
+        read_toml(&Self::path())
+    }
+
+    pub fn helper_image(&self, cli: Option<&str>) -> String {
+        cli.or(self.helper_image.as_deref())
+            .unwrap_or(constants::DEFAULT_IMAGE)
+            .to_string()
+    }
+
+    pub fn host_proxy(&self) -> RoozProxyCfg {
+        self.proxy
+            .clone()
+            .unwrap_or_default()
+            .or(&RoozProxyCfg::from_env())
+    }
+
+    pub fn engine_host(&self, name: &str) -> Result<&str, RoozError> {
+        self.engines.get(name).map(|h| h.as_str()).ok_or_else(|| {
+            RoozError::Config(format!(
+                "unknown engine: '{}'. Engines are defined in {} under [engines]",
+                name,
+                Self::path().display()
+            ))
+        })
+    }
+}
+
+// remembers which named engine each workspace was created on so later commands go there too
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct WorkspaceEngines {
+    #[serde(default)]
+    workspaces: LinkedHashMap<String, String>,
+}
+
+impl WorkspaceEngines {
+    fn path() -> PathBuf {
+        user_path("XDG_STATE_HOME", "~/.local/state", "engines.toml")
+    }
+
+    pub fn load() -> Result<Self, AnyError> {
+        read_toml(&Self::path())
+    }
+
+    fn save(&self) -> Result<(), AnyError> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, workspace_key: &str) -> Option<&str> {
+        self.workspaces.get(workspace_key).map(|e| e.as_str())
+    }
+
+    pub fn record(workspace_key: &str, engine: &str) -> Result<(), AnyError> {
+        let mut state = Self::load()?;
+        state
+            .workspaces
+            .insert(workspace_key.to_string(), engine.to_string());
+        state.save()
+    }
+
+    pub fn forget(workspace_key: &str, engine: &str) -> Result<(), AnyError> {
+        let mut state = Self::load()?;
+        if state.get(workspace_key) == Some(engine) {
+            state.workspaces.remove(workspace_key);
+            state.save()?;
+        }
+        Ok(())
+    }
+}
+
+// when each workspace was last entered from this machine. Container labels can't be updated after
+// creation so it can't live on the work container
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LastUsed {
+    #[serde(default)]
+    workspaces: LinkedHashMap<String, String>,
+}
+
+impl LastUsed {
+    fn path() -> PathBuf {
+        user_path("XDG_STATE_HOME", "~/.local/state", "last-used.toml")
+    }
+
+    pub fn load() -> Result<Self, AnyError> {
+        read_toml(&Self::path())
+    }
+
+    pub fn get(&self, workspace_key: &str) -> Option<&str> {
+        self.workspaces.get(workspace_key).map(|t| t.as_str())
+    }
+
+    pub fn record(workspace_key: &str) -> Result<(), AnyError> {
+        let mut state = Self::load()?;
+        state
+            .workspaces
+            .insert(workspace_key.to_string(), DateTime::now().to_rfc3339());
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(&state)?)?;
+        Ok(())
+    }
+}
+
+// images can't be labelled once pulled from a registry, so the ones rooz pulled get recorded here
+// per engine ("default" for the unnamed one), with the workspaces using them
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PulledImages {
+    #[serde(default)]
+    engines: LinkedHashMap<String, LinkedHashMap<String, Vec<String>>>,
+}
+
+impl PulledImages {
+    const DEFAULT_ENGINE: &str = "default";
+
+    fn path() -> PathBuf {
+        user_path("XDG_STATE_HOME", "~/.local/state", "images.toml")
+    }
+
+    pub fn load() -> Result<Self, AnyError> {
+        read_toml(&Self::path())
+    }
+
+    fn save(&self) -> Result<(), AnyError> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    // image -> workspaces
+    pub fn get(&self, engine: Option<&str>) -> LinkedHashMap<String, Vec<String>> {
+        self.engines
+            .get(engine.unwrap_or(Self::DEFAULT_ENGINE))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // images pulled elsewhere only get the workspace added when rooz pulled them before
+    pub fn record(
+        engine: Option<&str>,
+        workspace_key: &str,
+        pulled: &[String],
+        used: &[&str],
+    ) -> Result<(), AnyError> {
+        let mut state = Self::load()?;
+        let images = state
+            .engines
+            .entry(engine.unwrap_or(Self::DEFAULT_ENGINE).to_string())
+            .or_insert_with(LinkedHashMap::new);
+        for image in pulled {
+            if !images.contains_key(image) {
+                images.insert(image.to_string(), Vec::new());
+            }
+        }
+        for image in used {
+            if let Some(workspaces) = images.get_mut(*image) {
+                if !workspaces.iter().any(|w| w == workspace_key) {
+                    workspaces.push(workspace_key.to_string());
+                }
+            }
+        }
+        state.save()
+    }
+
+    pub fn forget(engine: Option<&str>, removed: &[String]) -> Result<(), AnyError> {
+        let mut state = Self::load()?;
+        if let Some(images) = state
+            .engines
+            .get_mut(engine.unwrap_or(Self::DEFAULT_ENGINE))
+        {
+            for image in removed {
+                images.remove(image);
+            }
+            state.save()?;
+        }
+        Ok(())
+    }
+}
+
+// only local config files can be read before connecting. Git configs get checked after cloning
+fn config_engine(config_path: &str) -> Option<String> {
+    if config_path == "-" || config_path.contains("//") {
+        return None;
+    }
+    let format = FileFormat::from_path(config_path).ok()?;
+    let body = fs::read_to_string(config_path).ok()?;
+    RoozCfg::from_string(&body, format).ok()?.engine
+}
+
+// --engine -> the engine the workspace was created on -> the engine of a local config file
+pub fn resolve_engine(
+    engine: Option<&str>,
+    workspace_key: Option<&str>,
+    config_path: Option<&str>,
+) -> Result<Option<(String, String)>, AnyError> {
+    let name = match engine {
+        Some(e) => Some(e.to_string()),
+        None => {
+            let recorded = match workspace_key {
+                Some(key) => WorkspaceEngines::load()?.get(key).map(|e| e.to_string()),
+                None => None,
+            };
+            recorded.or(config_path.and_then(config_engine))
+        }
+    };
+    match name {
+        Some(name) => {
+            let host = SystemConfig::load()?.engine_host(&name)?.to_string();
+            Ok(Some((name, host)))
+        }
+        None => Ok(None),
+    }
+}
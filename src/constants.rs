@@ -6,3 +6,4 @@ pub const DEFAULT_CONTAINER_NAME: &'static str = "work";
 pub const ROOT_UID: &'static str = "0";
 pub const ROOT_USER: &'static str = "root";
 pub const WORK_DIR: &'static str = "/work";
+pub const MAX_PARALLEL: usize = 4;
@@ -6,56 +6,242 @@ mod constants;
 mod model;
 mod util;
 
-use std::io;
+use std::{io, process, time::Duration};
 
 use crate::{
     api::{Api, ContainerApi, ExecApi, GitApi, ImageApi, VolumeApi, WorkspaceApi},
     cli::{
         Cli,
         Commands::{
-            Code, Config, Enter, List, New, Remote, Remove, Start, Stop, System, Tmp, Update,
+            Archive, Backup, Cache, Code, Config, Context, DiffConfig, Enter, Idea, Inspect, List,
+            New, Remote, Remove, Restore, Run, Snapshot, Start, Stats, Status, Stop, System, Tmp,
+            Unarchive, Update, Watch,
         },
-        CompletionParams, ListParams, NewParams, RemoveParams, ShowConfigParams, StopParams,
-        TmpParams,
+        CompleteKind, CompleteParams, CompletionParams, ListParams, NewParams, RemoveParams,
+        ShowConfigParams, StopParams, TmpParams,
+    },
+    cmd::{
+        complete, context, new,
+        snapshot::SnapshotTarget::{File, Volume},
+    },
+    model::{
+        error::{Context as _, RoozError},
+        types::{AnyError, ReconnectPolicy},
+    },
+    util::{
+        backend::{self, ContainerBackend},
+        logging, progress, uid,
     },
-    cmd::remote,
-    model::types::AnyError,
-    util::backend::ContainerBackend,
 };
 
 use api::{ConfigApi, CryptApi};
-use bollard::Docker;
-use clap::{CommandFactory, Parser};
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
 use clap_complete::generate;
 use cli::{
-    CodeParams, EditConfigParams, EnterParams, StartParams, TemplateConfigParams, UpdateParams,
+    CodeParams, DiffConfigParams, EditConfigParams, EnterParams, IdeaParams, InspectParams,
+    RunParams, StartParams, StatsParams, StatusParams, TemplateConfigParams, UpdateParams,
+    WatchParams,
+};
+use cmd::update::{UpdateBatch, UpdateConfig, UpdateMode};
+use colored::Colorize;
+use config::{
+    config::{ConfigPath, ConfigSource, FileFormat},
+    system,
 };
-use cmd::update::UpdateMode;
-use config::config::{ConfigPath, ConfigSource, FileFormat};
+use tracing::Instrument;
 use util::labels::{self, Labels};
 
 #[tokio::main]
-async fn main() -> Result<(), AnyError> {
-    env_logger::init();
+async fn main() {
+    let matches = Cli::command().get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    logging::init(args.log_format, args.verbose);
 
     log::debug!("Started");
 
-    let args = Cli::parse();
+    let command = matches.subcommand_name().unwrap_or_default();
+    let result = run(args, &matches)
+        .instrument(tracing::debug_span!("command", id = %command))
+        .await;
 
+    if let Err(e) = result {
+        log::debug!("{:?}", e);
+        // the command already reported its own failure, only its exit status is passed on
+        if !matches!(e.downcast_ref(), Some(RoozError::ExitCode(_))) {
+            eprintln!("{} {}", "Error:".bold().red(), e);
+        }
+        process::exit(RoozError::exit_code_of(e.as_ref()));
+    }
+}
+
+async fn run(args: Cli, matches: &ArgMatches) -> Result<(), AnyError> {
     if let Cli {
         command:
             Remote(cli::RemoteParams {
+                command,
                 ssh_url,
                 local_docker_host,
+                daemon,
+                pid_file,
+                log_file,
+                only,
+                exclude_ports,
+                map,
+                check,
             }),
+        ..
     } = &args
     {
-        remote::remote(ssh_url, local_docker_host).await?
+        #[cfg(unix)]
+        return match (command, ssh_url, local_docker_host) {
+            (
+                Some(cli::RemoteCommands::Status(cli::RemoteStatusParams { local_docker_host })),
+                ..,
+            ) => cmd::remote::status(local_docker_host),
+            (None, Some(ssh_url), Some(local_docker_host)) => {
+                cmd::remote::remote(
+                    ssh_url,
+                    local_docker_host,
+                    daemon.then(|| cmd::remote::DaemonSpec {
+                        pid_file: pid_file.clone(),
+                        log_file: log_file.clone(),
+                    }),
+                    cmd::remote::TunnelSpec::parse(only, exclude_ports, map, check)?,
+                )
+                .await
+            }
+            _ => Err("rooz remote requires --ssh-url and DOCKER_HOST".into()),
+        };
+        #[cfg(windows)]
+        {
+            let _ = (
+                command,
+                daemon,
+                pid_file,
+                log_file,
+                only,
+                exclude_ports,
+                map,
+                check,
+            );
+            return Err(format!(
+                "rooz remote is not supported on Windows. Use it from WSL2 instead ({} -> {})",
+                ssh_url.as_deref().unwrap_or_default(),
+                local_docker_host.as_deref().unwrap_or_default()
+            )
+            .into());
+        }
     }
 
-    let connection = Docker::connect_with_local_defaults();
+    // kubectl talks to the cluster, so no container engine is needed
+    if let Cli {
+        command:
+            cli::Commands::K8s(cli::K8s {
+                command,
+                context,
+                namespace,
+            }),
+        ..
+    } = &args
+    {
+        let kubectl = cmd::k8s::Kubectl {
+            context: context.clone(),
+            namespace: namespace.clone(),
+            helper_image: system::SystemConfig::load()?.helper_image(args.helper_image.as_deref()),
+        };
+        return match command {
+            cli::K8sCommands::New(cli::K8sNewParams {
+                name,
+                config_path,
+                storage,
+                uid,
+                ssh_secret,
+                dry_run,
+            }) => kubectl.create(
+                name,
+                config_path.as_deref(),
+                storage,
+                &uid::resolve(uid.clone().or(system::SystemConfig::load()?.uid).as_deref())?,
+                ssh_secret,
+                *dry_run,
+            ),
+            cli::K8sCommands::Enter(cli::K8sEnterParams { name }) => kubectl.enter(name),
+            cli::K8sCommands::Rm(cli::K8sRemoveParams { name }) => kubectl.remove(name),
+            cli::K8sCommands::List(_) => kubectl.list(),
+        };
+    }
 
-    let docker = connection.expect("Docker API connection established");
+    if let Cli {
+        command:
+            System(cli::System {
+                command: cli::SystemCommands::Completion(CompletionParams { shell }),
+            }),
+        ..
+    } = &args
+    {
+        let mut cli = Cli::command()
+            .disable_help_flag(true)
+            .disable_help_subcommand(true);
+        let name = &cli.get_name().to_string();
+        let workspace_commands = complete::workspace_commands(&cli);
+        generate(*shell, &mut cli, name, &mut io::stdout());
+        if let Some(script) = complete::dynamic_script(*shell, name, &workspace_commands) {
+            println!("{}", script);
+        }
+        return Ok(());
+    }
+
+    if let Cli {
+        command:
+            System(cli::System {
+                command:
+                    cli::SystemCommands::Upgrade(cli::UpgradeParams {
+                        check,
+                        version,
+                        rollback,
+                    }),
+            }),
+        ..
+    } = &args
+    {
+        return match rollback {
+            true => cmd::upgrade::rollback(),
+            false => cmd::upgrade::upgrade(*check, version.as_deref()),
+        };
+    }
+
+    // works inside workspaces where there is no container engine access
+    if let Cli {
+        command: Context(cli::ContextParams { name: None, .. }),
+        ..
+    } = &args
+    {
+        return context::current();
+    }
+
+    // commands act on the workspace given by the name argument, if any
+    let sub_matches = matches.subcommand().map(|(_, m)| m);
+    let arg = |id: &str| {
+        sub_matches
+            .and_then(|m| m.try_get_one::<String>(id).ok().flatten())
+            .map(|v| v.as_str())
+    };
+    // an explicit --host wins over the engine recorded for the workspace or set in its config
+    let engine = match &args.host {
+        Some(_) => None,
+        None => system::resolve_engine(args.engine.as_deref(), arg("name"), arg("config_path"))?,
+    };
+    if let Some((name, host)) = &engine {
+        log::debug!("Engine: {} ({})", name, host);
+    }
+    let connection = backend::connect(
+        engine
+            .as_ref()
+            .map(|(_, host)| host.as_str())
+            .or(args.host.as_deref()),
+    );
+
+    let docker = connection.context("Could not connect to the container engine")?;
 
     log::debug!("Client ver: {}", &docker.client_version());
 
@@ -76,12 +262,23 @@ async fn main() -> Result<(), AnyError> {
     let exec_api = ExecApi {
         client: &docker,
         backend: &backend,
+        non_interactive: args.non_interactive,
+    };
+    let system_config = system::SystemConfig::load()?;
+    let image_api = ImageApi {
+        client: &docker,
+        offline: args.offline,
+        helper: system_config.helper_image(args.helper_image.as_deref()),
+    };
+    let progress = progress::reporter(args.quiet);
+    let volume_api = VolumeApi {
+        client: &docker,
+        progress,
     };
-    let image_api = ImageApi { client: &docker };
-    let volume_api = VolumeApi { client: &docker };
     let container_api = ContainerApi {
         client: &docker,
         backend: &backend,
+        engine: engine.as_ref().map(|(name, _)| name.as_str()),
     };
 
     let rooz = Api {
@@ -90,15 +287,26 @@ async fn main() -> Result<(), AnyError> {
         volume: &volume_api,
         container: &container_api,
         client: &docker,
+        progress,
     };
 
-    let crypt_api = CryptApi { api: &rooz };
+    let crypt_api = CryptApi {
+        api: &rooz,
+        non_interactive: args.non_interactive,
+        key: args.secrets_key,
+    };
 
-    let git_api = GitApi { api: &rooz };
+    let git_api = GitApi {
+        api: &rooz,
+        offline: args.offline,
+        proxy: system_config.host_proxy(),
+        host_ssh: args.host_ssh || system_config.host_ssh.unwrap_or(false),
+    };
 
     let config_api = ConfigApi {
         api: &rooz,
         crypt: &crypt_api,
+        non_interactive: args.non_interactive,
     };
 
     let workspace = WorkspaceApi {
@@ -108,28 +316,51 @@ async fn main() -> Result<(), AnyError> {
         crypt: &crypt_api,
     };
 
+    let audit = cmd::audit::operation(&args.command)
+        .filter(|_| system_config.audit.unwrap_or(true))
+        .map(|(operation, workspace_key)| {
+            cmd::audit::AuditEntry::new(operation, workspace_key, matches)
+        });
+
+    let event = cmd::notify::event(&args.command);
+
+    // the audit entry is written once the operation is done so it records how it went
+    let result = async {
     match args {
         Cli {
             command:
                 New(NewParams {
                     work,
                     name,
+                    name_from_branch,
                     config_path,
+                    format,
+                    adopt_volumes,
+                    dry_run,
                 }),
             ..
         } => {
             let config_source = match config_path {
                 Some(path) => Some(ConfigSource::Path {
-                    value: ConfigPath::from_str(&path)?,
+                    value: ConfigPath::from_cli(&path, format.map(FileFormat::from))?,
                 }),
                 None => None,
             };
 
+            // clap makes sure the name is given unless it comes from the branch
+            let name = match (name_from_branch, &work.git_ssh_url, &work.branch) {
+                (true, Some(url), Some(branch)) => {
+                    new::branch_workspace_key(name.as_deref(), url, branch)
+                }
+                _ => name.unwrap_or_default(),
+            };
+
             let labels = Labels {
                 workspace: Labels::workspace(&name),
                 role: Labels::role(labels::ROLE_WORK),
                 ..Default::default()
-            };
+            }
+            .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
 
             match workspace.api.container.get_single(&labels).await? {
                     Some(_) => Err(format!("Workspace already exists. Did you mean: rooz enter {}? Otherwise, use rooz update to modify the workspace.", name.clone())),
@@ -138,8 +369,27 @@ async fn main() -> Result<(), AnyError> {
 
             let identity = crypt_api.read_age_identity().await?;
 
+            if dry_run {
+                return workspace
+                    .plan(
+                        &name,
+                        &work,
+                        config_source,
+                        &identity,
+                        adopt_volumes.as_deref(),
+                    )
+                    .await;
+            }
+
             workspace
-                .new(&name, &work, config_source, false, &identity)
+                .new(
+                    &name,
+                    &work,
+                    config_source,
+                    false,
+                    &identity,
+                    adopt_volumes.as_deref(),
+                )
                 .await?;
             println!(
                 "\nThe workspace is ready. Run 'rooz enter {}' to enter.",
@@ -152,108 +402,551 @@ async fn main() -> Result<(), AnyError> {
                 Enter(EnterParams {
                     name,
                     shell,
+                    command,
                     root,
                     work_dir,
                     container,
+                    mux,
+                    no_mux,
+                    session,
+                    no_reconnect,
+                    retries,
+                    backoff,
+                    create,
                 }),
             ..
         } => {
+            let name = match name {
+                Some(name) => name,
+                None => rooz.pick_workspace().await?,
+            };
+            if container.is_none() {
+                let labels = Labels::new(Some(&name), Some(labels::ROLE_WORK))
+                    .with_container(Some(constants::DEFAULT_CONTAINER_NAME));
+                if workspace.api.container.get_single(&labels).await?.is_none() {
+                    workspace.new_from_local(&name, create).await?;
+                }
+            }
             workspace
                 .enter(
                     &name,
                     work_dir.as_deref(),
                     shell.as_deref().map(|v| vec![v.as_ref()]),
+                    command.as_deref(),
                     container.as_deref(),
                     vec![],
                     constants::DEFAULT_UID,
                     root,
                     false,
+                    if no_mux { None } else { Some((mux, &session)) },
+                    if no_reconnect {
+                        ReconnectPolicy::none()
+                    } else {
+                        ReconnectPolicy {
+                            retries,
+                            backoff: Duration::from_secs(backoff),
+                        }
+                    },
+                )
+                .await?
+        }
+
+        Cli {
+            command: cli::Commands::Open(cli::OpenParams { path, name }),
+            ..
+        } => {
+            let name = workspace.open(&path, name.as_deref()).await?;
+            workspace
+                .enter(
+                    &name,
+                    None,
+                    None,
+                    None,
+                    None,
+                    vec![],
+                    constants::DEFAULT_UID,
+                    false,
+                    false,
+                    Some((None, "rooz")),
+                    ReconnectPolicy::default(),
                 )
                 .await?
         }
 
         Cli {
-            command: List(ListParams {}),
+            command: Stats(StatsParams { name, watch }),
+            ..
+        } => rooz.stats(name.as_deref(), watch).await?,
+
+        Cli {
+            command: cli::Commands::Clean(cli::CleanParams { name }),
+            ..
+        } => workspace.clean(&name).await?,
+
+        Cli {
+            command: cli::Commands::Doctor(cli::DoctorParams { workspace: key }),
+            ..
+        } => workspace.doctor(key.as_deref()).await?,
+
+        Cli {
+            command: List(ListParams { archived: true, .. }),
+            ..
+        } => workspace.archive_list().await?,
+
+        Cli {
+            command:
+                List(ListParams {
+                    sizes,
+                    project,
+                    group_by,
+                    filter,
+                    sort,
+                    ..
+                }),
+            ..
+        } => {
+            rooz.list(sizes, project.as_deref(), group_by, &filter, sort)
+                .await?
+        }
+
+        Cli {
+            command:
+                Cache(cli::Cache {
+                    command: cli::CacheCommands::List(_),
+                }),
+            ..
+        } => rooz.cache_list().await?,
+
+        Cli {
+            command:
+                Cache(cli::Cache {
+                    command: cli::CacheCommands::Inspect(cli::InspectCacheParams { cache }),
+                }),
+            ..
+        } => rooz.cache_inspect(&cache).await?,
+
+        Cli {
+            command:
+                Cache(cli::Cache {
+                    command: cli::CacheCommands::Rm(cli::RemoveCacheParams { cache }),
+                }),
+            ..
+        } => rooz.cache_remove(&cache).await?,
+
+        Cli {
+            command:
+                Cache(cli::Cache {
+                    command: cli::CacheCommands::Size(cli::CacheSizeParams { cache }),
+                }),
+            ..
+        } => rooz.cache_size(cache.as_deref()).await?,
+
+        Cli {
+            command:
+                cli::Commands::Image(cli::Image {
+                    command: cli::ImageCommands::List(_),
+                }),
+            ..
+        } => rooz.image_list().await?,
+
+        Cli {
+            command:
+                cli::Commands::Image(cli::Image {
+                    command: cli::ImageCommands::Prune(cli::PruneImageParams { dry_run }),
+                }),
+            ..
+        } => rooz.image_prune(dry_run).await?,
+
+        Cli {
+            command:
+                Context(cli::ContextParams {
+                    name: Some(name),
+                    container,
+                }),
+            ..
+        } => workspace.context(&name, container.as_deref()).await?,
+
+        Cli {
+            command: Context(cli::ContextParams { name: None, .. }),
+            ..
+        } => unreachable!(),
+
+        Cli {
+            command:
+                Snapshot(cli::Snapshot {
+                    command:
+                        cli::SnapshotCommands::Create(cli::CreateSnapshotParams {
+                            name,
+                            snapshot,
+                            home,
+                            file,
+                            all,
+                        }),
+                }),
+            ..
+        } => {
+            let target = match &file {
+                Some(path) => File { path },
+                None => Volume {
+                    name: snapshot.as_deref(),
+                },
+            };
+            workspace.snapshot_create(&name, target, home, all).await?
+        }
+
+        Cli {
+            command:
+                Snapshot(cli::Snapshot {
+                    command: cli::SnapshotCommands::List(cli::ListSnapshotParams { name }),
+                }),
+            ..
+        } => workspace.snapshot_list(name.as_deref()).await?,
+
+        Cli {
+            command:
+                Snapshot(cli::Snapshot {
+                    command:
+                        cli::SnapshotCommands::Restore(cli::RestoreSnapshotParams {
+                            name,
+                            snapshot,
+                            file,
+                        }),
+                }),
+            ..
+        } => {
+            let target = match &file {
+                Some(path) => File { path },
+                None => Volume {
+                    name: snapshot.as_deref(),
+                },
+            };
+            workspace.snapshot_restore(&name, target).await?
+        }
+
+        Cli {
+            command:
+                Snapshot(cli::Snapshot {
+                    command: cli::SnapshotCommands::Rm(cli::RemoveSnapshotParams { name, snapshot }),
+                }),
             ..
-        } => rooz.list().await?,
+        } => workspace.snapshot_remove(&name, &snapshot).await?,
+
+        Cli {
+            command:
+                Backup(cli::BackupParams {
+                    name,
+                    target,
+                    home,
+                    all,
+                }),
+            ..
+        } => workspace.backup(&name, &target, home, all).await?,
+
+        Cli {
+            command: Restore(cli::RestoreParams { name, from }),
+            ..
+        } => workspace.restore(&name, &from).await?,
+
+        Cli {
+            command: Archive(cli::ArchiveParams { name, force }),
+            ..
+        } => workspace.archive(&name, force).await?,
+
+        Cli {
+            command: Unarchive(cli::UnarchiveParams { name }),
+            ..
+        } => workspace.unarchive(&name).await?,
+
+        Cli {
+            command: Watch(WatchParams { name, json, notify }),
+            ..
+        } => {
+            let notifications = match (notify, &system_config.notifications) {
+                (false, _) => None,
+                (true, Some(n)) => Some(n),
+                (true, None) => {
+                    return Err(RoozError::Config(format!(
+                        "--notify needs a [notifications] section in {}",
+                        system::SystemConfig::path().display()
+                    ))
+                    .into())
+                }
+            };
+            rooz.watch(name.as_deref(), json, notifications).await?
+        }
 
         Cli {
             command:
                 Remove(RemoveParams {
                     name: Some(name),
                     force,
+                    purge_data,
                     ..
                 }),
             ..
-        } => workspace.remove(&name, force).await?,
+        } => {
+            workspace.guard_sessions(&name, "remove", force).await?;
+            workspace.remove(&name, force, purge_data).await?
+        }
 
         Cli {
-            command: Remove(RemoveParams {
-                name: None, force, ..
-            }),
+            command:
+                Remove(RemoveParams {
+                    name: None,
+                    force,
+                    purge_data,
+                    project: Some(project),
+                    ..
+                }),
             ..
-        } => workspace.remove_all(force).await?,
+        } => {
+            workspace
+                .remove_project(&project, force, purge_data)
+                .await?
+        }
 
         Cli {
-            command: Stop(StopParams {
-                name: Some(name), ..
-            }),
+            command:
+                Remove(RemoveParams {
+                    name: None,
+                    force,
+                    purge_data,
+                    all: true,
+                    ..
+                }),
+            ..
+        } => workspace.remove_all(force, purge_data).await?,
+
+        Cli {
+            command:
+                Remove(RemoveParams {
+                    name: None,
+                    force,
+                    purge_data,
+                    ..
+                }),
+            ..
+        } => {
+            let name = rooz.pick_workspace().await?;
+            workspace.guard_sessions(&name, "remove", force).await?;
+            workspace.remove(&name, force, purge_data).await?
+        }
+
+        Cli {
+            command:
+                Stop(StopParams {
+                    name: Some(name),
+                    force,
+                    ..
+                }),
             ..
         } => {
+            workspace.guard_sessions(&name, "stop", force).await?;
             workspace.stop(&name).await?;
         }
 
         Cli {
-            command: Start(StartParams { name }),
+            command: Inspect(InspectParams { name, output }),
+            ..
+        } => {
+            workspace.inspect(&name, output).await?;
+        }
+
+        Cli {
+            command:
+                Status(StatusParams {
+                    name,
+                    short,
+                    cache_ttl,
+                }),
+            ..
+        } => {
+            workspace.status(&name, short, cache_ttl).await?;
+        }
+
+        Cli {
+            command: Start(StartParams {
+                name: Some(name), ..
+            }),
             ..
         } => {
             workspace.start(&name).await?;
         }
 
         Cli {
-            command: Stop(StopParams { name: None, .. }),
+            command:
+                Start(StartParams {
+                    project: Some(project),
+                    ..
+                }),
+            ..
+        } => {
+            workspace.start_project(&project).await?;
+        }
+
+        Cli {
+            command: Start(StartParams { .. }),
+            ..
+        } => unreachable!("clap requires a workspace name or a project"),
+
+        Cli {
+            command:
+                Stop(StopParams {
+                    name: None,
+                    project: Some(project),
+                    force,
+                    ..
+                }),
+            ..
+        } => {
+            workspace.stop_project(&project, force).await?;
+        }
+
+        Cli {
+            command:
+                Stop(StopParams {
+                    name: None,
+                    all: true,
+                    force,
+                    ..
+                }),
             ..
         } => {
-            workspace.stop_all().await?;
+            workspace.stop_all(force).await?;
+        }
+
+        Cli {
+            command: Stop(StopParams {
+                name: None, force, ..
+            }),
+            ..
+        } => {
+            let name = rooz.pick_workspace().await?;
+            workspace.guard_sessions(&name, "stop", force).await?;
+            workspace.stop(&name).await?;
         }
 
         Cli {
             command:
                 Update(UpdateParams {
                     name,
+                    all,
+                    project,
+                    filter,
+                    continue_on_error,
                     env,
                     tweak,
                     purge,
+                    force,
+                    config_path,
+                    format,
                     no_pull,
+                    reset_home,
+                    dry_run,
+                    only,
+                    check,
                 }),
             ..
         } => {
-            workspace
-                .update(
-                    &name,
-                    &env,
-                    tweak,
-                    match purge {
-                        true => UpdateMode::Purge,
-                        _ => UpdateMode::Apply,
-                    },
-                    no_pull,
-                )
-                .await?;
+            if check {
+                workspace.update_check(name.as_deref()).await?;
+            } else if all || project.is_some() || !filter.is_empty() {
+                workspace
+                    .update_all(
+                        project.as_deref(),
+                        &filter,
+                        &env,
+                        UpdateBatch {
+                            no_pull,
+                            dry_run,
+                            reset_home,
+                            only,
+                            continue_on_error,
+                        },
+                    )
+                    .await?;
+            } else {
+                // clap requires the name unless --check or a batch selector is set
+                let name = name.unwrap_or_default();
+                let config = match (tweak, config_path) {
+                    (true, _) => UpdateConfig::Tweak,
+                    (_, Some(path)) => UpdateConfig::Replace(ConfigPath::from_cli(
+                        &path,
+                        format.map(FileFormat::from),
+                    )?),
+                    _ => UpdateConfig::Stored,
+                };
+                workspace
+                    .update(
+                        &name,
+                        &env,
+                        config,
+                        match purge {
+                            true => UpdateMode::Purge { force },
+                            _ => UpdateMode::Apply { reset_home, only },
+                        },
+                        no_pull,
+                        dry_run,
+                    )
+                    .await?;
+            }
         }
 
         Cli {
             command: Code(CodeParams { name }),
             ..
         } => {
+            let name = match name {
+                Some(name) => name,
+                None => rooz.pick_workspace().await?,
+            };
             workspace.attach_vscode(&name).await?;
         }
 
         Cli {
-            command: Tmp(TmpParams { work, root, shell }),
+            command: Idea(IdeaParams { name, print }),
             ..
         } => {
-            workspace.tmp(&work, root, &shell).await?;
+            workspace.attach_gateway(&name, print).await?;
+        }
+
+        Cli {
+            command:
+                Run(RunParams {
+                    name,
+                    container,
+                    work_dir,
+                    command,
+                }),
+            ..
+        } => {
+            workspace
+                .run(
+                    &name,
+                    container.as_deref(),
+                    work_dir.as_deref(),
+                    command.iter().map(String::as_str).collect(),
+                )
+                .await?
+        }
+
+        Cli {
+            command:
+                Tmp(TmpParams {
+                    work,
+                    config_path,
+                    root,
+                    shell,
+                }),
+            ..
+        } => {
+            let config_source = match config_path {
+                Some(path) => Some(ConfigSource::Path {
+                    value: ConfigPath::from_str(&path)?,
+                }),
+                None => None,
+            };
+            workspace.tmp(&work, config_source, root, &shell).await?;
         }
 
         Cli {
@@ -283,25 +976,41 @@ async fn main() -> Result<(), AnyError> {
         Cli {
             command:
                 Config(cli::Config {
-                    command: cli::ConfigCommands::Show(ShowConfigParams { name, part, output }),
+                    command:
+                        cli::ConfigCommands::Show(ShowConfigParams {
+                            name,
+                            part,
+                            output,
+                            reveal_secrets,
+                        }),
                 }),
             ..
         } => {
-            workspace.config.show(&name, part, output).await?;
+            workspace
+                .config
+                .show(&name, part, output, reveal_secrets)
+                .await?;
         }
 
         Cli {
-            command:
-                Remote(cli::RemoteParams {
-                    ssh_url: _,
-                    local_docker_host: _,
-                }),
+            command: DiffConfig(DiffConfigParams { left, right }),
+            ..
+        } => workspace.config.diff(&left, &right).await?,
+
+        Cli {
+            command: Remote(cli::RemoteParams { .. }),
+            ..
         } => {
             //TODO: this needs to be handled more elegantly. I.e. Rooz should
             // only connect to Docker API when actually running commands requiring that
             // this command only forwards a local socket to a remote one.
         }
 
+        Cli {
+            command: cli::Commands::K8s(_),
+            ..
+        } => unreachable!("k8s commands return before connecting to the engine"),
+
         Cli {
             command:
                 System(cli::System {
@@ -312,6 +1021,16 @@ async fn main() -> Result<(), AnyError> {
             rooz.prune_system().await?;
         }
 
+        Cli {
+            command:
+                System(cli::System {
+                    command: cli::SystemCommands::Gc(cli::GcParams { dry_run }),
+                }),
+            ..
+        } => {
+            rooz.gc(dry_run).await?;
+        }
+
         Cli {
             command:
                 System(cli::System {
@@ -319,26 +1038,155 @@ async fn main() -> Result<(), AnyError> {
                 }),
             ..
         } => {
-            rooz.init(
-                constants::DEFAULT_IMAGE,
-                constants::DEFAULT_UID,
-                &init_params,
-            )
-            .await?
+            rooz.init(&image_api.helper, constants::DEFAULT_UID, &init_params)
+                .await?
         }
 
         Cli {
             command:
                 System(cli::System {
-                    command: cli::SystemCommands::Completion(CompletionParams { shell }),
+                    command:
+                        cli::SystemCommands::Key(cli::Key {
+                            command:
+                                cli::KeyCommands::Export(cli::ExportKeyParams { passphrase, yes }),
+                        }),
+                }),
+            ..
+        } => crypt_api.key_export(passphrase, yes).await?,
+
+        Cli {
+            command:
+                System(cli::System {
+                    command:
+                        cli::SystemCommands::Key(cli::Key {
+                            command: cli::KeyCommands::Import(cli::ImportKeyParams { file, force }),
+                        }),
                 }),
+            ..
         } => {
-            let mut cli = Cli::command()
-                .disable_help_flag(true)
-                .disable_help_subcommand(true);
-            let name = &cli.get_name().to_string();
-            generate(shell, &mut cli, name, &mut io::stdout());
+            rooz.key_import(&image_api.helper, constants::DEFAULT_UID, &file, force)
+                .await?
         }
+
+        Cli {
+            command:
+                System(cli::System {
+                    command:
+                        cli::SystemCommands::Ssh(cli::Ssh {
+                            command: cli::SshCommands::ShowKey(_),
+                        }),
+                }),
+            ..
+        } => crypt_api.ssh_show_key().await?,
+
+        Cli {
+            command:
+                System(cli::System {
+                    command:
+                        cli::SystemCommands::Ssh(cli::Ssh {
+                            command:
+                                cli::SshCommands::Regenerate(cli::RegenerateSshKeyParams { yes }),
+                        }),
+                }),
+            ..
+        } => {
+            crypt_api
+                .ssh_regenerate(constants::DEFAULT_UID, yes)
+                .await?
+        }
+
+        Cli {
+            command:
+                System(cli::System {
+                    command:
+                        cli::SystemCommands::Ssh(cli::Ssh {
+                            command:
+                                cli::SshCommands::Import(cli::ImportSshKeyParams { file, force }),
+                        }),
+                }),
+            ..
+        } => {
+            crypt_api
+                .ssh_import(constants::DEFAULT_UID, &file, force)
+                .await?
+        }
+
+        Cli {
+            command:
+                System(cli::System {
+                    command:
+                        cli::SystemCommands::Autostart(cli::Autostart {
+                            command:
+                                cli::AutostartCommands::Enable(cli::EnableAutostartParams { name }),
+                        }),
+                }),
+            ..
+        } => workspace.autostart(&name, true).await?,
+
+        Cli {
+            command:
+                System(cli::System {
+                    command:
+                        cli::SystemCommands::Autostart(cli::Autostart {
+                            command:
+                                cli::AutostartCommands::Disable(cli::DisableAutostartParams { name }),
+                        }),
+                }),
+            ..
+        } => workspace.autostart(&name, false).await?,
+
+        Cli {
+            command:
+                System(cli::System {
+                    command: cli::SystemCommands::Complete(CompleteParams { kind, name }),
+                }),
+            ..
+        } => match (kind, name) {
+            (CompleteKind::Containers, Some(name)) => rooz.complete_containers(&name).await?,
+            _ => rooz.complete_workspaces().await?,
+        },
+
+        Cli {
+            command:
+                System(cli::System {
+                    command: cli::SystemCommands::Migrate(cli::MigrateParams { dry_run, force }),
+                }),
+            ..
+        } => workspace.migrate(dry_run, force).await?,
+
+        Cli {
+            command:
+                System(cli::System {
+                    command: cli::SystemCommands::Audit(cli::AuditParams { workspace, limit }),
+                }),
+            ..
+        } => rooz.audit_list(workspace.as_deref(), limit).await?,
+
+        Cli {
+            command:
+                System(cli::System {
+                    command: cli::SystemCommands::Completion(_) | cli::SystemCommands::Upgrade(_),
+                }),
+            ..
+        } => unreachable!("handled before connecting to the container engine"),
     };
+    Ok::<(), AnyError>(())
+    }
+    .await;
+    if let Some(entry) = audit {
+        if let Err(e) = rooz.audit(&entry.finish(&result)).await {
+            log::warn!("Could not record the operation in the audit log: {}", e);
+        }
+    }
+    result?;
+    if let (Some((event, workspace_key)), Some(notifications)) =
+        (event, &system_config.notifications)
+    {
+        notifications.send(&cmd::notify::Notification::new(
+            event,
+            workspace_key.as_deref(),
+            None,
+        ));
+    }
     Ok(())
 }
@@ -0,0 +1,151 @@
+use std::error::Error;
+
+use bollard::errors::Error as DockerError;
+
+use crate::model::types::AnyError;
+
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_CONFIG: i32 = 2;
+pub const EXIT_NOT_FOUND: i32 = 3;
+pub const EXIT_INTERACTIVE: i32 = 4;
+pub const EXIT_ENGINE: i32 = 125;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RoozError {
+    #[error("{}", engine_message(.0))]
+    Engine(#[from] DockerError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error(transparent)]
+    Task(#[from] tokio::task::JoinError),
+    #[error(transparent)]
+    Encrypt(#[from] age::EncryptError),
+    #[error(transparent)]
+    Decrypt(#[from] age::DecryptError),
+    #[error("Workspace not found: {0}")]
+    WorkspaceNotFound(String),
+    #[error("Expected a single container but found {0}")]
+    AmbiguousContainer(usize),
+    #[error("Config file format: {0} is not supported. Use toml or yaml")]
+    ConfigFormat(String),
+    #[error("Invalid config: {0}")]
+    Config(String),
+    #[error("Command terminated with exit code: {0}")]
+    ExitCode(i64),
+    #[error("{0} needs an interactive terminal")]
+    Interactive(String),
+    #[error("Session disconnected")]
+    Disconnected,
+    #[error("Unexpected response from the container engine: {0}")]
+    Unexpected(String),
+    #[error("{context}: {source}")]
+    Context {
+        context: String,
+        source: Box<RoozError>,
+    },
+    #[error("Could not {what}:\n{}", parallel_message(.errors))]
+    Parallel {
+        what: String,
+        errors: Vec<(String, RoozError)>,
+    },
+    #[error("{0}")]
+    Message(String),
+}
+
+fn parallel_message(errors: &[(String, RoozError)]) -> String {
+    errors
+        .iter()
+        .map(|(name, e)| format!("  {}: {}", name, e))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn engine_message(e: &DockerError) -> String {
+    match e {
+        DockerError::DockerResponseServerError {
+            status_code,
+            message,
+        } => format!(
+            "{} (Error code: {})",
+            message.replace("\"", ""),
+            status_code
+        ),
+        DockerError::DockerStreamError { error } => error.to_string(),
+        e => e.to_string(),
+    }
+}
+
+impl RoozError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RoozError::Engine(DockerError::DockerResponseServerError {
+                status_code: 404, ..
+            }) => EXIT_NOT_FOUND,
+            RoozError::Engine(DockerError::DockerResponseServerError { .. }) => EXIT_FAILURE,
+            RoozError::Engine(_) => EXIT_ENGINE,
+            RoozError::WorkspaceNotFound(_) => EXIT_NOT_FOUND,
+            RoozError::ConfigFormat(_) | RoozError::Config(_) => EXIT_CONFIG,
+            RoozError::Interactive(_) => EXIT_INTERACTIVE,
+            RoozError::ExitCode(code) => i32::try_from(*code)
+                .ok()
+                .filter(|c| (1..=255).contains(c))
+                .unwrap_or(EXIT_FAILURE),
+            RoozError::Context { source, .. } => source.exit_code(),
+            // the exit code all the tasks failed with, e.g. not found
+            RoozError::Parallel { errors, .. } => {
+                let mut codes = errors.iter().map(|(_, e)| e.exit_code());
+                let first = codes.next().unwrap_or(EXIT_FAILURE);
+                if codes.all(|c| c == first) {
+                    first
+                } else {
+                    EXIT_FAILURE
+                }
+            }
+            _ => EXIT_FAILURE,
+        }
+    }
+
+    // errors not raised by rooz itself (e.g. from the age or git layers) exit with the generic code
+    pub fn exit_code_of(error: &(dyn Error + 'static)) -> i32 {
+        error
+            .downcast_ref::<RoozError>()
+            .map(RoozError::exit_code)
+            .unwrap_or(EXIT_FAILURE)
+    }
+}
+
+impl From<String> for RoozError {
+    fn from(value: String) -> Self {
+        RoozError::Message(value)
+    }
+}
+
+impl From<&str> for RoozError {
+    fn from(value: &str) -> Self {
+        RoozError::Message(value.to_string())
+    }
+}
+
+impl From<AnyError> for RoozError {
+    fn from(value: AnyError) -> Self {
+        match value.downcast::<RoozError>() {
+            Ok(e) => *e,
+            Err(e) => RoozError::Message(e.to_string()),
+        }
+    }
+}
+
+pub trait Context<T> {
+    fn context(self, context: &str) -> Result<T, RoozError>;
+}
+
+impl<T, E: Into<RoozError>> Context<T> for Result<T, E> {
+    fn context(self, context: &str) -> Result<T, RoozError> {
+        self.map_err(|e| RoozError::Context {
+            context: context.to_string(),
+            source: Box::new(e.into()),
+        })
+    }
+}
@@ -1,10 +1,10 @@
 use crate::{
-    config::config::RoozCfg,
+    config::config::{RoozCfg, RoozIdMap, RoozRestart, RoozVolumeCfg},
     model::volume::RoozVolume,
     util::{git::RootRepoCloneResult, labels::Labels},
 };
 use bollard::service::Mount;
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 pub type AnyError = Box<dyn std::error::Error + 'static>;
 
@@ -28,22 +28,87 @@ pub enum VolumeResult {
     AlreadyExists,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct ReconnectPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl ReconnectPolicy {
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            ..Default::default()
+        }
+    }
+
+    // doubles with every attempt
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(Self::MAX_BACKOFF)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 10,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SecuritySpec {
+    pub security_opts: Vec<String>,
+    pub cap_add: Vec<String>,
+    pub cap_drop: Vec<String>,
+    pub read_only_rootfs: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DnsSpec {
+    pub dns: Vec<String>,
+    pub dns_search: Vec<String>,
+    pub extra_hosts: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct WorkSpec<'a> {
     pub image: &'a str,
     pub uid: &'a str,
     pub user: &'a str,
+    // defaults to /home/<user>
+    pub home_dir: Option<&'a str>,
     pub container_working_dir: &'a str,
     pub container_name: &'a str,
     pub workspace_key: &'a str,
+    pub volumes_key: Option<&'a str>,
     pub labels: Labels,
     pub ephemeral: bool,
     pub caches: Option<Vec<String>>,
+    pub volumes: Option<HashMap<String, RoozVolumeCfg>>,
+    // mounted on top of the volumes, e.g. the Docker socket
+    pub mounts: Vec<Mount>,
     pub privileged: bool,
     pub force_recreate: bool,
+    // existing containers are kept rather than failing the create
+    pub keep_existing: bool,
     pub network: Option<&'a str>,
+    pub network_aliases: Option<Vec<String>>,
+    pub extra_networks: Option<Vec<String>>,
     pub env_vars: Option<HashMap<String, String>>,
     pub ports: Option<HashMap<String, Option<String>>>,
+    pub auto_ports: bool,
+    pub bind_address: Option<&'a str>,
+    pub idmap: RoozIdMap,
+    pub restart: Option<RoozRestart>,
+    pub security: SecuritySpec,
+    pub dns: DnsSpec,
+    pub platform: Option<&'a str>,
 }
 
 impl Default for WorkSpec<'_> {
@@ -52,17 +117,31 @@ impl Default for WorkSpec<'_> {
             image: Default::default(),
             uid: Default::default(),
             user: Default::default(),
+            home_dir: Default::default(),
             container_working_dir: Default::default(),
             container_name: Default::default(),
             workspace_key: Default::default(),
+            volumes_key: None,
             labels: Labels::default(),
             ephemeral: false,
             caches: None,
+            volumes: None,
+            mounts: Vec::new(),
             privileged: false,
             force_recreate: false,
+            keep_existing: false,
             network: None,
+            network_aliases: None,
+            extra_networks: None,
             env_vars: None,
             ports: None,
+            auto_ports: false,
+            bind_address: None,
+            idmap: RoozIdMap::default(),
+            restart: None,
+            platform: None,
+            security: SecuritySpec::default(),
+            dns: DnsSpec::default(),
         }
     }
 }
@@ -84,9 +163,17 @@ pub struct RunSpec<'a> {
     pub labels: Labels,
     pub env: Option<HashMap<String, String>>,
     pub ports: Option<HashMap<String, Option<String>>>,
+    pub auto_ports: bool,
+    pub bind_address: Option<&'a str>,
     pub network: Option<&'a str>,
     pub network_aliases: Option<Vec<String>>,
+    pub extra_networks: Option<Vec<String>>,
     pub command: Option<Vec<&'a str>>,
+    pub idmap: Option<RoozIdMap>,
+    pub restart: Option<RoozRestart>,
+    pub security: SecuritySpec,
+    pub dns: DnsSpec,
+    pub platform: Option<&'a str>,
 }
 
 impl Default for RunSpec<'_> {
@@ -109,8 +196,16 @@ impl Default for RunSpec<'_> {
             env: Default::default(),
             network: None,
             network_aliases: None,
+            extra_networks: None,
             command: None,
             ports: None,
+            auto_ports: false,
+            bind_address: None,
+            idmap: None,
+            restart: None,
+            platform: None,
+            security: SecuritySpec::default(),
+            dns: DnsSpec::default(),
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::util::id::to_safe_id;
+use crate::{config::config::RoozVolumeCfg, util::id::to_safe_id};
 use bollard::models::{Mount, MountTypeEnum};
 
 #[derive(Debug, Clone)]
@@ -10,18 +10,26 @@ pub enum RoozVolumeSharing {
 pub const HOME_ROLE: &'static str = "home";
 pub const WORK_ROLE: &'static str = "work";
 pub const CACHE_ROLE: &'static str = "cache";
+pub const WORKSPACE_CACHE_ROLE: &str = "workspace-cache";
+// caches declared as ws:<path> are not shared
+pub const WORKSPACE_CACHE_PREFIX: &str = "ws:";
 pub const DATA_ROLE: &'static str = "data";
+pub const VOLUME_ROLE: &'static str = "volume";
 pub const SSH_KEY_ROLE: &'static str = "ssh-key";
 pub const AGE_KEY_ROLE: &'static str = "age-key";
+pub const CONFIG_ROLE: &str = "workspace-config";
 
 #[derive(Debug, Clone)]
 pub enum RoozVolumeRole {
     Home,
     Work,
     Cache,
+    WorkspaceCache,
     Data,
+    Volume,
     SshKey,
     AgeKey,
+    Config,
 }
 
 impl RoozVolumeRole {
@@ -30,9 +38,12 @@ impl RoozVolumeRole {
             RoozVolumeRole::Home => HOME_ROLE,
             RoozVolumeRole::Work => WORK_ROLE,
             RoozVolumeRole::Cache => CACHE_ROLE,
+            RoozVolumeRole::WorkspaceCache => WORKSPACE_CACHE_ROLE,
             RoozVolumeRole::Data => DATA_ROLE,
+            RoozVolumeRole::Volume => VOLUME_ROLE,
             RoozVolumeRole::SshKey => SSH_KEY_ROLE,
             RoozVolumeRole::AgeKey => AGE_KEY_ROLE,
+            RoozVolumeRole::Config => CONFIG_ROLE,
         }
     }
 }
@@ -42,6 +53,8 @@ pub struct RoozVolume {
     pub path: String,
     pub role: RoozVolumeRole,
     pub sharing: RoozVolumeSharing,
+    pub name: Option<String>,
+    pub keep: bool,
 }
 
 impl RoozVolume {
@@ -49,6 +62,36 @@ impl RoozVolume {
         let role_segment = to_safe_id(self.role.as_str());
 
         match self {
+            RoozVolume {
+                role: RoozVolumeRole::Volume,
+                sharing: RoozVolumeSharing::Exclusive { key },
+                name: Some(name),
+                ..
+            } => format!(
+                "rooz_{}_{}_{}",
+                to_safe_id(key),
+                to_safe_id(name),
+                &role_segment
+            ),
+            RoozVolume {
+                role: RoozVolumeRole::Volume,
+                sharing: RoozVolumeSharing::Shared,
+                name: Some(name),
+                ..
+            } => format!("rooz_{}_{}", &role_segment, to_safe_id(name)),
+            RoozVolume {
+                path,
+                role: RoozVolumeRole::Data,
+                sharing: RoozVolumeSharing::Exclusive { key },
+                name: Some(replica),
+                ..
+            } => format!(
+                "rooz_{}_{}_{}_{}",
+                to_safe_id(key),
+                to_safe_id(replica),
+                to_safe_id(path),
+                &role_segment
+            ),
             RoozVolume {
                 path,
                 role: RoozVolumeRole::Data,
@@ -66,6 +109,17 @@ impl RoozVolume {
                 role: RoozVolumeRole::Cache,
                 ..
             } => format!("rooz_{}_{}", &role_segment, to_safe_id(&path)),
+            RoozVolume {
+                path,
+                sharing: RoozVolumeSharing::Exclusive { key },
+                role: RoozVolumeRole::WorkspaceCache,
+                ..
+            } => format!(
+                "rooz_{}_{}_{}",
+                to_safe_id(key),
+                to_safe_id(path),
+                &role_segment
+            ),
             RoozVolume {
                 sharing: RoozVolumeSharing::Exclusive { key },
                 ..
@@ -118,6 +172,8 @@ impl RoozVolume {
             path: path.into(),
             sharing: RoozVolumeSharing::Exclusive { key: key.into() },
             role: RoozVolumeRole::Work,
+            name: None,
+            keep: false,
         }
     }
 
@@ -126,6 +182,19 @@ impl RoozVolume {
             path: path.into(),
             sharing: RoozVolumeSharing::Exclusive { key: key.into() },
             role: RoozVolumeRole::Home,
+            name: None,
+            keep: false,
+        }
+    }
+
+    // the stored config of the workspace. Only ever mounted by one-shot helper containers
+    pub fn workspace_config(key: &str) -> RoozVolume {
+        RoozVolume {
+            path: "/tmp/rooz-config".into(),
+            sharing: RoozVolumeSharing::Exclusive { key: key.into() },
+            role: RoozVolumeRole::Config,
+            name: None,
+            keep: false,
         }
     }
 
@@ -134,9 +203,33 @@ impl RoozVolume {
             path: path.into(),
             sharing: RoozVolumeSharing::Shared,
             role: RoozVolumeRole::Cache,
+            name: None,
+            keep: false,
         }
     }
 
+    pub fn workspace_cache(key: &str, path: &str) -> RoozVolume {
+        RoozVolume {
+            path: path.into(),
+            sharing: RoozVolumeSharing::Exclusive { key: key.into() },
+            role: RoozVolumeRole::WorkspaceCache,
+            name: None,
+            keep: false,
+        }
+    }
+
+    // a config cache entry: <path> for a shared cache, ws:<path> for one of the workspace only
+    pub fn from_cache_entry(key: &str, entry: &str) -> RoozVolume {
+        match entry.strip_prefix(WORKSPACE_CACHE_PREFIX) {
+            Some(path) => RoozVolume::workspace_cache(key, path),
+            None => RoozVolume::cache(entry),
+        }
+    }
+
+    pub fn cache_path(entry: &str) -> &str {
+        entry.strip_prefix(WORKSPACE_CACHE_PREFIX).unwrap_or(entry)
+    }
+
     pub fn sidecar_data(workspace_key: &str, path: &str) -> RoozVolume {
         RoozVolume {
             path: path.into(),
@@ -144,6 +237,29 @@ impl RoozVolume {
             sharing: RoozVolumeSharing::Exclusive {
                 key: workspace_key.into(),
             },
+            name: None,
+            keep: false,
+        }
+    }
+
+    // each replica of a sidecar gets its own data volumes
+    pub fn for_replica(self, replica: &str) -> RoozVolume {
+        RoozVolume {
+            name: Some(replica.into()),
+            ..self
+        }
+    }
+
+    pub fn user(key: &str, name: &str, cfg: &RoozVolumeCfg) -> RoozVolume {
+        RoozVolume {
+            path: cfg.path.to_string(),
+            role: RoozVolumeRole::Volume,
+            sharing: match cfg.shared {
+                Some(true) => RoozVolumeSharing::Shared,
+                _ => RoozVolumeSharing::Exclusive { key: key.into() },
+            },
+            name: Some(name.into()),
+            keep: cfg.keep.unwrap_or(false),
         }
     }
 }
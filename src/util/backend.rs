@@ -1,16 +1,84 @@
 use crate::model::types::AnyError;
+use bollard::errors::Error;
 use bollard::service::SystemInfo;
 use bollard::system::Version;
+use bollard::{Docker, API_DEFAULT_VERSION};
+use std::{env, path::Path};
+
+const TIMEOUT: u64 = 120;
+
+fn connect_to(host: &str) -> Result<Docker, Error> {
+    if host.starts_with("tcp://") || host.starts_with("http://") {
+        return Docker::connect_with_http(host, TIMEOUT, API_DEFAULT_VERSION);
+    }
+    #[cfg(unix)]
+    if host.starts_with("unix://") {
+        return Docker::connect_with_unix(host, TIMEOUT, API_DEFAULT_VERSION);
+    }
+    #[cfg(windows)]
+    if host.starts_with("npipe://") {
+        return Docker::connect_with_named_pipe(host, TIMEOUT, API_DEFAULT_VERSION);
+    }
+    // a plain socket path or (on Windows) a named pipe path like //./pipe/docker_engine
+    if Path::new(host).is_absolute() || host.starts_with("//") {
+        return Docker::connect_with_socket(host, TIMEOUT, API_DEFAULT_VERSION);
+    }
+    Err(Error::UnsupportedURISchemeError {
+        uri: host.to_string(),
+    })
+}
+
+#[cfg(windows)]
+fn podman_socket() -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn podman_socket() -> Option<String> {
+    let socket = Path::new(&env::var("XDG_RUNTIME_DIR").ok()?).join("podman/podman.sock");
+    socket
+        .exists()
+        .then(|| socket.to_string_lossy().into_owned())
+}
+
+// explicit host -> DOCKER_HOST -> Podman user socket -> Docker defaults
+pub fn connect(host: Option<&str>) -> Result<Docker, Error> {
+    if let Some(host) = host {
+        log::debug!("Connecting to: {} (--host)", host);
+        return connect_to(host);
+    }
+    if let Ok(host) = env::var("DOCKER_HOST") {
+        log::debug!("Connecting to: {} (DOCKER_HOST)", host);
+        return connect_to(&host);
+    }
+    if let Some(socket) = podman_socket() {
+        log::debug!("Connecting to: {} (Podman user socket)", socket);
+        return Docker::connect_with_socket(&socket, TIMEOUT, API_DEFAULT_VERSION);
+    }
+    // a unix socket or the npipe:////./pipe/docker_engine named pipe on Windows
+    log::debug!("Connecting to the Docker defaults");
+    Docker::connect_with_local_defaults()
+}
 
 #[derive(Debug, Clone)]
 pub enum ContainerBackend {
     DockerDesktop,
     RancherDesktop,
-    Podman,
+    Podman { rootless: bool },
     Unknown,
 }
 
 impl ContainerBackend {
+    pub fn name(&self) -> &str {
+        match self {
+            ContainerBackend::DockerDesktop => "docker-desktop",
+            ContainerBackend::RancherDesktop => "rancher-desktop",
+            ContainerBackend::Podman { rootless: true } => "podman-rootless",
+            ContainerBackend::Podman { rootless: false } => "podman",
+            ContainerBackend::Unknown => "unknown",
+        }
+    }
+
     pub async fn resolve(version: &Version, info: &SystemInfo) -> Result<Self, AnyError> {
         fn backend(info: &SystemInfo, version: &Version) -> ContainerBackend {
             if let SystemInfo {
@@ -24,7 +92,13 @@ impl ContainerBackend {
                     _ => {
                         if let Some(components) = &version.components {
                             if components.iter().any(|c| c.name == "Podman Engine") {
-                                ContainerBackend::Podman
+                                ContainerBackend::Podman {
+                                    rootless: info
+                                        .security_options
+                                        .iter()
+                                        .flatten()
+                                        .any(|o| o.contains("rootless")),
+                                }
                             } else {
                                 ContainerBackend::Unknown
                             }
@@ -1,8 +1,14 @@
+use url::Url;
+
 use crate::{
-    api::{container, ExecApi, GitApi},
-    config::config::FileFormat,
+    api::{
+        container::{self, quote},
+        ExecApi, GitApi,
+    },
+    config::config::{FileFormat, RoozCloneCfg, RoozDotfilesCfg, RoozProxyCfg},
     constants,
     model::{
+        error::RoozError,
         types::{AnyError, ContainerResult, RunSpec},
         volume::RoozVolume,
     },
@@ -10,31 +16,62 @@ use crate::{
 
 use super::{id, labels::Labels, ssh};
 
+pub const TOKEN_SECRET: &str = "GIT_TOKEN";
+// written over stdin once the clone container runs, so the token is in neither its env nor its config
+const TOKEN_FILE: &str = "/tmp/.rooz-git-token";
+
+const ASKPASS_SCRIPT: &str = r#"cat > /tmp/git-askpass.sh <<'EOF'
+#!/bin/sh
+case "$1" in
+  Username*) echo x-access-token ;;
+  *) cat /tmp/.rooz-git-token ;;
+esac
+EOF
+chmod +x /tmp/git-askpass.sh
+export GIT_ASKPASS=/tmp/git-askpass.sh
+export GIT_TERMINAL_PROMPT=0
+"#;
+
 #[derive(Clone, Debug)]
 pub enum CloneUrls {
-    Root { url: String },
-    Extra { urls: Vec<String> },
+    Root {
+        url: String,
+        dir: Option<String>,
+        options: RoozCloneCfg,
+    },
+    Extra {
+        urls: Vec<String>,
+    },
+    // cloned into the home volume, the working dir being the home dir
+    Dotfiles {
+        url: String,
+    },
 }
 
 #[derive(Clone, Debug)]
 pub struct CloneEnv {
-    pub image: String,
     pub uid: String,
     pub workspace_key: String,
+    pub volumes_key: Option<String>,
     pub working_dir: String,
     pub use_volume: bool,
     pub depth_override: Option<i64>,
+    pub token: Option<String>,
+    // the workspace proxy, on top of the one of the host
+    pub proxy: Option<RoozProxyCfg>,
 }
 
 impl Default for CloneEnv {
     fn default() -> Self {
         Self {
-            image: constants::DEFAULT_IMAGE.to_string(),
             uid: constants::DEFAULT_UID.to_string(),
             workspace_key: Default::default(),
+            volumes_key: None,
             working_dir: constants::WORK_DIR.to_string(),
             use_volume: true,
             depth_override: None,
+            token: None,
+            proxy: None,
         }
     }
 }
@@ -45,13 +82,40 @@ pub struct RootRepoCloneResult {
     pub dir: String,
 }
 
-fn get_clone_dir(root_dir: &str, git_ssh_url: &str) -> String {
-    let clone_work_dir = git_ssh_url
+pub fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            parsed.set_password(Some("***")).ok();
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+pub fn repo_name(git_ssh_url: &str) -> String {
+    git_ssh_url
         .split(&['/'])
         .last()
         .unwrap_or("repo")
         .replace(".git", "")
-        .to_string();
+        .to_string()
+}
+
+// the same repo shows up as git@host:org/repo.git, ssh://git@host:22/org/repo and https://host/org/repo
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let (host, path) = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/').unwrap_or((rest, "")),
+        None => url.split_once(':').unwrap_or((url, "")),
+    };
+    let host = host.rsplit('@').next().unwrap_or(host);
+    let host = host.split(':').next().unwrap_or(host);
+    format!("{}/{}", host.to_lowercase(), path.trim_start_matches('/'))
+}
+
+fn get_clone_dir(root_dir: &str, git_ssh_url: &str) -> String {
+    let clone_work_dir = repo_name(git_ssh_url);
 
     log::debug!("Clone dir: {}", &clone_work_dir);
 
@@ -61,6 +125,70 @@ fn get_clone_dir(root_dir: &str, git_ssh_url: &str) -> String {
     work_dir
 }
 
+fn clone_command(
+    url: &str,
+    dir: &str,
+    options: &RoozCloneCfg,
+    depth_override: Option<i64>,
+) -> String {
+    let mut args = vec!["git clone".to_string()];
+    match options.filter.as_deref() {
+        None => args.push("--filter=blob:none".into()),
+        Some("") | Some("none") => (),
+        Some(filter) => args.push(quote(&format!("--filter={}", filter))),
+    }
+    if let Some(depth) = depth_override.or(options.depth) {
+        args.push(format!("--depth={}", depth));
+    }
+    if let Some(true) = options.single_branch {
+        args.push("--single-branch".into());
+    }
+    if let Some(branch) = &options.branch {
+        args.push(format!("--branch {}", quote(branch)));
+    }
+    if let Some(true) = options.submodules {
+        args.push("--recurse-submodules".into());
+    }
+    args.push(quote(url));
+    args.push(quote(dir));
+
+    let mut command = args.join(" ");
+    if let Some(commit) = &options.commit {
+        command = format!(
+            "{} && git -C {} checkout {}",
+            command,
+            quote(dir),
+            quote(commit)
+        );
+    }
+    format!(
+        "ls {} > /dev/null 2>&1 || {{ {}; }}\n",
+        quote(&format!("{}/.git", dir)),
+        command
+    )
+}
+
+// the clone dir stays within the root dir like any other path of the config
+pub fn resolve_clone_dir(
+    root_dir: &str,
+    git_ssh_url: &str,
+    dir: Option<&str>,
+) -> Result<String, RoozError> {
+    let Some(dir) = dir else {
+        return Ok(get_clone_dir(root_dir, git_ssh_url));
+    };
+    if dir.starts_with('/') || dir.split('/').any(|c| c == "..") {
+        return Err(RoozError::Config(format!(
+            "clone_dir must be a path within {} without '..': {}",
+            root_dir, dir
+        )));
+    }
+    Ok(match dir.trim_end_matches('/') {
+        "" | "." => root_dir.to_string(),
+        d => format!("{}/{}", root_dir, d.trim_start_matches("./")),
+    })
+}
+
 impl<'a> ExecApi<'a> {
     async fn read_config_body(
         &self,
@@ -103,38 +231,65 @@ impl<'a> ExecApi<'a> {
 }
 
 impl<'a> GitApi<'a> {
+    #[tracing::instrument(name = "git.clone", level = "debug", skip_all, fields(id = %spec.workspace_key))]
     async fn clone_from_spec(&self, spec: &CloneEnv, urls: &CloneUrls) -> Result<String, AnyError> {
         let mut clone_script = "export GIT_SSH_COMMAND='ssh -i /tmp/.ssh/id_ed25519 -o UserKnownHostsFile=/tmp/.ssh/known_hosts'\n".to_string();
-        let all_urls: Vec<String> = match &urls {
-            CloneUrls::Root { url } => vec![url.to_string()],
-            CloneUrls::Extra { urls } => {
-                urls.iter().map(|x| x.to_string()).collect::<Vec<String>>()
-            }
-        };
-
-        let depth = if let Some(depth) = spec.depth_override {
-            format!("--depth={}", depth)
-        } else {
-            "".to_string()
+        if spec.token.is_some() {
+            clone_script.push_str(ASKPASS_SCRIPT);
+        }
+        let default_options = RoozCloneCfg::default();
+        let all_urls: Vec<(String, String, &RoozCloneCfg)> = match &urls {
+            CloneUrls::Root { url, dir, options } => vec![(
+                url.to_string(),
+                resolve_clone_dir(&spec.working_dir, url, dir.as_deref())?,
+                options,
+            )],
+            CloneUrls::Extra { urls } => urls
+                .iter()
+                .map(|x| {
+                    (
+                        x.to_string(),
+                        get_clone_dir(&spec.working_dir, x),
+                        &default_options,
+                    )
+                })
+                .collect::<Vec<_>>(),
+            CloneUrls::Dotfiles { url } => vec![(
+                url.to_string(),
+                RoozDotfilesCfg::DIR.replacen('~', &spec.working_dir, 1),
+                &default_options,
+            )],
         };
 
-        for url in all_urls {
-            let clone_dir = get_clone_dir(&spec.working_dir, &url);
-            clone_script.push_str(
-                format!(
-                    "ls '{}/.git' > /dev/null 2>&1 || git clone --filter=blob:none {} {}\n",
-                    &clone_dir, &depth, &url
-                )
-                .as_str(),
-            )
+        let mut check_script = String::new();
+        for (url, clone_dir, options) in all_urls {
+            log::debug!("Clone: {}", redact_url(&url));
+            clone_script.push_str(&clone_command(
+                &url,
+                &clone_dir,
+                options,
+                spec.depth_override,
+            ));
+            check_script.push_str(&format!(
+                "ls {} > /dev/null 2>&1 || echo {}\n",
+                quote(&format!("{}/.git", clone_dir)),
+                quote(&redact_url(&url))
+            ));
         }
 
         let clone_cmd = container::inject(&clone_script, "clone.sh");
         let labels = Labels::new(Some(&spec.workspace_key), Some("git"));
         let mut mounts = vec![ssh::mount("/tmp/.ssh")];
+        if self.host_ssh {
+            mounts.extend(ssh::host_mounts());
+        }
 
         if spec.use_volume {
-            let vol = RoozVolume::work(&spec.workspace_key, &spec.working_dir);
+            let volumes_key = spec.volumes_key.as_deref().unwrap_or(&spec.workspace_key);
+            let vol = match urls {
+                CloneUrls::Dotfiles { .. } => RoozVolume::home(volumes_key, &spec.working_dir),
+                _ => RoozVolume::work(volumes_key, &spec.working_dir),
+            };
 
             self.api
                 .volume
@@ -145,7 +300,7 @@ impl<'a> GitApi<'a> {
 
         let run_spec = RunSpec {
             reason: "git-clone",
-            image: &spec.image,
+            image: &self.api.image.helper,
             uid: &spec.uid,
             work_dir: Some(&spec.working_dir),
             container_name: &id::random_suffix("rooz-git"),
@@ -156,6 +311,13 @@ impl<'a> GitApi<'a> {
             force_recreate: false,
             auto_remove: true,
             labels,
+            env: Some({
+                let proxy = match &spec.proxy {
+                    Some(p) => p.or(&self.proxy),
+                    None => self.proxy.clone(),
+                };
+                proxy.env()
+            }),
             ..Default::default()
         };
 
@@ -166,6 +328,35 @@ impl<'a> GitApi<'a> {
                 .exec
                 .chown(&id, &spec.uid, &spec.working_dir)
                 .await?;
+            if let Some(token) = &spec.token {
+                self.api
+                    .exec
+                    .write_file("git-token", &id, TOKEN_FILE, token)
+                    .await?;
+            }
+
+            if self.offline {
+                let check_cmd = container::inject(&check_script, "check.sh");
+                let missing = self
+                    .api
+                    .exec
+                    .output(
+                        "git-check",
+                        &id,
+                        None,
+                        Some(check_cmd.iter().map(String::as_str).collect()),
+                    )
+                    .await?;
+                if !missing.trim().is_empty() {
+                    self.api.container.kill(&id).await?;
+                    return Err(format!(
+                        "Can't clone in offline mode: {}",
+                        missing.split_whitespace().collect::<Vec<_>>().join(", ")
+                    )
+                    .into());
+                }
+                return Ok(id.to_string());
+            }
 
             self.api
                 .exec
@@ -213,12 +404,21 @@ impl<'a> GitApi<'a> {
     pub async fn clone_root_repo(
         &self,
         url: &str,
+        dir: Option<&str>,
+        options: &RoozCloneCfg,
         spec: &CloneEnv,
     ) -> Result<RootRepoCloneResult, AnyError> {
         let container_id = self
-            .clone_from_spec(&spec, &CloneUrls::Root { url: url.into() })
+            .clone_from_spec(
+                &spec,
+                &CloneUrls::Root {
+                    url: url.into(),
+                    dir: dir.map(|d| d.to_string()),
+                    options: options.clone(),
+                },
+            )
             .await?;
-        let clone_dir = get_clone_dir(&spec.working_dir, &url);
+        let clone_dir = resolve_clone_dir(&spec.working_dir, &url, dir)?;
         let config = self.try_read_config(&container_id, &clone_dir).await?;
         self.api.container.kill(&container_id).await?;
 
@@ -231,6 +431,23 @@ impl<'a> GitApi<'a> {
         })
     }
 
+    // a throwaway clone outside of the work volume, so the dry-run plan can read the config of the repo
+    pub async fn read_root_config(
+        &self,
+        url: &str,
+        dir: Option<&str>,
+        options: &RoozCloneCfg,
+        spec: &CloneEnv,
+    ) -> Result<Option<(String, FileFormat)>, AnyError> {
+        let spec = CloneEnv {
+            use_volume: false,
+            // a pinned commit may be anywhere in the history
+            depth_override: options.commit.is_none().then_some(1),
+            ..spec.clone()
+        };
+        Ok(self.clone_root_repo(url, dir, options, &spec).await?.config)
+    }
+
     pub async fn clone_extra_repos(
         &self,
         spec: CloneEnv,
@@ -243,12 +460,38 @@ impl<'a> GitApi<'a> {
         Ok(())
     }
 
+    pub async fn clone_dotfiles(
+        &self,
+        spec: CloneEnv,
+        url: &str,
+        home_dir: &str,
+    ) -> Result<(), AnyError> {
+        let container_id = self
+            .clone_from_spec(
+                &CloneEnv {
+                    working_dir: home_dir.to_string(),
+                    ..spec
+                },
+                &CloneUrls::Dotfiles { url: url.into() },
+            )
+            .await?;
+        self.api.container.kill(&container_id).await?;
+        Ok(())
+    }
+
     pub async fn clone_config_repo(
         &self,
         spec: CloneEnv,
         url: &str,
         path: &str,
     ) -> Result<Option<String>, AnyError> {
+        if self.offline {
+            return Err(format!(
+                "Can't read the config file from {} in offline mode",
+                redact_url(url)
+            )
+            .into());
+        }
         let container_id = self
             .clone_from_spec(
                 &CloneEnv {
@@ -262,7 +505,7 @@ impl<'a> GitApi<'a> {
             )
             .await?;
         let clone_dir = get_clone_dir(&spec.working_dir, &url);
-        let file_format = FileFormat::from_path(path);
+        let file_format = FileFormat::from_path(path)?;
         let rooz_cfg = self
             .api
             .exec
@@ -1,6 +1,10 @@
 use std::{collections::HashMap, vec};
 
-use crate::config::runtime::RuntimeConfig;
+use crate::{
+    config::runtime::RuntimeConfig,
+    model::{error::RoozError, types::AnyError},
+    util::{backend::ContainerBackend, time::DateTime},
+};
 
 pub const WORKSPACE_KEY: &'static str = "dev.rooz.workspace";
 pub const CONTAINER: &'static str = "dev.rooz.workspace.container";
@@ -8,12 +12,90 @@ pub const ROLE: &'static str = "dev.rooz.role";
 pub const RUNTIME_CONFIG: &'static str = "dev.rooz.config.runtime";
 pub const CONFIG_ORIGIN: &'static str = "dev.rooz.config.origin";
 pub const CONFIG_BODY: &'static str = "dev.rooz.config.body";
+pub const VOLUMES_KEY: &'static str = "dev.rooz.workspace.volumes";
+pub const KEEP: &'static str = "dev.rooz.volume.keep";
+pub const PORTS: &'static str = "dev.rooz.ports";
+pub const CREATED: &'static str = "dev.rooz.created";
+pub const ROOZ_VERSION: &'static str = "dev.rooz.version";
+pub const ENGINE: &'static str = "dev.rooz.engine";
+pub const ORIGIN_HOST: &'static str = "dev.rooz.origin.host";
+pub const PROJECT: &'static str = "dev.rooz.project";
+pub const REPO: &str = "dev.rooz.repo";
+pub const BRANCH: &str = "dev.rooz.repo.branch";
+pub const IMAGE_DIGEST: &str = "dev.rooz.image.digest";
+pub const ENGINE_NAME: &str = "dev.rooz.engine.name";
+pub const UID: &str = "dev.rooz.uid";
+pub const GENERATED_SECRETS: &str = "dev.rooz.secrets.generated";
+pub const SCHEMA: &str = "dev.rooz.schema";
+pub const CONFIG_HASH: &str = "dev.rooz.config.hash";
+
+// bumped whenever the labels or the runtime config change shape. Workspaces without the label predate it
+// 2: the config body moved from the labels to the workspace-config volume
+pub const SCHEMA_VERSION: u32 = 2;
 const ROOZ: &'static str = "dev.rooz";
 const LABEL_KEY: &'static str = "label";
 const TRUE: &'static str = "true";
 
 pub const ROLE_WORK: &'static str = "work";
 pub const ROLE_SIDECAR: &'static str = "sidecar";
+pub const ROLE_RUN: &'static str = "run";
+pub const ROLE_INIT: &'static str = "init";
+pub const ROLE_SYNC: &str = "sync";
+
+// the key=value filters of the work containers, as given to --filter. Each command lists the keys it takes
+#[derive(Debug, Default)]
+pub struct LabelFilter {
+    pub running: Option<bool>,
+    pub project: Option<String>,
+    pub origin: Option<String>,
+    pub repo: Option<String>,
+    pub branch: Option<String>,
+}
+
+impl LabelFilter {
+    pub fn parse(filters: &[String], keys: &[&str]) -> Result<Self, RoozError> {
+        let mut parsed = LabelFilter::default();
+        for filter in filters {
+            let invalid = || RoozError::Message(format!("Invalid filter: {}", filter));
+            let (key, value) = filter.split_once('=').ok_or_else(invalid)?;
+            if !keys.contains(&key) {
+                return Err(invalid());
+            }
+            match key {
+                "status" => {
+                    parsed.running = Some(match value {
+                        "running" => true,
+                        "stopped" => false,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "project" => parsed.project = Some(value.to_string()),
+                "origin" => parsed.origin = Some(value.to_string()),
+                "repo" => parsed.repo = Some(value.to_string()),
+                "branch" => parsed.branch = Some(value.to_string()),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(parsed)
+    }
+
+    // the origin and the repo match by a part of them
+    pub fn matches(&self, labels: &HashMap<String, String>, running: bool) -> bool {
+        let contains = |label: &str, value: &Option<String>| {
+            value
+                .as_ref()
+                .is_none_or(|v| labels.get(label).is_some_and(|l| l.contains(v)))
+        };
+        let equals = |label: &str, value: &Option<String>| {
+            value.as_ref().is_none_or(|v| labels.get(label) == Some(v))
+        };
+        self.running.is_none_or(|r| r == running)
+            && equals(PROJECT, &self.project)
+            && contains(CONFIG_ORIGIN, &self.origin)
+            && contains(REPO, &self.repo)
+            && equals(BRANCH, &self.branch)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct KeyValue {
@@ -56,6 +138,26 @@ impl KeyValue {
     }
 }
 
+// the body is in the labels of workspaces created before the workspace-config volume
+pub fn has_config(labels: &HashMap<String, String>) -> bool {
+    labels.contains_key(CONFIG_ORIGIN)
+        && (labels.contains_key(CONFIG_HASH) || labels.contains_key(CONFIG_BODY))
+}
+
+#[cfg(unix)]
+fn host_name() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+}
+
+#[cfg(windows)]
+fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
 #[derive(Clone, Debug)]
 pub struct Labels {
     pub rooz: KeyValue,
@@ -65,6 +167,13 @@ pub struct Labels {
     pub role: Option<KeyValue>,
     pub config_source: Option<KeyValue>,
     pub config_body: Option<KeyValue>,
+    pub volumes: Option<KeyValue>,
+    pub keep: Option<KeyValue>,
+    pub project: Option<KeyValue>,
+    pub repo: Option<KeyValue>,
+    pub branch: Option<KeyValue>,
+    pub metadata: Vec<KeyValue>,
+    pub custom: Vec<KeyValue>,
 }
 
 impl Labels {
@@ -92,6 +201,10 @@ impl Labels {
         Some(KeyValue::new(CONFIG_BODY, body))
     }
 
+    pub fn volumes(key: &str) -> Option<KeyValue> {
+        Some(KeyValue::new(VOLUMES_KEY, key))
+    }
+
     pub fn with_role(self, role: &str) -> Labels {
         Labels {
             role: Some(KeyValue::new(ROLE, role)),
@@ -109,6 +222,92 @@ impl Labels {
         }
     }
 
+    pub fn with_project(self, project: Option<&str>) -> Labels {
+        Labels {
+            project: project.map(|p| KeyValue::new(PROJECT, p)),
+            ..self
+        }
+    }
+
+    pub fn with_repo(self, url: Option<&str>, branch: Option<&str>) -> Labels {
+        Labels {
+            repo: url.map(|u| KeyValue::new(REPO, u)),
+            branch: branch.map(|b| KeyValue::new(BRANCH, b)),
+            ..self
+        }
+    }
+
+    pub fn with_keep(self, keep: bool) -> Labels {
+        Labels {
+            keep: Some(KeyValue::new(KEEP, TRUE)).filter(|_| keep),
+            ..self
+        }
+    }
+
+    // custom labels are only set on containers and never used for filtering
+    pub fn with_custom<'a>(
+        self,
+        labels: impl IntoIterator<Item = (&'a String, &'a String)>,
+    ) -> Result<Self, AnyError> {
+        let mut custom = self.custom;
+        for (k, v) in labels {
+            if k.starts_with(ROOZ) {
+                return Err(format!(
+                    "Label '{}' is not allowed. The '{}' namespace is reserved for rooz",
+                    k, ROOZ
+                )
+                .into());
+            }
+            custom.push(KeyValue::new(k, v));
+        }
+        Ok(Labels { custom, ..self })
+    }
+
+    // like custom labels metadata is only informational and never used for filtering
+    pub fn with_metadata(self, backend: &ContainerBackend) -> Self {
+        let mut metadata = vec![
+            KeyValue::new(CREATED, &DateTime::now().to_rfc3339()),
+            KeyValue::new(ROOZ_VERSION, env!("CARGO_PKG_VERSION")),
+            KeyValue::new(SCHEMA, &SCHEMA_VERSION.to_string()),
+            KeyValue::new(ENGINE, backend.name()),
+        ];
+        if let Some(host) = host_name() {
+            metadata.push(KeyValue::new(ORIGIN_HOST, &host));
+        }
+        Labels { metadata, ..self }
+    }
+
+    pub fn with_engine(self, name: Option<&str>) -> Self {
+        let mut metadata = self.metadata;
+        metadata.extend(name.map(|n| KeyValue::new(ENGINE_NAME, n)));
+        Labels { metadata, ..self }
+    }
+
+    // rooz update and unarchive re-create the workspace with the same ids
+    pub fn with_uid(self, uid: &str) -> Self {
+        let mut metadata = self.metadata;
+        metadata.push(KeyValue::new(UID, uid));
+        Labels { metadata, ..self }
+    }
+
+    // the body itself is in the workspace-config volume, the hash tells which one the container got created from
+    pub fn with_config_hash(self, hash: &str) -> Self {
+        let mut metadata = self.metadata;
+        metadata.push(KeyValue::new(CONFIG_HASH, hash));
+        Labels {
+            metadata,
+            config_body: None,
+            ..self
+        }
+    }
+
+    // the digest lets rooz update --check tell whether the registry has a newer image for the tag
+    pub fn with_image_digest(self, digest: Option<String>) -> Self {
+        let mut metadata = self.metadata;
+        metadata.extend(digest.map(|d| KeyValue::new(IMAGE_DIGEST, &d)));
+        Labels { metadata, ..self }
+    }
+
     pub fn with_runtime_config(self, config: RuntimeConfig) -> Self {
         Labels {
             runtime_config: Some(KeyValue::new(RUNTIME_CONFIG, &config.to_string().unwrap())),
@@ -127,6 +326,13 @@ impl Default for Labels {
             role: None,
             config_source: None,
             config_body: None,
+            volumes: None,
+            keep: None,
+            project: None,
+            repo: None,
+            branch: None,
+            metadata: Vec::new(),
+            custom: Vec::new(),
         }
     }
 }
@@ -135,7 +341,7 @@ impl<'a> From<&'a Labels> for HashMap<&'a str, &'a str> {
     fn from(value: &'a Labels) -> Self {
         let labels: Vec<&KeyValue> = value.into();
         let mut h = HashMap::new();
-        for l in labels {
+        for l in value.custom.iter().chain(&value.metadata).chain(labels) {
             h.insert(l.key.as_ref(), l.value.as_ref());
         }
         return h;
@@ -175,6 +381,21 @@ impl<'a> From<&'a Labels> for Vec<&'a KeyValue> {
         if let Some(value) = &value.config_body {
             labels.push(value);
         }
+        if let Some(value) = &value.volumes {
+            labels.push(value);
+        }
+        if let Some(value) = &value.keep {
+            labels.push(value);
+        }
+        if let Some(value) = &value.project {
+            labels.push(value);
+        }
+        if let Some(value) = &value.repo {
+            labels.push(value);
+        }
+        if let Some(value) = &value.branch {
+            labels.push(value);
+        }
         labels
     }
 }
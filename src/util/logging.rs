@@ -0,0 +1,60 @@
+use std::io::{self, IsTerminal, Write};
+
+use tracing::level_filters::LevelFilter;
+use tracing_log::LogTracer;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+use crate::{cli::LogFormat, util::redact};
+
+// every formatted line goes through redact before reaching stderr, the fields of spans and events included
+struct RedactedStderr;
+
+impl Write for RedactedStderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stderr().write_all(redact::redact(&String::from_utf8_lossy(buf)).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+// -v shows info, -vv debug and -vvv trace logs of rooz, overriding RUST_LOG.
+// Spans get logged once closed with how long they took, e.g. to find out what makes creating a workspace slow
+pub fn init(format: LogFormat, verbose: u8) {
+    let level = match verbose {
+        0 => None,
+        1 => Some(LevelFilter::INFO),
+        2 => Some(LevelFilter::DEBUG),
+        _ => Some(LevelFilter::TRACE),
+    };
+    let mut filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::ERROR.into())
+        .from_env_lossy();
+    if let Some(directive) = level.and_then(|l| format!("rooz={}", l).parse().ok()) {
+        filter = filter.add_directive(directive);
+    }
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(io::stderr().is_terminal())
+        .with_writer(|| RedactedStderr);
+    let result = match format {
+        LogFormat::Pretty => tracing::subscriber::set_global_default(builder.pretty().finish()),
+        LogFormat::Json => tracing::subscriber::set_global_default(
+            builder
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .finish(),
+        ),
+    };
+    if let Err(e) = result {
+        eprintln!("Could not set up logging: {}", e);
+    }
+    // the log records of rooz and its dependencies become tracing events
+    if let Err(e) = LogTracer::init() {
+        eprintln!("Could not set up logging: {}", e);
+    }
+}
@@ -2,4 +2,14 @@ pub mod backend;
 pub mod git;
 pub mod id;
 pub mod labels;
+pub mod logging;
+pub mod parallel;
+pub mod passwd;
+pub mod progress;
+pub mod prompt;
+pub mod redact;
+pub mod size;
 pub mod ssh;
+pub mod term;
+pub mod time;
+pub mod uid;
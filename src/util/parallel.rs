@@ -0,0 +1,38 @@
+use futures::{stream, Future, StreamExt};
+
+use crate::{constants, model::error::RoozError};
+
+// Runs the named tasks with bounded concurrency.
+// All tasks run to completion so every failure gets reported, not just the first one.
+pub async fn run_all<T, F>(
+    what: &str,
+    tasks: impl IntoIterator<Item = (String, F)>,
+) -> Result<Vec<T>, RoozError>
+where
+    F: Future<Output = Result<T, RoozError>>,
+{
+    let results = stream::iter(tasks)
+        .map(|(name, task)| async move { (name, task.await) })
+        .buffer_unordered(constants::MAX_PARALLEL)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+    for (name, result) in results {
+        match result {
+            Ok(v) => values.push(v),
+            Err(e) => errors.push((name, e)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Err(RoozError::Parallel {
+            what: what.to_string(),
+            errors,
+        })
+    }
+}
@@ -0,0 +1,33 @@
+// a line of an image's /etc/passwd
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub uid: String,
+    pub gid: String,
+    pub home: String,
+}
+
+pub fn parse(passwd: &str) -> Vec<Entry> {
+    passwd
+        .lines()
+        .filter_map(
+            |line| match line.split(':').collect::<Vec<_>>().as_slice() {
+                [name, _, uid, gid, _, home, ..] if !name.starts_with('#') => Some(Entry {
+                    name: name.to_string(),
+                    uid: uid.to_string(),
+                    gid: gid.to_string(),
+                    home: home.to_string(),
+                }),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+// numeric users match by uid, named ones by name
+pub fn find<'a>(entries: &'a [Entry], user: &str) -> Option<&'a Entry> {
+    match user.parse::<u32>() {
+        Ok(_) => entries.iter().find(|e| e.uid == user),
+        Err(_) => entries.iter().find(|e| e.name == user),
+    }
+}
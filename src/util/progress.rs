@@ -0,0 +1,77 @@
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+
+// reports the steps of long-running operations on stderr, so stdout stays what the commands print
+pub trait Progress: Sync {
+    fn started(&self, step: &str);
+    fn finished(&self, step: &str, elapsed: Duration, ok: bool);
+}
+
+pub struct TermProgress;
+
+impl Progress for TermProgress {
+    fn started(&self, step: &str) {
+        eprintln!("{} {}", "•".dimmed(), step);
+    }
+
+    fn finished(&self, step: &str, elapsed: Duration, ok: bool) {
+        let mark = match ok {
+            true => "✓".green(),
+            false => "✗".red(),
+        };
+        eprintln!(
+            "{} {} {}",
+            mark,
+            step,
+            format!("({:.1}s)", elapsed.as_secs_f32()).dimmed()
+        );
+    }
+}
+
+// --quiet
+pub struct QuietProgress;
+
+impl Progress for QuietProgress {
+    fn started(&self, _: &str) {}
+    fn finished(&self, _: &str, _: Duration, _: bool) {}
+}
+
+pub fn reporter(quiet: bool) -> &'static dyn Progress {
+    match quiet {
+        true => &QuietProgress,
+        false => &TermProgress,
+    }
+}
+
+// a step dropped without done() failed, e.g. on an early return with an error
+pub struct Step<'a> {
+    progress: &'a dyn Progress,
+    name: String,
+    start: Instant,
+    ok: bool,
+}
+
+pub fn step(progress: &dyn Progress, name: impl Into<String>) -> Step<'_> {
+    let name = name.into();
+    progress.started(&name);
+    Step {
+        progress,
+        name,
+        start: Instant::now(),
+        ok: false,
+    }
+}
+
+impl Step<'_> {
+    pub fn done(mut self) {
+        self.ok = true;
+    }
+}
+
+impl Drop for Step<'_> {
+    fn drop(&mut self) {
+        self.progress
+            .finished(&self.name, self.start.elapsed(), self.ok);
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+pub const META_PREFIX: &str = "ROOZ_META_";
+// relative to the home dir
+pub const INIT_PATH: &str = ".config/rooz/init.sh";
+
+const PROMPT_SCRIPT: &str = r#"  case "$-" in
+    *i*)
+      printf '\033[1mrooz\033[0m workspace: %s, container: %s, image: %s\n' \
+        "$ROOZ_META_WORKSPACE" "$ROOZ_META_CONTAINER_NAME" "$ROOZ_META_IMAGE"
+      PS1="(rooz:$ROOZ_META_CONTAINER_NAME) ${PS1:-\$ }"
+      if ! command -v rooz > /dev/null 2>&1; then
+        rooz() {
+          case "$1" in
+            context) env | grep '^ROOZ_META_' | sort ;;
+            *) echo "rooz: only 'rooz context' is available inside workspaces" >&2; return 1 ;;
+          esac
+        }
+      fi
+      ;;
+  esac
+"#;
+
+// the shell_init snippet runs in every shell, interactive or not
+pub fn init_script(prompt: bool, shell_init: Option<&str>) -> (String, String) {
+    let mut script = vec![
+        "# Generated by rooz on workspace creation. Changes get overwritten".to_string(),
+        r#"if [ -z "$ROOZ_INIT_DONE" ]; then"#.into(),
+        "  ROOZ_INIT_DONE=1".into(),
+    ];
+    if prompt {
+        script.push(PROMPT_SCRIPT.trim_end().into());
+    }
+    if let Some(shell_init) = shell_init {
+        script.push(shell_init.trim_end().into());
+    }
+    script.push("fi\n".into());
+    (INIT_PATH.into(), script.join("\n"))
+}
+
+// ENV is read by POSIX shells (sh, dash, ash) and PROMPT_COMMAND by bash.
+// Both get expanded by the shell so the path follows the home dir of any container user.
+// Values set in the config take precedence.
+pub fn extend_env(env: &mut HashMap<String, String>) {
+    let script = format!("$ROOZ_META_HOME/{}", INIT_PATH);
+    env.entry("ENV".into()).or_insert(script.to_string());
+    env.entry("PROMPT_COMMAND".into())
+        .or_insert(format!("[ -z \"$ROOZ_INIT_DONE\" ] && . \"{}\"", script));
+}
+
+pub fn meta(env: impl IntoIterator<Item = (String, String)>) -> Vec<(String, String)> {
+    let mut meta = env
+        .into_iter()
+        .filter(|(k, _)| k.starts_with(META_PREFIX))
+        .collect::<Vec<_>>();
+    meta.sort();
+    meta
+}
@@ -0,0 +1,34 @@
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+
+pub const MASK: &str = "***";
+
+lazy_static! {
+    // every secret value decrypted by this process so it can be masked in any output
+    static ref SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+pub fn register(values: impl IntoIterator<Item = String>) {
+    if let Ok(mut secrets) = SECRETS.write() {
+        for v in values.into_iter().filter(|v| !v.is_empty()) {
+            if !secrets.contains(&v) {
+                secrets.push(v);
+            }
+        }
+        // longer values first so a secret containing another one gets masked as a whole
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    if let Ok(secrets) = SECRETS.read() {
+        for s in secrets.iter() {
+            if text.contains(s.as_str()) {
+                text = text.replace(s.as_str(), MASK);
+            }
+        }
+    }
+    text
+}
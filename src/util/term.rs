@@ -0,0 +1,235 @@
+use std::{
+    io::{self, stdout, IsTerminal, Read, Write},
+    sync::{
+        mpsc::{channel, Receiver},
+        Mutex,
+    },
+    thread,
+};
+
+use lazy_static::lazy_static;
+
+// works on unix terminals and on Windows 10+ consoles (VT sequences)
+const CLEAR: &str = "\x1b[2J";
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+#[cfg(unix)]
+const PICKER_ROWS: usize = 10;
+
+lazy_static! {
+    // a single reader thread shared by all sessions (e.g. across reconnects) so they do not compete for stdin
+    static ref STDIN: Mutex<Receiver<u8>> = {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let mut input = input();
+            let mut buf = [0u8; 1024];
+            while let Ok(n @ 1..) = input.read(&mut buf) {
+                if buf[..n].iter().any(|b| tx.send(*b).is_err()) {
+                    break;
+                }
+            }
+        });
+        Mutex::new(rx)
+    };
+}
+
+#[cfg(unix)]
+fn input() -> Box<dyn Read + Send> {
+    match termion::get_tty() {
+        Ok(tty) => Box::new(tty),
+        Err(_) => Box::new(io::stdin()),
+    }
+}
+
+#[cfg(windows)]
+fn input() -> Box<dyn Read + Send> {
+    Box::new(io::stdin())
+}
+
+pub fn try_read_stdin() -> Option<u8> {
+    STDIN.lock().ok()?.try_recv().ok()
+}
+
+pub fn clear() {
+    println!("{}", CLEAR);
+}
+
+#[cfg(unix)]
+pub fn size() -> (u16, u16) {
+    termion::terminal_size().unwrap_or(DEFAULT_SIZE)
+}
+
+#[cfg(windows)]
+pub fn size() -> (u16, u16) {
+    crossterm::terminal::size().unwrap_or(DEFAULT_SIZE)
+}
+
+// stays in raw mode until dropped
+#[cfg(unix)]
+pub fn raw_stdout() -> Result<Box<dyn Write>, io::Error> {
+    use termion::raw::IntoRawMode;
+    Ok(Box::new(stdout().into_raw_mode()?))
+}
+
+// leaves raw mode once dropped, as termion's RawTerminal does
+#[cfg(windows)]
+struct RawStdout(io::Stdout);
+
+#[cfg(windows)]
+impl Write for RawStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(windows)]
+impl Drop for RawStdout {
+    fn drop(&mut self) {
+        crossterm::terminal::disable_raw_mode().ok();
+    }
+}
+
+#[cfg(windows)]
+pub fn raw_stdout() -> Result<Box<dyn Write>, io::Error> {
+    crossterm::terminal::enable_raw_mode()?;
+    Ok(Box::new(RawStdout(stdout())))
+}
+
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+pub fn confirm(prompt: &str) -> Result<bool, io::Error> {
+    eprint!("{} [y/N] ", prompt);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// case-insensitive subsequence match, e.g. "mws" matches "my-workspace"
+fn fuzzy_match(item: &str, query: &str) -> bool {
+    let mut chars = item.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| chars.any(|c| c == q))
+}
+
+// returns the index of the picked item or None if aborted. Draws on stderr to keep stdout clean
+#[cfg(unix)]
+pub fn pick(prompt: &str, items: &[String]) -> Result<Option<usize>, io::Error> {
+    use termion::{clear, cursor, event::Key, input::TermRead, raw::IntoRawMode};
+
+    let mut out = io::stderr().into_raw_mode()?;
+    let mut keys = termion::get_tty()?.keys();
+    let mut query = String::new();
+    let mut selected = 0;
+    loop {
+        let matches = (0..items.len())
+            .filter(|i| fuzzy_match(&items[*i], &query))
+            .collect::<Vec<_>>();
+        selected = selected.min(matches.len().saturating_sub(1));
+        let offset = selected.saturating_sub(PICKER_ROWS - 1);
+
+        write!(out, "\r{}{} {}\r\n", clear::AfterCursor, prompt, query)?;
+        let rows = matches.iter().enumerate().skip(offset).take(PICKER_ROWS);
+        let mut drawn = 0;
+        for (i, item) in rows {
+            let marker = if i == selected { ">" } else { " " };
+            write!(out, "{} {}\r\n", marker, items[*item])?;
+            drawn += 1;
+        }
+        write!(
+            out,
+            "{}\r{}",
+            cursor::Up(drawn + 1),
+            cursor::Right((prompt.len() + 1 + query.chars().count()) as u16)
+        )?;
+        out.flush()?;
+
+        match keys.next() {
+            Some(Ok(Key::Char('\n'))) => {
+                write!(out, "\r{}", clear::AfterCursor)?;
+                return Ok(matches.get(selected).copied());
+            }
+            Some(Ok(Key::Esc)) | Some(Ok(Key::Ctrl('c'))) | None => {
+                write!(out, "\r{}", clear::AfterCursor)?;
+                return Ok(None);
+            }
+            Some(Ok(Key::Up)) | Some(Ok(Key::Ctrl('p'))) => selected = selected.saturating_sub(1),
+            Some(Ok(Key::Down)) | Some(Ok(Key::Ctrl('n'))) => selected += 1,
+            Some(Ok(Key::Backspace)) => {
+                query.pop();
+                selected = 0;
+            }
+            Some(Ok(Key::Char(c))) => {
+                query.push(c);
+                selected = 0;
+            }
+            Some(Err(e)) => return Err(e),
+            Some(Ok(_)) => (),
+        }
+    }
+}
+
+// the items get numbered and the choice is read as a line
+#[cfg(windows)]
+pub fn pick(prompt: &str, items: &[String]) -> Result<Option<usize>, io::Error> {
+    for (i, item) in items.iter().enumerate() {
+        eprintln!("{:>3}) {}", i + 1, item);
+    }
+    eprint!("{} ", prompt);
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    let line = line.trim();
+    Ok(match line.parse::<usize>() {
+        Ok(n) if (1..=items.len()).contains(&n) => Some(n - 1),
+        _ if line.is_empty() => None,
+        _ => (0..items.len()).find(|i| fuzzy_match(&items[*i], line)),
+    })
+}
+
+#[cfg(unix)]
+pub fn read_passwd() -> Result<Option<String>, io::Error> {
+    use termion::input::TermRead;
+    io::stdin().read_passwd(&mut stdout())
+}
+
+// read key by key in raw mode so the console doesn't echo it. Ctrl+C and Esc abort like EOF does on unix
+#[cfg(windows)]
+pub fn read_passwd() -> Result<Option<String>, io::Error> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    if !io::stdin().is_terminal() {
+        let mut line = String::new();
+        return match io::stdin().read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line.trim_end_matches(['\r', '\n']).to_string())),
+        };
+    }
+    let _raw = raw_stdout()?;
+    let mut passwd = String::new();
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(Some(passwd)),
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Backspace => {
+                passwd.pop();
+            }
+            KeyCode::Char(c) => passwd.push(c),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::process::Command;
+
+use crate::{constants, model::error::RoozError};
+
+pub const HOST: &str = "host";
+
+// the workspace uid is either "<uid>" or "<uid>:<gid>". The gid defaults to the uid
+pub fn split(uid: &str) -> (&str, &str) {
+    uid.split_once(':').unwrap_or((uid, uid))
+}
+
+// the owner in chown's format
+pub fn owner(uid: &str) -> String {
+    let (uid, gid) = split(uid);
+    format!("{}:{}", uid, gid)
+}
+
+fn host_id(flag: &str) -> Option<String> {
+    let output = Command::new("id").arg(flag).output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|s| output.status.success() && !s.is_empty())
+}
+
+// 'host' takes the ids of the user running rooz, so files written to bind mounts
+// and exported archives belong to them on the host
+pub fn resolve(uid: Option<&str>) -> Result<String, RoozError> {
+    match uid {
+        None => Ok(constants::DEFAULT_UID.to_string()),
+        Some(HOST) => match (host_id("-u"), host_id("-g")) {
+            (Some(uid), Some(gid)) => Ok(format!("{}:{}", uid, gid)),
+            _ => Err(RoozError::Config(
+                "uid = \"host\" needs the id command to look up the host user".into(),
+            )),
+        },
+        Some(value) => {
+            let (uid, gid) = split(value);
+            if [uid, gid].iter().all(|id| id.parse::<u32>().is_ok()) {
+                Ok(value.to_string())
+            } else {
+                Err(RoozError::Config(format!(
+                    "invalid uid: '{}'. Expected 'host', <uid> or <uid>:<gid>",
+                    value
+                )))
+            }
+        }
+    }
+}